@@ -0,0 +1,200 @@
+use alvr_common::SlidingWindowAverage;
+use std::time::{Duration, Instant};
+
+const DELAY_HISTORY_SIZE: usize = 128;
+// Offset errors smaller than this are not corrected, to avoid resampling continuously
+const OFFSET_ERROR_DEADBAND: f32 = 0.005;
+// Rate correction for each second of offset error
+const RATE_CORRECTION_GAIN: f32 = 0.25;
+// A correction of 0.5% is not audible and recovers 5ms of offset each second
+const MAX_RATE_CORRECTION: f32 = 0.005;
+
+// Audio and video packets are stamped with timestamps of the same timeline. A video frame is
+// displayed with a certain delay from its timestamp, and audio should be played with the same delay
+// (plus the target offset). Delays are measured against a local epoch: the difference between the
+// audio and the video delay does not depend on the clock of the timestamps.
+pub struct AudioVideoSync {
+    epoch: Instant,
+    target_offset: f32,
+    video_delay: Option<SlidingWindowAverage<f32>>,
+    audio_delay: Option<SlidingWindowAverage<f32>>,
+}
+
+fn submit_delay(
+    average: &mut Option<SlidingWindowAverage<f32>>,
+    epoch: Instant,
+    timestamp: Duration,
+    instant: Instant,
+) {
+    let delay = instant.saturating_duration_since(epoch).as_secs_f32() - timestamp.as_secs_f32();
+
+    if let Some(average) = average {
+        average.submit_sample(delay);
+    } else {
+        *average = Some(SlidingWindowAverage::new(delay, DELAY_HISTORY_SIZE));
+    }
+}
+
+impl AudioVideoSync {
+    // Seconds. A positive target offset means that audio is played after the corresponding video
+    // frame
+    pub fn new(target_offset: f32) -> Self {
+        Self {
+            epoch: Instant::now(),
+            target_offset,
+            video_delay: None,
+            audio_delay: None,
+        }
+    }
+
+    pub fn report_video_display(&mut self, timestamp: Duration, display_instant: Instant) {
+        submit_delay(
+            &mut self.video_delay,
+            self.epoch,
+            timestamp,
+            display_instant,
+        );
+    }
+
+    pub fn report_audio_playback(&mut self, timestamp: Duration, playback_instant: Instant) {
+        submit_delay(
+            &mut self.audio_delay,
+            self.epoch,
+            timestamp,
+            playback_instant,
+        );
+    }
+
+    // Seconds, positive if audio is late
+    pub fn offset(&self) -> Option<f32> {
+        Some(self.audio_delay.as_ref()?.get_average() - self.video_delay.as_ref()?.get_average())
+    }
+
+    // Ratio between the number of played and received audio samples. It is less than 1 when audio
+    // is late, so that the buffered audio is consumed faster.
+    pub fn playback_rate(&self) -> f32 {
+        let Some(offset) = self.offset() else {
+            return 1.0;
+        };
+
+        let error = offset - self.target_offset;
+        if error.abs() < OFFSET_ERROR_DEADBAND {
+            return 1.0;
+        }
+
+        1.0 - (error * RATE_CORRECTION_GAIN).clamp(-MAX_RATE_CORRECTION, MAX_RATE_CORRECTION)
+    }
+}
+
+// Linear interpolation resampler for interleaved samples. It keeps the last frame of each batch so
+// that consecutive batches are joined without discontinuities.
+#[derive(Default)]
+pub struct LinearResampler {
+    previous_frame: Vec<f32>,
+    // Position of the next output frame, relative to previous_frame
+    position: f64,
+}
+
+impl LinearResampler {
+    // rate is the ratio between the number of output and input frames
+    pub fn process(&mut self, samples: &[f32], channels_count: usize, rate: f32) -> Vec<f32> {
+        if self.previous_frame.len() != channels_count {
+            self.previous_frame.clear();
+            self.position = 0.0;
+        }
+
+        let frames = self
+            .previous_frame
+            .iter()
+            .chain(samples)
+            .copied()
+            .collect::<Vec<_>>();
+        let frames_count = frames.len() / channels_count;
+        if frames_count < 2 {
+            return vec![];
+        }
+
+        let step = 1.0 / rate as f64;
+
+        let mut output = vec![];
+        while self.position < (frames_count - 1) as f64 {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+
+            for c in 0..channels_count {
+                let current = frames[index * channels_count + c];
+                let next = frames[(index + 1) * channels_count + c];
+                output.push(current + (next - current) * fraction);
+            }
+
+            self.position += step;
+        }
+
+        self.position -= (frames_count - 1) as f64;
+        self.previous_frame = frames[(frames_count - 1) * channels_count..].to_vec();
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_estimation() {
+        let mut sync = AudioVideoSync::new(0.0);
+        assert_eq!(sync.playback_rate(), 1.0);
+
+        let start = sync.epoch;
+        for i in 0..10 {
+            let timestamp = Duration::from_millis(1000 + i * 10);
+            let instant = start + Duration::from_millis(i * 10);
+
+            // The timestamp clock is unrelated to the local clock
+            sync.report_video_display(timestamp, instant + Duration::from_millis(40));
+            sync.report_audio_playback(timestamp, instant + Duration::from_millis(100));
+        }
+
+        assert!((sync.offset().unwrap() - 0.06).abs() < 1e-4);
+        // Audio is late, play it faster
+        assert!(sync.playback_rate() < 1.0);
+        assert!(sync.playback_rate() >= 1.0 - MAX_RATE_CORRECTION);
+
+        // The target offset is already reached
+        let mut sync = AudioVideoSync::new(0.06);
+        let start = sync.epoch;
+        sync.report_video_display(Duration::ZERO, start + Duration::from_millis(40));
+        sync.report_audio_playback(Duration::ZERO, start + Duration::from_millis(100));
+        assert_eq!(sync.playback_rate(), 1.0);
+    }
+
+    #[test]
+    fn test_resampler() {
+        let mut resampler = LinearResampler::default();
+
+        // Stereo ramp
+        let samples = (0..200)
+            .flat_map(|i| [i as f32, -(i as f32)])
+            .collect::<Vec<_>>();
+
+        let mut output = vec![];
+        for chunk in samples.chunks(20) {
+            output.extend(resampler.process(chunk, 2, 1.0));
+        }
+        // One frame is held back
+        assert_eq!(output, samples[..samples.len() - 2]);
+
+        let mut resampler = LinearResampler::default();
+        let mut output = vec![];
+        for chunk in samples.chunks(20) {
+            output.extend(resampler.process(chunk, 2, 0.5));
+        }
+        assert_eq!(output.len(), 200);
+        // The ramp is preserved across batches
+        assert!(output
+            .chunks_exact(2)
+            .enumerate()
+            .all(|(i, frame)| frame == [i as f32 * 2.0, -(i as f32) * 2.0]));
+    }
+}
@@ -0,0 +1,377 @@
+use alvr_common::{
+    anyhow::{bail, Result},
+    warn,
+};
+use alvr_packets::{AudioCodec, AudioPacketHeader};
+use alvr_session::AudioCodecConfig;
+use std::time::Duration;
+
+const OPUS_SAMPLE_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+// 10ms frames
+const OPUS_FRAMES_PER_SECOND: u32 = 100;
+// Recommended by the libopus documentation
+const MAX_OPUS_PACKET_SIZE: usize = 4000;
+// 120ms at 48kHz, the longest Opus frame
+const MAX_OPUS_FRAME_SAMPLES: usize = 5760;
+// Expected packet loss percentage. The encoder adds in-band FEC data only if this is not zero
+const OPUS_FEC_PACKET_LOSS_PERC: i32 = 10;
+
+// Longer gaps are not concealed. The receive loop renders a fade-in after re-buffering instead
+const MAX_CONCEALED_PACKETS: u32 = 3;
+
+fn i16_to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+enum EncoderBackend {
+    Pcm,
+    Opus {
+        encoder: opus::Encoder,
+        frame_samples: usize,
+        pending_samples: Vec<i16>,
+        packet_buffer: Vec<u8>,
+    },
+}
+
+fn create_opus_encoder(
+    channels_count: u16,
+    sample_rate: u32,
+    bitrate_kbps: u32,
+    in_band_fec: bool,
+) -> Result<EncoderBackend> {
+    if !OPUS_SAMPLE_RATES.contains(&sample_rate) {
+        bail!("Sample rate {sample_rate} is not supported by Opus");
+    }
+    let channels = match channels_count {
+        1 => opus::Channels::Mono,
+        2 => opus::Channels::Stereo,
+        _ => bail!("Opus supports only mono or stereo audio"),
+    };
+
+    let mut encoder = opus::Encoder::new(sample_rate, channels, opus::Application::LowDelay)?;
+    encoder.set_bitrate(opus::Bitrate::Bits(bitrate_kbps as i32 * 1000))?;
+    encoder.set_inband_fec(in_band_fec)?;
+    if in_band_fec {
+        encoder.set_packet_loss_perc(OPUS_FEC_PACKET_LOSS_PERC)?;
+    }
+
+    Ok(EncoderBackend::Opus {
+        encoder,
+        frame_samples: (sample_rate / OPUS_FRAMES_PER_SECOND) as usize * channels_count as usize,
+        pending_samples: vec![],
+        packet_buffer: vec![0; MAX_OPUS_PACKET_SIZE],
+    })
+}
+
+pub struct AudioEncoder {
+    backend: EncoderBackend,
+    header: AudioPacketHeader,
+}
+
+impl AudioEncoder {
+    // Falls back to PCM if the codec cannot be used with this audio format
+    pub fn new(config: &AudioCodecConfig, channels_count: u16, sample_rate: u32) -> Self {
+        let backend = match config {
+            AudioCodecConfig::Pcm => EncoderBackend::Pcm,
+            AudioCodecConfig::Opus {
+                bitrate_kbps,
+                in_band_fec,
+            } => create_opus_encoder(channels_count, sample_rate, *bitrate_kbps, *in_band_fec)
+                .unwrap_or_else(|e| {
+                    warn!("Cannot use Opus, falling back to PCM: {e}");
+
+                    EncoderBackend::Pcm
+                }),
+        };
+
+        let codec = match backend {
+            EncoderBackend::Pcm => AudioCodec::Pcm,
+            EncoderBackend::Opus { .. } => AudioCodec::Opus,
+        };
+
+        Self {
+            backend,
+            header: AudioPacketHeader {
+                codec,
+                channels_count,
+                sample_rate,
+                sequence: 0,
+                timestamp: None,
+            },
+        }
+    }
+
+    // Encode interleaved samples. Opus packets contain one full frame each, so send_packet can be
+    // called zero or more times. timestamp refers to the first sample of this call.
+    pub fn encode(
+        &mut self,
+        samples: &[i16],
+        timestamp: Option<Duration>,
+        mut send_packet: impl FnMut(&AudioPacketHeader, &[u8]),
+    ) -> Result<()> {
+        match &mut self.backend {
+            EncoderBackend::Pcm => {
+                self.header.timestamp = timestamp;
+
+                let payload = samples
+                    .iter()
+                    .flat_map(|sample| sample.to_ne_bytes())
+                    .collect::<Vec<_>>();
+
+                send_packet(&self.header, &payload);
+                self.header.sequence = self.header.sequence.wrapping_add(1);
+            }
+            EncoderBackend::Opus {
+                encoder,
+                frame_samples,
+                pending_samples,
+                packet_buffer,
+            } => {
+                let samples_per_second =
+                    self.header.channels_count as u32 * self.header.sample_rate;
+                let pending_duration =
+                    Duration::from_secs(1) * pending_samples.len() as u32 / samples_per_second;
+                let frame_duration = Duration::from_secs(1) / OPUS_FRAMES_PER_SECOND;
+
+                pending_samples.extend_from_slice(samples);
+
+                let mut frames = pending_samples.chunks_exact(*frame_samples);
+                for (index, frame) in (&mut frames).enumerate() {
+                    let size = encoder.encode(frame, packet_buffer)?;
+
+                    self.header.timestamp = timestamp.map(|timestamp| {
+                        timestamp.saturating_sub(pending_duration) + frame_duration * index as u32
+                    });
+
+                    send_packet(&self.header, &packet_buffer[..size]);
+                    self.header.sequence = self.header.sequence.wrapping_add(1);
+                }
+
+                let remainder_len = frames.remainder().len();
+                pending_samples.drain(..pending_samples.len() - remainder_len);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum DecoderBackend {
+    Pcm,
+    Opus {
+        decoder: opus::Decoder,
+        // Per channel
+        last_frame_samples: usize,
+    },
+}
+
+pub struct DecodedAudio {
+    // Interleaved
+    pub samples: Vec<f32>,
+    // The samples of the lost packets have been reconstructed and prepended to the samples of the
+    // current packet, so the stream can continue without a discontinuity
+    pub concealed_loss: bool,
+}
+
+// The decoder is recreated whenever the codec parameters of the received packets change
+#[derive(Default)]
+pub struct AudioDecoder {
+    backend: Option<(AudioCodec, u16, u32, DecoderBackend)>,
+    last_sequence: Option<u32>,
+}
+
+impl AudioDecoder {
+    pub fn decode(&mut self, header: &AudioPacketHeader, payload: &[u8]) -> Result<DecodedAudio> {
+        let lost_packets = self
+            .last_sequence
+            .map(|last| header.sequence.wrapping_sub(last).wrapping_sub(1))
+            .unwrap_or(0);
+        self.last_sequence = Some(header.sequence);
+
+        let mut can_conceal = true;
+        let backend = match &mut self.backend {
+            Some((codec, channels_count, sample_rate, backend))
+                if *codec == header.codec
+                    && *channels_count == header.channels_count
+                    && *sample_rate == header.sample_rate =>
+            {
+                backend
+            }
+            backend_ref => {
+                let backend = match header.codec {
+                    AudioCodec::Pcm => DecoderBackend::Pcm,
+                    AudioCodec::Opus => {
+                        let channels = match header.channels_count {
+                            1 => opus::Channels::Mono,
+                            2 => opus::Channels::Stereo,
+                            count => bail!("Invalid Opus channels count: {count}"),
+                        };
+
+                        DecoderBackend::Opus {
+                            decoder: opus::Decoder::new(header.sample_rate, channels)?,
+                            last_frame_samples: 0,
+                        }
+                    }
+                };
+                can_conceal = false;
+
+                &mut backend_ref
+                    .insert((
+                        header.codec,
+                        header.channels_count,
+                        header.sample_rate,
+                        backend,
+                    ))
+                    .3
+            }
+        };
+
+        let channels_count = header.channels_count as usize;
+
+        Ok(match backend {
+            DecoderBackend::Pcm => DecodedAudio {
+                samples: payload
+                    .chunks_exact(2)
+                    .map(|c| i16_to_f32(i16::from_ne_bytes([c[0], c[1]])))
+                    .collect(),
+                concealed_loss: false,
+            },
+            DecoderBackend::Opus {
+                decoder,
+                last_frame_samples,
+            } => {
+                let mut samples = vec![];
+
+                let concealed_loss = can_conceal
+                    && *last_frame_samples > 0
+                    && (1..=MAX_CONCEALED_PACKETS).contains(&lost_packets);
+                if concealed_loss {
+                    let mut frame = vec![0.0; *last_frame_samples * channels_count];
+
+                    // Packet loss concealment for all but the last lost packet
+                    for _ in 1..lost_packets {
+                        decoder.decode_float(&[], &mut frame, false)?;
+                        samples.extend_from_slice(&frame);
+                    }
+
+                    // The last lost packet can be recovered from the FEC data of the current
+                    // packet. If there is none, this falls back to packet loss concealment
+                    decoder.decode_float(payload, &mut frame, true)?;
+                    samples.extend_from_slice(&frame);
+                }
+
+                let mut frame = vec![0.0; MAX_OPUS_FRAME_SAMPLES * channels_count];
+                let frame_samples = decoder.decode_float(payload, &mut frame, false)?;
+                samples.extend_from_slice(&frame[..frame_samples * channels_count]);
+                *last_frame_samples = frame_samples;
+
+                DecodedAudio {
+                    samples,
+                    concealed_loss,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPUS_CONFIG: AudioCodecConfig = AudioCodecConfig::Opus {
+        bitrate_kbps: 128,
+        in_band_fec: true,
+    };
+
+    fn encode_packets(
+        encoder: &mut AudioEncoder,
+        samples: &[i16],
+        timestamp: Option<Duration>,
+    ) -> Vec<(AudioPacketHeader, Vec<u8>)> {
+        let mut packets = vec![];
+        encoder
+            .encode(samples, timestamp, |header, payload| {
+                packets.push((header.clone(), payload.to_vec()))
+            })
+            .unwrap();
+
+        packets
+    }
+
+    #[test]
+    fn test_pcm_round_trip() {
+        let mut encoder = AudioEncoder::new(&AudioCodecConfig::Pcm, 2, 44100);
+        let mut decoder = AudioDecoder::default();
+
+        let packets = encode_packets(&mut encoder, &[0, 16384, -16384, -32768], None);
+        assert_eq!(packets.len(), 1);
+
+        let (header, payload) = &packets[0];
+        let decoded = decoder.decode(header, payload).unwrap();
+        assert_eq!(decoded.samples, [0.0, 0.5, -0.5, -1.0]);
+    }
+
+    #[test]
+    fn test_opus_fallback() {
+        // 44.1kHz is not supported by Opus
+        let mut encoder = AudioEncoder::new(&OPUS_CONFIG, 2, 44100);
+
+        let packets = encode_packets(&mut encoder, &[0; 8], None);
+        assert_eq!(packets[0].0.codec, AudioCodec::Pcm);
+    }
+
+    #[test]
+    fn test_opus_packet_loss_concealment() {
+        let mut encoder = AudioEncoder::new(&OPUS_CONFIG, 2, 48000);
+        let mut decoder = AudioDecoder::default();
+
+        // 6 stereo frames of 10ms (480 samples per channel), sent in arbitrary chunks
+        let samples = (0..2 * 480 * 6)
+            .map(|i| ((i as f32 * 0.01).sin() * 10000.0) as i16)
+            .collect::<Vec<_>>();
+        let mut packets = vec![];
+        for chunk in samples.chunks(700) {
+            packets.extend(encode_packets(&mut encoder, chunk, None));
+        }
+        assert_eq!(packets.len(), 6);
+        assert!(packets
+            .iter()
+            .all(|(header, _)| header.codec == AudioCodec::Opus));
+
+        let decoded = decoder.decode(&packets[0].0, &packets[0].1).unwrap();
+        assert_eq!(decoded.samples.len(), 2 * 480);
+        assert!(!decoded.concealed_loss);
+
+        // Lose two packets
+        let decoded = decoder.decode(&packets[3].0, &packets[3].1).unwrap();
+        assert_eq!(decoded.samples.len(), 3 * 2 * 480);
+        assert!(decoded.concealed_loss);
+
+        // Changing the codec parameters resets the decoder
+        let mut encoder = AudioEncoder::new(&OPUS_CONFIG, 1, 48000);
+        let packets = encode_packets(&mut encoder, &[0; 480 * 3], None);
+        let decoded = decoder.decode(&packets[2].0, &packets[2].1).unwrap();
+        assert_eq!(decoded.samples.len(), 480);
+        assert!(!decoded.concealed_loss);
+    }
+
+    #[test]
+    fn test_opus_frame_timestamps() {
+        let mut encoder = AudioEncoder::new(&OPUS_CONFIG, 1, 48000);
+
+        // 5ms of samples are left pending
+        let packets = encode_packets(&mut encoder, &[0; 240], Some(Duration::from_millis(100)));
+        assert!(packets.is_empty());
+
+        // The first frame starts with the pending samples
+        let packets = encode_packets(&mut encoder, &[0; 960], Some(Duration::from_millis(105)));
+        let timestamps = packets
+            .iter()
+            .map(|(header, _)| header.timestamp.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timestamps,
+            [Duration::from_millis(100), Duration::from_millis(110)]
+        );
+    }
+}
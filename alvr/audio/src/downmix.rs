@@ -0,0 +1,302 @@
+use alvr_session::AudioDownmixConfig;
+use serde::{Deserialize, Serialize};
+
+// ITU-R BS.775 level for center and surround channels (-3dB)
+const STANDARD_DOWNMIX_LEVEL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Speaker {
+    Mono,
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    SideLeft,
+    SideRight,
+}
+
+// The channel order is the one used by WAVE and WASAPI
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    Surround51,
+    Surround71,
+}
+
+impl ChannelLayout {
+    pub fn from_channels_count(channels_count: u16) -> Option<Self> {
+        match channels_count {
+            1 => Some(Self::Mono),
+            2 => Some(Self::Stereo),
+            6 => Some(Self::Surround51),
+            8 => Some(Self::Surround71),
+            _ => None,
+        }
+    }
+
+    pub fn channels_count(self) -> u16 {
+        self.speakers().len() as u16
+    }
+
+    fn speakers(self) -> &'static [Speaker] {
+        use Speaker::*;
+
+        match self {
+            Self::Mono => &[Mono],
+            Self::Stereo => &[FrontLeft, FrontRight],
+            Self::Surround51 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+            ],
+            Self::Surround71 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ],
+        }
+    }
+}
+
+struct DownmixLevels {
+    center: f32,
+    surround: f32,
+    lfe: f32,
+    normalize: bool,
+}
+
+impl From<&AudioDownmixConfig> for DownmixLevels {
+    fn from(config: &AudioDownmixConfig) -> Self {
+        match config {
+            AudioDownmixConfig::Standard => Self {
+                center: STANDARD_DOWNMIX_LEVEL,
+                surround: STANDARD_DOWNMIX_LEVEL,
+                lfe: 0.0,
+                normalize: true,
+            },
+            AudioDownmixConfig::Custom {
+                center_level,
+                surround_level,
+                lfe_level,
+                normalize,
+            } => Self {
+                center: *center_level,
+                surround: *surround_level,
+                lfe: *lfe_level,
+                normalize: *normalize,
+            },
+        }
+    }
+}
+
+// (left, right) levels of a channel when folded into stereo
+fn stereo_levels(speaker: Speaker, levels: &DownmixLevels) -> (f32, f32) {
+    match speaker {
+        Speaker::Mono => (1.0, 1.0),
+        Speaker::FrontLeft => (1.0, 0.0),
+        Speaker::FrontRight => (0.0, 1.0),
+        Speaker::FrontCenter => (levels.center, levels.center),
+        Speaker::LowFrequency => (levels.lfe, levels.lfe),
+        Speaker::BackLeft | Speaker::SideLeft => (levels.surround, 0.0),
+        Speaker::BackRight | Speaker::SideRight => (0.0, levels.surround),
+    }
+}
+
+// Level of an input channel in an output channel, when the output layout is not mono or stereo.
+// Channels are not synthesized, missing channels are left silent.
+fn upmix_level(input: Speaker, output: Speaker, output_layout: ChannelLayout) -> f32 {
+    let has_side_channels = output_layout.speakers().contains(&Speaker::SideLeft);
+
+    match (input, output) {
+        (input, output) if input == output => 1.0,
+        (Speaker::Mono, Speaker::FrontLeft | Speaker::FrontRight) => 1.0,
+        (Speaker::SideLeft, Speaker::BackLeft) | (Speaker::SideRight, Speaker::BackRight)
+            if !has_side_channels =>
+        {
+            1.0
+        }
+        _ => 0.0,
+    }
+}
+
+// Converts interleaved samples between channel layouts
+pub struct DownmixMatrix {
+    input_channels_count: usize,
+    // One row of input_channels_count coefficients for each output channel
+    coefficients: Vec<f32>,
+}
+
+impl DownmixMatrix {
+    pub fn new(input: ChannelLayout, output: ChannelLayout, config: &AudioDownmixConfig) -> Self {
+        let levels = DownmixLevels::from(config);
+
+        let input_speakers = input.speakers();
+        let mut rows = output
+            .speakers()
+            .iter()
+            .map(|&output_speaker| {
+                input_speakers
+                    .iter()
+                    .map(|&input_speaker| {
+                        if input == output {
+                            (input_speaker == output_speaker) as u8 as f32
+                        } else {
+                            let (left, right) = stereo_levels(input_speaker, &levels);
+                            match output_speaker {
+                                Speaker::Mono => (left + right) / 2.0,
+                                Speaker::FrontLeft if output == ChannelLayout::Stereo => left,
+                                Speaker::FrontRight if output == ChannelLayout::Stereo => right,
+                                output_speaker => {
+                                    upmix_level(input_speaker, output_speaker, output)
+                                }
+                            }
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        if levels.normalize {
+            for row in &mut rows {
+                let sum = row.iter().map(|c| c.abs()).sum::<f32>();
+                if sum > 1.0 {
+                    for coefficient in row {
+                        *coefficient /= sum;
+                    }
+                }
+            }
+        }
+
+        Self {
+            input_channels_count: input_speakers.len(),
+            coefficients: rows.concat(),
+        }
+    }
+
+    pub fn input_channels_count(&self) -> usize {
+        self.input_channels_count
+    }
+
+    pub fn apply(&self, samples: &[f32]) -> Vec<f32> {
+        samples
+            .chunks_exact(self.input_channels_count)
+            .flat_map(|input_frame| {
+                self.coefficients
+                    .chunks_exact(self.input_channels_count)
+                    .map(move |row| {
+                        row.iter()
+                            .zip(input_frame)
+                            .map(|(coefficient, sample)| coefficient * sample)
+                            .sum::<f32>()
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM_CONFIG: AudioDownmixConfig = AudioDownmixConfig::Custom {
+        center_level: 0.5,
+        surround_level: 0.25,
+        lfe_level: 0.1,
+        normalize: false,
+    };
+
+    fn assert_samples_eq(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_same_layout_is_identity() {
+        let matrix = DownmixMatrix::new(
+            ChannelLayout::Surround51,
+            ChannelLayout::Surround51,
+            &AudioDownmixConfig::Standard,
+        );
+        let samples = [
+            0.1, 0.2, 0.3, 0.4, 0.5, 0.6, -0.1, -0.2, -0.3, -0.4, -0.5, -0.6,
+        ];
+
+        assert_samples_eq(&matrix.apply(&samples), &samples);
+    }
+
+    #[test]
+    fn test_surround51_to_stereo_custom() {
+        let matrix = DownmixMatrix::new(
+            ChannelLayout::Surround51,
+            ChannelLayout::Stereo,
+            &CUSTOM_CONFIG,
+        );
+
+        // FL, FR, FC, LFE, BL, BR
+        let samples = [0.2, 0.4, 0.5, 1.0, 0.4, 0.8];
+        assert_samples_eq(
+            &matrix.apply(&samples),
+            &[
+                0.2 + 0.5 * 0.5 + 0.1 * 1.0 + 0.25 * 0.4,
+                0.4 + 0.5 * 0.5 + 0.1 * 1.0 + 0.25 * 0.8,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_standard_downmix_does_not_clip() {
+        let matrix = DownmixMatrix::new(
+            ChannelLayout::Surround71,
+            ChannelLayout::Stereo,
+            &AudioDownmixConfig::Standard,
+        );
+
+        let output = matrix.apply(&[1.0; 8]);
+        assert_eq!(output.len(), 2);
+        assert!(output.iter().all(|sample| *sample <= 1.0 + 1e-6));
+
+        // A centered source stays centered and the LFE is discarded
+        let output = matrix.apply(&[0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(output[0] > 0.0);
+        assert_samples_eq(&[output[0]], &[output[1]]);
+    }
+
+    #[test]
+    fn test_mono_and_stereo_conversions() {
+        let config = AudioDownmixConfig::Standard;
+
+        let matrix = DownmixMatrix::new(ChannelLayout::Mono, ChannelLayout::Stereo, &config);
+        assert_samples_eq(&matrix.apply(&[0.5, -0.25]), &[0.5, 0.5, -0.25, -0.25]);
+
+        let matrix = DownmixMatrix::new(ChannelLayout::Stereo, ChannelLayout::Mono, &config);
+        assert_samples_eq(&matrix.apply(&[0.5, -0.25]), &[0.125]);
+    }
+
+    #[test]
+    fn test_surround71_to_surround51_folds_side_channels() {
+        let matrix = DownmixMatrix::new(
+            ChannelLayout::Surround71,
+            ChannelLayout::Surround51,
+            &CUSTOM_CONFIG,
+        );
+
+        let samples = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.25, 0.125];
+        assert_samples_eq(
+            &matrix.apply(&samples),
+            &[0.1, 0.2, 0.3, 0.4, 0.5 + 0.25, 0.6 + 0.125],
+        );
+    }
+}
@@ -1,18 +1,30 @@
+mod av_sync;
+mod codec;
+mod downmix;
+#[cfg(target_os = "linux")]
+mod linux;
 #[cfg(windows)]
 mod windows;
 
+#[cfg(target_os = "linux")]
+pub use crate::linux::*;
 #[cfg(windows)]
 pub use crate::windows::*;
+pub use av_sync::*;
+pub use codec::*;
+pub use downmix::*;
 
 use alvr_common::{
     anyhow::{self, anyhow, bail, Context, Result},
-    info,
+    debug, info,
     once_cell::sync::Lazy,
     parking_lot::Mutex,
-    ConnectionError, ToAny,
+    warn, ConnectionError, ToAny,
 };
+use alvr_packets::AudioPacketHeader;
 use alvr_session::{
-    AudioBufferingConfig, CustomAudioDeviceConfig, LinuxAudioBackend, MicrophoneDevicesConfig,
+    AudioBufferingConfig, AudioCodecConfig, AudioDownmixConfig, CustomAudioDeviceConfig,
+    LinuxAudioBackend, MicrophoneDevicesConfig,
 };
 use alvr_sockets::{StreamReceiver, StreamSender};
 use cpal::{
@@ -24,7 +36,7 @@ use std::{
     collections::{HashMap, VecDeque},
     sync::Arc,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 static VIRTUAL_MICROPHONE_PAIRS: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
@@ -95,7 +107,8 @@ impl AudioDevice {
         let host = match linux_backend {
             Some(LinuxAudioBackend::Alsa) => cpal::host_from_id(cpal::HostId::Alsa).unwrap(),
             Some(LinuxAudioBackend::Jack) => cpal::host_from_id(cpal::HostId::Jack).unwrap(),
-            None => cpal::default_host(),
+            // Virtual devices are used instead, see VirtualAudioDevice
+            Some(LinuxAudioBackend::PipeWire) | None => cpal::default_host(),
         };
         #[cfg(not(target_os = "linux"))]
         let host = cpal::default_host();
@@ -138,7 +151,8 @@ impl AudioDevice {
         let host = match linux_backend {
             Some(LinuxAudioBackend::Alsa) => cpal::host_from_id(cpal::HostId::Alsa).unwrap(),
             Some(LinuxAudioBackend::Jack) => cpal::host_from_id(cpal::HostId::Jack).unwrap(),
-            None => cpal::default_host(),
+            // Virtual devices are used instead, see VirtualAudioDevice
+            Some(LinuxAudioBackend::PipeWire) | None => cpal::default_host(),
         };
         #[cfg(not(target_os = "linux"))]
         let host = cpal::default_host();
@@ -194,6 +208,15 @@ impl AudioDevice {
 
         Ok(config.sample_rate().0)
     }
+
+    pub fn input_channels_count(&self) -> Result<u16> {
+        let config = self
+            .inner
+            .default_input_config()
+            .or_else(|_| self.inner.default_output_config())?;
+
+        Ok(config.channels())
+    }
 }
 
 pub fn is_same_device(device1: &AudioDevice, device2: &AudioDevice) -> bool {
@@ -210,13 +233,19 @@ pub enum AudioRecordState {
     Err(Option<anyhow::Error>),
 }
 
+// stream_timestamp maps the capture instant of the samples to the timeline of the video
+// timestamps, if available
 #[allow(unused_variables)]
+#[allow(clippy::too_many_arguments)]
 pub fn record_audio_blocking(
     is_running: Arc<dyn Fn() -> bool + Send + Sync>,
-    mut sender: StreamSender<()>,
+    stream_timestamp: Arc<dyn Fn(Instant) -> Option<Duration> + Send + Sync>,
+    mut sender: StreamSender<AudioPacketHeader>,
     device: &AudioDevice,
-    channels_count: u16,
+    channel_layout: ChannelLayout,
     mute: bool,
+    codec_config: &AudioCodecConfig,
+    downmix_config: &AudioDownmixConfig,
 ) -> Result<()> {
     let config = device
         .inner
@@ -224,13 +253,14 @@ pub fn record_audio_blocking(
         // On Windows, loopback devices are not recognized as input devices. Use output config.
         .or_else(|_| device.inner.default_output_config())?;
 
-    if config.channels() > 2 {
-        // todo: handle more than 2 channels
+    let Some(device_layout) = ChannelLayout::from_channels_count(config.channels()) else {
         bail!(
-            "Audio devices with more than 2 channels are not supported. {}",
-            "Please turn off surround audio."
+            "Audio devices with {} channels are not supported",
+            config.channels()
         );
-    }
+    };
+    let downmix = (device_layout != channel_layout)
+        .then(|| DownmixMatrix::new(device_layout, channel_layout, downmix_config));
 
     let stream_config = StreamConfig {
         channels: config.channels(),
@@ -240,6 +270,12 @@ pub fn record_audio_blocking(
 
     let state = Arc::new(Mutex::new(AudioRecordState::Recording));
 
+    let mut encoder = AudioEncoder::new(
+        codec_config,
+        channel_layout.channels_count(),
+        config.sample_rate().0,
+    );
+
     let stream = device.inner.build_input_stream_raw(
         &stream_config,
         config.sample_format(),
@@ -247,36 +283,50 @@ pub fn record_audio_blocking(
             let state = Arc::clone(&state);
             let is_running = is_running.clone();
             move |data, _| {
-                let data = if config.sample_format() == SampleFormat::F32 {
+                let samples = if config.sample_format() == SampleFormat::F32 {
                     data.bytes()
                         .chunks_exact(4)
-                        .flat_map(|b| {
-                            f32::from_ne_bytes([b[0], b[1], b[2], b[3]])
-                                .to_sample::<i16>()
-                                .to_ne_bytes()
-                                .to_vec()
-                        })
-                        .collect()
+                        .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]).to_sample::<i16>())
+                        .collect::<Vec<_>>()
                 } else {
-                    data.bytes().to_vec()
+                    data.bytes()
+                        .chunks_exact(2)
+                        .map(|b| i16::from_ne_bytes([b[0], b[1]]))
+                        .collect()
                 };
 
-                let data = if config.channels() == 1 && channels_count == 2 {
-                    data.chunks_exact(2)
-                        .flat_map(|c| vec![c[0], c[1], c[0], c[1]])
-                        .collect()
-                } else if config.channels() == 2 && channels_count == 1 {
-                    data.chunks_exact(4)
-                        .flat_map(|c| vec![c[0], c[1]])
+                let samples = if let Some(downmix) = &downmix {
+                    let samples = samples
+                        .iter()
+                        .map(|sample| sample.to_sample::<f32>())
+                        .collect::<Vec<_>>();
+
+                    downmix
+                        .apply(&samples)
+                        .into_iter()
+                        .map(|sample| sample.to_sample::<i16>())
                         .collect()
                 } else {
-                    data
+                    samples
                 };
 
                 if is_running() {
-                    let mut buffer = sender.get_buffer(&()).unwrap();
-                    buffer.get_range_mut(0, data.len()).copy_from_slice(&data);
-                    sender.send(buffer).ok();
+                    let frames_count = samples.len() / channel_layout.channels_count() as usize;
+                    let capture_instant = Instant::now().checked_sub(
+                        Duration::from_secs(1) * frames_count as u32 / config.sample_rate().0,
+                    );
+                    let timestamp = capture_instant.and_then(&*stream_timestamp);
+
+                    let res = encoder.encode(&samples, timestamp, |header, payload| {
+                        let mut buffer = sender.get_buffer(header).unwrap();
+                        buffer
+                            .get_range_mut(0, payload.len())
+                            .copy_from_slice(payload);
+                        sender.send(buffer).ok();
+                    });
+                    if let Err(e) = res {
+                        *state.lock() = AudioRecordState::Err(Some(e));
+                    }
                 } else {
                     *state.lock() = AudioRecordState::ShouldStop;
                 }
@@ -347,15 +397,24 @@ pub fn get_next_frame_batch(
 // The receive loop is resposible for ensuring smooth transitions in case of disruptions (buffer
 // underflow, overflow, packet loss). In case the computation takes too much time, the audio
 // callback will gracefully handle an interruption, and the callback timing and sound wave
-// continuity will not be affected.
+// continuity will not be affected. Short losses concealed by the decoder are not disruptions.
+#[allow(clippy::too_many_arguments)]
 pub fn receive_samples_loop(
     is_running: impl Fn() -> bool,
-    receiver: &mut StreamReceiver<()>,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
     sample_buffer: Arc<Mutex<VecDeque<f32>>>,
     channels_count: usize,
     batch_frames_count: usize,
     average_buffer_frames_count: usize,
+    downmix_config: &AudioDownmixConfig,
+    av_sync: Option<&Mutex<AudioVideoSync>>,
 ) -> Result<()> {
+    let output_layout = ChannelLayout::from_channels_count(channels_count as _)
+        .context("Unsupported output channels count")?;
+
+    let mut decoder = AudioDecoder::default();
+    let mut downmix: Option<DownmixMatrix> = None;
+    let mut resampler = LinearResampler::default();
     let mut recovery_sample_buffer = vec![];
     while is_running() {
         let data = match receiver.recv(Duration::from_millis(500)) {
@@ -363,16 +422,71 @@ pub fn receive_samples_loop(
             Err(ConnectionError::TryAgain(_)) => continue,
             Err(ConnectionError::Other(e)) => return Err(e),
         };
-        let (_, packet) = data.get()?;
+        let (header, packet) = data.get()?;
 
-        let new_samples = packet
-            .chunks_exact(2)
-            .map(|c| i16::from_ne_bytes([c[0], c[1]]).to_sample::<f32>())
-            .collect::<Vec<_>>();
+        let decoded = match decoder.decode(&header, packet) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                warn!("Audio decoding failed: {e}");
+                continue;
+            }
+        };
+
+        let new_samples = if header.channels_count as usize == channels_count {
+            decoded.samples
+        } else {
+            if downmix.as_ref().map(|matrix| matrix.input_channels_count())
+                != Some(header.channels_count as usize)
+            {
+                let Some(input_layout) = ChannelLayout::from_channels_count(header.channels_count)
+                else {
+                    warn!(
+                        "Unsupported audio channels count: {}",
+                        header.channels_count
+                    );
+                    continue;
+                };
+
+                info!("Converting audio from {input_layout:?} to {output_layout:?}");
+                downmix = Some(DownmixMatrix::new(
+                    input_layout,
+                    output_layout,
+                    downmix_config,
+                ));
+            }
+
+            downmix.as_ref().unwrap().apply(&decoded.samples)
+        };
+
+        let new_samples = if let Some(av_sync) = av_sync {
+            let buffered_frames_count = sample_buffer.lock().len() / channels_count;
+
+            let mut av_sync = av_sync.lock();
+            if let Some(timestamp) = header.timestamp {
+                let playback_instant = Instant::now()
+                    + Duration::from_secs(1) * buffered_frames_count as u32 / header.sample_rate;
+                av_sync.report_audio_playback(timestamp, playback_instant);
+            }
+
+            // Do not shrink the buffer to the point of causing an underflow
+            let mut playback_rate = av_sync.playback_rate();
+            if playback_rate < 1.0 && buffered_frames_count < 2 * batch_frames_count {
+                playback_rate = 1.0;
+            }
+
+            resampler.process(&new_samples, channels_count, playback_rate)
+        } else {
+            new_samples
+        };
+
+        let had_packet_loss = data.had_packet_loss() && !decoded.concealed_loss;
+        if decoded.concealed_loss {
+            debug!("Audio packet loss concealed");
+        }
 
         let mut sample_buffer_ref = sample_buffer.lock();
 
-        if data.had_packet_loss() {
+        if had_packet_loss {
             info!("Audio packet loss!");
 
             if sample_buffer_ref.len() / channels_count < batch_frames_count {
@@ -389,7 +503,7 @@ pub fn receive_samples_loop(
             recovery_sample_buffer.extend(sample_buffer_ref.drain(..));
         }
 
-        if sample_buffer_ref.len() == 0 || data.had_packet_loss() {
+        if sample_buffer_ref.len() == 0 || had_packet_loss {
             recovery_sample_buffer.extend(&new_samples);
 
             if recovery_sample_buffer.len() / channels_count
@@ -403,8 +517,7 @@ pub fn receive_samples_loop(
                     }
                 }
 
-                if data.had_packet_loss()
-                    && sample_buffer_ref.len() / channels_count == batch_frames_count
+                if had_packet_loss && sample_buffer_ref.len() / channels_count == batch_frames_count
                 {
                     // Add a fade-out to make a cross-fade.
                     for f in 0..batch_frames_count {
@@ -498,13 +611,16 @@ impl Iterator for StreamingSource {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn play_audio_loop(
     is_running: impl Fn() -> bool,
     device: &AudioDevice,
     channels_count: u16,
     sample_rate: u32,
     config: AudioBufferingConfig,
-    receiver: &mut StreamReceiver<()>,
+    downmix_config: &AudioDownmixConfig,
+    av_sync: Option<&Mutex<AudioVideoSync>>,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
 ) -> Result<()> {
     // Size of a chunk of frames. It corresponds to the duration if a fade-in/out in frames.
     let batch_frames_count = sample_rate as usize * config.batch_ms as usize / 1000;
@@ -533,6 +649,8 @@ pub fn play_audio_loop(
         channels_count as _,
         batch_frames_count,
         average_buffer_frames_count,
+        downmix_config,
+        av_sync,
     )
     .ok();
 
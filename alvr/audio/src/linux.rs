@@ -0,0 +1,387 @@
+// Virtual devices are created through the PulseAudio protocol, which is also served by PipeWire
+// (pipewire-pulse). Modules are loaded with pactl and unloaded when the device is dropped.
+
+use crate::{get_next_frame_batch, receive_samples_loop, AudioEncoder, ChannelLayout};
+use alvr_common::{
+    anyhow::{bail, Context, Result},
+    info,
+    parking_lot::Mutex,
+    warn,
+};
+use alvr_packets::AudioPacketHeader;
+use alvr_session::{AudioBufferingConfig, AudioCodecConfig, AudioDownmixConfig};
+use alvr_sockets::{StreamReceiver, StreamSender};
+use libpulse_binding::{
+    channelmap::{Map, MapDef},
+    def::BufferAttr,
+    sample::{Format, Spec},
+    stream::Direction,
+};
+use libpulse_simple_binding::Simple;
+use std::{
+    collections::VecDeque,
+    process::Command,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const GAME_AUDIO_SINK_NAME: &str = "alvr_game_audio";
+const MICROPHONE_SINK_NAME: &str = "alvr_microphone_sink";
+const MICROPHONE_SOURCE_NAME: &str = "alvr_microphone";
+const VIRTUAL_DEVICE_SAMPLE_RATE: u32 = 48000;
+// Duration of a read from the game audio sink
+const RECORD_CHUNK_MS: u32 = 10;
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .context("pactl not found. Please install pulseaudio-utils")?;
+
+    if !output.status.success() {
+        bail!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn load_module(name: &str, args: &[String]) -> Result<u32> {
+    let mut pactl_args = vec!["load-module", name];
+    pactl_args.extend(args.iter().map(String::as_str));
+
+    pactl(&pactl_args)?
+        .parse()
+        .context("Invalid module index returned by pactl")
+}
+
+// Parse the output of "pactl list short modules", in the format "<index>\t<name>\t<arguments>", and
+// return the indices of the modules that create a device with the given name
+fn modules_of_device<'a>(modules: &'a str, device_name: &str) -> Vec<&'a str> {
+    modules
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (index, arguments) = (fields.next()?, fields.nth(1)?);
+
+            arguments
+                .split_whitespace()
+                .any(|argument| argument.ends_with(&format!("_name={device_name}")))
+                .then_some(index)
+        })
+        .collect()
+}
+
+// Modules left behind by a crash would shadow the new devices
+fn unload_stale_modules(device_name: &str) {
+    let Ok(modules) = pactl(&["list", "short", "modules"]) else {
+        return;
+    };
+
+    for index in modules_of_device(&modules, device_name) {
+        warn!("Removing stale audio device {device_name}");
+        pactl(&["unload-module", index]).ok();
+    }
+}
+
+fn pulse_channel_map(layout: ChannelLayout) -> &'static str {
+    match layout {
+        ChannelLayout::Mono => "mono",
+        ChannelLayout::Stereo => "front-left,front-right",
+        ChannelLayout::Surround51 => "front-left,front-right,front-center,lfe,rear-left,rear-right",
+        ChannelLayout::Surround71 => {
+            "front-left,front-right,front-center,lfe,rear-left,rear-right,side-left,side-right"
+        }
+    }
+}
+
+// Same channel order of ChannelLayout
+fn stream_channel_map(layout: ChannelLayout) -> Result<Map> {
+    let mut map = Map::default();
+    map.init_auto(layout.channels_count() as u8, MapDef::WAVEEx)
+        .context("Invalid channel map")?;
+
+    Ok(map)
+}
+
+// A virtual device that exists as long as this object is alive. It is set as the default device of
+// its kind, so applications use it without further configuration.
+pub struct VirtualAudioDevice {
+    // Name of the device used for streaming audio to or from it
+    stream_device_name: String,
+    channel_layout: ChannelLayout,
+    module_indices: Vec<u32>,
+    previous_default: Option<(&'static str, String)>,
+}
+
+impl VirtualAudioDevice {
+    // Applications play audio to this sink, and its monitor is recorded by ALVR
+    pub fn new_game_audio_sink(channel_layout: ChannelLayout) -> Result<Self> {
+        unload_stale_modules(GAME_AUDIO_SINK_NAME);
+
+        let module_index = load_module(
+            "module-null-sink",
+            &[
+                format!("sink_name={GAME_AUDIO_SINK_NAME}"),
+                "sink_properties='device.description=\"ALVR Game Audio\"'".into(),
+                format!("rate={VIRTUAL_DEVICE_SAMPLE_RATE}"),
+                format!("channels={}", channel_layout.channels_count()),
+                format!("channel_map={}", pulse_channel_map(channel_layout)),
+            ],
+        )?;
+
+        let mut device = Self {
+            stream_device_name: format!("{GAME_AUDIO_SINK_NAME}.monitor"),
+            channel_layout,
+            module_indices: vec![module_index],
+            previous_default: None,
+        };
+        device.set_default("sink", GAME_AUDIO_SINK_NAME);
+
+        Ok(device)
+    }
+
+    // ALVR plays the headset microphone to a hidden sink. Applications record from a source
+    // remapped from its monitor
+    pub fn new_microphone_source() -> Result<Self> {
+        unload_stale_modules(MICROPHONE_SOURCE_NAME);
+        unload_stale_modules(MICROPHONE_SINK_NAME);
+
+        let sink_module_index = load_module(
+            "module-null-sink",
+            &[
+                format!("sink_name={MICROPHONE_SINK_NAME}"),
+                "sink_properties='device.description=\"ALVR Microphone Sink\"'".into(),
+                "channels=1".into(),
+                format!("channel_map={}", pulse_channel_map(ChannelLayout::Mono)),
+            ],
+        )?;
+
+        let mut device = Self {
+            stream_device_name: MICROPHONE_SINK_NAME.into(),
+            channel_layout: ChannelLayout::Mono,
+            module_indices: vec![sink_module_index],
+            previous_default: None,
+        };
+
+        let source_module_index = load_module(
+            "module-remap-source",
+            &[
+                format!("master={MICROPHONE_SINK_NAME}.monitor"),
+                format!("source_name={MICROPHONE_SOURCE_NAME}"),
+                "source_properties='device.description=\"ALVR Microphone\"'".into(),
+            ],
+        )?;
+        device.module_indices.push(source_module_index);
+        device.set_default("source", MICROPHONE_SOURCE_NAME);
+
+        Ok(device)
+    }
+
+    fn set_default(&mut self, kind: &'static str, name: &str) {
+        let previous = pactl(&[&format!("get-default-{kind}")]).ok();
+
+        if let Err(e) = pactl(&[&format!("set-default-{kind}"), name]) {
+            warn!("Cannot set {name} as the default {kind}: {e}");
+        } else if let Some(previous) = previous.filter(|previous| previous != name) {
+            self.previous_default = Some((kind, previous));
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        VIRTUAL_DEVICE_SAMPLE_RATE
+    }
+
+    pub fn channel_layout(&self) -> ChannelLayout {
+        self.channel_layout
+    }
+}
+
+impl Drop for VirtualAudioDevice {
+    fn drop(&mut self) {
+        // Unloading a default device would make the server pick a random one
+        if let Some((kind, previous)) = &self.previous_default {
+            pactl(&[&format!("set-default-{kind}"), previous]).ok();
+        }
+
+        for index in self.module_indices.iter().rev() {
+            if let Err(e) = pactl(&["unload-module", &index.to_string()]) {
+                warn!("Failed to remove virtual audio device: {e}");
+            }
+        }
+    }
+}
+
+pub fn record_virtual_sink_blocking(
+    is_running: Arc<dyn Fn() -> bool + Send + Sync>,
+    stream_timestamp: Arc<dyn Fn(Instant) -> Option<Duration> + Send + Sync>,
+    mut sender: StreamSender<AudioPacketHeader>,
+    device: &VirtualAudioDevice,
+    codec_config: &AudioCodecConfig,
+) -> Result<()> {
+    let channels_count = device.channel_layout.channels_count();
+    let sample_rate = device.sample_rate();
+
+    let spec = Spec {
+        format: Format::S16NE,
+        channels: channels_count as u8,
+        rate: sample_rate,
+    };
+    let chunk_frames_count = sample_rate * RECORD_CHUNK_MS / 1000;
+    let mut buffer = vec![0; chunk_frames_count as usize * channels_count as usize * 2];
+
+    // By default the server sends data in large fragments, adding latency
+    let buffer_attributes = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: u32::MAX,
+        prebuf: u32::MAX,
+        minreq: u32::MAX,
+        fragsize: buffer.len() as u32,
+    };
+    let stream = Simple::new(
+        None,
+        "ALVR",
+        Direction::Record,
+        Some(device.stream_device_name.as_str()),
+        "Game audio",
+        &spec,
+        Some(&stream_channel_map(device.channel_layout)?),
+        Some(&buffer_attributes),
+    )?;
+
+    let mut encoder = AudioEncoder::new(codec_config, channels_count, sample_rate);
+
+    while is_running() {
+        stream.read(&mut buffer)?;
+
+        let timestamp = Instant::now()
+            .checked_sub(Duration::from_millis(RECORD_CHUNK_MS as u64))
+            .and_then(&*stream_timestamp);
+
+        let samples = buffer
+            .chunks_exact(2)
+            .map(|b| i16::from_ne_bytes([b[0], b[1]]))
+            .collect::<Vec<_>>();
+
+        encoder.encode(&samples, timestamp, |header, payload| {
+            let mut buffer = sender.get_buffer(header).unwrap();
+            buffer
+                .get_range_mut(0, payload.len())
+                .copy_from_slice(payload);
+            sender.send(buffer).ok();
+        })?;
+    }
+
+    Ok(())
+}
+
+pub fn play_virtual_source_loop(
+    is_running: impl Fn() -> bool + Sync,
+    device: &VirtualAudioDevice,
+    sample_rate: u32,
+    config: AudioBufferingConfig,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
+) -> Result<()> {
+    if sample_rate < 8000 {
+        bail!("Invalid audio sample rate");
+    }
+
+    let channels_count = device.channel_layout.channels_count() as usize;
+    let batch_frames_count = sample_rate as usize * config.batch_ms as usize / 1000;
+    let average_buffer_frames_count =
+        sample_rate as usize * config.average_buffering_ms as usize / 1000;
+
+    let spec = Spec {
+        format: Format::FLOAT32NE,
+        channels: channels_count as u8,
+        rate: sample_rate,
+    };
+    // Keep the server side buffer small, the buffering is handled by receive_samples_loop
+    let batch_bytes_count = (batch_frames_count * channels_count * 4) as u32;
+    let buffer_attributes = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: 2 * batch_bytes_count,
+        prebuf: u32::MAX,
+        minreq: batch_bytes_count,
+        fragsize: u32::MAX,
+    };
+    let stream = Simple::new(
+        None,
+        "ALVR",
+        Direction::Playback,
+        Some(device.stream_device_name.as_str()),
+        "Microphone",
+        &spec,
+        Some(&stream_channel_map(device.channel_layout)?),
+        Some(&buffer_attributes),
+    )?;
+
+    let sample_buffer = Arc::new(Mutex::new(VecDeque::new()));
+
+    info!("Playing microphone audio to {MICROPHONE_SOURCE_NAME}");
+
+    thread::scope(|s| {
+        // Writes block until the server has room for them, so this loop is paced by the device
+        let player_thread = s.spawn({
+            let is_running = &is_running;
+            let sample_buffer = Arc::clone(&sample_buffer);
+            move || -> Result<()> {
+                while is_running() {
+                    let batch = get_next_frame_batch(
+                        &mut sample_buffer.lock(),
+                        channels_count,
+                        batch_frames_count,
+                    );
+
+                    let bytes = batch
+                        .iter()
+                        .flat_map(|sample| sample.to_ne_bytes())
+                        .collect::<Vec<_>>();
+                    stream.write(&bytes)?;
+                }
+
+                Ok(())
+            }
+        });
+
+        receive_samples_loop(
+            &is_running,
+            receiver,
+            sample_buffer,
+            channels_count,
+            batch_frames_count,
+            average_buffer_frames_count,
+            &AudioDownmixConfig::Standard,
+            None,
+        )
+        .ok();
+
+        player_thread.join().unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modules_of_device() {
+        let modules = "\
+            6\tmodule-always-sink\t\n\
+            23\tmodule-null-sink\tsink_name=alvr_game_audio sink_properties='device.description=\"ALVR Game Audio\"' rate=48000 channels=2 channel_map=front-left,front-right\n\
+            24\tmodule-null-sink\tsink_name=alvr_microphone_sink channels=1 channel_map=mono\n\
+            25\tmodule-remap-source\tmaster=alvr_microphone_sink.monitor source_name=alvr_microphone\n\
+            26\tmodule-null-sink\tsink_name=other_alvr_game_audio\n\
+            27\tmodule-null-sink\n";
+
+        assert_eq!(modules_of_device(modules, GAME_AUDIO_SINK_NAME), ["23"]);
+        assert_eq!(modules_of_device(modules, MICROPHONE_SINK_NAME), ["24"]);
+        assert_eq!(modules_of_device(modules, MICROPHONE_SOURCE_NAME), ["25"]);
+        assert!(modules_of_device(modules, "alvr").is_empty());
+        assert!(modules_of_device("", GAME_AUDIO_SINK_NAME).is_empty());
+    }
+}
@@ -1,21 +1,30 @@
-use alvr_audio::{AudioDevice, AudioRecordState};
+use alvr_audio::{AudioDevice, AudioEncoder, AudioRecordState, AudioVideoSync, ChannelLayout};
 use alvr_common::{
     anyhow::{bail, Result},
     parking_lot::Mutex,
     ToAny,
 };
-use alvr_session::AudioBufferingConfig;
+use alvr_packets::AudioPacketHeader;
+use alvr_session::{AudioBufferingConfig, AudioCodecConfig, AudioDownmixConfig};
 use alvr_sockets::{StreamReceiver, StreamSender};
 use oboe::{
     AudioInputCallback, AudioInputStreamSafe, AudioOutputCallback, AudioOutputStreamSafe,
     AudioStream, AudioStreamBuilder, DataCallbackResult, InputPreset, Mono, PerformanceMode,
     SampleRateConversionQuality, Stereo, Usage,
 };
-use std::{collections::VecDeque, mem, sync::Arc, thread, time::Duration};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 struct RecorderCallback {
     is_running: Arc<dyn Fn() -> bool + Send + Sync>,
-    sender: StreamSender<()>,
+    stream_timestamp: Arc<dyn Fn(Instant) -> Option<Duration> + Send + Sync>,
+    sender: StreamSender<AudioPacketHeader>,
+    encoder: AudioEncoder,
+    sample_rate: u32,
     state: Arc<Mutex<AudioRecordState>>,
 }
 
@@ -27,20 +36,27 @@ impl AudioInputCallback for RecorderCallback {
         _: &mut dyn AudioInputStreamSafe,
         frames: &[i16],
     ) -> DataCallbackResult {
-        let mut sample_buffer = Vec::with_capacity(frames.len() * mem::size_of::<i16>());
-
-        for frame in frames {
-            sample_buffer.extend(&frame.to_ne_bytes());
-        }
-
         if (self.is_running)() {
-            let mut buffer = self.sender.get_buffer(&()).unwrap();
-            buffer
-                .get_range_mut(0, sample_buffer.len())
-                .copy_from_slice(&sample_buffer);
-            self.sender.send(buffer).ok();
-
-            DataCallbackResult::Continue
+            let timestamp = Instant::now()
+                .checked_sub(Duration::from_secs(1) * frames.len() as u32 / self.sample_rate)
+                .and_then(&*self.stream_timestamp);
+
+            let sender = &mut self.sender;
+            let res = self.encoder.encode(frames, timestamp, |header, payload| {
+                let mut buffer = sender.get_buffer(header).unwrap();
+                buffer
+                    .get_range_mut(0, payload.len())
+                    .copy_from_slice(payload);
+                sender.send(buffer).ok();
+            });
+
+            if let Err(e) = res {
+                *self.state.lock() = AudioRecordState::Err(Some(e));
+
+                DataCallbackResult::Stop
+            } else {
+                DataCallbackResult::Continue
+            }
         } else {
             *self.state.lock() = AudioRecordState::ShouldStop;
 
@@ -58,12 +74,16 @@ impl AudioInputCallback for RecorderCallback {
 }
 
 #[allow(unused_variables)]
+#[allow(clippy::too_many_arguments)]
 pub fn record_audio_blocking(
     is_running: Arc<dyn Fn() -> bool + Send + Sync>,
-    sender: StreamSender<()>,
+    stream_timestamp: Arc<dyn Fn(Instant) -> Option<Duration> + Send + Sync>,
+    sender: StreamSender<AudioPacketHeader>,
     device: &AudioDevice,
-    channels_count: u16,
+    channel_layout: ChannelLayout,
     mute: bool,
+    codec_config: &AudioCodecConfig,
+    downmix_config: &AudioDownmixConfig,
 ) -> Result<()> {
     let sample_rate = device.input_sample_rate()?;
 
@@ -81,7 +101,10 @@ pub fn record_audio_blocking(
         .set_input_preset(InputPreset::VoiceCommunication)
         .set_callback(RecorderCallback {
             is_running: Arc::clone(&is_running),
+            stream_timestamp,
             sender,
+            encoder: AudioEncoder::new(codec_config, 1, sample_rate),
+            sample_rate,
             state: Arc::clone(&state),
         })
         .open_stream()?;
@@ -133,13 +156,16 @@ impl AudioOutputCallback for PlayerCallback {
 }
 
 #[allow(unused_variables)]
+#[allow(clippy::too_many_arguments)]
 pub fn play_audio_loop(
     is_running: impl Fn() -> bool,
     device: &AudioDevice,
     channels_count: u16,
     sample_rate: u32,
     config: AudioBufferingConfig,
-    receiver: &mut StreamReceiver<()>,
+    downmix_config: &AudioDownmixConfig,
+    av_sync: Option<&Mutex<AudioVideoSync>>,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
 ) -> Result<()> {
     // the client sends invalid sample rates sometimes, and we crash if we try and use one
     // (batch_frames_count ends up zero and the audio callback gets confused)
@@ -178,6 +204,8 @@ pub fn play_audio_loop(
         2,
         batch_frames_count,
         average_buffer_frames_count,
+        downmix_config,
+        av_sync,
     )
     .ok();
 
@@ -410,6 +410,13 @@ pub extern "C" fn alvr_report_submit(target_timestamp_ns: u64, vsync_queue_ns: u
     );
 }
 
+/// Call only on an explicit user action, after the user compared the pairing code with the one on
+/// the dashboard
+#[no_mangle]
+pub extern "C" fn alvr_confirm_pairing() {
+    crate::confirm_pairing();
+}
+
 /// Call only with external decoder
 #[no_mangle]
 pub extern "C" fn alvr_request_idr() {
@@ -6,28 +6,29 @@ use crate::{
     platform,
     sockets::AnnouncerSocket,
     statistics::StatisticsManager,
-    storage::Config,
+    storage::{self, Config},
     ClientCoreEvent, EVENT_QUEUE, LIFECYCLE_STATE, STATISTICS_MANAGER,
 };
-use alvr_audio::AudioDevice;
+use alvr_audio::{AudioDevice, AudioVideoSync, ChannelLayout};
 use alvr_common::{
     debug, error,
     glam::UVec2,
     info,
     once_cell::sync::Lazy,
-    parking_lot::{Condvar, RwLock},
+    parking_lot::{Condvar, Mutex, RwLock},
     wait_rwlock, warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState,
     OptLazy, ToCon, ALVR_VERSION,
 };
 use alvr_packets::{
-    ClientConnectionResult, ClientControlPacket, ClientStatistics, Haptics, ServerControlPacket,
-    StreamConfigPacket, Tracking, VideoPacketHeader, VideoStreamingCapabilities, AUDIO, HAPTICS,
-    STATISTICS, TRACKING, VIDEO,
+    ClientConnectionResult, ClientControlPacket, ClientStatistics, Haptics, PairingStatus,
+    ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader,
+    VideoStreamingCapabilities, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
 };
-use alvr_session::{settings_schema::Switch, SessionConfig};
+use alvr_session::{settings_schema::Switch, AudioDownmixConfig, SessionConfig};
 use alvr_sockets::{
-    ControlSocketSender, PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder,
-    KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
+    CaptureSide, ControlSocketSender, Identity, PacketDirection, PacketRecorder, PeerType,
+    ProtoControlSocket, PublicKey, StreamSender, StreamSocketBuilder, KEEPALIVE_INTERVAL,
+    KEEPALIVE_TIMEOUT,
 };
 use serde_json as json;
 use std::{
@@ -47,6 +48,18 @@ const INITIAL_MESSAGE: &str = concat!(
     "Open ALVR on your PC then click \"Trust\"\n",
     "next to the client entry",
 );
+const PAIRING_MESSAGE: &str = concat!(
+    "Check that the dashboard on the PC shows the same\n",
+    "pairing code, then click \"Trust\" on the PC and\n",
+    "press the trigger to trust the PC on this headset\n",
+    "Pairing code:",
+);
+const PAIRING_CONFIRMED_MESSAGE: &str = "The streamer is trusted\nWaiting for connection...";
+const UNKNOWN_SERVER_MESSAGE: &str = concat!(
+    "The streamer identity is not trusted\n",
+    "Remove this client from the dashboard\n",
+    "on the PC and pair it again",
+);
 const NETWORK_UNREACHABLE_MESSAGE: &str = "Cannot connect to the internet";
 // const INCOMPATIBLE_VERSIONS_MESSAGE: &str = concat!(
 //     "Streamer and client have\n",
@@ -63,6 +76,7 @@ const DISCOVERY_RETRY_PAUSE: Duration = Duration::from_millis(500);
 const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const CONNECTION_RETRY_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
+const PAIRING_CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
 const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
 
 const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
@@ -77,6 +91,16 @@ pub static TRACKING_SENDER: OptLazy<StreamSender<Tracking>> = alvr_common::lazy_
 pub static STATISTICS_SENDER: OptLazy<StreamSender<ClientStatistics>> =
     alvr_common::lazy_mut_none();
 
+// Streamer whose pairing code is shown to the user. It is trusted only when the user confirms the
+// code on the headset, since the streamer could lie about having been trusted on the dashboard.
+// It is not persisted: the code can be confirmed only while it is shown.
+struct PendingPairing {
+    server_key: PublicKey,
+    deadline: Instant,
+}
+
+static PENDING_PAIRING: Mutex<Option<PendingPairing>> = Mutex::new(None);
+
 fn set_hud_message(message: &str) {
     let message = format!(
         "ALVR v{}\nhostname: {}\nIP: {}\n\n{message}",
@@ -120,6 +144,28 @@ pub fn connection_lifecycle_loop(
     }
 }
 
+pub fn confirm_pairing() {
+    let Some(pairing) = PENDING_PAIRING.lock().take() else {
+        return;
+    };
+
+    if pairing.deadline < Instant::now() {
+        info!("Pairing code expired");
+        set_hud_message(INITIAL_MESSAGE);
+
+        return;
+    }
+
+    let mut config = Config::load();
+    if !config.trusted_server_keys.contains(&pairing.server_key) {
+        config.trusted_server_keys.push(pairing.server_key);
+        config.store();
+    }
+
+    info!("Pairing confirmed. Saved streamer identity key");
+    set_hud_message(PAIRING_CONFIRMED_MESSAGE);
+}
+
 fn connection_pipeline(
     recommended_view_resolution: UVec2,
     supported_refresh_rates: Vec<f32>,
@@ -156,6 +202,57 @@ fn connection_pipeline(
         }
     };
 
+    let identity = Identity::load_or_generate(&storage::identity_key_path()).to_con()?;
+    let handshake = proto_control_socket.handshake(&identity, HANDSHAKE_ACTION_TIMEOUT)?;
+
+    match proto_control_socket.recv::<PairingStatus>(HANDSHAKE_ACTION_TIMEOUT)? {
+        PairingStatus::PairingRequired => {
+            let now = Instant::now();
+            let mut pending_pairing = PENDING_PAIRING.lock();
+
+            // The code shown to the user cannot be replaced by the one of another streamer,
+            // otherwise the user could compare the code of a streamer and trust another one
+            if let Some(pairing) = &*pending_pairing {
+                if pairing.server_key != handshake.peer_identity_key && pairing.deadline > now {
+                    warn!("Ignoring the pairing request of another streamer");
+
+                    return Ok(());
+                }
+            }
+
+            info!(
+                "Streamer requires pairing. Pairing code: {}",
+                handshake.pairing_code
+            );
+            *pending_pairing = Some(PendingPairing {
+                server_key: handshake.peer_identity_key,
+                deadline: now + PAIRING_CONFIRM_TIMEOUT,
+            });
+
+            set_hud_message(&format!("{PAIRING_MESSAGE} {}", handshake.pairing_code));
+
+            return Ok(());
+        }
+        PairingStatus::Paired => {
+            if !Config::load()
+                .trusted_server_keys
+                .contains(&handshake.peer_identity_key)
+            {
+                // Keep showing the pairing code until it is confirmed
+                if !PENDING_PAIRING
+                    .lock()
+                    .as_ref()
+                    .is_some_and(|pairing| pairing.deadline > Instant::now())
+                {
+                    warn!("Streamer presented an unknown identity key");
+                    set_hud_message(UNKNOWN_SERVER_MESSAGE);
+                }
+
+                return Ok(());
+            }
+        }
+    }
+
     let mut connection_state_lock = CONNECTION_STATE.write();
     let disconnect_notif = Arc::new(Condvar::new());
 
@@ -166,18 +263,17 @@ fn connection_pipeline(
         .input_sample_rate()
         .unwrap();
 
-    proto_control_socket
-        .send(&ClientConnectionResult::ConnectionAccepted {
-            client_protocol_id: alvr_common::protocol_id(),
-            display_name: platform::device_model(),
-            server_ip,
-            streaming_capabilities: Some(VideoStreamingCapabilities {
-                default_view_resolution: recommended_view_resolution,
-                supported_refresh_rates,
-                microphone_sample_rate,
-            }),
-        })
-        .to_con()?;
+    let connection_result = ClientConnectionResult::ConnectionAccepted {
+        client_protocol_id: alvr_common::protocol_id(),
+        display_name: platform::device_model(),
+        server_ip,
+        streaming_capabilities: Some(VideoStreamingCapabilities {
+            default_view_resolution: recommended_view_resolution,
+            supported_refresh_rates,
+            microphone_sample_rate,
+        }),
+    };
+    proto_control_socket.send(&connection_result).to_con()?;
     let config_packet =
         proto_control_socket.recv::<StreamConfigPacket>(HANDSHAKE_ACTION_TIMEOUT)?;
 
@@ -189,6 +285,31 @@ fn connection_pipeline(
         session_desc.to_settings()
     };
 
+    // The capture setting is known only after receiving the stream configuration, so the packets
+    // exchanged until now are recorded manually
+    let maybe_recorder = if settings.capture.packet_capture {
+        let path = storage::capture_path();
+        match PacketRecorder::create(&path, CaptureSide::Client) {
+            Ok(recorder) => {
+                recorder
+                    .record_control(PacketDirection::Sent, &connection_result)
+                    .to_con()?;
+                recorder
+                    .record_control(PacketDirection::Received, &config_packet)
+                    .to_con()?;
+                proto_control_socket.set_recorder(Arc::clone(&recorder));
+
+                Some(recorder)
+            }
+            Err(e) => {
+                error!("Failed to create packet capture file: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     let negotiated_config =
         json::from_str::<HashMap<String, json::Value>>(&config_packet.negotiated).to_con()?;
 
@@ -204,6 +325,10 @@ fn connection_pipeline(
         .get("game_audio_sample_rate")
         .and_then(|v| v.as_u64())
         .unwrap_or(44100) as u32;
+    let game_audio_channel_layout = negotiated_config
+        .get("game_audio_channel_layout")
+        .and_then(|v| json::from_value(v.clone()).ok())
+        .unwrap_or(ChannelLayout::Stereo);
 
     let streaming_start_event = ClientCoreEvent::StreamingStarted {
         view_resolution,
@@ -211,6 +336,16 @@ fn connection_pipeline(
         settings: Box::new(settings.clone()),
     };
 
+    let audio_video_sync = if let Switch::Enabled(config) = &settings.audio.game_audio {
+        config.audio_video_sync.as_option().map(|config| {
+            Arc::new(Mutex::new(AudioVideoSync::new(
+                config.target_offset_ms / 1000.0,
+            )))
+        })
+    } else {
+        None
+    };
+
     *STATISTICS_MANAGER.lock() = Some(StatisticsManager::new(
         settings.connection.statistics_history_size,
         Duration::from_secs_f32(1.0 / refresh_rate_hint),
@@ -219,6 +354,7 @@ fn connection_pipeline(
         } else {
             0.0
         },
+        audio_video_sync.clone(),
     ));
 
     let (mut control_sender, mut control_receiver) = proto_control_socket
@@ -268,8 +404,14 @@ fn connection_pipeline(
         settings.connection.stream_port,
         settings.connection.packet_size as _,
         HANDSHAKE_ACTION_TIMEOUT,
+        &handshake.stream_keys,
+        settings.debug.network_emulator.as_option(),
     )?;
 
+    if let Some(recorder) = maybe_recorder {
+        stream_socket.set_recorder(recorder);
+    }
+
     info!("Connected to server");
 
     {
@@ -280,8 +422,15 @@ fn connection_pipeline(
         config.options = settings.video.mediacodec_extra_options;
     }
 
-    let mut video_receiver =
-        stream_socket.subscribe_to_stream::<VideoPacketHeader>(VIDEO, MAX_UNREAD_PACKETS);
+    let mut video_receiver = stream_socket.subscribe_to_stream_with_fec::<VideoPacketHeader>(
+        VIDEO,
+        MAX_UNREAD_PACKETS,
+        settings
+            .connection
+            .video_fec
+            .as_option()
+            .map(|config| config.group_size as usize),
+    );
     let mut game_audio_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
     let tracking_sender = stream_socket.request_stream(TRACKING);
     let mut haptics_receiver =
@@ -308,8 +457,9 @@ fn connection_pipeline(
                 stream_corrupted = false;
             } else if data.had_packet_loss() {
                 stream_corrupted = true;
+                // The streamer schedules an IDR frame when receiving the error report
                 if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                    sender.send(&ClientControlPacket::RequestIdr).ok();
+                    sender.send(&ClientControlPacket::VideoErrorReport).ok();
                 }
                 warn!("Network dropped video packet");
             }
@@ -334,6 +484,11 @@ fn connection_pipeline(
     let game_audio_thread = if let Switch::Enabled(config) = settings.audio.game_audio {
         let device = AudioDevice::new_output(None, None).to_con()?;
 
+        // The audio output supports only stereo. Other layouts are converted by the receive loop
+        if game_audio_channel_layout != ChannelLayout::Stereo {
+            info!("Converting game audio from {game_audio_channel_layout:?} to stereo");
+        }
+
         thread::spawn(move || {
             while is_streaming() {
                 alvr_common::show_err(audio::play_audio_loop(
@@ -342,6 +497,8 @@ fn connection_pipeline(
                     2,
                     game_audio_sample_rate,
                     config.buffering.clone(),
+                    &config.downmix,
+                    audio_video_sync.as_deref(),
                     &mut game_audio_receiver,
                 ));
             }
@@ -350,7 +507,7 @@ fn connection_pipeline(
         thread::spawn(|| ())
     };
 
-    let microphone_thread = if matches!(settings.audio.microphone, Switch::Enabled(_)) {
+    let microphone_thread = if let Switch::Enabled(config) = settings.audio.microphone {
         let device = AudioDevice::new_input(None).to_con()?;
 
         let microphone_sender = stream_socket.request_stream(AUDIO);
@@ -359,10 +516,13 @@ fn connection_pipeline(
             while is_streaming() {
                 match audio::record_audio_blocking(
                     Arc::new(is_streaming),
+                    Arc::new(|_| None),
                     microphone_sender.clone(),
                     &device,
-                    1,
+                    ChannelLayout::Mono,
                     false,
+                    &config.codec,
+                    &AudioDownmixConfig::Standard,
                 ) {
                     Ok(()) => break,
                     Err(e) => {
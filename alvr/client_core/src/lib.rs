@@ -140,6 +140,12 @@ pub fn pause() {
     }
 }
 
+// Trust the streamer whose pairing code is shown in the HUD. Call only on an explicit user action
+// on the headset, after the user checked that the dashboard shows the same code
+pub fn confirm_pairing() {
+    connection::confirm_pairing();
+}
+
 pub fn poll_event() -> Option<ClientCoreEvent> {
     EVENT_QUEUE.lock().pop_front()
 }
@@ -1,7 +1,9 @@
-use alvr_common::SlidingWindowAverage;
+use alvr_audio::AudioVideoSync;
+use alvr_common::{parking_lot::Mutex, SlidingWindowAverage};
 use alvr_packets::ClientStatistics;
 use std::{
     collections::VecDeque,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -17,6 +19,7 @@ pub struct StatisticsManager {
     prev_vsync: Instant,
     total_pipeline_latency_average: SlidingWindowAverage<Duration>,
     steamvr_pipeline_latency: Duration,
+    audio_video_sync: Option<Arc<Mutex<AudioVideoSync>>>,
 }
 
 impl StatisticsManager {
@@ -24,6 +27,7 @@ impl StatisticsManager {
         max_history_size: usize,
         nominal_server_frame_interval: Duration,
         steamvr_pipeline_frames: f32,
+        audio_video_sync: Option<Arc<Mutex<AudioVideoSync>>>,
     ) -> Self {
         Self {
             max_history_size,
@@ -36,6 +40,7 @@ impl StatisticsManager {
             steamvr_pipeline_latency: Duration::from_secs_f32(
                 steamvr_pipeline_frames * nominal_server_frame_interval.as_secs_f32(),
             ),
+            audio_video_sync,
         }
     }
 
@@ -118,6 +123,12 @@ impl StatisticsManager {
             let vsync = now + vsync_queue;
             frame.client_stats.frame_interval = vsync.saturating_duration_since(self.prev_vsync);
             self.prev_vsync = vsync;
+
+            if let Some(av_sync) = &self.audio_video_sync {
+                let mut av_sync = av_sync.lock();
+                av_sync.report_video_display(target_timestamp, vsync);
+                frame.client_stats.audio_video_offset = av_sync.offset();
+            }
         }
     }
 
@@ -1,10 +1,15 @@
 use alvr_common::{error, info};
+use alvr_sockets::PublicKey;
 use app_dirs2::{AppDataType, AppInfo};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-fn config_path() -> PathBuf {
+fn config_dir() -> PathBuf {
     app_dirs2::app_root(
         AppDataType::UserConfig,
         &AppInfo {
@@ -13,13 +18,33 @@ fn config_path() -> PathBuf {
         },
     )
     .unwrap()
-    .join("session.json")
+}
+
+fn config_path() -> PathBuf {
+    config_dir().join("session.json")
+}
+
+pub fn identity_key_path() -> PathBuf {
+    config_dir().join("identity.key")
+}
+
+// Packet captures are saved next to the config, since the client has no log folder
+pub fn capture_path() -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    config_dir().join(format!("capture.{timestamp}.alvrcap"))
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub protocol_id: u64,
     pub hostname: String,
+    // Identity keys of the streamers this client has been paired with
+    #[serde(default)]
+    pub trusted_server_keys: Vec<PublicKey>,
 }
 
 impl Default for Config {
@@ -35,6 +60,7 @@ impl Default for Config {
                 rng.gen_range(0..10),
                 rng.gen_range(0..10),
             ),
+            trusted_server_keys: vec![],
         }
     }
 }
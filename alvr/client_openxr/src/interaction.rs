@@ -1,4 +1,4 @@
-use crate::{to_pose, to_quat, to_vec3, Platform, XrContext};
+use crate::{to_pose, to_quat, to_vec3, XrContext};
 use alvr_common::{glam::Vec3, *};
 use alvr_packets::{ButtonEntry, ButtonValue};
 use alvr_session::FaceTrackingSourcesConfig;
@@ -11,7 +11,6 @@ pub enum ButtonAction {
 }
 
 pub struct HandInteraction {
-    pub controllers_profile_id: u64,
     pub grip_action: xr::Action<xr::Posef>,
     pub grip_space: xr::Space,
     pub aim_action: xr::Action<xr::Posef>,
@@ -37,7 +36,6 @@ pub struct InteractionContext {
 
 pub fn initialize_interaction(
     xr_ctx: &XrContext,
-    platform: Platform,
     face_tracking_sources: Option<FaceTrackingSourcesConfig>,
 ) -> InteractionContext {
     let action_set = xr_ctx
@@ -45,48 +43,40 @@ pub fn initialize_interaction(
         .create_action_set("alvr_interaction", "ALVR interaction", 0)
         .unwrap();
 
-    let mut bindings = vec![];
-
     fn binding<'a, T: xr::ActionTy>(action: &'a xr::Action<T>, path: &str) -> xr::Binding<'a> {
         xr::Binding::new(action, action.instance().string_to_path(path).unwrap())
     }
 
-    let controllers_profile_path = match platform {
-        Platform::Quest => QUEST_CONTROLLER_PROFILE_PATH,
-        Platform::PicoNeo3 => PICO_NEO3_CONTROLLER_PROFILE_PATH,
-        Platform::Pico4 => PICO4_CONTROLLER_PROFILE_PATH,
-        Platform::Focus3 => FOCUS3_CONTROLLER_PROFILE_PATH,
-        Platform::Yvr => YVR_CONTROLLER_PROFILE_PATH,
-        _ => QUEST_CONTROLLER_PROFILE_PATH,
-    };
-    let controllers_profile_id = alvr_common::hash_string(controllers_profile_path);
-
     // Create actions:
 
+    // Bindings are suggested for every known profile, the runtime chooses the one matching the
+    // connected controllers. The buttons shared by multiple profiles use the same action
     let mut button_actions = HashMap::new();
-    for button_id in &CONTROLLER_PROFILE_INFO
-        .get(&controllers_profile_id)
-        .unwrap()
-        .button_set
-    {
-        let info = BUTTON_INFO.get(button_id).unwrap();
-
-        let name = info.path[1..].replace('/', "_");
-        let display_name = format!(
-            "{}{}",
-            name[0..1].to_uppercase(),
-            name[1..].replace('_', " ")
-        );
-
-        let action = match info.button_type {
-            ButtonType::Binary => {
-                ButtonAction::Binary(action_set.create_action(&name, &display_name, &[]).unwrap())
-            }
-            ButtonType::Scalar => {
-                ButtonAction::Scalar(action_set.create_action(&name, &display_name, &[]).unwrap())
+    for profile_info in BUILTIN_INTERACTION_PROFILES.profiles.values() {
+        for button_id in &profile_info.button_set {
+            if button_actions.contains_key(button_id) {
+                continue;
             }
-        };
-        button_actions.insert(*button_id, action);
+
+            let info = BUILTIN_INTERACTION_PROFILES.buttons.get(button_id).unwrap();
+
+            let name = info.path[1..].replace('/', "_");
+            let display_name = format!(
+                "{}{}",
+                name[0..1].to_uppercase(),
+                name[1..].replace('_', " ")
+            );
+
+            let action = match info.button_type {
+                ButtonType::Binary => ButtonAction::Binary(
+                    action_set.create_action(&name, &display_name, &[]).unwrap(),
+                ),
+                ButtonType::Scalar => ButtonAction::Scalar(
+                    action_set.create_action(&name, &display_name, &[]).unwrap(),
+                ),
+            };
+            button_actions.insert(*button_id, action);
+        }
     }
 
     let left_grip_action = action_set
@@ -112,53 +102,57 @@ pub fn initialize_interaction(
 
     // Create action bindings:
 
-    for (id, action) in &button_actions {
-        let path = &BUTTON_INFO.get(id).unwrap().path;
-        match action {
-            ButtonAction::Binary(action) => {
-                bindings.push(binding(action, path));
-            }
-            ButtonAction::Scalar(action) => {
-                bindings.push(binding(action, path));
+    for profile_info in BUILTIN_INTERACTION_PROFILES.profiles.values() {
+        let mut bindings = vec![];
+
+        for id in &profile_info.button_set {
+            let path = &BUILTIN_INTERACTION_PROFILES.buttons.get(id).unwrap().path;
+            match button_actions.get(id).unwrap() {
+                ButtonAction::Binary(action) => {
+                    bindings.push(binding(action, path));
+                }
+                ButtonAction::Scalar(action) => {
+                    bindings.push(binding(action, path));
+                }
             }
         }
-    }
 
-    bindings.push(binding(
-        &left_grip_action,
-        "/user/hand/left/input/grip/pose",
-    ));
-    bindings.push(binding(
-        &right_grip_action,
-        "/user/hand/right/input/grip/pose",
-    ));
-
-    bindings.push(binding(&left_aim_action, "/user/hand/left/input/aim/pose"));
-    bindings.push(binding(
-        &right_aim_action,
-        "/user/hand/right/input/aim/pose",
-    ));
-
-    bindings.push(binding(
-        &left_vibration_action,
-        "/user/hand/left/output/haptic",
-    ));
-    bindings.push(binding(
-        &right_vibration_action,
-        "/user/hand/right/output/haptic",
-    ));
-
-    // Apply bindings:
-    xr_ctx
-        .instance
-        .suggest_interaction_profile_bindings(
-            xr_ctx
-                .instance
-                .string_to_path(controllers_profile_path)
-                .unwrap(),
+        bindings.push(binding(
+            &left_grip_action,
+            "/user/hand/left/input/grip/pose",
+        ));
+        bindings.push(binding(
+            &right_grip_action,
+            "/user/hand/right/input/grip/pose",
+        ));
+
+        bindings.push(binding(&left_aim_action, "/user/hand/left/input/aim/pose"));
+        bindings.push(binding(
+            &right_aim_action,
+            "/user/hand/right/input/aim/pose",
+        ));
+
+        bindings.push(binding(
+            &left_vibration_action,
+            "/user/hand/left/output/haptic",
+        ));
+        bindings.push(binding(
+            &right_vibration_action,
+            "/user/hand/right/output/haptic",
+        ));
+
+        // Apply bindings. Profiles that need an extension not enabled or that the runtime does not
+        // know are rejected
+        if let Err(e) = xr_ctx.instance.suggest_interaction_profile_bindings(
+            xr_ctx.instance.string_to_path(&profile_info.path).unwrap(),
             &bindings,
-        )
-        .unwrap();
+        ) {
+            info!(
+                "Interaction profile {} not supported: {e}",
+                profile_info.name
+            );
+        }
+    }
 
     let combined_eyes_source = if face_tracking_sources
         .as_ref()
@@ -287,7 +281,6 @@ pub fn initialize_interaction(
         button_actions,
         hands_interaction: [
             HandInteraction {
-                controllers_profile_id,
                 grip_action: left_grip_action,
                 grip_space: left_grip_space,
                 aim_action: left_aim_action,
@@ -296,7 +289,6 @@ pub fn initialize_interaction(
                 skeleton_tracker: left_hand_tracker,
             },
             HandInteraction {
-                controllers_profile_id,
                 grip_action: right_grip_action,
                 grip_space: right_grip_space,
                 aim_action: right_aim_action,
@@ -315,6 +307,33 @@ pub fn initialize_interaction(
     }
 }
 
+// Profile chosen by the runtime for the controller of the hand. None if no controller is connected
+pub fn get_active_interaction_profile(xr_ctx: &XrContext, hand_path: &str) -> Option<u64> {
+    let profile_path = xr_ctx
+        .session
+        .current_interaction_profile(xr_ctx.instance.string_to_path(hand_path).unwrap())
+        .ok()?;
+    if profile_path == xr::Path::NULL {
+        return None;
+    }
+
+    let path = xr_ctx.instance.path_to_string(profile_path).ok()?;
+
+    Some(alvr_common::hash_string(&path))
+}
+
+// Sends the active profiles to the server, which adapts the button mappings to them
+pub fn send_active_interaction_profiles(xr_ctx: &XrContext) {
+    for (device_id, hand_path) in [
+        (*LEFT_HAND_ID, LEFT_HAND_PATH),
+        (*RIGHT_HAND_ID, RIGHT_HAND_PATH),
+    ] {
+        if let Some(profile_id) = get_active_interaction_profile(xr_ctx, hand_path) {
+            alvr_client_core::send_active_interaction_profile(device_id, profile_id);
+        }
+    }
+}
+
 pub fn get_hand_motion(
     xr_session: &xr::Session<xr::OpenGlEs>,
     reference_space: &xr::Space,
@@ -430,6 +449,31 @@ pub fn update_buttons(
     button_entries
 }
 
+// Used outside of the stream, where no other input is polled. The trigger is considered pressed
+// only when its value changed since the last poll, so a trigger held down does not confirm anything
+pub fn is_trigger_pressed(xr_ctx: &XrContext, interaction_ctx: &InteractionContext) -> bool {
+    // Syncing fails while the session is not focused, this is not an error
+    if xr_ctx
+        .session
+        .sync_actions(&[(&interaction_ctx.action_set).into()])
+        .is_err()
+    {
+        return false;
+    }
+
+    [*LEFT_TRIGGER_VALUE_ID, *RIGHT_TRIGGER_VALUE_ID]
+        .iter()
+        .any(|id| {
+            let Some(ButtonAction::Scalar(action)) = interaction_ctx.button_actions.get(id) else {
+                return false;
+            };
+
+            action
+                .state(&xr_ctx.session, xr::Path::NULL)
+                .is_ok_and(|state| state.changed_since_last_sync && state.current_state > 0.8)
+        })
+}
+
 pub fn get_eye_gazes(
     xr_session: &xr::Session<xr::OpenGlEs>,
     sources: &FaceSources,
@@ -520,14 +520,7 @@ fn initialize_stream(
             .map(|a| Vec2::new(a.width, a.height)),
     );
 
-    alvr_client_core::send_active_interaction_profile(
-        *LEFT_HAND_ID,
-        interaction_ctx.hands_interaction[0].controllers_profile_id,
-    );
-    alvr_client_core::send_active_interaction_profile(
-        *RIGHT_HAND_ID,
-        interaction_ctx.hands_interaction[1].controllers_profile_id,
-    );
+    interaction::send_active_interaction_profiles(xr_ctx);
 
     let running = Arc::new(RelaxedAtomic::new(true));
 
@@ -693,7 +686,6 @@ pub fn entry_point() {
 
         let interaction_context = Arc::new(interaction::initialize_interaction(
             &xr_ctx,
-            platform,
             stream_config
                 .as_ref()
                 .and_then(|c| c.face_sources_config.clone()),
@@ -804,7 +796,7 @@ pub fn entry_point() {
                         );
                     }
                     xr::Event::InteractionProfileChanged(_) => {
-                        // todo
+                        interaction::send_active_interaction_profiles(&xr_ctx);
                     }
                     xr::Event::PassthroughStateChangedFB(_) => {
                         // todo
@@ -988,6 +980,10 @@ pub fn entry_point() {
                 view_resolution = context.view_resolution;
                 swapchains = &context.swapchains;
             } else {
+                if interaction::is_trigger_pressed(&xr_ctx, &interaction_context) {
+                    alvr_client_core::confirm_pairing();
+                }
+
                 let (flags, maybe_views) = xr_session
                     .locate_views(
                         xr::ViewConfigurationType::PRIMARY_STEREO,
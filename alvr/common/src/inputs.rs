@@ -1,6 +1,8 @@
 use crate::hash_string;
+use anyhow::{bail, Result};
 use once_cell::sync::Lazy;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 // OpenXR interaction paths. They are used for the communication protocol and can also be used
 // directly for OpenXR interop.
@@ -8,6 +10,17 @@ pub const HEAD_PATH: &str = "/user/head";
 pub const LEFT_HAND_PATH: &str = "/user/hand/left";
 pub const RIGHT_HAND_PATH: &str = "/user/hand/right";
 
+// Extra tracked points used for full-body tracking. The paths follow the roles of
+// XR_HTCX_vive_tracker_interaction
+pub const WAIST_PATH: &str = "/user/vive_tracker_htcx/role/waist";
+pub const CHEST_PATH: &str = "/user/vive_tracker_htcx/role/chest";
+pub const LEFT_FOOT_PATH: &str = "/user/vive_tracker_htcx/role/left_foot";
+pub const RIGHT_FOOT_PATH: &str = "/user/vive_tracker_htcx/role/right_foot";
+pub const LEFT_KNEE_PATH: &str = "/user/vive_tracker_htcx/role/left_knee";
+pub const RIGHT_KNEE_PATH: &str = "/user/vive_tracker_htcx/role/right_knee";
+pub const LEFT_ELBOW_PATH: &str = "/user/vive_tracker_htcx/role/left_elbow";
+pub const RIGHT_ELBOW_PATH: &str = "/user/vive_tracker_htcx/role/right_elbow";
+
 pub const QUEST_CONTROLLER_PROFILE_PATH: &str = "/interaction_profiles/oculus/touch_controller";
 pub const VIVE_CONTROLLER_PROFILE_PATH: &str = "/interaction_profiles/htc/vive_controller";
 pub const INDEX_CONTROLLER_PROFILE_PATH: &str = "/interaction_profiles/valve/index_controller";
@@ -78,6 +91,15 @@ pub static HEAD_ID: Lazy<u64> = Lazy::new(|| hash_string(HEAD_PATH));
 pub static LEFT_HAND_ID: Lazy<u64> = Lazy::new(|| hash_string(LEFT_HAND_PATH));
 pub static RIGHT_HAND_ID: Lazy<u64> = Lazy::new(|| hash_string(RIGHT_HAND_PATH));
 
+pub static WAIST_ID: Lazy<u64> = Lazy::new(|| hash_string(WAIST_PATH));
+pub static CHEST_ID: Lazy<u64> = Lazy::new(|| hash_string(CHEST_PATH));
+pub static LEFT_FOOT_ID: Lazy<u64> = Lazy::new(|| hash_string(LEFT_FOOT_PATH));
+pub static RIGHT_FOOT_ID: Lazy<u64> = Lazy::new(|| hash_string(RIGHT_FOOT_PATH));
+pub static LEFT_KNEE_ID: Lazy<u64> = Lazy::new(|| hash_string(LEFT_KNEE_PATH));
+pub static RIGHT_KNEE_ID: Lazy<u64> = Lazy::new(|| hash_string(RIGHT_KNEE_PATH));
+pub static LEFT_ELBOW_ID: Lazy<u64> = Lazy::new(|| hash_string(LEFT_ELBOW_PATH));
+pub static RIGHT_ELBOW_ID: Lazy<u64> = Lazy::new(|| hash_string(RIGHT_ELBOW_PATH));
+
 pub static QUEST_CONTROLLER_PROFILE_ID: Lazy<u64> =
     Lazy::new(|| hash_string(QUEST_CONTROLLER_PROFILE_PATH));
 pub static VIVE_CONTROLLER_PROFILE_ID: Lazy<u64> =
@@ -164,742 +186,146 @@ pub static DEVICE_ID_TO_PATH: Lazy<HashMap<u64, &str>> = Lazy::new(|| {
         (*HEAD_ID, HEAD_PATH),
         (*LEFT_HAND_ID, LEFT_HAND_PATH),
         (*RIGHT_HAND_ID, RIGHT_HAND_PATH),
+        (*WAIST_ID, WAIST_PATH),
+        (*CHEST_ID, CHEST_PATH),
+        (*LEFT_FOOT_ID, LEFT_FOOT_PATH),
+        (*RIGHT_FOOT_ID, RIGHT_FOOT_PATH),
+        (*LEFT_KNEE_ID, LEFT_KNEE_PATH),
+        (*RIGHT_KNEE_ID, RIGHT_KNEE_PATH),
+        (*LEFT_ELBOW_ID, LEFT_ELBOW_PATH),
+        (*RIGHT_ELBOW_ID, RIGHT_ELBOW_PATH),
     ]
     .into_iter()
     .collect()
 });
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
 pub enum ButtonType {
     Binary,
     Scalar,
 }
 
+#[derive(Clone)]
 pub struct ButtonInfo {
-    pub path: &'static str,
+    pub path: String,
     pub button_type: ButtonType,
     pub device_id: u64,
 }
 
-pub static BUTTON_INFO: Lazy<HashMap<u64, ButtonInfo>> = Lazy::new(|| {
-    [
-        (
-            *HEAD_ENTER_CLICK_ID,
-            ButtonInfo {
-                path: HEAD_ENTER_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *HEAD_ID,
-            },
-        ),
-        (
-            *LEFT_SYSTEM_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_SYSTEM_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_SYSTEM_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_SYSTEM_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_MENU_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_MENU_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_BACK_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_BACK_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_A_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_A_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_A_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_A_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_B_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_B_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_B_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_B_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_X_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_X_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_X_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_X_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_Y_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_Y_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_Y_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_Y_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_SQUEEZE_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_SQUEEZE_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_SQUEEZE_VALUE_ID,
-            ButtonInfo {
-                path: LEFT_SQUEEZE_VALUE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_SQUEEZE_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_SQUEEZE_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_SQUEEZE_FORCE_ID,
-            ButtonInfo {
-                path: LEFT_SQUEEZE_FORCE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRIGGER_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_TRIGGER_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRIGGER_VALUE_ID,
-            ButtonInfo {
-                path: LEFT_TRIGGER_VALUE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRIGGER_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_TRIGGER_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_THUMBSTICK_X_ID,
-            ButtonInfo {
-                path: LEFT_THUMBSTICK_X_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_THUMBSTICK_Y_ID,
-            ButtonInfo {
-                path: LEFT_THUMBSTICK_Y_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_THUMBSTICK_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_THUMBSTICK_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_THUMBSTICK_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_THUMBSTICK_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRACKPAD_X_ID,
-            ButtonInfo {
-                path: LEFT_TRACKPAD_X_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRACKPAD_Y_ID,
-            ButtonInfo {
-                path: LEFT_TRACKPAD_Y_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRACKPAD_CLICK_ID,
-            ButtonInfo {
-                path: LEFT_TRACKPAD_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRACKPAD_FORCE_ID,
-            ButtonInfo {
-                path: LEFT_TRACKPAD_FORCE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_TRACKPAD_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_TRACKPAD_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *LEFT_THUMBREST_TOUCH_ID,
-            ButtonInfo {
-                path: LEFT_THUMBREST_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *LEFT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_SYSTEM_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_SYSTEM_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_SYSTEM_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_SYSTEM_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_MENU_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_MENU_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_BACK_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_BACK_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_A_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_A_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_A_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_A_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_B_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_B_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_B_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_B_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_SQUEEZE_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_SQUEEZE_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_SQUEEZE_VALUE_ID,
-            ButtonInfo {
-                path: RIGHT_SQUEEZE_VALUE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_SQUEEZE_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_SQUEEZE_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_SQUEEZE_FORCE_ID,
-            ButtonInfo {
-                path: RIGHT_SQUEEZE_FORCE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRIGGER_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_TRIGGER_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRIGGER_VALUE_ID,
-            ButtonInfo {
-                path: RIGHT_TRIGGER_VALUE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRIGGER_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_TRIGGER_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_THUMBSTICK_X_ID,
-            ButtonInfo {
-                path: RIGHT_THUMBSTICK_X_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_THUMBSTICK_Y_ID,
-            ButtonInfo {
-                path: RIGHT_THUMBSTICK_Y_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_THUMBSTICK_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_THUMBSTICK_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_THUMBSTICK_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_THUMBSTICK_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRACKPAD_X_ID,
-            ButtonInfo {
-                path: RIGHT_TRACKPAD_X_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRACKPAD_Y_ID,
-            ButtonInfo {
-                path: RIGHT_TRACKPAD_Y_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRACKPAD_CLICK_ID,
-            ButtonInfo {
-                path: RIGHT_TRACKPAD_CLICK_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRACKPAD_FORCE_ID,
-            ButtonInfo {
-                path: RIGHT_TRACKPAD_FORCE_PATH,
-                button_type: ButtonType::Scalar,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_TRACKPAD_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_TRACKPAD_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-        (
-            *RIGHT_THUMBREST_TOUCH_ID,
-            ButtonInfo {
-                path: RIGHT_THUMBREST_TOUCH_PATH,
-                button_type: ButtonType::Binary,
-                device_id: *RIGHT_HAND_ID,
-            },
-        ),
-    ]
-    .into_iter()
-    .collect()
-});
-
+#[derive(Clone)]
 pub struct InteractionProfileInfo {
-    pub path: &'static str,
+    pub name: String,
+    pub path: String,
     pub button_set: HashSet<u64>,
 }
 
-pub static CONTROLLER_PROFILE_INFO: Lazy<HashMap<u64, InteractionProfileInfo>> = Lazy::new(|| {
-    [
-        (
-            *QUEST_CONTROLLER_PROFILE_ID,
-            InteractionProfileInfo {
-                path: QUEST_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_X_CLICK_ID,
-                    *LEFT_X_TOUCH_ID,
-                    *LEFT_Y_CLICK_ID,
-                    *LEFT_Y_TOUCH_ID,
-                    *LEFT_MENU_CLICK_ID,
-                    *LEFT_SQUEEZE_VALUE_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_TRIGGER_TOUCH_ID,
-                    *LEFT_THUMBSTICK_X_ID,
-                    *LEFT_THUMBSTICK_Y_ID,
-                    *LEFT_THUMBSTICK_CLICK_ID,
-                    *LEFT_THUMBSTICK_TOUCH_ID,
-                    *LEFT_THUMBREST_TOUCH_ID,
-                    *RIGHT_A_CLICK_ID,
-                    *RIGHT_A_TOUCH_ID,
-                    *RIGHT_B_CLICK_ID,
-                    *RIGHT_B_TOUCH_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_SQUEEZE_VALUE_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_TRIGGER_TOUCH_ID,
-                    *RIGHT_THUMBSTICK_X_ID,
-                    *RIGHT_THUMBSTICK_Y_ID,
-                    *RIGHT_THUMBSTICK_CLICK_ID,
-                    *RIGHT_THUMBSTICK_TOUCH_ID,
-                    *RIGHT_THUMBREST_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
-            },
-        ),
-        (
-            *VIVE_CONTROLLER_PROFILE_ID,
-            InteractionProfileInfo {
-                path: VIVE_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_SYSTEM_CLICK_ID,
-                    *LEFT_SQUEEZE_CLICK_ID,
-                    *LEFT_MENU_CLICK_ID,
-                    *LEFT_TRIGGER_CLICK_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_TRACKPAD_X_ID,
-                    *LEFT_TRACKPAD_Y_ID,
-                    *LEFT_TRACKPAD_CLICK_ID,
-                    *LEFT_TRACKPAD_TOUCH_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_SQUEEZE_CLICK_ID,
-                    *RIGHT_MENU_CLICK_ID,
-                    *RIGHT_TRIGGER_CLICK_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_TRACKPAD_X_ID,
-                    *RIGHT_TRACKPAD_Y_ID,
-                    *RIGHT_TRACKPAD_CLICK_ID,
-                    *RIGHT_TRACKPAD_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
-            },
-        ),
-        (
-            *INDEX_CONTROLLER_PROFILE_ID,
-            InteractionProfileInfo {
-                path: INDEX_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_SYSTEM_CLICK_ID,
-                    *LEFT_SYSTEM_TOUCH_ID,
-                    *LEFT_A_CLICK_ID,
-                    *LEFT_A_TOUCH_ID,
-                    *LEFT_B_CLICK_ID,
-                    *LEFT_B_TOUCH_ID,
-                    *LEFT_SQUEEZE_VALUE_ID,
-                    *LEFT_SQUEEZE_FORCE_ID,
-                    *LEFT_TRIGGER_CLICK_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_TRIGGER_TOUCH_ID,
-                    *LEFT_THUMBSTICK_X_ID,
-                    *LEFT_THUMBSTICK_Y_ID,
-                    *LEFT_THUMBSTICK_CLICK_ID,
-                    *LEFT_THUMBSTICK_TOUCH_ID,
-                    *LEFT_TRACKPAD_X_ID,
-                    *LEFT_TRACKPAD_Y_ID,
-                    *LEFT_TRACKPAD_FORCE_ID,
-                    *LEFT_TRACKPAD_TOUCH_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_SYSTEM_TOUCH_ID,
-                    *RIGHT_A_CLICK_ID,
-                    *RIGHT_A_TOUCH_ID,
-                    *RIGHT_B_CLICK_ID,
-                    *RIGHT_B_TOUCH_ID,
-                    *RIGHT_SQUEEZE_VALUE_ID,
-                    *RIGHT_SQUEEZE_FORCE_ID,
-                    *RIGHT_TRIGGER_CLICK_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_TRIGGER_TOUCH_ID,
-                    *RIGHT_THUMBSTICK_X_ID,
-                    *RIGHT_THUMBSTICK_Y_ID,
-                    *RIGHT_THUMBSTICK_CLICK_ID,
-                    *RIGHT_THUMBSTICK_TOUCH_ID,
-                    *RIGHT_TRACKPAD_X_ID,
-                    *RIGHT_TRACKPAD_Y_ID,
-                    *RIGHT_TRACKPAD_FORCE_ID,
-                    *RIGHT_TRACKPAD_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
-            },
-        ),
-        (
-            *PICO_NEO3_CONTROLLER_PROFILE_ID,
-            InteractionProfileInfo {
-                path: PICO_NEO3_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_X_CLICK_ID,
-                    *LEFT_X_TOUCH_ID,
-                    *LEFT_Y_CLICK_ID,
-                    *LEFT_Y_TOUCH_ID,
-                    *LEFT_MENU_CLICK_ID,
-                    *LEFT_SYSTEM_CLICK_ID,
-                    *LEFT_TRIGGER_CLICK_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_TRIGGER_TOUCH_ID,
-                    *LEFT_THUMBSTICK_Y_ID,
-                    *LEFT_THUMBSTICK_X_ID,
-                    *LEFT_THUMBSTICK_CLICK_ID,
-                    *LEFT_THUMBSTICK_TOUCH_ID,
-                    *LEFT_SQUEEZE_CLICK_ID,
-                    *LEFT_SQUEEZE_VALUE_ID,
-                    *LEFT_THUMBREST_TOUCH_ID,
-                    *RIGHT_A_CLICK_ID,
-                    *RIGHT_A_TOUCH_ID,
-                    *RIGHT_B_CLICK_ID,
-                    *RIGHT_B_TOUCH_ID,
-                    *RIGHT_MENU_CLICK_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_TRIGGER_CLICK_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_TRIGGER_TOUCH_ID,
-                    *RIGHT_THUMBSTICK_Y_ID,
-                    *RIGHT_THUMBSTICK_X_ID,
-                    *RIGHT_THUMBSTICK_CLICK_ID,
-                    *RIGHT_THUMBSTICK_TOUCH_ID,
-                    *RIGHT_SQUEEZE_CLICK_ID,
-                    *RIGHT_SQUEEZE_VALUE_ID,
-                    *RIGHT_THUMBREST_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
-            },
-        ),
-        (
-            *PICO4_CONTROLLER_PROFILE_ID,
-            InteractionProfileInfo {
-                path: PICO4_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_X_CLICK_ID,
-                    *LEFT_X_TOUCH_ID,
-                    *LEFT_Y_CLICK_ID,
-                    *LEFT_Y_TOUCH_ID,
-                    *LEFT_MENU_CLICK_ID,
-                    *LEFT_SYSTEM_CLICK_ID,
-                    *LEFT_TRIGGER_CLICK_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_TRIGGER_TOUCH_ID,
-                    *LEFT_THUMBSTICK_Y_ID,
-                    *LEFT_THUMBSTICK_X_ID,
-                    *LEFT_THUMBSTICK_CLICK_ID,
-                    *LEFT_THUMBSTICK_TOUCH_ID,
-                    *LEFT_SQUEEZE_CLICK_ID,
-                    *LEFT_SQUEEZE_VALUE_ID,
-                    *LEFT_THUMBREST_TOUCH_ID,
-                    *RIGHT_A_CLICK_ID,
-                    *RIGHT_A_TOUCH_ID,
-                    *RIGHT_B_CLICK_ID,
-                    *RIGHT_B_TOUCH_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_TRIGGER_CLICK_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_TRIGGER_TOUCH_ID,
-                    *RIGHT_THUMBSTICK_Y_ID,
-                    *RIGHT_THUMBSTICK_X_ID,
-                    *RIGHT_THUMBSTICK_CLICK_ID,
-                    *RIGHT_THUMBSTICK_TOUCH_ID,
-                    *RIGHT_SQUEEZE_CLICK_ID,
-                    *RIGHT_SQUEEZE_VALUE_ID,
-                    *RIGHT_THUMBREST_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
-            },
-        ),
-        (
-            *FOCUS3_CONTROLLER_PROFILE_ID,
-            InteractionProfileInfo {
-                path: FOCUS3_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_X_CLICK_ID,
-                    *LEFT_Y_CLICK_ID,
-                    *LEFT_MENU_CLICK_ID,
-                    *LEFT_SQUEEZE_CLICK_ID,
-                    // *LEFT_SQUEEZE_TOUCH_ID, // not actually working
-                    *LEFT_SQUEEZE_VALUE_ID,
-                    *LEFT_TRIGGER_CLICK_ID,
-                    *LEFT_TRIGGER_TOUCH_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_THUMBSTICK_X_ID,
-                    *LEFT_THUMBSTICK_Y_ID,
-                    *LEFT_THUMBSTICK_CLICK_ID,
-                    *LEFT_THUMBSTICK_TOUCH_ID,
-                    *LEFT_THUMBREST_TOUCH_ID,
-                    *RIGHT_A_CLICK_ID,
-                    *RIGHT_B_CLICK_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_SQUEEZE_CLICK_ID,
-                    // *RIGHT_SQUEEZE_TOUCH_ID, // not actually working
-                    *RIGHT_SQUEEZE_VALUE_ID,
-                    *RIGHT_TRIGGER_CLICK_ID,
-                    *RIGHT_TRIGGER_TOUCH_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_THUMBSTICK_X_ID,
-                    *RIGHT_THUMBSTICK_Y_ID,
-                    *RIGHT_THUMBSTICK_CLICK_ID,
-                    *RIGHT_THUMBSTICK_TOUCH_ID,
-                    *RIGHT_THUMBREST_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
+// Format of the interaction profile data files. Buttons are listed by their full OpenXR path. The
+// device is the part of the path before "/input/"
+#[derive(Deserialize)]
+struct InteractionProfileData {
+    name: String,
+    path: String,
+    buttons: BTreeMap<String, ButtonType>,
+}
+
+// Buttons known independently of the controller profiles. They include the buttons that no
+// controller exposes but that can still be emulated, like the head enter button
+const BASE_BUTTONS_DATA: &str = include_str!("../resources/buttons.json");
+
+// Profiles embedded at build time. Supporting a new controller only requires adding its data file
+// to this list
+const BUILTIN_INTERACTION_PROFILES_DATA: [&str; 9] = [
+    include_str!("../resources/interaction_profiles/oculus_touch.json"),
+    include_str!("../resources/interaction_profiles/meta_touch_pro.json"),
+    include_str!("../resources/interaction_profiles/htc_vive.json"),
+    include_str!("../resources/interaction_profiles/valve_index.json"),
+    include_str!("../resources/interaction_profiles/pico_neo3.json"),
+    include_str!("../resources/interaction_profiles/pico4.json"),
+    include_str!("../resources/interaction_profiles/htc_vive_focus3.json"),
+    include_str!("../resources/interaction_profiles/yvr_touch.json"),
+    include_str!("../resources/interaction_profiles/khr_simple.json"),
+];
+
+#[derive(Clone, Default)]
+pub struct InteractionProfiles {
+    pub buttons: HashMap<u64, ButtonInfo>,
+    pub profiles: HashMap<u64, InteractionProfileInfo>,
+}
+
+impl InteractionProfiles {
+    // Buttons can be shared between profiles but must always have the same type
+    fn button_info(&self, path: String, button_type: ButtonType) -> Result<(u64, ButtonInfo)> {
+        let Some((device_path, _)) = path.split_once("/input/") else {
+            bail!("Invalid button path \"{path}\"");
+        };
+
+        let id = hash_string(&path);
+        if let Some(info) = self.buttons.get(&id) {
+            if info.button_type != button_type {
+                bail!(
+                    "Button \"{path}\" is already defined as {:?}",
+                    info.button_type
+                );
+            }
+        }
+
+        Ok((
+            id,
+            ButtonInfo {
+                device_id: hash_string(device_path),
+                path,
+                button_type,
             },
-        ),
-        (
-            *YVR_CONTROLLER_PROFILE_ID,
+        ))
+    }
+
+    // Adds buttons that are not part of any profile. The data file maps the button paths to
+    // their type
+    pub fn add_buttons_from_json(&mut self, json: &str) -> Result<()> {
+        let data: BTreeMap<String, ButtonType> = serde_json::from_str(json)?;
+
+        let buttons = data
+            .into_iter()
+            .map(|(path, button_type)| self.button_info(path, button_type))
+            .collect::<Result<Vec<_>>>()?;
+        self.buttons.extend(buttons);
+
+        Ok(())
+    }
+
+    // Adds the profile described by a data file, replacing any profile with the same path.
+    // Returns the profile ID
+    pub fn add_from_json(&mut self, json: &str) -> Result<u64> {
+        let data: InteractionProfileData = serde_json::from_str(json)?;
+
+        let buttons = data
+            .buttons
+            .into_iter()
+            .map(|(path, button_type)| self.button_info(path, button_type))
+            .collect::<Result<Vec<_>>>()?;
+
+        let id = hash_string(&data.path);
+        self.profiles.insert(
+            id,
             InteractionProfileInfo {
-                path: YVR_CONTROLLER_PROFILE_PATH,
-                button_set: [
-                    *LEFT_X_CLICK_ID,
-                    *LEFT_Y_CLICK_ID,
-                    *LEFT_MENU_CLICK_ID,
-                    *LEFT_SQUEEZE_CLICK_ID,
-                    *LEFT_TRIGGER_TOUCH_ID,
-                    *LEFT_TRIGGER_VALUE_ID,
-                    *LEFT_THUMBSTICK_X_ID,
-                    *LEFT_THUMBSTICK_Y_ID,
-                    *LEFT_THUMBSTICK_CLICK_ID,
-                    *LEFT_THUMBSTICK_TOUCH_ID,
-                    *LEFT_THUMBREST_TOUCH_ID,
-                    *RIGHT_A_CLICK_ID,
-                    *RIGHT_B_CLICK_ID,
-                    *RIGHT_SYSTEM_CLICK_ID,
-                    *RIGHT_SQUEEZE_CLICK_ID,
-                    *RIGHT_TRIGGER_TOUCH_ID,
-                    *RIGHT_TRIGGER_VALUE_ID,
-                    *RIGHT_THUMBSTICK_X_ID,
-                    *RIGHT_THUMBSTICK_Y_ID,
-                    *RIGHT_THUMBSTICK_CLICK_ID,
-                    *RIGHT_THUMBSTICK_TOUCH_ID,
-                    *RIGHT_THUMBREST_TOUCH_ID,
-                ]
-                .into_iter()
-                .collect(),
+                name: data.name,
+                path: data.path,
+                button_set: buttons.iter().map(|(id, _)| *id).collect(),
             },
-        ),
-    ]
-    .into_iter()
-    .collect()
+        );
+        self.buttons.extend(buttons);
+
+        Ok(id)
+    }
+}
+
+pub static BUILTIN_INTERACTION_PROFILES: Lazy<InteractionProfiles> = Lazy::new(|| {
+    let mut profiles = InteractionProfiles::default();
+    profiles.add_buttons_from_json(BASE_BUTTONS_DATA).unwrap();
+    for data in BUILTIN_INTERACTION_PROFILES_DATA {
+        profiles.add_from_json(data).unwrap();
+    }
+
+    profiles
 });
@@ -1,24 +1,38 @@
 use crate::dashboard::ServerRequest;
 use alvr_common::ConnectionState;
 use alvr_gui_common::theme::{self, log_colors};
-use alvr_packets::ClientListAction;
+use alvr_packets::{ClientListAction, PathValuePair};
 use alvr_session::{ClientConnectionConfig, SessionConfig};
 use eframe::{
-    egui::{self, Frame, Grid, Layout, RichText, TextEdit, Ui, Window},
+    egui::{self, Button, ComboBox, Frame, Grid, Layout, RichText, TextEdit, Ui, Window},
     emath::{Align, Align2},
     epaint::Color32,
 };
+use serde_json as json;
+use std::collections::BTreeMap;
 
 struct EditPopupState {
     new_client: bool,
     hostname: String,
     ips: Vec<String>,
+    settings_profile: Option<String>,
+    // JSON text, empty if there is no override
+    settings_override: String,
+}
+
+struct ProfilePopupState {
+    new_profile: bool,
+    name: String,
+    // JSON text of the sparse session settings
+    content: String,
 }
 
 pub struct ConnectionsTab {
     new_clients: Option<Vec<(String, ClientConnectionConfig)>>,
     trusted_clients: Option<Vec<(String, ClientConnectionConfig)>>,
+    settings_profiles: BTreeMap<String, json::Value>,
     edit_popup_state: Option<EditPopupState>,
+    profile_popup_state: Option<ProfilePopupState>,
 }
 
 impl ConnectionsTab {
@@ -26,7 +40,9 @@ impl ConnectionsTab {
         Self {
             new_clients: None,
             trusted_clients: None,
+            settings_profiles: BTreeMap::new(),
             edit_popup_state: None,
+            profile_popup_state: None,
         }
     }
 
@@ -36,10 +52,12 @@ impl ConnectionsTab {
                 .client_connections
                 .clone()
                 .into_iter()
-                .partition::<Vec<_>, _>(|(_, data)| data.trusted);
+                .partition::<Vec<_>, _>(|(_, data)| data.trusted && data.identity_key.is_some());
 
         self.trusted_clients = Some(trusted_clients);
         self.new_clients = Some(untrusted_clients);
+
+        self.settings_profiles = session.settings_profiles.clone().into_iter().collect();
     }
 
     pub fn ui(&mut self, ui: &mut Ui, connected_to_server: bool) -> Vec<ServerRequest> {
@@ -90,6 +108,16 @@ impl ConnectionsTab {
                     requests.push(request);
                 }
             }
+
+            ui.add_space(10.0);
+
+            if let Some(request) = settings_profiles_section(
+                ui,
+                &self.settings_profiles,
+                &mut self.profile_popup_state,
+            ) {
+                requests.push(request);
+            }
         });
 
         if let Some(mut state) = self.edit_popup_state.take() {
@@ -111,19 +139,47 @@ impl ConnectionsTab {
                         if ui[1].button("Add new").clicked() {
                             state.ips.push("192.168.X.X".to_string());
                         }
+                        ui[0].label("Settings profile:");
+                        ComboBox::from_id_source("settings_profile")
+                            .selected_text(state.settings_profile.as_deref().unwrap_or("None"))
+                            .show_ui(&mut ui[1], |ui| {
+                                ui.selectable_value(&mut state.settings_profile, None, "None");
+                                for name in self.settings_profiles.keys() {
+                                    ui.selectable_value(
+                                        &mut state.settings_profile,
+                                        Some(name.clone()),
+                                        name,
+                                    );
+                                }
+                            });
+                        ui[0].label("Settings override (JSON):");
+                        ui[1].text_edit_multiline(&mut state.settings_override);
                     });
+
+                    let settings_override = if state.settings_override.trim().is_empty() {
+                        Some(None)
+                    } else {
+                        json::from_str::<json::Value>(&state.settings_override)
+                            .ok()
+                            .map(Some)
+                    };
+
                     ui.columns(2, |ui| {
                         if ui[0].button("Cancel").clicked() {
                             return;
                         }
 
-                        if ui[1].button("Save").clicked() {
+                        if ui[1]
+                            .add_enabled(settings_override.is_some(), Button::new("Save"))
+                            .on_disabled_hover_text("The settings override is not valid JSON")
+                            .clicked()
+                        {
                             let manual_ips =
                                 state.ips.iter().filter_map(|s| s.parse().ok()).collect();
 
                             if state.new_client {
                                 requests.push(ServerRequest::UpdateClientList {
-                                    hostname: state.hostname,
+                                    hostname: state.hostname.clone(),
                                     action: ClientListAction::AddIfMissing {
                                         trusted: true,
                                         manual_ips,
@@ -131,10 +187,22 @@ impl ConnectionsTab {
                                 });
                             } else {
                                 requests.push(ServerRequest::UpdateClientList {
-                                    hostname: state.hostname,
+                                    hostname: state.hostname.clone(),
                                     action: ClientListAction::SetManualIps(manual_ips),
                                 });
                             }
+                            requests.push(ServerRequest::UpdateClientList {
+                                hostname: state.hostname.clone(),
+                                action: ClientListAction::SetSettingsProfile(
+                                    state.settings_profile,
+                                ),
+                            });
+                            requests.push(ServerRequest::UpdateClientList {
+                                hostname: state.hostname,
+                                action: ClientListAction::SetSettingsOverride(
+                                    settings_override.flatten(),
+                                ),
+                            });
                         } else {
                             self.edit_popup_state = Some(state);
                         }
@@ -142,10 +210,71 @@ impl ConnectionsTab {
                 });
         }
 
+        if let Some(mut state) = self.profile_popup_state.take() {
+            Window::new("Edit settings profile")
+                .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+                .resizable(false)
+                .collapsible(false)
+                .show(ui.ctx(), |ui| {
+                    ui.columns(2, |ui| {
+                        ui[0].label("Name:");
+                        ui[1].add_enabled(state.new_profile, TextEdit::singleline(&mut state.name));
+                        ui[0].label("Session settings (sparse JSON):");
+                        ui[1].text_edit_multiline(&mut state.content);
+                    });
+
+                    // A profile must be an object, with the same layout as session_settings
+                    let content = json::from_str::<json::Value>(&state.content)
+                        .ok()
+                        .filter(json::Value::is_object);
+                    let name_error = if state.name.trim().is_empty() {
+                        Some("The name is empty")
+                    } else if state.new_profile
+                        && self.settings_profiles.contains_key(state.name.trim())
+                    {
+                        Some("A profile with this name already exists")
+                    } else {
+                        None
+                    };
+
+                    ui.columns(2, |ui| {
+                        if ui[0].button("Cancel").clicked() {
+                            return;
+                        }
+
+                        if ui[1]
+                            .add_enabled(
+                                content.is_some() && name_error.is_none(),
+                                Button::new("Save"),
+                            )
+                            .on_disabled_hover_text(
+                                name_error.unwrap_or("The settings are not a valid JSON object"),
+                            )
+                            .clicked()
+                        {
+                            let mut profiles = self.settings_profiles.clone();
+                            profiles.insert(state.name.trim().to_owned(), content.unwrap());
+
+                            requests.push(set_settings_profiles_request(profiles));
+                        } else {
+                            self.profile_popup_state = Some(state);
+                        }
+                    })
+                });
+        }
+
         requests
     }
 }
 
+// The whole map is sent, since a path to a profile that does not exist yet cannot be set
+fn set_settings_profiles_request(profiles: BTreeMap<String, json::Value>) -> ServerRequest {
+    ServerRequest::SetValues(vec![PathValuePair {
+        path: alvr_packets::parse_path("settings_profiles"),
+        value: json::to_value(profiles).unwrap(),
+    }])
+}
+
 fn new_clients_section(
     ui: &mut Ui,
     clients: &[(String, ClientConnectionConfig)],
@@ -159,7 +288,7 @@ fn new_clients_section(
                 ui.add_space(5.0);
                 ui.heading("New clients");
             });
-            for (hostname, _) in clients {
+            for (hostname, data) in clients {
                 Frame::group(ui.style())
                     .fill(theme::DARKER_BG)
                     .inner_margin(egui::vec2(15.0, 12.0))
@@ -170,9 +299,30 @@ fn new_clients_section(
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
                                     ui.label(hostname);
+                                    if let Some(pairing) = &data.pending_pairing {
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "Pairing code: {}",
+                                                pairing.code
+                                            ))
+                                            .strong(),
+                                        );
+                                    }
                                 });
                                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                                    if ui.button("Trust").clicked() {
+                                    // The client can be trusted only after the handshake, so the
+                                    // user can compare the pairing code with the one on the headset
+                                    if ui
+                                        .add_enabled(
+                                            data.pending_pairing.is_some(),
+                                            Button::new("Trust"),
+                                        )
+                                        .on_disabled_hover_text("Waiting for the client to connect")
+                                        .on_hover_text(
+                                            "Make sure the headset shows the same pairing code",
+                                        )
+                                        .clicked()
+                                    {
                                         request = Some(ServerRequest::UpdateClientList {
                                             hostname: hostname.clone(),
                                             action: ClientListAction::Trust,
@@ -264,6 +414,14 @@ fn trusted_clients_section(
                                                     .iter()
                                                     .map(|addr| addr.to_string())
                                                     .collect::<Vec<String>>(),
+                                                settings_profile: data.settings_profile.clone(),
+                                                settings_override: data
+                                                    .settings_override
+                                                    .as_ref()
+                                                    .and_then(|value| {
+                                                        json::to_string_pretty(value).ok()
+                                                    })
+                                                    .unwrap_or_default(),
                                             });
                                         }
                                     });
@@ -277,6 +435,69 @@ fn trusted_clients_section(
                     hostname: "XXXX.client.alvr".into(),
                     new_client: true,
                     ips: Vec::new(),
+                    settings_profile: None,
+                    settings_override: String::new(),
+                });
+            }
+        });
+
+    request
+}
+
+fn settings_profiles_section(
+    ui: &mut Ui,
+    profiles: &BTreeMap<String, json::Value>,
+    profile_popup_state: &mut Option<ProfilePopupState>,
+) -> Option<ServerRequest> {
+    let mut request = None;
+
+    Frame::group(ui.style())
+        .fill(theme::SECTION_BG)
+        .show(ui, |ui| {
+            ui.vertical_centered_justified(|ui| {
+                ui.add_space(5.0);
+                ui.heading("Settings profiles");
+            });
+
+            ui.vertical(|ui| {
+                for (name, content) in profiles {
+                    Frame::group(ui.style())
+                        .fill(theme::DARKER_BG)
+                        .inner_margin(egui::vec2(15.0, 12.0))
+                        .show(ui, |ui| {
+                            Grid::new(format!("{}-settings-profile", name))
+                                .num_columns(2)
+                                .spacing(egui::vec2(8.0, 8.0))
+                                .show(ui, |ui| {
+                                    ui.label(name);
+                                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                        // Clients that select a removed profile use the global
+                                        // settings and their override
+                                        if ui.button("Remove").clicked() {
+                                            let mut profiles = profiles.clone();
+                                            profiles.remove(name);
+
+                                            request = Some(set_settings_profiles_request(profiles));
+                                        }
+                                        if ui.button("Edit").clicked() {
+                                            *profile_popup_state = Some(ProfilePopupState {
+                                                new_profile: false,
+                                                name: name.clone(),
+                                                content: json::to_string_pretty(content)
+                                                    .unwrap_or_default(),
+                                            });
+                                        }
+                                    });
+                                });
+                        });
+                }
+            });
+
+            if ui.button("Add profile").clicked() {
+                *profile_popup_state = Some(ProfilePopupState {
+                    new_profile: true,
+                    name: String::new(),
+                    content: "{}".into(),
                 });
             }
         });
@@ -1,7 +1,8 @@
 use alvr_packets::ServerRequest;
+use alvr_session::SessionConfig;
 use eframe::egui::Ui;
 
-pub fn debug_tab_ui(ui: &mut Ui) -> Option<ServerRequest> {
+pub fn debug_tab_ui(ui: &mut Ui, session: Option<&SessionConfig>) -> Option<ServerRequest> {
     let mut request = None;
 
     ui.columns(4, |ui| {
@@ -22,5 +23,41 @@ pub fn debug_tab_ui(ui: &mut Ui) -> Option<ServerRequest> {
         }
     });
 
+    if let Some(external_tracking) = session
+        .map(|session| &session.session_settings.headset.external_tracking)
+        .filter(|external_tracking| external_tracking.enabled)
+    {
+        ui.separator();
+        ui.label("External tracking calibration");
+        ui.horizontal_wrapped(|ui| {
+            for source in &external_tracking.content.sources.content {
+                if ui.button(format!("Calibrate {}", source.name)).clicked() {
+                    request = Some(ServerRequest::StartExternalTrackingCalibration(
+                        source.name.clone(),
+                    ));
+                }
+            }
+        });
+    }
+
+    if let Some(controllers) = session
+        .map(|session| &session.session_settings.headset.controllers)
+        .filter(|controllers| controllers.enabled)
+    {
+        let gestures = &controllers.content.custom_hand_gestures.content;
+        if !gestures.is_empty() {
+            ui.separator();
+            ui.label("Custom hand gestures");
+            ui.horizontal_wrapped(|ui| {
+                for gesture in gestures {
+                    if ui.button(format!("Record {}", gesture.name)).clicked() {
+                        request =
+                            Some(ServerRequest::RecordCustomHandGesture(gesture.name.clone()));
+                    }
+                }
+            });
+        }
+    }
+
     request
 }
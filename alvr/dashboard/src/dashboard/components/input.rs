@@ -0,0 +1,281 @@
+use alvr_common::{glam::Vec3, DeviceMotion, Pose};
+use alvr_events::{ButtonEvent, HandGestureEvent, TrackingEvent};
+use alvr_gui_common::theme;
+use alvr_packets::{ButtonValue, PathValuePair, ServerRequest};
+use alvr_session::SessionConfig;
+use eframe::{
+    egui::{pos2, vec2, Color32, Frame, Grid, Painter, ProgressBar, Rect, RichText, Stroke, Ui},
+    emath::RectTransform,
+};
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+const CANVAS_SIZE: f32 = 220.0;
+// Area around the head seen from above, in meters
+const TRACKING_VIEW_RANGE: f32 = 1.0;
+// Area around the wrist, in meters
+const HAND_VIEW_X_RANGE: RangeInclusive<f32> = -0.12..=0.12;
+const HAND_VIEW_Y_RANGE: RangeInclusive<f32> = 0.2..=-0.04;
+
+const LEFT_COLOR: Color32 = Color32::LIGHT_BLUE;
+const RIGHT_COLOR: Color32 = Color32::KHAKI;
+
+// Parent of each joint of the SteamVR hand skeleton. The palm is unused and the wrist is the root
+// of the fingers
+const JOINT_PARENTS: [usize; 26] = [
+    0, 0, 1, 2, 3, 4, 1, 6, 7, 8, 9, 1, 11, 12, 13, 14, 1, 16, 17, 18, 19, 1, 21, 22, 23, 24,
+];
+
+const LOGGING_FLAGS: [&str; 3] = ["log_tracking", "log_button_presses", "log_button_mappings"];
+
+fn set_input_logging(enabled: bool) -> ServerRequest {
+    ServerRequest::SetValues(
+        LOGGING_FLAGS
+            .iter()
+            .map(|flag| PathValuePair {
+                path: alvr_packets::parse_path(&format!("session_settings.logging.{flag}")),
+                value: serde_json::Value::Bool(enabled),
+            })
+            .collect(),
+    )
+}
+
+fn draw_canvas(ui: &mut Ui, data_rect: Rect, content: impl FnOnce(&Painter, RectTransform)) {
+    Frame::canvas(ui.style()).show(ui, |ui| {
+        let (_, canvas_rect) = ui.allocate_space(vec2(CANVAS_SIZE, CANVAS_SIZE));
+        let to_screen = RectTransform::from_to(data_rect, canvas_rect);
+
+        content(&ui.painter().with_clip_rect(canvas_rect), to_screen);
+    });
+}
+
+// Top view, centered on the head. Forward is up
+fn draw_device(
+    painter: &Painter,
+    to_screen: RectTransform,
+    center: Vec3,
+    motion: &DeviceMotion,
+    color: Color32,
+) {
+    let position = motion.pose.position - center;
+    let forward = position + motion.pose.orientation * -Vec3::Z * 0.15;
+
+    let position = to_screen * pos2(position.x, position.z);
+    painter.circle_filled(position, 5.0, color);
+    painter.line_segment(
+        [position, to_screen * pos2(forward.x, forward.z)],
+        Stroke::new(2.0, color),
+    );
+}
+
+// The joints of the SteamVR skeleton are relative to their parent. The hand is seen from the back,
+// with the fingers pointing up
+fn draw_hand_skeleton(
+    painter: &Painter,
+    to_screen: RectTransform,
+    skeleton: &[Pose; 26],
+    is_left: bool,
+    color: Color32,
+) {
+    let mirror = if is_left { 1.0 } else { -1.0 };
+
+    let mut global_joints = [Pose::default(); 26];
+    for joint in 2..26 {
+        global_joints[joint] = global_joints[JOINT_PARENTS[joint]] * skeleton[joint];
+    }
+    let points =
+        global_joints.map(|pose| to_screen * pos2(mirror * pose.position.y, pose.position.z));
+
+    for joint in 2..26 {
+        painter.line_segment(
+            [points[JOINT_PARENTS[joint]], points[joint]],
+            Stroke::new(2.0, color),
+        );
+        painter.circle_filled(points[joint], 2.5, color);
+    }
+    painter.circle_filled(points[1], 4.0, color);
+}
+
+fn button_value_ui(ui: &mut Ui, value: ButtonValue) {
+    match value {
+        ButtonValue::Binary(value) => {
+            if value {
+                ui.label(RichText::new("ON").color(theme::OK_GREEN));
+            } else {
+                ui.label("OFF");
+            }
+        }
+        ButtonValue::Scalar(value) => {
+            ui.add(
+                ProgressBar::new(value.abs().min(1.0))
+                    .desired_width(120.0)
+                    .text(format!("{value:.2}")),
+            );
+        }
+    }
+}
+
+fn buttons_grid_ui(ui: &mut Ui, id: &str, buttons: &BTreeMap<String, ButtonValue>) {
+    if buttons.is_empty() {
+        ui.label("No buttons received");
+        return;
+    }
+
+    Grid::new(id).num_columns(2).striped(true).show(ui, |ui| {
+        for (path, value) in buttons {
+            ui.label(path);
+            button_value_ui(ui, *value);
+            ui.end_row();
+        }
+    });
+}
+
+pub struct InputTab {
+    tracking: Option<TrackingEvent>,
+    source_buttons: BTreeMap<String, ButtonValue>,
+    mapped_buttons: BTreeMap<String, ButtonValue>,
+    hand_gestures: Vec<HandGestureEvent>,
+}
+
+impl InputTab {
+    pub fn new() -> Self {
+        Self {
+            tracking: None,
+            source_buttons: BTreeMap::new(),
+            mapped_buttons: BTreeMap::new(),
+            hand_gestures: vec![],
+        }
+    }
+
+    pub fn update_tracking(&mut self, tracking: TrackingEvent) {
+        self.tracking = Some(tracking);
+    }
+
+    pub fn update_buttons(&mut self, buttons: Vec<ButtonEvent>) {
+        for button in buttons {
+            self.source_buttons.insert(button.path, button.value);
+        }
+    }
+
+    pub fn update_mapped_button(&mut self, button: ButtonEvent) {
+        self.mapped_buttons.insert(button.path, button.value);
+    }
+
+    pub fn update_hand_gestures(&mut self, gestures: Vec<HandGestureEvent>) {
+        self.hand_gestures = gestures;
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, session: Option<&SessionConfig>) -> Option<ServerRequest> {
+        let mut request = None;
+
+        ui.horizontal(|ui| {
+            if let Some(logging) = session.map(|session| &session.session_settings.logging) {
+                let enabled = logging.log_tracking
+                    && logging.log_button_presses
+                    && logging.log_button_mappings;
+                if enabled {
+                    if ui.button("Stop input logging").clicked() {
+                        request = Some(set_input_logging(false));
+                    }
+                } else if ui.button("Start input logging").clicked() {
+                    request = Some(set_input_logging(true));
+                }
+            }
+
+            if ui.button("Clear").clicked() {
+                *self = Self::new();
+            }
+        });
+
+        ui.add_space(10.0);
+        ui.label(RichText::new("Tracking").size(20.0));
+        if let Some(tracking) = &self.tracking {
+            ui.horizontal(|ui| {
+                let range = -TRACKING_VIEW_RANGE..=TRACKING_VIEW_RANGE;
+                draw_canvas(
+                    ui,
+                    Rect::from_x_y_ranges(range.clone(), range),
+                    |painter, to_screen| {
+                        let center = tracking
+                            .head_motion
+                            .map(|motion| motion.pose.position)
+                            .unwrap_or_default();
+
+                        if let Some(motion) = &tracking.head_motion {
+                            draw_device(painter, to_screen, center, motion, theme::FG);
+                        }
+                        for (motion, color) in tracking
+                            .controller_motions
+                            .iter()
+                            .zip([LEFT_COLOR, RIGHT_COLOR])
+                        {
+                            if let Some(motion) = motion {
+                                draw_device(painter, to_screen, center, motion, color);
+                            }
+                        }
+                    },
+                );
+
+                for (index, (skeleton, color)) in tracking
+                    .hand_skeletons
+                    .iter()
+                    .zip([LEFT_COLOR, RIGHT_COLOR])
+                    .enumerate()
+                {
+                    draw_canvas(
+                        ui,
+                        Rect::from_x_y_ranges(HAND_VIEW_X_RANGE, HAND_VIEW_Y_RANGE),
+                        |painter, to_screen| {
+                            if let Some(skeleton) = skeleton {
+                                draw_hand_skeleton(painter, to_screen, skeleton, index == 0, color);
+                            }
+                        },
+                    );
+                }
+            });
+        } else {
+            ui.label("No tracking received");
+        }
+
+        ui.add_space(10.0);
+        ui.columns(2, |ui| {
+            ui[0].label(RichText::new("Buttons from the headset").size(20.0));
+            buttons_grid_ui(&mut ui[0], "source_buttons", &self.source_buttons);
+
+            ui[1].label(RichText::new("Buttons sent to SteamVR").size(20.0));
+            buttons_grid_ui(&mut ui[1], "mapped_buttons", &self.mapped_buttons);
+        });
+
+        ui.add_space(10.0);
+        ui.label(RichText::new("Hand gestures").size(20.0));
+        if self.hand_gestures.is_empty() {
+            ui.label("No hand gestures received");
+        } else {
+            Grid::new("hand_gestures")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Hand");
+                    ui.label("Gesture");
+                    ui.label("Touching");
+                    ui.label("Clicked");
+                    ui.label("Value");
+                    ui.end_row();
+
+                    for gesture in &self.hand_gestures {
+                        ui.label(&gesture.hand_path);
+                        if gesture.active {
+                            ui.label(RichText::new(&gesture.name).color(theme::OK_GREEN));
+                        } else {
+                            ui.label(&gesture.name);
+                        }
+                        button_value_ui(ui, ButtonValue::Binary(gesture.touching));
+                        button_value_ui(ui, ButtonValue::Binary(gesture.clicked));
+                        button_value_ui(ui, ButtonValue::Scalar(gesture.value));
+                        ui.end_row();
+                    }
+                });
+        }
+
+        request
+    }
+}
@@ -76,6 +76,7 @@ impl LogsTab {
                             EventType::StatisticsSummary(_)
                                 | EventType::GraphStatistics(_)
                                 | EventType::Tracking(_)
+                                | EventType::HandGestures(_)
                         )
                     {
                         self.entries.push_back(Entry {
@@ -1,6 +1,7 @@
 mod about;
 mod connections;
 mod debug;
+mod input;
 mod logs;
 mod notifications;
 mod settings;
@@ -14,6 +15,7 @@ mod installation;
 pub use about::*;
 pub use connections::*;
 pub use debug::*;
+pub use input::*;
 pub use logs::*;
 pub use notifications::*;
 pub use settings::*;
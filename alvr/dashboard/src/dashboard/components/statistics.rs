@@ -374,6 +374,13 @@ impl StatisticsTab {
             ui[0].label("Streamer FPS:");
             ui[1].label(&format!("{} FPS", statistics.server_fps));
 
+            ui[0].label("A/V offset:");
+            ui[1].label(&if let Some(offset) = statistics.audio_video_offset_ms {
+                format!("{offset:.1} ms")
+            } else {
+                "-".into()
+            });
+
             ui[0].label("Headset battery");
             ui[1].label(&format!(
                 "{}% ({})",
@@ -2,7 +2,8 @@ mod basic_components;
 mod components;
 
 use self::components::{
-    ConnectionsTab, LogsTab, NotificationBar, SettingsTab, SetupWizard, SetupWizardRequest,
+    ConnectionsTab, InputTab, LogsTab, NotificationBar, SettingsTab, SetupWizard,
+    SetupWizardRequest,
 };
 use crate::{dashboard::components::StatisticsTab, DataSources};
 use alvr_common::parking_lot::{Condvar, Mutex};
@@ -49,6 +50,7 @@ fn get_id() -> usize {
 enum Tab {
     Connections,
     Statistics,
+    Input,
     Settings,
     #[cfg(not(target_arch = "wasm32"))]
     Installation,
@@ -66,6 +68,7 @@ pub struct Dashboard {
     tab_labels: BTreeMap<Tab, &'static str>,
     connections_tab: ConnectionsTab,
     statistics_tab: StatisticsTab,
+    input_tab: InputTab,
     settings_tab: SettingsTab,
     #[cfg(not(target_arch = "wasm32"))]
     installation_tab: components::InstallationTab,
@@ -93,6 +96,7 @@ impl Dashboard {
             tab_labels: [
                 (Tab::Connections, "🔌  Connections"),
                 (Tab::Statistics, "📈  Statistics"),
+                (Tab::Input, "🎮  Input"),
                 (Tab::Settings, "⚙  Settings"),
                 #[cfg(not(target_arch = "wasm32"))]
                 (Tab::Installation, "💾  Installation"),
@@ -104,6 +108,7 @@ impl Dashboard {
             .collect(),
             connections_tab: ConnectionsTab::new(),
             statistics_tab: StatisticsTab::new(),
+            input_tab: InputTab::new(),
             settings_tab: SettingsTab::new(),
             #[cfg(not(target_arch = "wasm32"))]
             installation_tab: components::InstallationTab::new(),
@@ -162,6 +167,10 @@ impl eframe::App for Dashboard {
                 EventType::StatisticsSummary(statistics) => {
                     self.statistics_tab.update_statistics(statistics)
                 }
+                EventType::Tracking(tracking) => self.input_tab.update_tracking(*tracking),
+                EventType::Buttons(buttons) => self.input_tab.update_buttons(buttons),
+                EventType::MappedButton(button) => self.input_tab.update_mapped_button(button),
+                EventType::HandGestures(gestures) => self.input_tab.update_hand_gestures(gestures),
                 EventType::Session(session) => {
                     let settings = session.to_settings();
 
@@ -303,6 +312,12 @@ impl eframe::App for Dashboard {
                                     requests.push(request);
                                 }
                             }
+                            Tab::Input => {
+                                if let Some(request) = self.input_tab.ui(ui, self.session.as_ref())
+                                {
+                                    requests.push(request);
+                                }
+                            }
                             Tab::Settings => {
                                 requests.extend(self.settings_tab.ui(ui));
                             }
@@ -323,7 +338,9 @@ impl eframe::App for Dashboard {
                             }
                             Tab::Logs => self.logs_tab.ui(ui),
                             Tab::Debug => {
-                                if let Some(request) = components::debug_tab_ui(ui) {
+                                if let Some(request) =
+                                    components::debug_tab_ui(ui, self.session.as_ref())
+                                {
                                     requests.push(request);
                                 }
                             }
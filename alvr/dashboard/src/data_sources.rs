@@ -182,7 +182,9 @@ impl DataSources {
                                 ServerRequest::CaptureFrame
                                 | ServerRequest::InsertIdr
                                 | ServerRequest::StartRecording
-                                | ServerRequest::StopRecording => {
+                                | ServerRequest::StopRecording
+                                | ServerRequest::StartExternalTrackingCalibration(_)
+                                | ServerRequest::RecordCustomHandGesture(_) => {
                                     warn!("Cannot perform action, streamer (SteamVR) is not connected.")
                                 }
                                 ServerRequest::RestartSteamvr | ServerRequest::ShutdownSteamvr => {
@@ -20,6 +20,9 @@ pub struct StatisticsSummary {
     pub server_fps: u32,
     pub battery_hmd: u32,
     pub hmd_plugged: bool,
+    // Positive if audio is played after the corresponding video frame. None if audio/video
+    // synchronization is disabled or not yet measured
+    pub audio_video_offset_ms: Option<f32>,
 }
 
 // Bitrate statistics minus the empirical output value
@@ -60,6 +63,8 @@ pub struct TrackingEvent {
     pub fb_face_expression: Option<Vec<f32>>,
     pub htc_eye_expression: Option<Vec<f32>>,
     pub htc_lip_expression: Option<Vec<f32>>,
+    // Face expressions converted from any of the formats above, ordered as ArkitBlendshape::ALL
+    pub arkit_blendshapes: Option<Vec<f32>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -68,6 +73,16 @@ pub struct ButtonEvent {
     pub value: ButtonValue,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HandGestureEvent {
+    pub hand_path: String,
+    pub name: String,
+    pub active: bool,
+    pub clicked: bool,
+    pub touching: bool,
+    pub value: f32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct HapticsEvent {
     pub path: String,
@@ -85,6 +100,9 @@ pub enum EventType {
     GraphStatistics(GraphStatistics),
     Tracking(Box<TrackingEvent>),
     Buttons(Vec<ButtonEvent>),
+    // Value sent to SteamVR by the button mappings
+    MappedButton(ButtonEvent),
+    HandGestures(Vec<HandGestureEvent>),
     Haptics(HapticsEvent),
     AudioDevices(AudioDevicesList),
     DriversList(Vec<PathBuf>),
@@ -215,6 +215,18 @@ impl Layout {
         self.config_dir.join("session.json")
     }
 
+    pub fn hand_gestures_dir(&self) -> PathBuf {
+        self.config_dir.join("hand_gestures")
+    }
+
+    pub fn interaction_profiles_dir(&self) -> PathBuf {
+        self.config_dir.join("interaction_profiles")
+    }
+
+    pub fn identity_key(&self) -> PathBuf {
+        self.config_dir.join("identity.key")
+    }
+
     pub fn session_log(&self) -> PathBuf {
         if cfg!(target_os = "linux") {
             self.log_dir.join("alvr_session_log.txt")
@@ -227,6 +239,10 @@ impl Layout {
         self.log_dir.join("crash_log.txt")
     }
 
+    pub fn frame_statistics_log(&self) -> PathBuf {
+        self.log_dir.join("frame_statistics.csv")
+    }
+
     pub fn openvr_driver_lib_dir(&self) -> PathBuf {
         let platform = if cfg!(windows) {
             "win64"
@@ -0,0 +1,320 @@
+// Conversion of the vendor specific face expressions to the 52 blendshapes defined by ARKit
+// (ARFaceAnchor.BlendShapeLocation). All weights are in the range [0, 1].
+//
+// Sources:
+// * XR_FB_face_tracking: 63 weights, indexed as XrFaceExpressionFB. Data from
+//   XR_FB_face_tracking2 (70 weights) has the same first 63 entries, followed by the tongue.
+// * XR_HTC_facial_tracking: 14 eye weights (XrEyeExpressionHTC) and 37 lip weights
+//   (XrLipExpressionHTC).
+//
+// Blendshapes with no counterpart in the source format are set to 0, and vendor expressions with no
+// ARKit counterpart (like cheek suck) are ignored. Where ARKit has a single blendshape for two or
+// four vendor regions, the average is used.
+
+use crate::FaceData;
+use serde::{Deserialize, Serialize};
+
+pub const ARKIT_BLENDSHAPES_COUNT: usize = 52;
+
+macro_rules! arkit_blendshapes {
+    ($($variant:ident => $name:literal,)*) => {
+        #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum ArkitBlendshape {
+            $(#[serde(rename = $name)] $variant,)*
+        }
+
+        impl ArkitBlendshape {
+            pub const ALL: [Self; ARKIT_BLENDSHAPES_COUNT] = [$(Self::$variant,)*];
+
+            // Name used by ARKit, also used by most avatars that support "perfect sync"
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+arkit_blendshapes! {
+    EyeBlinkLeft => "eyeBlinkLeft",
+    EyeLookDownLeft => "eyeLookDownLeft",
+    EyeLookInLeft => "eyeLookInLeft",
+    EyeLookOutLeft => "eyeLookOutLeft",
+    EyeLookUpLeft => "eyeLookUpLeft",
+    EyeSquintLeft => "eyeSquintLeft",
+    EyeWideLeft => "eyeWideLeft",
+    EyeBlinkRight => "eyeBlinkRight",
+    EyeLookDownRight => "eyeLookDownRight",
+    EyeLookInRight => "eyeLookInRight",
+    EyeLookOutRight => "eyeLookOutRight",
+    EyeLookUpRight => "eyeLookUpRight",
+    EyeSquintRight => "eyeSquintRight",
+    EyeWideRight => "eyeWideRight",
+    JawForward => "jawForward",
+    JawLeft => "jawLeft",
+    JawRight => "jawRight",
+    JawOpen => "jawOpen",
+    MouthClose => "mouthClose",
+    MouthFunnel => "mouthFunnel",
+    MouthPucker => "mouthPucker",
+    MouthLeft => "mouthLeft",
+    MouthRight => "mouthRight",
+    MouthSmileLeft => "mouthSmileLeft",
+    MouthSmileRight => "mouthSmileRight",
+    MouthFrownLeft => "mouthFrownLeft",
+    MouthFrownRight => "mouthFrownRight",
+    MouthDimpleLeft => "mouthDimpleLeft",
+    MouthDimpleRight => "mouthDimpleRight",
+    MouthStretchLeft => "mouthStretchLeft",
+    MouthStretchRight => "mouthStretchRight",
+    MouthRollLower => "mouthRollLower",
+    MouthRollUpper => "mouthRollUpper",
+    MouthShrugLower => "mouthShrugLower",
+    MouthShrugUpper => "mouthShrugUpper",
+    MouthPressLeft => "mouthPressLeft",
+    MouthPressRight => "mouthPressRight",
+    MouthLowerDownLeft => "mouthLowerDownLeft",
+    MouthLowerDownRight => "mouthLowerDownRight",
+    MouthUpperUpLeft => "mouthUpperUpLeft",
+    MouthUpperUpRight => "mouthUpperUpRight",
+    BrowDownLeft => "browDownLeft",
+    BrowDownRight => "browDownRight",
+    BrowInnerUp => "browInnerUp",
+    BrowOuterUpLeft => "browOuterUpLeft",
+    BrowOuterUpRight => "browOuterUpRight",
+    CheekPuff => "cheekPuff",
+    CheekSquintLeft => "cheekSquintLeft",
+    CheekSquintRight => "cheekSquintRight",
+    NoseSneerLeft => "noseSneerLeft",
+    NoseSneerRight => "noseSneerRight",
+    TongueOut => "tongueOut",
+}
+
+// Indexed by ArkitBlendshape
+pub type ArkitBlendshapes = [f32; ARKIT_BLENDSHAPES_COUNT];
+
+// Missing entries (for example with a truncated array) are read as 0
+fn weight(weights: &[f32], index: usize) -> f32 {
+    weights.get(index).copied().unwrap_or(0.0)
+}
+
+fn average(weights: &[f32], indices: &[usize]) -> f32 {
+    indices.iter().map(|i| weight(weights, *i)).sum::<f32>() / indices.len() as f32
+}
+
+pub fn fb_to_arkit(fb: &[f32]) -> ArkitBlendshapes {
+    use ArkitBlendshape as B;
+
+    let w = |index| weight(fb, index);
+    let avg = |indices: &[usize]| average(fb, indices);
+
+    let mut out = [0.0; ARKIT_BLENDSHAPES_COUNT];
+    let mut set = |shape: B, value: f32| out[shape as usize] = value;
+
+    // Gaze directions are relative to the user: the left eye looks inwards when looking right
+    set(B::EyeBlinkLeft, w(12)); // EYES_CLOSED_L
+    set(B::EyeLookDownLeft, w(14)); // EYES_LOOK_DOWN_L
+    set(B::EyeLookInLeft, w(18)); // EYES_LOOK_RIGHT_L
+    set(B::EyeLookOutLeft, w(16)); // EYES_LOOK_LEFT_L
+    set(B::EyeLookUpLeft, w(20)); // EYES_LOOK_UP_L
+    set(B::EyeSquintLeft, w(28)); // LID_TIGHTENER_L
+    set(B::EyeWideLeft, w(59)); // UPPER_LID_RAISER_L
+    set(B::EyeBlinkRight, w(13)); // EYES_CLOSED_R
+    set(B::EyeLookDownRight, w(15)); // EYES_LOOK_DOWN_R
+    set(B::EyeLookInRight, w(17)); // EYES_LOOK_LEFT_R
+    set(B::EyeLookOutRight, w(19)); // EYES_LOOK_RIGHT_R
+    set(B::EyeLookUpRight, w(21)); // EYES_LOOK_UP_R
+    set(B::EyeSquintRight, w(29)); // LID_TIGHTENER_R
+    set(B::EyeWideRight, w(60)); // UPPER_LID_RAISER_R
+    set(B::JawForward, w(27)); // JAW_THRUST
+    set(B::JawLeft, w(25)); // JAW_SIDEWAYS_LEFT
+    set(B::JawRight, w(26)); // JAW_SIDEWAYS_RIGHT
+    set(B::JawOpen, w(24)); // JAW_DROP
+    set(B::MouthClose, w(50)); // LIPS_TOWARD
+    set(B::MouthFunnel, avg(&[34, 35, 36, 37])); // LIP_FUNNELER_LB, _LT, _RB, _RT
+    set(B::MouthPucker, avg(&[40, 41])); // LIP_PUCKER_L, _R
+    set(B::MouthLeft, w(53)); // MOUTH_LEFT
+    set(B::MouthRight, w(54)); // MOUTH_RIGHT
+    set(B::MouthSmileLeft, w(32)); // LIP_CORNER_PULLER_L
+    set(B::MouthSmileRight, w(33)); // LIP_CORNER_PULLER_R
+    set(B::MouthFrownLeft, w(30)); // LIP_CORNER_DEPRESSOR_L
+    set(B::MouthFrownRight, w(31)); // LIP_CORNER_DEPRESSOR_R
+    set(B::MouthDimpleLeft, w(10)); // DIMPLER_L
+    set(B::MouthDimpleRight, w(11)); // DIMPLER_R
+    set(B::MouthStretchLeft, w(42)); // LIP_STRETCHER_L
+    set(B::MouthStretchRight, w(43)); // LIP_STRETCHER_R
+    set(B::MouthRollLower, avg(&[44, 46])); // LIP_SUCK_LB, _RB
+    set(B::MouthRollUpper, avg(&[45, 47])); // LIP_SUCK_LT, _RT
+    set(B::MouthShrugLower, w(8)); // CHIN_RAISER_B
+    set(B::MouthShrugUpper, w(9)); // CHIN_RAISER_T
+    set(B::MouthPressLeft, w(38)); // LIP_PRESSOR_L
+    set(B::MouthPressRight, w(39)); // LIP_PRESSOR_R
+    set(B::MouthLowerDownLeft, w(51)); // LOWER_LIP_DEPRESSOR_L
+    set(B::MouthLowerDownRight, w(52)); // LOWER_LIP_DEPRESSOR_R
+    set(B::MouthUpperUpLeft, w(61)); // UPPER_LIP_RAISER_L
+    set(B::MouthUpperUpRight, w(62)); // UPPER_LIP_RAISER_R
+    set(B::BrowDownLeft, w(0)); // BROW_LOWERER_L
+    set(B::BrowDownRight, w(1)); // BROW_LOWERER_R
+    set(B::BrowInnerUp, avg(&[22, 23])); // INNER_BROW_RAISER_L, _R
+    set(B::BrowOuterUpLeft, w(57)); // OUTER_BROW_RAISER_L
+    set(B::BrowOuterUpRight, w(58)); // OUTER_BROW_RAISER_R
+    set(B::CheekPuff, avg(&[2, 3])); // CHEEK_PUFF_L, _R
+    set(B::CheekSquintLeft, w(4)); // CHEEK_RAISER_L
+    set(B::CheekSquintRight, w(5)); // CHEEK_RAISER_R
+    set(B::NoseSneerLeft, w(55)); // NOSE_WRINKLER_L
+    set(B::NoseSneerRight, w(56)); // NOSE_WRINKLER_R
+    set(B::TongueOut, w(68)); // TONGUE_OUT, only with XR_FB_face_tracking2
+
+    out
+}
+
+// Either array can be missing, in which case the corresponding blendshapes are 0. Brows, nose,
+// dimples and lip stretching/pressing are not tracked by HTC devices.
+pub fn htc_to_arkit(eye: Option<&[f32]>, lip: Option<&[f32]>) -> ArkitBlendshapes {
+    use ArkitBlendshape as B;
+
+    let mut out = [0.0; ARKIT_BLENDSHAPES_COUNT];
+    let mut set = |shape: B, value: f32| out[shape as usize] = value;
+
+    if let Some(eye) = eye {
+        let w = |index| weight(eye, index);
+
+        set(B::EyeBlinkLeft, w(0)); // LEFT_BLINK
+        set(B::EyeWideLeft, w(1)); // LEFT_WIDE
+        set(B::EyeSquintLeft, w(4)); // LEFT_SQUEEZE
+        set(B::EyeLookDownLeft, w(6)); // LEFT_DOWN
+        set(B::EyeLookOutLeft, w(8)); // LEFT_OUT
+        set(B::EyeLookInLeft, w(10)); // LEFT_IN
+        set(B::EyeLookUpLeft, w(12)); // LEFT_UP
+        set(B::EyeBlinkRight, w(2)); // RIGHT_BLINK
+        set(B::EyeWideRight, w(3)); // RIGHT_WIDE
+        set(B::EyeSquintRight, w(5)); // RIGHT_SQUEEZE
+        set(B::EyeLookDownRight, w(7)); // RIGHT_DOWN
+        set(B::EyeLookInRight, w(9)); // RIGHT_IN
+        set(B::EyeLookOutRight, w(11)); // RIGHT_OUT
+        set(B::EyeLookUpRight, w(13)); // RIGHT_UP
+    }
+
+    if let Some(lip) = lip {
+        let w = |index| weight(lip, index);
+        let avg = |indices: &[usize]| average(lip, indices);
+
+        set(B::JawRight, w(0)); // JAW_RIGHT
+        set(B::JawLeft, w(1)); // JAW_LEFT
+        set(B::JawForward, w(2)); // JAW_FORWARD
+        set(B::JawOpen, w(3)); // JAW_OPEN
+
+        // The "ape shape" is the jaw opened with closed lips
+        set(B::MouthClose, w(4)); // MOUTH_APE_SHAPE
+        set(B::MouthRight, avg(&[5, 7])); // MOUTH_UPPER_RIGHT, MOUTH_LOWER_RIGHT
+        set(B::MouthLeft, avg(&[6, 8])); // MOUTH_UPPER_LEFT, MOUTH_LOWER_LEFT
+        set(B::MouthFunnel, avg(&[9, 10])); // MOUTH_UPPER_OVERTURN, MOUTH_LOWER_OVERTURN
+        set(B::MouthPucker, w(11)); // MOUTH_POUT
+        set(B::MouthSmileRight, w(12)); // MOUTH_SMILE_RIGHT
+        set(B::MouthSmileLeft, w(13)); // MOUTH_SMILE_LEFT
+        set(B::MouthFrownRight, w(14)); // MOUTH_SAD_RIGHT
+        set(B::MouthFrownLeft, w(15)); // MOUTH_SAD_LEFT
+        set(B::CheekPuff, avg(&[16, 17])); // CHEEK_PUFF_RIGHT, CHEEK_PUFF_LEFT
+        set(B::MouthUpperUpRight, w(19)); // MOUTH_UPPER_UPRIGHT
+        set(B::MouthUpperUpLeft, w(20)); // MOUTH_UPPER_UPLEFT
+        set(B::MouthLowerDownRight, w(21)); // MOUTH_LOWER_DOWNRIGHT
+        set(B::MouthLowerDownLeft, w(22)); // MOUTH_LOWER_DOWNLEFT
+        set(B::MouthRollUpper, w(23)); // MOUTH_UPPER_INSIDE
+        set(B::MouthRollLower, w(24)); // MOUTH_LOWER_INSIDE
+
+        // The lower lip covering the upper one is the result of raising the chin
+        set(B::MouthShrugLower, w(25)); // MOUTH_LOWER_OVERLAY
+        set(B::TongueOut, w(26).max(w(32))); // TONGUE_LONGSTEP1, TONGUE_LONGSTEP2
+    }
+
+    out
+}
+
+impl FaceData {
+    // Face expressions in the ARKit format, or None if no face expression is available. FB data is
+    // preferred when both vendor formats are present.
+    pub fn arkit_blendshapes(&self) -> Option<ArkitBlendshapes> {
+        if let Some(fb) = &self.fb_face_expression {
+            Some(fb_to_arkit(fb))
+        } else if self.htc_eye_expression.is_some() || self.htc_lip_expression.is_some() {
+            Some(htc_to_arkit(
+                self.htc_eye_expression.as_deref(),
+                self.htc_lip_expression.as_deref(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ArkitBlendshape as B;
+
+    #[test]
+    fn test_blendshape_names() {
+        for (index, shape) in ArkitBlendshape::ALL.into_iter().enumerate() {
+            assert_eq!(shape as usize, index);
+            assert_eq!(
+                serde_json::to_string(&shape).unwrap(),
+                format!("\"{}\"", shape.name())
+            );
+        }
+        assert_eq!(B::TongueOut as usize, ARKIT_BLENDSHAPES_COUNT - 1);
+    }
+
+    #[test]
+    fn test_fb_conversion() {
+        let mut fb = vec![0.0; 63];
+        fb[12] = 1.0; // EYES_CLOSED_L
+        fb[17] = 0.5; // EYES_LOOK_LEFT_R
+        fb[34] = 1.0; // LIP_FUNNELER_LB
+        fb[35] = 1.0; // LIP_FUNNELER_LT
+
+        let arkit = fb_to_arkit(&fb);
+        assert_eq!(arkit[B::EyeBlinkLeft as usize], 1.0);
+        assert_eq!(arkit[B::EyeBlinkRight as usize], 0.0);
+        assert_eq!(arkit[B::EyeLookInRight as usize], 0.5);
+        assert_eq!(arkit[B::MouthFunnel as usize], 0.5);
+        // Not available without XR_FB_face_tracking2
+        assert_eq!(arkit[B::TongueOut as usize], 0.0);
+
+        // Each FB expression that has an ARKit counterpart is used
+        for index in 0..63 {
+            let mut fb = vec![0.0; 63];
+            fb[index] = 1.0;
+
+            let used = fb_to_arkit(&fb).iter().any(|w| *w > 0.0);
+            // CHEEK_SUCK_L, _R and LIP_TIGHTENER_L, _R have no counterpart
+            assert_eq!(used, ![6, 7, 48, 49].contains(&index), "index {index}");
+        }
+    }
+
+    #[test]
+    fn test_htc_conversion() {
+        let mut eye = vec![0.0; 14];
+        eye[2] = 1.0; // RIGHT_BLINK
+        let mut lip = vec![0.0; 37];
+        lip[3] = 0.8; // JAW_OPEN
+        lip[32] = 0.6; // TONGUE_LONGSTEP2
+
+        let face_data = FaceData {
+            htc_eye_expression: Some(eye),
+            htc_lip_expression: Some(lip),
+            ..Default::default()
+        };
+        let arkit = face_data.arkit_blendshapes().unwrap();
+        assert_eq!(arkit[B::EyeBlinkRight as usize], 1.0);
+        assert_eq!(arkit[B::JawOpen as usize], 0.8);
+        assert_eq!(arkit[B::TongueOut as usize], 0.6);
+
+        // Only eye tracking
+        let arkit = htc_to_arkit(Some(&[1.0; 14]), None);
+        assert_eq!(arkit[B::EyeLookUpRight as usize], 1.0);
+        assert_eq!(arkit[B::JawOpen as usize], 0.0);
+
+        assert!(FaceData::default().arkit_blendshapes().is_none());
+    }
+}
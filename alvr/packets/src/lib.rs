@@ -1,8 +1,12 @@
+mod arkit;
+
+pub use arkit::*;
+
 use alvr_common::{
     glam::{UVec2, Vec2},
     ConnectionState, DeviceMotion, Fov, LogEntry, LogSeverity, Pose,
 };
-use alvr_session::{CodecType, SessionConfig};
+use alvr_session::{ClientPairing, CodecType, SessionConfig};
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Debug},
@@ -24,6 +28,15 @@ pub struct VideoStreamingCapabilities {
     pub microphone_sample_rate: u32,
 }
 
+// Sent by the streamer right after the handshake
+#[derive(Serialize, Deserialize)]
+pub enum PairingStatus {
+    Paired,
+    // The streamer does not know the client identity key yet and closes the connection. The client
+    // should show the pairing code until the user trusts it on the streamer
+    PairingRequired,
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum ClientConnectionResult {
     ConnectionAccepted {
@@ -79,7 +92,7 @@ pub enum ButtonValue {
     Scalar(f32),
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ButtonEntry {
     pub path_id: u64,
     pub value: ButtonValue,
@@ -93,7 +106,7 @@ pub enum ClientControlPacket {
     StreamReady, // This flag notifies the server the client streaming socket is ready listening
     ViewsConfig(ViewsConfig),
     Battery(BatteryPacket),
-    VideoErrorReport, // legacy
+    VideoErrorReport,
     Buttons(Vec<ButtonEntry>),
     ActiveInteractionProfile { device_id: u64, profile_id: u64 },
     Log { level: LogSeverity, message: String },
@@ -101,7 +114,7 @@ pub enum ClientControlPacket {
     ReservedBuffer(Vec<u8>),
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct FaceData {
     pub eye_gazes: [Option<Pose>; 2],
     pub fb_face_expression: Option<Vec<f32>>, // issue: Serialize does not support [f32; 63]
@@ -115,8 +128,28 @@ pub struct VideoPacketHeader {
     pub is_idr: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioCodec {
+    Pcm,
+    Opus,
+}
+
+// The codec parameters are sent with every packet, so the receiver can recreate its decoder when
+// they change without a separate negotiation.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AudioPacketHeader {
+    pub codec: AudioCodec,
+    pub channels_count: u16,
+    pub sample_rate: u32,
+    // Incremented for every packet. Used to count lost packets
+    pub sequence: u32,
+    // Time of the first sample in the same timeline of the video timestamps, if known. Used for
+    // audio/video synchronization
+    pub timestamp: Option<Duration>,
+}
+
 // Note: face_data does not respect target_timestamp.
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Tracking {
     pub target_timestamp: Duration,
     pub device_motions: Vec<(u64, DeviceMotion)>,
@@ -124,7 +157,7 @@ pub struct Tracking {
     pub face_data: FaceData,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Haptics {
     pub device_id: u64,
     pub duration: Duration,
@@ -183,8 +216,12 @@ pub enum ClientListAction {
         manual_ips: Vec<IpAddr>,
     },
     SetDisplayName(String),
+    // If the client has a pending pairing, its identity key is saved as trusted
     Trust,
+    SetPendingPairing(Option<ClientPairing>),
     SetManualIps(Vec<IpAddr>),
+    SetSettingsProfile(Option<String>),
+    SetSettingsOverride(Option<serde_json::Value>),
     RemoveEntry,
     UpdateCurrentIp(Option<IpAddr>),
     SetConnectionState(ConnectionState),
@@ -199,6 +236,7 @@ pub struct ClientStatistics {
     pub rendering: Duration,
     pub vsync_queue: Duration,
     pub total_pipeline_latency: Duration,
+    pub audio_video_offset: Option<f32>, // seconds, positive if audio is late
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -228,6 +266,8 @@ pub enum ServerRequest {
     InsertIdr,
     StartRecording,
     StopRecording,
+    StartExternalTrackingCalibration(String),
+    RecordCustomHandGesture(String),
     FirewallRules(FirewallRulesAction),
     RegisterAlvrDriver,
     UnregisterDriver(PathBuf),
@@ -0,0 +1,718 @@
+use alvr_common::{
+    anyhow::Result, con_bail, error, info, warn, AnyhowToCon, ConResult, ConnectionError, ToCon,
+    ALVR_NAME,
+};
+use alvr_packets::{
+    ClientConnectionResult, ClientControlPacket, PairingStatus, ServerControlPacket,
+    StreamConfigPacket, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
+};
+use alvr_session::{SessionConfig, Settings};
+use alvr_sockets::{
+    CaptureChannel, CaptureHeader, CaptureReader, CaptureSide, CapturedPacket,
+    ControlSocketReceiver, ControlSocketSender, HandshakeResult, Identity, PeerType,
+    ProtoControlSocket, StreamReceiver, StreamSender, StreamSocket, StreamSocketBuilder,
+    CONTROL_PORT, HANDSHAKE_PACKET_SIZE_BYTES, LOCAL_IP,
+};
+use pico_args::Arguments;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json as json;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, UdpSocket},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+const HELP_STR: &str = r#"
+alvr_replay
+Replay ALVR packet captures, to debug sessions offline.
+
+USAGE:
+    alvr_replay <SUBCOMMAND> <CAPTURE> [ARGS]
+
+SUBCOMMANDS:
+    info                Print a summary of the capture
+    server              Act as a streamer, replaying the packets sent by the streamer to a client
+    client              Act as a client, replaying the packets sent by the client to a streamer
+
+FLAGS:
+    --help              Print this text
+    --pair              Server subcommand: request pairing instead of replaying. The client trusts
+                        the replay identity once the pairing code is confirmed on the headset
+
+ARGS:
+    --client-ip <IP>    IP of the client to connect to. Required for the server subcommand
+    --hostname <NAME>   Hostname announced by the client subcommand. Default: replay.client.alvr
+    --identity <PATH>   File of the identity key. Default: alvr_replay_identity.key
+"#;
+
+const RETRY_CONNECT_INTERVAL: Duration = Duration::from_secs(1);
+const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
+const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
+
+const MAX_UNREAD_PACKETS: usize = 10;
+
+struct Capture {
+    side: CaptureSide,
+    // Packets sent by the side that is being replayed
+    packets: Vec<CapturedPacket>,
+}
+
+fn load_capture(path: &Path, origin: CaptureSide) -> Result<Capture> {
+    let reader = CaptureReader::open(path)?;
+    let header = reader.header().clone();
+    check_protocol(&header);
+
+    let mut packets = vec![];
+    for packet in reader {
+        let packet = packet?;
+        if packet.origin(header.side) == origin {
+            packets.push(packet);
+        }
+    }
+
+    Ok(Capture {
+        side: header.side,
+        packets,
+    })
+}
+
+fn check_protocol(header: &CaptureHeader) {
+    if header.protocol_id != alvr_common::protocol_id() {
+        warn!(
+            "Capture protocol ID {} does not match the current one {}",
+            header.protocol_id,
+            alvr_common::protocol_id()
+        );
+    }
+}
+
+fn settings_from_config(config_packet: &StreamConfigPacket) -> ConResult<Settings> {
+    let mut session_desc = SessionConfig::default();
+    session_desc
+        .merge_from_json(&json::from_str(&config_packet.session).to_con()?)
+        .to_con()?;
+
+    Ok(session_desc.to_settings())
+}
+
+fn video_fec_group_size(settings: &Settings) -> Option<usize> {
+    settings
+        .connection
+        .video_fec
+        .as_option()
+        .map(|config| config.group_size as usize)
+}
+
+// Returns the index of the first control packet that deserializes to the specified type
+fn find_control_packet<T: DeserializeOwned>(packets: &[CapturedPacket]) -> Option<(usize, T)> {
+    packets.iter().enumerate().find_map(|(index, packet)| {
+        (packet.channel == CaptureChannel::Control)
+            .then(|| bincode::deserialize(&packet.data).ok())
+            .flatten()
+            .map(|packet| (index, packet))
+    })
+}
+
+// Received packets are not inspected, but they must be consumed to keep the sockets flowing
+fn spawn_drain_threads<R: DeserializeOwned + Send + 'static>(
+    mut control_receiver: ControlSocketReceiver<R>,
+    mut stream_socket: StreamSocket,
+    stream_receivers: Vec<StreamReceiver<()>>,
+) {
+    thread::spawn(move || loop {
+        match control_receiver.recv(STREAMING_RECV_TIMEOUT) {
+            Ok(_) | Err(ConnectionError::TryAgain(_)) => (),
+            Err(ConnectionError::Other(e)) => {
+                info!("Control socket closed: {e}");
+                return;
+            }
+        }
+    });
+
+    for mut receiver in stream_receivers {
+        thread::spawn(move || loop {
+            if let Err(ConnectionError::Other(_)) = receiver.recv(STREAMING_RECV_TIMEOUT) {
+                return;
+            }
+        });
+    }
+
+    thread::spawn(move || loop {
+        if let Err(ConnectionError::Other(e)) = stream_socket.recv() {
+            info!("Stream socket closed: {e}");
+            return;
+        }
+    });
+}
+
+// Send the packets with the same relative timing they have been captured with. Stream packets
+// already contain the serialized header, so they are sent as raw buffers.
+fn replay_packets<S: Serialize + DeserializeOwned>(
+    packets: &[CapturedPacket],
+    control_sender: &mut ControlSocketSender<S>,
+    stream_senders: &mut HashMap<u16, StreamSender<()>>,
+    skip_control_packet: impl Fn(&S) -> bool,
+) -> ConResult {
+    let Some(first_timestamp) = packets.first().map(|packet| packet.timestamp) else {
+        return Ok(());
+    };
+    let start_instant = Instant::now();
+
+    for packet in packets {
+        let deadline = start_instant + packet.timestamp.saturating_sub(first_timestamp);
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+
+        match packet.channel {
+            CaptureChannel::Control => {
+                let control_packet = bincode::deserialize::<S>(&packet.data).to_con()?;
+                if !skip_control_packet(&control_packet) {
+                    control_sender.send(&control_packet).to_con()?;
+                }
+            }
+            CaptureChannel::Stream { stream_id, .. } => {
+                let Some(sender) = stream_senders.get_mut(&stream_id) else {
+                    continue;
+                };
+
+                let mut buffer = sender.get_buffer(&()).to_con()?;
+                buffer
+                    .get_range_mut(0, packet.data.len())
+                    .copy_from_slice(&packet.data);
+                sender.send(buffer).to_con()?;
+            }
+        }
+    }
+
+    info!("Replayed {} packets", packets.len());
+
+    Ok(())
+}
+
+fn stream_ids(packets: &[CapturedPacket]) -> Vec<u16> {
+    let mut ids = packets
+        .iter()
+        .filter_map(|packet| match packet.channel {
+            CaptureChannel::Stream { stream_id, .. } => Some(stream_id),
+            CaptureChannel::Control => None,
+        })
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+
+    ids
+}
+
+fn print_info(path: &Path) -> Result<()> {
+    let reader = CaptureReader::open(path)?;
+    let header = reader.header().clone();
+    check_protocol(&header);
+
+    // (origin, channel name) -> (packet count, total bytes)
+    let mut channels = HashMap::<(CaptureSide, String), (usize, usize)>::new();
+    let mut duration = Duration::ZERO;
+    for packet in reader {
+        let packet = packet?;
+        duration = duration.max(packet.timestamp);
+
+        let channel = match packet.channel {
+            CaptureChannel::Control => "control".into(),
+            CaptureChannel::Stream { stream_id, .. } => match stream_id {
+                TRACKING => "tracking".into(),
+                HAPTICS => "haptics".into(),
+                AUDIO => "audio".into(),
+                VIDEO => "video".into(),
+                STATISTICS => "statistics".into(),
+                id => format!("stream {id}"),
+            },
+        };
+
+        let entry = channels
+            .entry((packet.origin(header.side), channel))
+            .or_default();
+        entry.0 += 1;
+        entry.1 += packet.data.len();
+    }
+
+    println!("Recorded on: {:?}", header.side);
+    println!("Protocol ID: {}", header.protocol_id);
+    println!("Duration: {:.3}s", duration.as_secs_f32());
+
+    let mut channels = channels.into_iter().collect::<Vec<_>>();
+    channels.sort_by_key(|((side, name), _)| (format!("{side:?}"), name.clone()));
+    for ((origin, name), (count, bytes)) in channels {
+        println!("{origin:?} {name}: {count} packets, {bytes} bytes");
+    }
+
+    Ok(())
+}
+
+fn replay_server(
+    capture: Capture,
+    client_ip: IpAddr,
+    identity: &Identity,
+    pair: bool,
+) -> ConResult {
+    let Some((config_index, config_packet)) =
+        find_control_packet::<StreamConfigPacket>(&capture.packets)
+    else {
+        con_bail!("The capture does not contain the stream configuration");
+    };
+
+    let mut proto_socket = loop {
+        match ProtoControlSocket::connect_to(
+            HANDSHAKE_ACTION_TIMEOUT,
+            PeerType::AnyClient(vec![client_ip]),
+        ) {
+            Ok((socket, _)) => break socket,
+            Err(_) => thread::sleep(RETRY_CONNECT_INTERVAL),
+        }
+    };
+
+    let handshake = proto_socket.handshake(identity, HANDSHAKE_ACTION_TIMEOUT)?;
+    info!("Connected. Pairing code: {}", handshake.pairing_code);
+
+    // The client is not authenticated: this tool trusts any client it is pointed to
+    if pair {
+        proto_socket
+            .send(&PairingStatus::PairingRequired)
+            .to_con()?;
+        info!(concat!(
+            "Check that the headset shows the same pairing code and confirm it on the headset, ",
+            "then run again without --pair"
+        ));
+
+        return Ok(());
+    }
+    proto_socket.send(&PairingStatus::Paired).to_con()?;
+
+    proto_socket.recv::<ClientConnectionResult>(HANDSHAKE_ACTION_TIMEOUT)?;
+    proto_socket.send(&config_packet).to_con()?;
+
+    // The stream configuration is taken from the capture, so the client sets up the same sockets
+    // as in the recorded session
+    let settings = settings_from_config(&config_packet)?;
+
+    let (mut control_sender, mut control_receiver) = proto_socket
+        .split::<ServerControlPacket, ClientControlPacket>(STREAMING_RECV_TIMEOUT)
+        .to_con()?;
+
+    control_sender
+        .send(&ServerControlPacket::StartStream)
+        .to_con()?;
+
+    if !matches!(
+        control_receiver.recv(HANDSHAKE_ACTION_TIMEOUT)?,
+        ClientControlPacket::StreamReady
+    ) {
+        con_bail!("Got unexpected packet waiting for stream ack");
+    }
+
+    let fec_group_size = video_fec_group_size(&settings);
+    let mut stream_socket = StreamSocketBuilder::connect_to_client(
+        HANDSHAKE_ACTION_TIMEOUT,
+        client_ip,
+        settings.connection.stream_port,
+        settings.connection.stream_protocol,
+        settings.connection.dscp,
+        settings.connection.server_send_buffer_bytes,
+        settings.connection.server_recv_buffer_bytes,
+        settings.connection.packet_size as _,
+        &handshake.stream_keys,
+        settings.debug.network_emulator.as_option(),
+    )?;
+
+    let packets = &capture.packets[config_index + 1..];
+
+    let mut stream_senders = HashMap::new();
+    for stream_id in stream_ids(packets) {
+        let sender = if stream_id == VIDEO {
+            let mut sender = stream_socket.request_stream_with_fec(VIDEO, fec_group_size);
+            sender.set_unreliable(true);
+
+            sender
+        } else {
+            stream_socket.request_stream(stream_id)
+        };
+        stream_senders.insert(stream_id, sender);
+    }
+
+    let stream_receivers = [TRACKING, AUDIO, STATISTICS]
+        .into_iter()
+        .map(|stream_id| stream_socket.subscribe_to_stream(stream_id, MAX_UNREAD_PACKETS))
+        .collect();
+
+    spawn_drain_threads(control_receiver, stream_socket, stream_receivers);
+
+    replay_packets(
+        packets,
+        &mut control_sender,
+        &mut stream_senders,
+        |packet| {
+            matches!(
+                packet,
+                ServerControlPacket::StartStream | ServerControlPacket::Restarting
+            )
+        },
+    )
+}
+
+// Connect to the streamer, waiting for the user to trust this client if needed
+fn connect_to_server(
+    capture: &Capture,
+    hostname: &str,
+    identity: &Identity,
+) -> ConResult<(
+    ProtoControlSocket,
+    HandshakeResult,
+    StreamConfigPacket,
+    IpAddr,
+)> {
+    let listener = alvr_sockets::get_server_listener(HANDSHAKE_ACTION_TIMEOUT).to_con()?;
+
+    let announcer_socket = UdpSocket::bind((LOCAL_IP, 0)).to_con()?;
+    announcer_socket.set_broadcast(true).to_con()?;
+    let mut announce_packet = [0; HANDSHAKE_PACKET_SIZE_BYTES];
+    announce_packet[0..ALVR_NAME.len()].copy_from_slice(ALVR_NAME.as_bytes());
+    announce_packet[16..24].copy_from_slice(&alvr_common::protocol_id().to_le_bytes());
+    announce_packet[24..24 + hostname.len()].copy_from_slice(hostname.as_bytes());
+
+    let Some((_, connection_result)) =
+        find_control_packet::<ClientConnectionResult>(&capture.packets)
+    else {
+        con_bail!("The capture does not contain the client connection result");
+    };
+
+    loop {
+        if let Err(e) =
+            announcer_socket.send_to(&announce_packet, (Ipv4Addr::BROADCAST, CONTROL_PORT))
+        {
+            warn!("Broadcast error: {e}");
+        }
+
+        let Ok((mut proto_socket, server_ip)) =
+            ProtoControlSocket::connect_to(RETRY_CONNECT_INTERVAL, PeerType::Server(&listener))
+        else {
+            continue;
+        };
+
+        let handshake = proto_socket.handshake(identity, HANDSHAKE_ACTION_TIMEOUT)?;
+
+        // The streamer identity is not checked: any streamer that trusts this client is accepted
+        if let PairingStatus::PairingRequired =
+            proto_socket.recv::<PairingStatus>(HANDSHAKE_ACTION_TIMEOUT)?
+        {
+            info!(
+                "Trust the client \"{hostname}\" on the streamer with pairing code {}",
+                handshake.pairing_code
+            );
+            continue;
+        }
+
+        // The streamer IP is different from the recorded session
+        let connection_result = match &connection_result {
+            ClientConnectionResult::ConnectionAccepted {
+                client_protocol_id,
+                display_name,
+                streaming_capabilities,
+                ..
+            } => ClientConnectionResult::ConnectionAccepted {
+                client_protocol_id: *client_protocol_id,
+                display_name: display_name.clone(),
+                server_ip,
+                streaming_capabilities: streaming_capabilities.clone(),
+            },
+            ClientConnectionResult::ClientStandby => ClientConnectionResult::ClientStandby,
+        };
+        proto_socket.send(&connection_result).to_con()?;
+
+        let config_packet = proto_socket.recv::<StreamConfigPacket>(HANDSHAKE_ACTION_TIMEOUT)?;
+
+        return Ok((proto_socket, handshake, config_packet, server_ip));
+    }
+}
+
+fn replay_client(capture: Capture, hostname: &str, identity: &Identity) -> ConResult {
+    let (proto_socket, handshake, config_packet, server_ip) =
+        connect_to_server(&capture, hostname, identity)?;
+    info!("Connected to streamer at {server_ip}");
+
+    // Unlike the server subcommand, the configuration is dictated by the live streamer
+    let settings = settings_from_config(&config_packet)?;
+
+    let (mut control_sender, mut control_receiver) = proto_socket
+        .split::<ClientControlPacket, ServerControlPacket>(STREAMING_RECV_TIMEOUT)
+        .to_con()?;
+
+    if !matches!(
+        control_receiver.recv(HANDSHAKE_ACTION_TIMEOUT)?,
+        ServerControlPacket::StartStream
+    ) {
+        con_bail!("Got unexpected packet waiting for stream start");
+    }
+
+    let fec_group_size = video_fec_group_size(&settings);
+    let stream_socket_builder = StreamSocketBuilder::listen_for_server(
+        Duration::from_secs(1),
+        settings.connection.stream_port,
+        settings.connection.stream_protocol,
+        settings.connection.dscp,
+        settings.connection.client_send_buffer_bytes,
+        settings.connection.client_recv_buffer_bytes,
+    )
+    .to_con()?;
+
+    control_sender
+        .send(&ClientControlPacket::StreamReady)
+        .to_con()?;
+
+    let mut stream_socket = stream_socket_builder.accept_from_server(
+        server_ip,
+        settings.connection.stream_port,
+        settings.connection.packet_size as _,
+        HANDSHAKE_ACTION_TIMEOUT,
+        &handshake.stream_keys,
+        settings.debug.network_emulator.as_option(),
+    )?;
+
+    // Skip the packets exchanged before the split, already handled above
+    let first_index = find_control_packet::<ClientConnectionResult>(&capture.packets)
+        .map(|(index, _)| index + 1)
+        .unwrap_or(0);
+    let packets = &capture.packets[first_index..];
+
+    let mut stream_senders = stream_ids(packets)
+        .into_iter()
+        .map(|stream_id| (stream_id, stream_socket.request_stream(stream_id)))
+        .collect();
+
+    let stream_receivers = vec![
+        stream_socket.subscribe_to_stream_with_fec(VIDEO, MAX_UNREAD_PACKETS, fec_group_size),
+        stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS),
+        stream_socket.subscribe_to_stream(HAPTICS, MAX_UNREAD_PACKETS),
+    ];
+
+    spawn_drain_threads(control_receiver, stream_socket, stream_receivers);
+
+    replay_packets(
+        packets,
+        &mut control_sender,
+        &mut stream_senders,
+        |packet| matches!(packet, ClientControlPacket::StreamReady),
+    )
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut args = Arguments::from_env();
+
+    if args.contains(["-h", "--help"]) {
+        println!("{HELP_STR}");
+        return;
+    }
+
+    let subcommand = args.subcommand().ok().flatten();
+    let capture_path = args.free_from_str::<PathBuf>().ok();
+    let client_ip = args
+        .opt_value_from_str::<_, IpAddr>("--client-ip")
+        .ok()
+        .flatten();
+    let hostname = args
+        .opt_value_from_str::<_, String>("--hostname")
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "replay.client.alvr".into());
+    let pair = args.contains("--pair");
+    let identity_path = args
+        .opt_value_from_str::<_, PathBuf>("--identity")
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| PathBuf::from("alvr_replay_identity.key"));
+
+    let (Some(subcommand), Some(capture_path)) = (subcommand, capture_path) else {
+        println!("{HELP_STR}");
+        return;
+    };
+
+    // The hostname is announced after the name and the protocol ID
+    if hostname.len() > HANDSHAKE_PACKET_SIZE_BYTES - 24 {
+        println!(
+            "\n--hostname must be at most {} bytes long.",
+            HANDSHAKE_PACKET_SIZE_BYTES - 24
+        );
+        return;
+    }
+
+    if subcommand == "info" {
+        if let Err(e) = print_info(&capture_path) {
+            error!("Failed to read capture: {e}");
+        }
+        return;
+    }
+
+    let origin = match subcommand.as_str() {
+        "server" => CaptureSide::Server,
+        "client" => CaptureSide::Client,
+        _ => {
+            println!("\nUnrecognized subcommand.");
+            println!("{HELP_STR}");
+            return;
+        }
+    };
+
+    let capture = match load_capture(&capture_path, origin) {
+        Ok(capture) => capture,
+        Err(e) => {
+            error!("Failed to read capture: {e}");
+            return;
+        }
+    };
+    info!(
+        "Loaded {} packets sent by the {origin:?} (recorded on the {:?})",
+        capture.packets.len(),
+        capture.side
+    );
+
+    let identity = match Identity::load_or_generate(&identity_path) {
+        Ok(identity) => identity,
+        Err(e) => {
+            error!("Failed to load identity: {e}");
+            return;
+        }
+    };
+
+    let res = match origin {
+        CaptureSide::Server => {
+            let Some(client_ip) = client_ip else {
+                println!("\n--client-ip is required for the server subcommand.");
+                return;
+            };
+
+            replay_server(capture, client_ip, &identity, pair)
+        }
+        CaptureSide::Client => replay_client(capture, &hostname, &identity),
+    };
+
+    if let Err(e) = res {
+        error!("Replay failed: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_sockets::PacketDirection;
+    use std::{
+        env,
+        fs::{self, File},
+        io::Write,
+    };
+
+    fn packet(
+        direction: PacketDirection,
+        channel: CaptureChannel,
+        data: Vec<u8>,
+    ) -> CapturedPacket {
+        CapturedPacket {
+            timestamp: Duration::ZERO,
+            direction,
+            channel,
+            data,
+        }
+    }
+
+    fn stream(stream_id: u16) -> CaptureChannel {
+        CaptureChannel::Stream {
+            stream_id,
+            packet_index: 0,
+        }
+    }
+
+    // Capture recorded on the server, with the layout written by PacketRecorder
+    fn write_capture(path: &Path, packets: &[CapturedPacket]) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(b"ALVRCAP1").unwrap();
+        alvr_sockets::write_record(
+            &mut file,
+            &CaptureHeader {
+                side: CaptureSide::Server,
+                protocol_id: alvr_common::protocol_id(),
+            },
+        )
+        .unwrap();
+        for packet in packets {
+            alvr_sockets::write_record(&mut file, packet).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_load_server_capture() {
+        let path = env::temp_dir().join(format!("alvr_replay_test_{}", std::process::id()));
+        let config_packet = StreamConfigPacket {
+            session: json::to_string(&SessionConfig::default()).unwrap(),
+            negotiated: "{}".into(),
+        };
+        write_capture(
+            &path,
+            &[
+                packet(
+                    PacketDirection::Sent,
+                    CaptureChannel::Control,
+                    bincode::serialize(&config_packet).unwrap(),
+                ),
+                packet(
+                    PacketDirection::Sent,
+                    CaptureChannel::Control,
+                    bincode::serialize(&ServerControlPacket::StartStream).unwrap(),
+                ),
+                packet(PacketDirection::Received, stream(TRACKING), vec![1; 10]),
+                packet(PacketDirection::Sent, stream(VIDEO), vec![2; 100]),
+                packet(PacketDirection::Sent, stream(HAPTICS), vec![3; 10]),
+                packet(PacketDirection::Sent, stream(VIDEO), vec![4; 100]),
+            ],
+        );
+
+        let server_capture = load_capture(&path, CaptureSide::Server).unwrap();
+        let client_capture = load_capture(&path, CaptureSide::Client).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(server_capture.side, CaptureSide::Server);
+        assert_eq!(server_capture.packets.len(), 5);
+        assert_eq!(client_capture.packets.len(), 1);
+        assert_eq!(client_capture.packets[0].data, [1; 10]);
+
+        // Same steps as replay_server(), before connecting
+        let (config_index, found_config) =
+            find_control_packet::<StreamConfigPacket>(&server_capture.packets).unwrap();
+        assert_eq!(config_index, 0);
+        let settings = settings_from_config(&found_config).ok().unwrap();
+        assert_eq!(
+            video_fec_group_size(&settings),
+            video_fec_group_size(&SessionConfig::default().to_settings())
+        );
+        assert_eq!(
+            stream_ids(&server_capture.packets[config_index + 1..]),
+            [HAPTICS, VIDEO]
+        );
+    }
+
+    #[test]
+    fn test_load_corrupted_capture() {
+        let path = env::temp_dir().join(format!("alvr_replay_corrupted_{}", std::process::id()));
+        write_capture(&path, &[]);
+        // Size prefix of a packet bigger than any packet that can be captured
+        File::options()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&u32::MAX.to_le_bytes())
+            .unwrap();
+
+        let res = load_capture(&path, CaptureSide::Server);
+        fs::remove_file(&path).ok();
+
+        assert!(res.is_err());
+    }
+}
@@ -106,6 +106,11 @@ void Settings::Load() {
         m_enableControllers = config.get("controllers_enabled").get<bool>();
         m_controllerIsTracker = config.get("controller_is_tracker").get<bool>();
 
+        m_bodyTrackerPaths.clear();
+        for (auto &path : config.get("body_tracker_paths").get<picojson::array>()) {
+            m_bodyTrackerPaths.push_back(path.get<std::string>());
+        }
+
         Info("Render Target: %d %d\n", m_renderWidth, m_renderHeight);
         Info("Refresh Rate: %d\n", m_refreshRate);
         m_loaded = true;
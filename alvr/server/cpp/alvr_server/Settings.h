@@ -2,6 +2,7 @@
 
 #include "ALVR-common/packet_types.h"
 #include <string>
+#include <vector>
 
 class Settings {
     static Settings m_Instance;
@@ -82,4 +83,6 @@ class Settings {
 
     bool m_enableControllers;
     int m_controllerIsTracker = false;
+
+    std::vector<std::string> m_bodyTrackerPaths;
 };
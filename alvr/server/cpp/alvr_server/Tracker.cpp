@@ -0,0 +1,80 @@
+#include "Tracker.h"
+#include "Logger.h"
+#include "Utils.h"
+
+Tracker::Tracker(uint64_t deviceID) : TrackedDevice(deviceID) {
+    m_pose = vr::DriverPose_t{};
+    m_pose.poseIsValid = false;
+    m_pose.deviceIsConnected = false;
+    m_pose.result = vr::TrackingResult_Uninitialized;
+
+    m_pose.qDriverFromHeadRotation = HmdQuaternion_Init(1, 0, 0, 0);
+    m_pose.qWorldFromDriverRotation = HmdQuaternion_Init(1, 0, 0, 0);
+    m_pose.qRotation = HmdQuaternion_Init(1, 0, 0, 0);
+}
+
+vr::EVRInitError Tracker::Activate(vr::TrackedDeviceIndex_t unObjectId) {
+    Debug("Tracker::Activate. objectId=%d\n", unObjectId);
+
+    this->object_id = unObjectId;
+    this->prop_container = vr::VRProperties()->TrackedDeviceToPropertyContainer(this->object_id);
+
+    SetOpenvrProps(this->device_id);
+
+    return vr::VRInitError_None;
+}
+
+void Tracker::Deactivate() {
+    Debug("Tracker::Deactivate\n");
+    this->object_id = vr::k_unTrackedDeviceIndexInvalid;
+}
+
+void Tracker::DebugRequest(const char * /*pchRequest*/,
+                           char *pchResponseBuffer,
+                           uint32_t unResponseBufferSize) {
+    if (unResponseBufferSize >= 1)
+        pchResponseBuffer[0] = 0;
+}
+
+void Tracker::onPoseUpdate(float predictionS, const FfiDeviceMotion *motion) {
+    if (this->object_id == vr::k_unTrackedDeviceIndexInvalid) {
+        return;
+    }
+
+    auto pose = m_pose;
+    if (motion != nullptr) {
+        pose.poseIsValid = true;
+        pose.deviceIsConnected = true;
+        pose.result = vr::TrackingResult_Running_OK;
+
+        pose.qRotation = HmdQuaternion_Init(motion->orientation.w,
+                                            motion->orientation.x,
+                                            motion->orientation.y,
+                                            motion->orientation.z);
+
+        pose.vecPosition[0] = motion->position[0];
+        pose.vecPosition[1] = motion->position[1];
+        pose.vecPosition[2] = motion->position[2];
+
+        pose.vecVelocity[0] = motion->linearVelocity[0];
+        pose.vecVelocity[1] = motion->linearVelocity[1];
+        pose.vecVelocity[2] = motion->linearVelocity[2];
+
+        pose.vecAngularVelocity[0] = motion->angularVelocity[0];
+        pose.vecAngularVelocity[1] = motion->angularVelocity[1];
+        pose.vecAngularVelocity[2] = motion->angularVelocity[2];
+
+        pose.poseTimeOffset = predictionS;
+    } else {
+        // Keep the last pose, so the tracker does not jump when the tracking comes back
+        pose.poseIsValid = false;
+        pose.result = vr::TrackingResult_Running_OutOfRange;
+        pose.vecVelocity[0] = pose.vecVelocity[1] = pose.vecVelocity[2] = 0;
+        pose.vecAngularVelocity[0] = pose.vecAngularVelocity[1] = pose.vecAngularVelocity[2] = 0;
+    }
+
+    m_pose = pose;
+
+    vr::VRServerDriverHost()->TrackedDevicePoseUpdated(
+        this->object_id, pose, sizeof(vr::DriverPose_t));
+}
@@ -0,0 +1,35 @@
+#pragma once
+
+#include "TrackedDevice.h"
+#include "openvr_driver.h"
+
+// Generic tracker used for full-body tracking. The role and the other properties are set from Rust
+class Tracker : public TrackedDevice, public vr::ITrackedDeviceServerDriver {
+  public:
+    Tracker(uint64_t deviceID);
+
+    virtual ~Tracker(){};
+
+    //
+    // ITrackedDeviceServerDriver
+    //
+
+    virtual vr::EVRInitError Activate(vr::TrackedDeviceIndex_t unObjectId);
+
+    virtual void Deactivate();
+
+    virtual void EnterStandby() {}
+
+    void *GetComponent(const char * /*pchComponentNameAndVersion*/) { return nullptr; }
+
+    virtual void
+    DebugRequest(const char *pchRequest, char *pchResponseBuffer, uint32_t unResponseBufferSize);
+
+    virtual vr::DriverPose_t GetPose() { return m_pose; }
+
+    // motion is null if the tracked point is not available in the last tracking packet
+    void onPoseUpdate(float predictionS, const FfiDeviceMotion *motion);
+
+  private:
+    vr::DriverPose_t m_pose;
+};
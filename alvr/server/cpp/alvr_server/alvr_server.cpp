@@ -13,6 +13,7 @@
 #include "PoseHistory.h"
 #include "Settings.h"
 #include "TrackedDevice.h"
+#include "Tracker.h"
 #include "bindings.h"
 #include "driverlog.h"
 #include "openvr_driver.h"
@@ -66,7 +67,7 @@ class DriverProvider : public vr::IServerTrackedDeviceProvider {
   public:
     std::unique_ptr<Hmd> hmd;
     std::unique_ptr<Controller> left_controller, right_controller;
-    // std::vector<ViveTrackerProxy> generic_trackers;
+    std::map<uint64_t, std::unique_ptr<Tracker>> trackers;
     bool shutdown_called = false;
 
     std::map<uint64_t, TrackedDevice *> tracked_devices;
@@ -107,11 +108,31 @@ class DriverProvider : public vr::IServerTrackedDeviceProvider {
             }
         }
 
+        for (auto &path : Settings::Instance().m_bodyTrackerPaths) {
+            auto id = PathStringToHash(path.c_str());
+            if (this->trackers.find(id) != this->trackers.end()) {
+                // The same role is listed more than once
+                continue;
+            }
+
+            auto tracker = std::make_unique<Tracker>(id);
+            this->tracked_devices.insert({id, (TrackedDevice *)tracker.get()});
+
+            if (!vr::VRServerDriverHost()->TrackedDeviceAdded(tracker->get_serial_number().c_str(),
+                                                              vr::TrackedDeviceClass_GenericTracker,
+                                                              tracker.get())) {
+                Warn("Failed to register tracker %s", path.c_str());
+            }
+
+            this->trackers.insert({id, std::move(tracker)});
+        }
+
         return vr::VRInitError_None;
     }
     virtual void Cleanup() override {
         this->left_controller.reset();
         this->right_controller.reset();
+        this->trackers.clear();
         this->hmd.reset();
 
         CleanupDriverLog();
@@ -266,6 +287,18 @@ void SetTracking(unsigned long long targetTimestampNs,
             }
         }
     }
+
+    for (auto &[id, tracker] : g_driver_provider.trackers) {
+        const FfiDeviceMotion *motion = nullptr;
+        for (int i = 0; i < motionsCount; i++) {
+            if (deviceMotions[i].deviceID == id) {
+                motion = &deviceMotions[i];
+                break;
+            }
+        }
+
+        tracker->onPoseUpdate(controllerPoseTimeOffsetS, motion);
+    }
 }
 
 void VideoErrorReportReceive() {
@@ -1,3 +1,4 @@
+use super::{BitrateController, FrameIntervalEstimator, UPDATE_INTERVAL};
 use crate::FfiDynamicEncoderParams;
 use alvr_common::SlidingWindowAverage;
 use alvr_events::NominalBitrateStats;
@@ -9,11 +10,10 @@ use std::{
     time::{Duration, Instant},
 };
 
-const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
-
-pub struct BitrateManager {
-    nominal_frame_interval: Duration,
-    frame_interval_average: SlidingWindowAverage<Duration>,
+// Controller for the constant and adaptive modes. The adaptive bitrate is derived from the average
+// network throughput, then it is capped by a series of limiters.
+pub struct AdaptiveController {
+    frame_interval: FrameIntervalEstimator,
     // note: why packet_sizes_bits_history is a queue and not a sliding average? Because some
     // network samples will be dropped but not any packet size sample
     packet_sizes_bits_history: VecDeque<(Duration, usize)>,
@@ -21,21 +21,16 @@ pub struct BitrateManager {
     network_latency_average: SlidingWindowAverage<Duration>,
     bitrate_average: SlidingWindowAverage<f32>,
     decoder_latency_overstep_count: usize,
-    last_frame_instant: Instant,
-    last_update_instant: Instant,
+    last_update_instant: Option<Instant>,
     dynamic_max_bitrate: f32,
     previous_config: Option<BitrateConfig>,
     update_needed: bool,
 }
 
-impl BitrateManager {
+impl AdaptiveController {
     pub fn new(max_history_size: usize, initial_framerate: f32) -> Self {
         Self {
-            nominal_frame_interval: Duration::from_secs_f32(1. / initial_framerate),
-            frame_interval_average: SlidingWindowAverage::new(
-                Duration::from_millis(16),
-                max_history_size,
-            ),
+            frame_interval: FrameIntervalEstimator::new(max_history_size, initial_framerate),
             packet_sizes_bits_history: VecDeque::new(),
             encoder_latency_average: SlidingWindowAverage::new(
                 Duration::from_millis(5),
@@ -47,39 +42,26 @@ impl BitrateManager {
             ),
             bitrate_average: SlidingWindowAverage::new(30_000_000.0, max_history_size),
             decoder_latency_overstep_count: 0,
-            last_frame_instant: Instant::now(),
-            last_update_instant: Instant::now(),
+            last_update_instant: None,
             dynamic_max_bitrate: f32::MAX,
             previous_config: None,
             update_needed: true,
         }
     }
+}
 
-    // Note: This is used to calculate the framerate/frame interval. The frame present is the most
-    // accurate event for this use.
-    pub fn report_frame_present(&mut self, config: &Switch<BitrateAdaptiveFramerateConfig>) {
-        let now = Instant::now();
-
-        let interval = now - self.last_frame_instant;
-        self.last_frame_instant = now;
-
-        if let Some(config) = config.as_option() {
-            let interval_ratio =
-                interval.as_secs_f32() / self.frame_interval_average.get_average().as_secs_f32();
-
-            self.frame_interval_average.submit_sample(interval);
-
-            if interval_ratio > config.framerate_reset_threshold_multiplier
-                || interval_ratio < 1.0 / config.framerate_reset_threshold_multiplier
-            {
-                // Clear most of the samples, keep some for stability
-                self.frame_interval_average.retain(5);
-                self.update_needed = true;
-            }
+impl BitrateController for AdaptiveController {
+    fn report_frame_present(
+        &mut self,
+        now: Instant,
+        config: &Switch<BitrateAdaptiveFramerateConfig>,
+    ) {
+        if self.frame_interval.report_frame_present(now, config) {
+            self.update_needed = true;
         }
     }
 
-    pub fn report_frame_encoded(
+    fn report_frame_encoded(
         &mut self,
         timestamp: Duration,
         encoder_latency: Duration,
@@ -91,9 +73,7 @@ impl BitrateManager {
             .push_back((timestamp, size_bytes * 8));
     }
 
-    // decoder_latency is used to learn a suitable maximum bitrate bound to avoid decoder runaway
-    // latency
-    pub fn report_frame_latencies(
+    fn report_frame_latencies(
         &mut self,
         config: &BitrateMode,
         timestamp: Duration,
@@ -142,12 +122,13 @@ impl BitrateManager {
         }
     }
 
-    pub fn get_encoder_params(
+    fn report_packet_loss(&mut self) {}
+
+    fn get_encoder_params(
         &mut self,
+        now: Instant,
         config: &BitrateConfig,
     ) -> (FfiDynamicEncoderParams, Option<NominalBitrateStats>) {
-        let now = Instant::now();
-
         if self
             .previous_config
             .as_ref()
@@ -157,20 +138,16 @@ impl BitrateManager {
             self.previous_config = Some(config.clone());
             // Continue method. Always update bitrate in this case
         } else if !self.update_needed
-            && (now < self.last_update_instant + UPDATE_INTERVAL
+            && (self
+                .last_update_instant
+                .map(|instant| now < instant + UPDATE_INTERVAL)
+                .unwrap_or(false)
                 || matches!(config.mode, BitrateMode::ConstantMbps(_)))
         {
-            return (
-                FfiDynamicEncoderParams {
-                    updated: 0,
-                    bitrate_bps: 0,
-                    framerate: 0.0,
-                },
-                None,
-            );
+            return (super::no_update_params(), None);
         }
 
-        self.last_update_instant = now;
+        self.last_update_instant = Some(now);
         self.update_needed = false;
 
         let mut stats = NominalBitrateStats::default();
@@ -203,7 +180,7 @@ impl BitrateManager {
 
                 if let Switch::Enabled(config) = encoder_latency_limiter {
                     let saturation = self.encoder_latency_average.get_average().as_secs_f32()
-                        / self.nominal_frame_interval.as_secs_f32();
+                        / self.frame_interval.nominal().as_secs_f32();
                     let max =
                         initial_bitrate_average_bps * config.max_saturation_multiplier / saturation;
                     stats.encoder_latency_limiter_bps = Some(max);
@@ -230,21 +207,17 @@ impl BitrateManager {
 
                 bitrate_bps
             }
+            // Handled by DelayGradientController
+            BitrateMode::DelayGradient { .. } => self.bitrate_average.get_average(),
         };
 
         stats.requested_bps = bitrate_bps;
 
-        let frame_interval = if config.adapt_to_framerate.enabled() {
-            self.frame_interval_average.get_average()
-        } else {
-            self.nominal_frame_interval
-        };
-
         (
             FfiDynamicEncoderParams {
                 updated: 1,
                 bitrate_bps: bitrate_bps as u64,
-                framerate: 1.0 / frame_interval.as_secs_f32().min(1.0),
+                framerate: self.frame_interval.framerate(config),
             },
             Some(stats),
         )
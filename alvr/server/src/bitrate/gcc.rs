@@ -0,0 +1,514 @@
+// Bitrate controller in the style of Google Congestion Control (draft-ietf-rmcat-gcc).
+//
+// The trend of the queuing delay is estimated with a linear regression over the network latency of
+// the last frames. An overuse detector with adaptive threshold compares the trend against the
+// noise level. The bitrate follows an AIMD scheme: it grows multiplicatively while the delay is
+// stable and it drops below the measured throughput when the delay grows. A loss based limiter caps
+// the bitrate when the client reports lost packets.
+
+use super::{BitrateController, FrameIntervalEstimator, UPDATE_INTERVAL};
+use crate::FfiDynamicEncoderParams;
+use alvr_events::NominalBitrateStats;
+use alvr_session::{
+    settings_schema::Switch, BitrateAdaptiveFramerateConfig, BitrateConfig, BitrateMode,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const TRENDLINE_WINDOW_SIZE: usize = 20;
+const TRENDLINE_SMOOTHING_COEFF: f64 = 0.9;
+const TRENDLINE_THRESHOLD_GAIN: f64 = 4.0;
+const MAX_TREND_DELTAS: usize = 60;
+const OVERUSE_TIME_THRESHOLD_MS: f64 = 10.0;
+
+const INITIAL_THRESHOLD_MS: f64 = 12.5;
+const MIN_THRESHOLD_MS: f64 = 6.0;
+const MAX_THRESHOLD_MS: f64 = 600.0;
+const THRESHOLD_UP_GAIN: f64 = 0.0087;
+const THRESHOLD_DOWN_GAIN: f64 = 0.039;
+const MAX_THRESHOLD_ADAPT_OFFSET_MS: f64 = 15.0;
+const MAX_THRESHOLD_UPDATE_INTERVAL_MS: f64 = 100.0;
+
+const THROUGHPUT_WINDOW_MS: f64 = 1000.0;
+const MIN_THROUGHPUT_WINDOW_MS: f64 = 100.0;
+// Leave time to the queue to drain before reacting again
+const MIN_DECREASE_INTERVAL_MS: f64 = 300.0;
+// Limit the increase when the encoder does not fill the available bitrate
+const MAX_THROUGHPUT_MULTIPLIER: f32 = 1.5;
+const MAX_INCREASE_INTERVAL: Duration = Duration::from_secs(1);
+
+const LOSS_EVALUATION_INTERVAL: Duration = Duration::from_secs(1);
+const LOW_LOSS_FRACTION: f32 = 0.02;
+const HIGH_LOSS_FRACTION: f32 = 0.1;
+const LOSS_BASED_INCREASE_MULTIPLIER: f32 = 1.05;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BandwidthUsage {
+    Normal,
+    Underusing,
+    Overusing,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RateControlState {
+    Hold,
+    Increase,
+}
+
+// Estimates the slope of the queuing delay and detects when the link is overused
+struct TrendlineEstimator {
+    first_arrival_ms: Option<f64>,
+    previous_latency_ms: Option<f64>,
+    previous_arrival_ms: Option<f64>,
+    accumulated_delay_ms: f64,
+    smoothed_delay_ms: f64,
+    // (arrival time, smoothed accumulated delay)
+    samples: VecDeque<(f64, f64)>,
+    deltas_count: usize,
+    threshold_ms: f64,
+    time_over_using_ms: Option<f64>,
+    overuse_count: usize,
+    previous_trend: f64,
+}
+
+impl TrendlineEstimator {
+    fn new() -> Self {
+        Self {
+            first_arrival_ms: None,
+            previous_latency_ms: None,
+            previous_arrival_ms: None,
+            accumulated_delay_ms: 0.0,
+            smoothed_delay_ms: 0.0,
+            samples: VecDeque::new(),
+            deltas_count: 0,
+            threshold_ms: INITIAL_THRESHOLD_MS,
+            time_over_using_ms: None,
+            overuse_count: 0,
+            previous_trend: 0.0,
+        }
+    }
+
+    // Frames are sent at regular intervals, so the variation of the network latency between two
+    // frames is equal to the inter-arrival time minus the inter-departure time
+    fn update(&mut self, arrival_ms: f64, latency_ms: f64) -> BandwidthUsage {
+        let first_arrival_ms = *self.first_arrival_ms.get_or_insert(arrival_ms);
+        let (Some(previous_latency_ms), Some(previous_arrival_ms)) = (
+            self.previous_latency_ms.replace(latency_ms),
+            self.previous_arrival_ms.replace(arrival_ms),
+        ) else {
+            return BandwidthUsage::Normal;
+        };
+
+        self.deltas_count = usize::min(self.deltas_count + 1, MAX_TREND_DELTAS);
+        self.accumulated_delay_ms += latency_ms - previous_latency_ms;
+        self.smoothed_delay_ms = TRENDLINE_SMOOTHING_COEFF * self.smoothed_delay_ms
+            + (1.0 - TRENDLINE_SMOOTHING_COEFF) * self.accumulated_delay_ms;
+
+        self.samples
+            .push_back((arrival_ms - first_arrival_ms, self.smoothed_delay_ms));
+        if self.samples.len() > TRENDLINE_WINDOW_SIZE {
+            self.samples.pop_front();
+        }
+
+        let trend = if self.samples.len() == TRENDLINE_WINDOW_SIZE {
+            linear_fit_slope(&self.samples).unwrap_or(self.previous_trend)
+        } else {
+            self.previous_trend
+        };
+
+        self.detect(trend, arrival_ms - previous_arrival_ms)
+    }
+
+    fn detect(&mut self, trend: f64, arrival_delta_ms: f64) -> BandwidthUsage {
+        let modified_trend = usize::min(self.deltas_count, MAX_TREND_DELTAS) as f64
+            * trend
+            * TRENDLINE_THRESHOLD_GAIN;
+
+        let usage = if modified_trend > self.threshold_ms {
+            let time_over_using_ms = match self.time_over_using_ms {
+                // Assume that the overuse started halfway between the last two frames
+                None => arrival_delta_ms / 2.0,
+                Some(time) => time + arrival_delta_ms,
+            };
+            self.time_over_using_ms = Some(time_over_using_ms);
+            self.overuse_count += 1;
+
+            if time_over_using_ms > OVERUSE_TIME_THRESHOLD_MS
+                && self.overuse_count > 1
+                && trend >= self.previous_trend
+            {
+                self.time_over_using_ms = Some(0.0);
+                self.overuse_count = 0;
+
+                BandwidthUsage::Overusing
+            } else {
+                BandwidthUsage::Normal
+            }
+        } else if modified_trend < -self.threshold_ms {
+            self.time_over_using_ms = None;
+            self.overuse_count = 0;
+
+            BandwidthUsage::Underusing
+        } else {
+            self.time_over_using_ms = None;
+            self.overuse_count = 0;
+
+            BandwidthUsage::Normal
+        };
+
+        self.previous_trend = trend;
+        self.update_threshold(modified_trend, arrival_delta_ms);
+
+        usage
+    }
+
+    // The threshold follows the magnitude of the trend, so that it adapts to the network noise
+    // level, but it ignores sudden spikes
+    fn update_threshold(&mut self, modified_trend: f64, arrival_delta_ms: f64) {
+        if modified_trend.abs() > self.threshold_ms + MAX_THRESHOLD_ADAPT_OFFSET_MS {
+            return;
+        }
+
+        let gain = if modified_trend.abs() < self.threshold_ms {
+            THRESHOLD_DOWN_GAIN
+        } else {
+            THRESHOLD_UP_GAIN
+        };
+        let interval_ms = f64::min(arrival_delta_ms, MAX_THRESHOLD_UPDATE_INTERVAL_MS);
+
+        self.threshold_ms += gain * (modified_trend.abs() - self.threshold_ms) * interval_ms;
+        self.threshold_ms = self.threshold_ms.clamp(MIN_THRESHOLD_MS, MAX_THRESHOLD_MS);
+    }
+}
+
+fn linear_fit_slope(samples: &VecDeque<(f64, f64)>) -> Option<f64> {
+    let count = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let mut numerator = 0.0;
+    let mut denominator = 0.0;
+    for (x, y) in samples {
+        numerator += (x - mean_x) * (y - mean_y);
+        denominator += (x - mean_x) * (x - mean_x);
+    }
+
+    (denominator != 0.0).then(|| numerator / denominator)
+}
+
+struct DelayGradientConfig {
+    initial_bitrate_bps: f32,
+    max_bitrate_bps: f32,
+    min_bitrate_bps: f32,
+    increase_multiplier_per_second: f32,
+    decrease_multiplier: f32,
+}
+
+impl DelayGradientConfig {
+    fn from_mode(mode: &BitrateMode) -> Option<Self> {
+        if let BitrateMode::DelayGradient {
+            initial_bitrate_mbps,
+            max_bitrate_mbps,
+            min_bitrate_mbps,
+            increase_multiplier_per_second,
+            decrease_multiplier,
+        } = mode
+        {
+            Some(Self {
+                initial_bitrate_bps: *initial_bitrate_mbps as f32 * 1e6,
+                max_bitrate_bps: *max_bitrate_mbps as f32 * 1e6,
+                min_bitrate_bps: *min_bitrate_mbps as f32 * 1e6,
+                increase_multiplier_per_second: *increase_multiplier_per_second,
+                decrease_multiplier: *decrease_multiplier,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn clamp(&self, bitrate_bps: f32) -> f32 {
+        bitrate_bps.clamp(self.min_bitrate_bps, self.max_bitrate_bps)
+    }
+}
+
+pub struct DelayGradientController {
+    frame_interval: FrameIntervalEstimator,
+    trendline: TrendlineEstimator,
+    state: RateControlState,
+    // Frames encoded but not acknowledged yet by the client statistics
+    frame_sizes_bits: VecDeque<(Duration, usize)>,
+    // (arrival time, size) of the acknowledged frames
+    received_frames: VecDeque<(f64, usize)>,
+    last_decrease_arrival_ms: Option<f64>,
+    delay_based_bitrate_bps: f32,
+    loss_based_bitrate_bps: f32,
+    lost_packets_count: usize,
+    received_frames_count: usize,
+    last_loss_evaluation_instant: Option<Instant>,
+    last_increase_instant: Option<Instant>,
+    last_update_instant: Option<Instant>,
+    last_requested_bitrate_bps: f32,
+    previous_config: Option<BitrateConfig>,
+    update_needed: bool,
+}
+
+impl DelayGradientController {
+    pub fn new(max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            frame_interval: FrameIntervalEstimator::new(max_history_size, initial_framerate),
+            trendline: TrendlineEstimator::new(),
+            state: RateControlState::Increase,
+            frame_sizes_bits: VecDeque::new(),
+            received_frames: VecDeque::new(),
+            last_decrease_arrival_ms: None,
+            delay_based_bitrate_bps: 0.0,
+            loss_based_bitrate_bps: f32::MAX,
+            lost_packets_count: 0,
+            received_frames_count: 0,
+            last_loss_evaluation_instant: None,
+            last_increase_instant: None,
+            last_update_instant: None,
+            last_requested_bitrate_bps: 0.0,
+            previous_config: None,
+            update_needed: true,
+        }
+    }
+
+    // Bitrate of the frames that reached the client in the last second
+    fn throughput_bps(&self) -> Option<f32> {
+        let (first_arrival_ms, _) = self.received_frames.front()?;
+        let (last_arrival_ms, _) = self.received_frames.back()?;
+        let interval_ms = last_arrival_ms - first_arrival_ms;
+
+        // The estimation is too noisy with few frames
+        if interval_ms < MIN_THROUGHPUT_WINDOW_MS {
+            return None;
+        }
+
+        // The first frame arrived at the start of the interval, so it is not counted
+        let bits = self
+            .received_frames
+            .iter()
+            .skip(1)
+            .map(|(_, size)| *size)
+            .sum::<usize>();
+
+        Some((bits as f64 / (interval_ms / 1000.0)) as f32)
+    }
+
+    fn apply_usage(
+        &mut self,
+        usage: BandwidthUsage,
+        arrival_ms: f64,
+        config: &DelayGradientConfig,
+    ) {
+        match usage {
+            BandwidthUsage::Overusing => {
+                let can_decrease = self
+                    .last_decrease_arrival_ms
+                    .map(|last| arrival_ms - last >= MIN_DECREASE_INTERVAL_MS)
+                    .unwrap_or(true);
+
+                if can_decrease {
+                    let reference_bps = self
+                        .throughput_bps()
+                        .unwrap_or(self.delay_based_bitrate_bps);
+                    self.delay_based_bitrate_bps = config.clamp(f32::min(
+                        reference_bps * config.decrease_multiplier,
+                        self.delay_based_bitrate_bps,
+                    ));
+
+                    self.last_decrease_arrival_ms = Some(arrival_ms);
+                    self.state = RateControlState::Hold;
+                    self.update_needed = true;
+                }
+            }
+            BandwidthUsage::Underusing => self.state = RateControlState::Hold,
+            BandwidthUsage::Normal => {
+                if self.state == RateControlState::Hold {
+                    self.state = RateControlState::Increase;
+                    // Do not count the hold time as increase time
+                    self.last_increase_instant = None;
+                }
+            }
+        }
+    }
+
+    fn increase(&mut self, now: Instant, config: &DelayGradientConfig) {
+        if self.state != RateControlState::Increase {
+            return;
+        }
+
+        let Some(last_increase_instant) = self.last_increase_instant.replace(now) else {
+            return;
+        };
+        let interval = Duration::min(now - last_increase_instant, MAX_INCREASE_INTERVAL);
+
+        let mut bitrate_bps = self.delay_based_bitrate_bps
+            * config
+                .increase_multiplier_per_second
+                .powf(interval.as_secs_f32());
+        if let Some(throughput_bps) = self.throughput_bps() {
+            bitrate_bps = f32::min(
+                bitrate_bps,
+                f32::max(
+                    throughput_bps * MAX_THROUGHPUT_MULTIPLIER,
+                    self.delay_based_bitrate_bps,
+                ),
+            );
+        }
+
+        self.delay_based_bitrate_bps = config.clamp(bitrate_bps);
+    }
+
+    fn evaluate_loss(&mut self, now: Instant) {
+        let Some(last_evaluation_instant) = self.last_loss_evaluation_instant else {
+            self.last_loss_evaluation_instant = Some(now);
+            return;
+        };
+        if now < last_evaluation_instant + LOSS_EVALUATION_INTERVAL {
+            return;
+        }
+        self.last_loss_evaluation_instant = Some(now);
+
+        let total_count = self.lost_packets_count + self.received_frames_count;
+        let loss_fraction = if total_count > 0 {
+            self.lost_packets_count as f32 / total_count as f32
+        } else {
+            0.0
+        };
+        self.lost_packets_count = 0;
+        self.received_frames_count = 0;
+
+        if loss_fraction > HIGH_LOSS_FRACTION {
+            let current_bps = f32::min(self.delay_based_bitrate_bps, self.loss_based_bitrate_bps);
+            self.loss_based_bitrate_bps = current_bps * (1.0 - 0.5 * loss_fraction);
+            self.update_needed = true;
+        } else if loss_fraction < LOW_LOSS_FRACTION {
+            self.loss_based_bitrate_bps *= LOSS_BASED_INCREASE_MULTIPLIER;
+        }
+    }
+}
+
+impl BitrateController for DelayGradientController {
+    fn report_frame_present(
+        &mut self,
+        now: Instant,
+        config: &Switch<BitrateAdaptiveFramerateConfig>,
+    ) {
+        if self.frame_interval.report_frame_present(now, config) {
+            self.update_needed = true;
+        }
+    }
+
+    fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        _encoder_latency: Duration,
+        size_bytes: usize,
+    ) {
+        self.frame_sizes_bits.push_back((timestamp, size_bytes * 8));
+    }
+
+    fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        _decoder_latency: Duration,
+    ) {
+        let Some(config) = DelayGradientConfig::from_mode(config) else {
+            return;
+        };
+        if network_latency.is_zero() {
+            return;
+        }
+
+        let arrival_ms = (timestamp + network_latency).as_secs_f64() * 1000.0;
+        let latency_ms = network_latency.as_secs_f64() * 1000.0;
+
+        while let Some(&(timestamp_, size_bits)) = self.frame_sizes_bits.front() {
+            self.frame_sizes_bits.pop_front();
+
+            if timestamp_ == timestamp {
+                self.received_frames.push_back((arrival_ms, size_bits));
+                break;
+            }
+        }
+        while let Some((first_arrival_ms, _)) = self.received_frames.front() {
+            if arrival_ms - first_arrival_ms > THROUGHPUT_WINDOW_MS {
+                self.received_frames.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.received_frames_count += 1;
+
+        let usage = self.trendline.update(arrival_ms, latency_ms);
+        self.apply_usage(usage, arrival_ms, &config);
+    }
+
+    fn report_packet_loss(&mut self) {
+        self.lost_packets_count += 1;
+    }
+
+    fn get_encoder_params(
+        &mut self,
+        now: Instant,
+        config: &BitrateConfig,
+    ) -> (FfiDynamicEncoderParams, Option<NominalBitrateStats>) {
+        let Some(mode_config) = DelayGradientConfig::from_mode(&config.mode) else {
+            return (super::no_update_params(), None);
+        };
+
+        if self.previous_config.is_none() {
+            self.delay_based_bitrate_bps = mode_config.initial_bitrate_bps;
+        }
+        if self.previous_config.as_ref() != Some(config) {
+            self.previous_config = Some(config.clone());
+            self.delay_based_bitrate_bps = mode_config.clamp(self.delay_based_bitrate_bps);
+            self.update_needed = true;
+        }
+
+        self.increase(now, &mode_config);
+        self.evaluate_loss(now);
+
+        let bitrate_bps = mode_config.clamp(f32::min(
+            self.delay_based_bitrate_bps,
+            self.loss_based_bitrate_bps,
+        ));
+
+        // Decreases are applied immediately, increases at most once per update interval
+        let increase_due = self
+            .last_update_instant
+            .map(|instant| now >= instant + UPDATE_INTERVAL)
+            .unwrap_or(true)
+            && bitrate_bps != self.last_requested_bitrate_bps;
+        if !self.update_needed && !increase_due {
+            return (super::no_update_params(), None);
+        }
+
+        self.last_update_instant = Some(now);
+        self.last_requested_bitrate_bps = bitrate_bps;
+        self.update_needed = false;
+
+        let stats = NominalBitrateStats {
+            scaled_calculated_bps: Some(self.delay_based_bitrate_bps),
+            manual_max_bps: Some(mode_config.max_bitrate_bps),
+            manual_min_bps: Some(mode_config.min_bitrate_bps),
+            requested_bps: bitrate_bps,
+            ..Default::default()
+        };
+
+        (
+            FfiDynamicEncoderParams {
+                updated: 1,
+                bitrate_bps: bitrate_bps as u64,
+                framerate: self.frame_interval.framerate(config),
+            },
+            Some(stats),
+        )
+    }
+}
@@ -0,0 +1,493 @@
+mod adaptive;
+mod gcc;
+
+use crate::FfiDynamicEncoderParams;
+use adaptive::AdaptiveController;
+use alvr_common::SlidingWindowAverage;
+use alvr_events::NominalBitrateStats;
+use alvr_session::{
+    settings_schema::Switch, BitrateAdaptiveFramerateConfig, BitrateConfig, BitrateMode,
+};
+use gcc::DelayGradientController;
+use std::time::{Duration, Instant};
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Algorithm that chooses the encoder bitrate, using the feedback from the encoder and the client.
+// Time is passed explicitly so that controllers can be driven by simulations.
+pub trait BitrateController: Send {
+    // Note: This is used to calculate the framerate/frame interval. The frame present is the most
+    // accurate event for this use.
+    fn report_frame_present(
+        &mut self,
+        now: Instant,
+        config: &Switch<BitrateAdaptiveFramerateConfig>,
+    );
+
+    fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    );
+
+    // decoder_latency can be used to learn a suitable maximum bitrate bound to avoid decoder
+    // runaway latency
+    fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    );
+
+    // Called when the client lost a video packet
+    fn report_packet_loss(&mut self);
+
+    // Returned params have `updated` set to 0 if the encoder does not need to be reconfigured
+    fn get_encoder_params(
+        &mut self,
+        now: Instant,
+        config: &BitrateConfig,
+    ) -> (FfiDynamicEncoderParams, Option<NominalBitrateStats>);
+}
+
+fn no_update_params() -> FfiDynamicEncoderParams {
+    FfiDynamicEncoderParams {
+        updated: 0,
+        bitrate_bps: 0,
+        framerate: 0.0,
+    }
+}
+
+// Framerate estimation shared by all controllers
+struct FrameIntervalEstimator {
+    nominal_frame_interval: Duration,
+    frame_interval_average: SlidingWindowAverage<Duration>,
+    last_frame_instant: Option<Instant>,
+}
+
+impl FrameIntervalEstimator {
+    fn new(max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            nominal_frame_interval: Duration::from_secs_f32(1. / initial_framerate),
+            frame_interval_average: SlidingWindowAverage::new(
+                Duration::from_millis(16),
+                max_history_size,
+            ),
+            last_frame_instant: None,
+        }
+    }
+
+    // Returns true if the framerate changed abruptly and the encoder should be updated
+    fn report_frame_present(
+        &mut self,
+        now: Instant,
+        config: &Switch<BitrateAdaptiveFramerateConfig>,
+    ) -> bool {
+        let Some(last_frame_instant) = self.last_frame_instant.replace(now) else {
+            return false;
+        };
+        let interval = now - last_frame_instant;
+
+        if let Some(config) = config.as_option() {
+            let interval_ratio =
+                interval.as_secs_f32() / self.frame_interval_average.get_average().as_secs_f32();
+
+            self.frame_interval_average.submit_sample(interval);
+
+            if interval_ratio > config.framerate_reset_threshold_multiplier
+                || interval_ratio < 1.0 / config.framerate_reset_threshold_multiplier
+            {
+                // Clear most of the samples, keep some for stability
+                self.frame_interval_average.retain(5);
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn nominal(&self) -> Duration {
+        self.nominal_frame_interval
+    }
+
+    fn framerate(&self, config: &BitrateConfig) -> f32 {
+        let frame_interval = if config.adapt_to_framerate.enabled() {
+            self.frame_interval_average.get_average()
+        } else {
+            self.nominal_frame_interval
+        };
+
+        1.0 / frame_interval.as_secs_f32().min(1.0)
+    }
+}
+
+// Owns the controller for the current bitrate mode. The controller is replaced when the mode
+// changes, since the mode can be changed in real time.
+pub struct BitrateManager {
+    max_history_size: usize,
+    initial_framerate: f32,
+    controller: Box<dyn BitrateController>,
+    is_delay_gradient: bool,
+}
+
+impl BitrateManager {
+    pub fn new(max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            max_history_size,
+            initial_framerate,
+            controller: Box::new(AdaptiveController::new(max_history_size, initial_framerate)),
+            is_delay_gradient: false,
+        }
+    }
+
+    fn select_controller(&mut self, mode: &BitrateMode) {
+        let is_delay_gradient = matches!(mode, BitrateMode::DelayGradient { .. });
+
+        if is_delay_gradient != self.is_delay_gradient {
+            self.controller = if is_delay_gradient {
+                Box::new(DelayGradientController::new(
+                    self.max_history_size,
+                    self.initial_framerate,
+                ))
+            } else {
+                Box::new(AdaptiveController::new(
+                    self.max_history_size,
+                    self.initial_framerate,
+                ))
+            };
+            self.is_delay_gradient = is_delay_gradient;
+        }
+    }
+
+    pub fn report_frame_present(&mut self, config: &Switch<BitrateAdaptiveFramerateConfig>) {
+        self.controller.report_frame_present(Instant::now(), config);
+    }
+
+    pub fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    ) {
+        self.controller
+            .report_frame_encoded(timestamp, encoder_latency, size_bytes);
+    }
+
+    pub fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    ) {
+        self.select_controller(config);
+
+        self.controller
+            .report_frame_latencies(config, timestamp, network_latency, decoder_latency);
+    }
+
+    pub fn report_packet_loss(&mut self) {
+        self.controller.report_packet_loss();
+    }
+
+    pub fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+    ) -> (FfiDynamicEncoderParams, Option<NominalBitrateStats>) {
+        self.select_controller(&config.mode);
+
+        self.controller.get_encoder_params(Instant::now(), config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_session::{DecoderLatencyLimiter, EncoderLatencyLimiter};
+
+    const FRAMERATE: f32 = 90.0;
+    const ENCODER_LATENCY: Duration = Duration::from_millis(4);
+    const DECODER_LATENCY: Duration = Duration::from_millis(3);
+
+    // Bottleneck link with a FIFO queue. The network latency of a frame is the propagation delay
+    // plus the time spent waiting for the previous frames to be transmitted.
+    struct BottleneckLink {
+        capacity_bps: f32,
+        propagation_delay: Duration,
+        busy_until: Duration,
+    }
+
+    impl BottleneckLink {
+        fn new(capacity_mbps: f32) -> Self {
+            Self {
+                capacity_bps: capacity_mbps * 1e6,
+                propagation_delay: Duration::from_millis(2),
+                busy_until: Duration::ZERO,
+            }
+        }
+
+        fn send(&mut self, timestamp: Duration, size_bits: usize) -> Duration {
+            let start = Duration::max(timestamp, self.busy_until);
+            self.busy_until = start + Duration::from_secs_f32(size_bits as f32 / self.capacity_bps);
+
+            self.busy_until - timestamp + self.propagation_delay
+        }
+    }
+
+    struct Simulation {
+        controller: Box<dyn BitrateController>,
+        config: BitrateConfig,
+        start: Instant,
+        frame_index: u32,
+        bitrate_bps: f32,
+    }
+
+    impl Simulation {
+        fn new(controller: Box<dyn BitrateController>, mode: BitrateMode) -> Self {
+            Self {
+                controller,
+                config: BitrateConfig {
+                    mode,
+                    adapt_to_framerate: Switch::Disabled,
+                    history_size: 256,
+                    image_corruption_fix: false,
+                },
+                start: Instant::now(),
+                frame_index: 0,
+                bitrate_bps: 0.0,
+            }
+        }
+
+        // Encodes frames at the current bitrate for the given time. `network` returns the network
+        // latency for a frame timestamp and size, or None if the frame was lost. Returns the
+        // average bitrate of the run.
+        fn run(
+            &mut self,
+            duration_s: f32,
+            mut network: impl FnMut(Duration, usize) -> Option<Duration>,
+        ) -> f32 {
+            let frame_count = (duration_s * FRAMERATE) as u32;
+            let mut bitrate_sum_bps = 0.0_f64;
+
+            for _ in 0..frame_count {
+                let timestamp = Duration::from_secs_f32(self.frame_index as f32 / FRAMERATE);
+                let now = self.start + timestamp;
+                self.frame_index += 1;
+
+                self.controller
+                    .report_frame_present(now, &self.config.adapt_to_framerate);
+
+                let (params, _) = self.controller.get_encoder_params(now, &self.config);
+                if params.updated != 0 {
+                    assert_eq!(params.framerate, FRAMERATE);
+                    self.bitrate_bps = params.bitrate_bps as f32;
+                }
+                bitrate_sum_bps += self.bitrate_bps as f64;
+
+                let size_bytes = (self.bitrate_bps / FRAMERATE / 8.0) as usize;
+                self.controller
+                    .report_frame_encoded(timestamp, ENCODER_LATENCY, size_bytes);
+
+                match network(timestamp, size_bytes * 8) {
+                    Some(network_latency) => self.controller.report_frame_latencies(
+                        &self.config.mode,
+                        timestamp,
+                        network_latency,
+                        DECODER_LATENCY,
+                    ),
+                    None => self.controller.report_packet_loss(),
+                }
+            }
+
+            (bitrate_sum_bps / frame_count as f64) as f32
+        }
+    }
+
+    fn adaptive_mode(decoder_latency_limiter: Switch<DecoderLatencyLimiter>) -> BitrateMode {
+        BitrateMode::Adaptive {
+            saturation_multiplier: 0.95,
+            max_bitrate_mbps: Switch::Enabled(200),
+            min_bitrate_mbps: Switch::Enabled(5),
+            max_network_latency_ms: Switch::Disabled,
+            encoder_latency_limiter: Switch::Enabled(EncoderLatencyLimiter {
+                max_saturation_multiplier: 0.9,
+            }),
+            decoder_latency_limiter,
+        }
+    }
+
+    fn delay_gradient_mode(initial_bitrate_mbps: u64) -> BitrateMode {
+        BitrateMode::DelayGradient {
+            initial_bitrate_mbps,
+            max_bitrate_mbps: 200,
+            min_bitrate_mbps: 5,
+            increase_multiplier_per_second: 1.08,
+            decrease_multiplier: 0.85,
+        }
+    }
+
+    fn adaptive_simulation(mode: BitrateMode) -> Simulation {
+        Simulation::new(Box::new(AdaptiveController::new(256, FRAMERATE)), mode)
+    }
+
+    fn delay_gradient_simulation(initial_bitrate_mbps: u64) -> Simulation {
+        Simulation::new(
+            Box::new(DelayGradientController::new(256, FRAMERATE)),
+            delay_gradient_mode(initial_bitrate_mbps),
+        )
+    }
+
+    #[test]
+    fn test_constant_bitrate() {
+        let mut simulation = adaptive_simulation(BitrateMode::ConstantMbps(50));
+
+        let mut link = BottleneckLink::new(20.0);
+        let bitrate_bps = simulation.run(5.0, |timestamp, size| Some(link.send(timestamp, size)));
+
+        assert_eq!(bitrate_bps, 50e6);
+    }
+
+    #[test]
+    fn test_adaptive_constant_latency_trace() {
+        let mut simulation = adaptive_simulation(adaptive_mode(Switch::Disabled));
+
+        // With a fixed latency, the estimated throughput is proportional to the frame size and the
+        // bitrate keeps growing up to the manual maximum
+        simulation.run(10.0, |_, _| Some(Duration::from_millis(5)));
+        let bitrate_bps = simulation.run(1.0, |_, _| Some(Duration::from_millis(5)));
+
+        assert_eq!(bitrate_bps, 200e6);
+    }
+
+    #[test]
+    fn test_adaptive_bottleneck_link() {
+        let mut simulation = adaptive_simulation(adaptive_mode(Switch::Disabled));
+
+        let mut link = BottleneckLink::new(100.0);
+        simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+        let bitrate_bps = simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+
+        assert!(bitrate_bps < 100e6, "{bitrate_bps}");
+        assert!(bitrate_bps > 50e6, "{bitrate_bps}");
+    }
+
+    #[test]
+    fn test_adaptive_decoder_latency_limiter() {
+        let mut unlimited_simulation = adaptive_simulation(adaptive_mode(Switch::Disabled));
+        let mut limited_simulation =
+            adaptive_simulation(adaptive_mode(Switch::Enabled(DecoderLatencyLimiter {
+                // Always exceeded, since the simulated decoder latency is 3ms
+                max_decoder_latency_ms: 1,
+                latency_overstep_frames: 10,
+                latency_overstep_multiplier: 0.9,
+            })));
+
+        let trace = |_, _| Some(Duration::from_millis(5));
+        let unlimited_bps = unlimited_simulation.run(5.0, trace);
+        let limited_bps = limited_simulation.run(5.0, trace);
+
+        assert!(
+            limited_bps < unlimited_bps * 0.5,
+            "{limited_bps} {unlimited_bps}"
+        );
+    }
+
+    #[test]
+    fn test_delay_gradient_converges_below_capacity() {
+        let mut simulation = delay_gradient_simulation(100);
+
+        let mut link = BottleneckLink::new(50.0);
+        simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+
+        let mut max_latency = Duration::ZERO;
+        let bitrate_bps = simulation.run(10.0, |timestamp, size| {
+            let latency = link.send(timestamp, size);
+            max_latency = Duration::max(max_latency, latency);
+
+            Some(latency)
+        });
+
+        assert!(bitrate_bps < 50e6, "{bitrate_bps}");
+        assert!(bitrate_bps > 35e6, "{bitrate_bps}");
+        // The queue has been drained and it is kept short
+        assert!(max_latency < Duration::from_millis(100), "{max_latency:?}");
+    }
+
+    #[test]
+    fn test_delay_gradient_ramp_up() {
+        let mut simulation = delay_gradient_simulation(10);
+
+        let mut link = BottleneckLink::new(100.0);
+        simulation.run(20.0, |timestamp, size| Some(link.send(timestamp, size)));
+        let bitrate_bps = simulation.run(5.0, |timestamp, size| Some(link.send(timestamp, size)));
+
+        assert!(bitrate_bps > 40e6, "{bitrate_bps}");
+        assert!(bitrate_bps < 100e6, "{bitrate_bps}");
+    }
+
+    #[test]
+    fn test_delay_gradient_capacity_drop() {
+        let mut simulation = delay_gradient_simulation(40);
+
+        let mut link = BottleneckLink::new(50.0);
+        simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+        let high_capacity_bps =
+            simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+
+        link.capacity_bps = 20e6;
+        simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+        let low_capacity_bps =
+            simulation.run(10.0, |timestamp, size| Some(link.send(timestamp, size)));
+
+        assert!(high_capacity_bps > 35e6, "{high_capacity_bps}");
+        assert!(low_capacity_bps < 20e6, "{low_capacity_bps}");
+        assert!(low_capacity_bps > 14e6, "{low_capacity_bps}");
+    }
+
+    #[test]
+    fn test_delay_gradient_latency_ramp_trace() {
+        let mut simulation = delay_gradient_simulation(50);
+
+        let stable_bps = simulation.run(2.0, |_, _| Some(Duration::from_millis(5)));
+
+        // Latency growing by 1ms every frame, as if a queue was building up
+        let mut latency = Duration::from_millis(5);
+        simulation.run(0.5, |_, _| {
+            latency += Duration::from_millis(1);
+
+            Some(latency)
+        });
+        let congested_bps = simulation.run(0.5, |_, _| {
+            latency += Duration::from_millis(1);
+
+            Some(latency)
+        });
+
+        assert!(stable_bps >= 50e6, "{stable_bps}");
+        assert!(congested_bps < 50e6, "{congested_bps}");
+    }
+
+    #[test]
+    fn test_delay_gradient_loss_limiter() {
+        let mut lossless_simulation = delay_gradient_simulation(50);
+        let mut lossy_simulation = delay_gradient_simulation(50);
+
+        let lossless_bps = lossless_simulation.run(5.0, |_, _| Some(Duration::from_millis(5)));
+
+        // One frame in 5 is lost, but the latency is stable
+        let mut frame_count = 0;
+        let mut lossy_trace = |_, _| {
+            frame_count += 1;
+
+            (frame_count % 5 != 0).then_some(Duration::from_millis(5))
+        };
+        lossy_simulation.run(3.0, &mut lossy_trace);
+        let lossy_bps = lossy_simulation.run(2.0, &mut lossy_trace);
+
+        assert!(lossless_bps > 50e6, "{lossless_bps}");
+        assert!(lossy_bps < 40e6, "{lossy_bps}");
+    }
+}
@@ -1,16 +1,24 @@
 use crate::{
     bitrate::BitrateManager,
-    face_tracking::FaceTrackingSink,
-    hand_gestures::{trigger_hand_gesture_actions, HandGestureManager, HAND_GESTURE_BUTTON_SET},
+    custom_hand_gestures::{self, CustomHandGestureManager},
+    external_tracking::{self, ExternalTrackingManager, ExternalTrackingReceiver},
+    face_tracking,
+    hand_gestures::{
+        self, trigger_hand_gesture_actions, HandGestureManager, HAND_GESTURE_BUTTON_SET,
+    },
+    hand_pointer::{self, HandPointer},
     haptics,
-    input_mapping::ButtonMappingManager,
+    input_mapping::{self, ButtonMappingManager},
     sockets::WelcomeSocket,
     statistics::StatisticsManager,
+    statistics_log::FrameStatisticsLog,
     tracking::{self, TrackingManager},
-    FfiFov, FfiViewsConfig, VideoPacket, BITRATE_MANAGER, DECODER_CONFIG, LIFECYCLE_STATE,
-    SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
+    tracking_recording::{self, RecordedInput, TrackingPlayer, TrackingRecorder},
+    FfiFov, FfiViewsConfig, VideoPacket, BITRATE_MANAGER, DECODER_CONFIG, FILESYSTEM_LAYOUT,
+    LIFECYCLE_STATE, SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER,
+    VIDEO_RECORDING_FILE,
 };
-use alvr_audio::AudioDevice;
+use alvr_audio::{AudioDevice, ChannelLayout};
 use alvr_common::{
     con_bail, debug, error,
     glam::{UVec2, Vec2},
@@ -19,19 +27,22 @@ use alvr_common::{
     parking_lot::{Condvar, Mutex},
     settings_schema::Switch,
     warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState, OptLazy, ToCon,
-    BUTTON_INFO, CONTROLLER_PROFILE_INFO, DEVICE_ID_TO_PATH, HEAD_ID, LEFT_HAND_ID,
-    QUEST_CONTROLLER_PROFILE_PATH, RIGHT_HAND_ID,
+    DEVICE_ID_TO_PATH, HEAD_ID, LEFT_HAND_ID, LEFT_HAND_PATH, QUEST_CONTROLLER_PROFILE_ID,
+    RIGHT_HAND_ID, RIGHT_HAND_PATH,
 };
 use alvr_events::{ButtonEvent, EventType, HapticsEvent, TrackingEvent};
 use alvr_packets::{
     ClientConnectionResult, ClientControlPacket, ClientListAction, ClientStatistics, Haptics,
-    ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader, AUDIO, HAPTICS,
-    STATISTICS, TRACKING, VIDEO,
+    PairingStatus, ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader, AUDIO,
+    HAPTICS, STATISTICS, TRACKING, VIDEO,
+};
+use alvr_session::{
+    AudioDownmixConfig, ClientPairing, ControllersConfig, ControllersEmulationMode, FrameSize,
+    OpenvrConfig, SessionConfig,
 };
-use alvr_session::{ControllersEmulationMode, FrameSize, OpenvrConfig, SessionConfig};
 use alvr_sockets::{
-    PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder, KEEPALIVE_INTERVAL,
-    KEEPALIVE_TIMEOUT,
+    CaptureSide, Identity, PacketRecorder, PeerType, ProtoControlSocket, StreamSender,
+    StreamSocketBuilder, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
 };
 use std::{
     collections::{HashMap, HashSet},
@@ -41,7 +52,7 @@ use std::{
     ptr,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::{RecvTimeoutError, SyncSender, TrySendError},
+        mpsc::{self, RecvTimeoutError, SyncSender, TrySendError},
         Arc,
     },
     thread::{self, JoinHandle},
@@ -51,11 +62,18 @@ use std::{
 const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
 const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
+// During tracking playback, the last pose is sent at least at this interval
+const PLAYBACK_REPEAT_INTERVAL: Duration = Duration::from_millis(10);
 
 const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
 
 static VIDEO_CHANNEL_SENDER: OptLazy<SyncSender<VideoPacket>> = alvr_common::lazy_mut_none();
 static HAPTICS_SENDER: OptLazy<StreamSender<Haptics>> = alvr_common::lazy_mut_none();
+static TRACKING_RECORDER: OptLazy<TrackingRecorder> = alvr_common::lazy_mut_none();
+pub static EXTERNAL_TRACKING_MANAGER: OptLazy<ExternalTrackingManager> =
+    alvr_common::lazy_mut_none();
+pub static CUSTOM_HAND_GESTURE_MANAGER: OptLazy<CustomHandGestureManager> =
+    alvr_common::lazy_mut_none();
 static CONNECTION_THREADS: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(vec![]));
 pub static CLIENTS_TO_BE_REMOVED: Lazy<Mutex<HashSet<String>>> =
     Lazy::new(|| Mutex::new(HashSet::new()));
@@ -73,6 +91,24 @@ fn is_streaming(client_hostname: &str) -> bool {
         .unwrap_or(false)
 }
 
+// Custom hand gestures are mapped together with the controller buttons, so their bindings can have
+// conditions on the controller buttons
+fn create_controller_button_mapping_manager(
+    config: &ControllersConfig,
+    button_set: &HashSet<u64>,
+) -> ButtonMappingManager {
+    let mut manager = if let Some(mappings) = &config.button_mappings {
+        ButtonMappingManager::new_manual(mappings)
+    } else {
+        ButtonMappingManager::new_automatic(button_set, &config.button_mapping_config)
+    };
+    manager.add_manual_mappings(&custom_hand_gestures::gesture_mappings(
+        &config.custom_hand_gestures,
+    ));
+
+    manager
+}
+
 pub fn contruct_openvr_config(session: &SessionConfig) -> OpenvrConfig {
     let old_config = session.openvr_config.clone();
     let settings = session.to_settings();
@@ -161,6 +197,12 @@ pub fn contruct_openvr_config(session: &SessionConfig) -> OpenvrConfig {
         sw_thread_count: settings.video.encoder_config.software.thread_count,
         controllers_enabled,
         controller_is_tracker,
+        body_tracker_paths: settings
+            .headset
+            .body_trackers
+            .iter()
+            .map(|tracker| tracking::body_tracker_path(tracker.role).into())
+            .collect(),
         enable_foveated_encoding,
         foveation_center_size_x,
         foveation_center_size_y,
@@ -210,6 +252,14 @@ pub fn handshake_loop() {
         }
     };
 
+    let identity = match Identity::load_or_generate(&FILESYSTEM_LAYOUT.identity_key()) {
+        Ok(identity) => Arc::new(identity),
+        Err(e) => {
+            error!("Failed to load streamer identity key: {e:?}");
+            return;
+        }
+    };
+
     while *LIFECYCLE_STATE.write() != LifecycleState::ShuttingDown {
         let available_manual_client_ips = {
             let mut manual_client_ips = HashMap::new();
@@ -217,7 +267,10 @@ pub fn handshake_loop() {
                 .read()
                 .client_list()
                 .iter()
-                .filter(|(_, info)| info.connection_state == ConnectionState::Disconnected)
+                .filter(|(_, info)| {
+                    info.connection_state == ConnectionState::Disconnected
+                        && (info.identity_key.is_some() || info.pending_pairing.is_none())
+                })
             {
                 for ip in &connection_info.manual_ips {
                     manual_client_ips.insert(*ip, hostname.clone());
@@ -227,7 +280,7 @@ pub fn handshake_loop() {
         };
 
         if !available_manual_client_ips.is_empty()
-            && try_connect(available_manual_client_ips).is_ok()
+            && try_connect(available_manual_client_ips, Arc::clone(&identity)).is_ok()
         {
             thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
             continue;
@@ -256,7 +309,7 @@ pub fn handshake_loop() {
             }
 
             for (client_hostname, client_ip) in clients {
-                let trusted = {
+                let (paired, pairing_pending) = {
                     let mut data_manager = SERVER_DATA_MANAGER.write();
 
                     data_manager.update_client_list(
@@ -275,12 +328,13 @@ pub fn handshake_loop() {
                     data_manager
                         .client_list()
                         .get(&client_hostname)
-                        .map(|c| c.trusted)
-                        .unwrap_or(false)
+                        .map(|c| (c.identity_key.is_some(), c.pending_pairing.is_some()))
+                        .unwrap_or((false, false))
                 };
 
-                // do not attempt connection if the client is already connected
-                if trusted
+                // Unpaired clients are connected only once, to run the handshake and obtain the
+                // pairing code. Do not attempt connection if the client is already connected
+                if (paired || !pairing_pending)
                     && SERVER_DATA_MANAGER
                         .read()
                         .client_list()
@@ -288,9 +342,10 @@ pub fn handshake_loop() {
                         .map(|c| c.connection_state == ConnectionState::Disconnected)
                         .unwrap_or(false)
                 {
-                    if let Err(e) =
-                        try_connect([(client_ip, client_hostname.clone())].into_iter().collect())
-                    {
+                    if let Err(e) = try_connect(
+                        [(client_ip, client_hostname.clone())].into_iter().collect(),
+                        Arc::clone(&identity),
+                    ) {
                         error!("Could not initiate connection for {client_hostname}: {e}");
                     }
                 }
@@ -308,7 +363,7 @@ pub fn handshake_loop() {
     }
 }
 
-fn try_connect(mut client_ips: HashMap<IpAddr, String>) -> ConResult {
+fn try_connect(mut client_ips: HashMap<IpAddr, String>, identity: Arc<Identity>) -> ConResult {
     let (proto_socket, client_ip) = ProtoControlSocket::connect_to(
         Duration::from_secs(1),
         PeerType::AnyClient(client_ips.keys().cloned().collect()),
@@ -319,7 +374,9 @@ fn try_connect(mut client_ips: HashMap<IpAddr, String>) -> ConResult {
     };
 
     CONNECTION_THREADS.lock().push(thread::spawn(move || {
-        if let Err(e) = connection_pipeline(proto_socket, client_hostname.clone(), client_ip) {
+        if let Err(e) =
+            connection_pipeline(proto_socket, &identity, client_hostname.clone(), client_ip)
+        {
             error!("Handshake error for {client_hostname}: {e}");
         }
 
@@ -342,13 +399,77 @@ fn try_connect(mut client_ips: HashMap<IpAddr, String>) -> ConResult {
 
 fn connection_pipeline(
     mut proto_socket: ProtoControlSocket,
+    identity: &Identity,
     client_hostname: String,
     client_ip: IpAddr,
 ) -> ConResult {
+    let handshake = proto_socket.handshake(identity, HANDSHAKE_ACTION_TIMEOUT)?;
+
     // This session lock will make sure settings cannot be changed while connecting and no other
     // client can connect (until handshake is finished)
     let mut server_data_lock = SERVER_DATA_MANAGER.write();
 
+    // The hostname is chosen by the client and anyone on the network can spoof it. The identity key
+    // is what actually identifies the client.
+    let Some(client_config) = server_data_lock
+        .client_list()
+        .get(&client_hostname)
+        .cloned()
+    else {
+        con_bail!("Client {client_hostname} has been removed");
+    };
+    match client_config.identity_key {
+        Some(key) if key == handshake.peer_identity_key => (),
+        Some(_) => {
+            con_bail!(
+                "Client {client_hostname} presented an unknown identity key! {}",
+                "If the client has been reinstalled, remove it and pair it again"
+            );
+        }
+        // Clients without a key (discovered, added manually or trusted before pairing was
+        // introduced) must be paired explicitly, even if they are marked as trusted
+        None => {
+            info!(
+                "Client {client_hostname} is waiting to be trusted. Pairing code: {}",
+                handshake.pairing_code
+            );
+            server_data_lock.update_client_list(
+                client_hostname,
+                ClientListAction::SetPendingPairing(Some(ClientPairing {
+                    identity_key: handshake.peer_identity_key,
+                    code: handshake.pairing_code,
+                })),
+            );
+
+            proto_socket
+                .send(&PairingStatus::PairingRequired)
+                .to_con()?;
+
+            return Ok(());
+        }
+    }
+
+    proto_socket.send(&PairingStatus::Paired).to_con()?;
+
+    let maybe_recorder = if server_data_lock.settings().capture.packet_capture {
+        let path = FILESYSTEM_LAYOUT.log_dir.join(format!(
+            "capture.{}.alvrcap",
+            chrono::Local::now().format("%F.%H-%M-%S")
+        ));
+        match PacketRecorder::create(&path, CaptureSide::Server) {
+            Ok(recorder) => {
+                proto_socket.set_recorder(Arc::clone(&recorder));
+                Some(recorder)
+            }
+            Err(e) => {
+                error!("Failed to create packet capture file: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     server_data_lock.update_client_list(
         client_hostname.clone(),
         ClientListAction::SetConnectionState(ConnectionState::Connecting),
@@ -404,7 +525,8 @@ fn connection_pipeline(
         con_bail!("Only streaming clients are supported for now");
     };
 
-    let settings = server_data_lock.settings().clone();
+    // Settings with the profile and override of this client applied
+    let settings = server_data_lock.client_settings(&client_hostname).clone();
 
     fn get_view_res(config: FrameSize, default_res: UVec2) -> UVec2 {
         let res = match config {
@@ -454,8 +576,31 @@ fn connection_pipeline(
         warn!("Chosen refresh rate not supported. Using {fps}Hz");
     }
 
-    let game_audio_sample_rate =
-        if let Switch::Enabled(game_audio_config) = &settings.audio.game_audio {
+    // Removed when dropped, at the end of the connection
+    #[cfg(target_os = "linux")]
+    let virtual_game_audio_sink = match (&settings.audio.game_audio, settings.audio.linux_backend) {
+        (Switch::Enabled(config), alvr_session::LinuxAudioBackend::PipeWire) => {
+            let channel_layout = if config.surround {
+                ChannelLayout::Surround51
+            } else {
+                ChannelLayout::Stereo
+            };
+
+            Some(alvr_audio::VirtualAudioDevice::new_game_audio_sink(channel_layout).to_con()?)
+        }
+        _ => None,
+    };
+    #[cfg(target_os = "linux")]
+    let virtual_game_audio_format = virtual_game_audio_sink
+        .as_ref()
+        .map(|sink| (sink.sample_rate(), sink.channel_layout()));
+    #[cfg(not(target_os = "linux"))]
+    let virtual_game_audio_format = None;
+
+    let (game_audio_sample_rate, game_audio_channel_layout) =
+        if let Some(format) = virtual_game_audio_format {
+            format
+        } else if let Switch::Enabled(game_audio_config) = &settings.audio.game_audio {
             let game_audio_device = AudioDevice::new_output(
                 Some(settings.audio.linux_backend),
                 game_audio_config.device.as_ref(),
@@ -476,20 +621,36 @@ fn connection_pipeline(
                 }
             }
 
-            game_audio_device.input_sample_rate().to_con()?
+            let channel_layout = if game_audio_config.surround {
+                let channels_count = game_audio_device.input_channels_count().to_con()?;
+
+                ChannelLayout::from_channels_count(channels_count).unwrap_or_else(|| {
+                    warn!("Unsupported game audio channels count {channels_count}, using stereo");
+                    ChannelLayout::Stereo
+                })
+            } else {
+                ChannelLayout::Stereo
+            };
+
+            (
+                game_audio_device.input_sample_rate().to_con()?,
+                channel_layout,
+            )
         } else {
-            0
+            (0, ChannelLayout::Stereo)
         };
 
+    let client_session = server_data_lock
+        .session()
+        .to_client_session(&client_hostname);
+
     let client_config = StreamConfigPacket {
-        session: {
-            let session = server_data_lock.session().clone();
-            serde_json::to_string(&session).to_con()?
-        },
+        session: serde_json::to_string(&client_session).to_con()?,
         negotiated: serde_json::json!({
             "view_resolution": stream_view_resolution,
             "refresh_rate_hint": fps,
             "game_audio_sample_rate": game_audio_sample_rate,
+            "game_audio_channel_layout": game_audio_channel_layout,
         })
         .to_string(),
     };
@@ -498,15 +659,23 @@ fn connection_pipeline(
     let (mut control_sender, mut control_receiver) =
         proto_socket.split(STREAMING_RECV_TIMEOUT).to_con()?;
 
-    let mut new_openvr_config = contruct_openvr_config(server_data_lock.session());
+    let mut new_openvr_config = contruct_openvr_config(&client_session);
     new_openvr_config.eye_resolution_width = stream_view_resolution.x;
     new_openvr_config.eye_resolution_height = stream_view_resolution.y;
     new_openvr_config.target_eye_resolution_width = target_view_resolution.x;
     new_openvr_config.target_eye_resolution_height = target_view_resolution.y;
     new_openvr_config.refresh_rate = fps as _;
 
-    if server_data_lock.session().openvr_config != new_openvr_config {
-        server_data_lock.session_mut().openvr_config = new_openvr_config;
+    // The driver reads the settings of the streaming client when it starts, so it is restarted
+    // also when another client connects
+    let session = server_data_lock.session();
+    if session.openvr_config != new_openvr_config
+        || session.streaming_client_hostname.as_ref() != Some(&client_hostname)
+    {
+        let mut session_lock = server_data_lock.session_mut();
+        session_lock.openvr_config = new_openvr_config;
+        session_lock.streaming_client_hostname = Some(client_hostname.clone());
+        drop(session_lock);
 
         control_sender.send(&ServerControlPacket::Restarting).ok();
 
@@ -521,6 +690,13 @@ fn connection_pipeline(
     if !matches!(signal, ClientControlPacket::StreamReady) {
         con_bail!("Got unexpected packet waiting for stream ack");
     }
+    let frame_log = if let Switch::Enabled(config) = &settings.logging.log_frame_statistics {
+        FrameStatisticsLog::new(FILESYSTEM_LAYOUT.frame_statistics_log(), config)
+            .map_err(|e| warn!("Failed to create frame statistics log: {e}"))
+            .ok()
+    } else {
+        None
+    };
     *STATISTICS_MANAGER.lock() = Some(StatisticsManager::new(
         settings.connection.statistics_history_size,
         Duration::from_secs_f32(1.0 / fps),
@@ -529,6 +705,7 @@ fn connection_pipeline(
         } else {
             0.0
         },
+        frame_log,
     ));
 
     *BITRATE_MANAGER.lock() = BitrateManager::new(settings.video.bitrate.history_size, fps);
@@ -542,9 +719,23 @@ fn connection_pipeline(
         settings.connection.server_send_buffer_bytes,
         settings.connection.server_recv_buffer_bytes,
         settings.connection.packet_size as _,
+        &handshake.stream_keys,
+        settings.debug.network_emulator.as_option(),
     )?;
 
-    let mut video_sender = stream_socket.request_stream(VIDEO);
+    if let Some(recorder) = maybe_recorder {
+        stream_socket.set_recorder(recorder);
+    }
+
+    let mut video_sender = stream_socket.request_stream_with_fec(
+        VIDEO,
+        settings
+            .connection
+            .video_fec
+            .as_option()
+            .map(|config| config.group_size as usize),
+    );
+    video_sender.set_unreliable(true);
     let game_audio_sender = stream_socket.request_stream(AUDIO);
     let mut microphone_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
     let mut tracking_receiver =
@@ -558,6 +749,35 @@ fn connection_pipeline(
     *VIDEO_CHANNEL_SENDER.lock() = Some(video_channel_sender);
     *HAPTICS_SENDER.lock() = Some(haptics_sender);
 
+    if settings.capture.tracking_recording {
+        let path = FILESYSTEM_LAYOUT.log_dir.join(format!(
+            "tracking.{}.alvrtrk",
+            chrono::Local::now().format("%F.%H-%M-%S")
+        ));
+        match TrackingRecorder::create(&path) {
+            Ok(recorder) => *TRACKING_RECORDER.lock() = Some(recorder),
+            Err(e) => error!("Failed to create tracking recording file: {e}"),
+        }
+    }
+
+    let tracking_player = if let Switch::Enabled(config) = &settings.capture.tracking_playback {
+        // Relative paths are resolved from the log directory, where recordings are saved
+        let path = FILESYSTEM_LAYOUT.log_dir.join(&config.recording_file);
+        match tracking_recording::load_recording(&path) {
+            Ok(events) => {
+                info!("Playing back tracking recording {}", path.display());
+                Some(TrackingPlayer::new(events, config.looped))
+            }
+            Err(e) => {
+                error!("Failed to load tracking recording: {e:?}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let is_playing_back = tracking_player.is_some();
+
     let video_send_thread = thread::spawn({
         let client_hostname = client_hostname.clone();
         move || {
@@ -582,6 +802,35 @@ fn connection_pipeline(
     let game_audio_thread = if let Switch::Enabled(config) = settings.audio.game_audio {
         let client_hostname = client_hostname.clone();
         thread::spawn(move || {
+            let stream_timestamp: Arc<dyn Fn(Instant) -> Option<Duration> + Send + Sync> =
+                Arc::new(|instant| {
+                    STATISTICS_MANAGER
+                        .lock()
+                        .as_ref()
+                        .and_then(|stats| stats.stream_timestamp(instant))
+                });
+
+            #[cfg(target_os = "linux")]
+            if let Some(sink) = virtual_game_audio_sink {
+                while is_streaming(&client_hostname) {
+                    if let Err(e) = alvr_audio::record_virtual_sink_blocking(
+                        Arc::new({
+                            let client_hostname = client_hostname.clone();
+                            move || is_streaming(&client_hostname)
+                        }),
+                        Arc::clone(&stream_timestamp),
+                        game_audio_sender.clone(),
+                        &sink,
+                        &config.codec,
+                    ) {
+                        error!("Audio record error: {e:?}");
+                        thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
+                    }
+                }
+
+                return;
+            }
+
             while is_streaming(&client_hostname) {
                 let device = match AudioDevice::new_output(
                     Some(settings.audio.linux_backend),
@@ -614,10 +863,13 @@ fn connection_pipeline(
                         let client_hostname = client_hostname.clone();
                         move || is_streaming(&client_hostname)
                     }),
+                    Arc::clone(&stream_timestamp),
                     game_audio_sender.clone(),
                     &device,
-                    2,
+                    game_audio_channel_layout,
                     config.mute_when_streaming,
+                    &config.codec,
+                    &config.downmix,
                 ) {
                     error!("Audio record error: {e:?}");
                 }
@@ -642,49 +894,240 @@ fn connection_pipeline(
     };
 
     let microphone_thread = if let Switch::Enabled(config) = settings.audio.microphone {
-        #[allow(unused_variables)]
-        let (sink, source) = AudioDevice::new_virtual_microphone_pair(
-            Some(settings.audio.linux_backend),
-            config.devices,
+        // Removed when dropped, at the end of the connection
+        #[cfg(target_os = "linux")]
+        let virtual_source = matches!(
+            settings.audio.linux_backend,
+            alvr_session::LinuxAudioBackend::PipeWire
         )
+        .then(alvr_audio::VirtualAudioDevice::new_microphone_source)
+        .transpose()
         .to_con()?;
+        #[cfg(target_os = "linux")]
+        let uses_virtual_source = virtual_source.is_some();
+        #[cfg(not(target_os = "linux"))]
+        let uses_virtual_source = false;
 
-        #[cfg(windows)]
-        if let Ok(id) = alvr_audio::get_windows_device_id(&source) {
-            unsafe {
-                crate::SetOpenvrProperty(
-                    *alvr_common::HEAD_ID,
-                    crate::openvr_props::to_ffi_openvr_prop(
-                        alvr_session::OpenvrProperty::AudioDefaultRecordingDeviceId(id),
-                    ),
-                )
+        let sink = if uses_virtual_source {
+            None
+        } else {
+            #[allow(unused_variables)]
+            let (sink, source) = AudioDevice::new_virtual_microphone_pair(
+                Some(settings.audio.linux_backend),
+                config.devices,
+            )
+            .to_con()?;
+
+            #[cfg(windows)]
+            if let Ok(id) = alvr_audio::get_windows_device_id(&source) {
+                unsafe {
+                    crate::SetOpenvrProperty(
+                        *alvr_common::HEAD_ID,
+                        crate::openvr_props::to_ffi_openvr_prop(
+                            alvr_session::OpenvrProperty::AudioDefaultRecordingDeviceId(id),
+                        ),
+                    )
+                }
             }
-        }
+
+            Some(sink)
+        };
 
         let client_hostname = client_hostname.clone();
         thread::spawn(move || {
-            alvr_common::show_err(alvr_audio::play_audio_loop(
-                {
-                    let client_hostname = client_hostname.clone();
-                    move || is_streaming(&client_hostname)
-                },
-                &sink,
-                1,
-                streaming_caps.microphone_sample_rate,
-                config.buffering,
-                &mut microphone_receiver,
-            ));
+            #[cfg(target_os = "linux")]
+            if let Some(source) = virtual_source {
+                alvr_common::show_err(alvr_audio::play_virtual_source_loop(
+                    || is_streaming(&client_hostname),
+                    &source,
+                    streaming_caps.microphone_sample_rate,
+                    config.buffering,
+                    &mut microphone_receiver,
+                ));
+
+                return;
+            }
+
+            if let Some(sink) = sink {
+                alvr_common::show_err(alvr_audio::play_audio_loop(
+                    {
+                        let client_hostname = client_hostname.clone();
+                        move || is_streaming(&client_hostname)
+                    },
+                    &sink,
+                    1,
+                    streaming_caps.microphone_sample_rate,
+                    config.buffering,
+                    &AudioDownmixConfig::Standard,
+                    None,
+                    &mut microphone_receiver,
+                ));
+            }
         })
     } else {
         thread::spawn(|| ())
     };
 
+    // Shared with the tracking thread, which reports played back buttons
+    let controller_button_mapping_manager = Arc::new(Mutex::new(
+        server_data_lock
+            .client_settings(&client_hostname)
+            .headset
+            .controllers
+            .as_option()
+            .map(|config| {
+                create_controller_button_mapping_manager(
+                    config,
+                    &input_mapping::profile_button_set(*QUEST_CONTROLLER_PROFILE_ID)
+                        .unwrap_or_default(),
+                )
+            }),
+    ));
+
+    let external_tracking_thread = if let Switch::Enabled(config) =
+        settings.headset.external_tracking.clone()
+    {
+        let bind_address = config.bind_address.clone();
+        let port = config.port;
+        *EXTERNAL_TRACKING_MANAGER.lock() = Some(ExternalTrackingManager::new(config));
+
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            let mut receiver =
+                match ExternalTrackingReceiver::new(&bind_address, port, STREAMING_RECV_TIMEOUT) {
+                    Ok(receiver) => receiver,
+                    Err(e) => {
+                        warn!("Failed to bind external tracking socket: {e:?}");
+                        return;
+                    }
+                };
+
+            while is_streaming(&client_hostname) {
+                if let Some(packet) = receiver.recv() {
+                    if let Some(manager) = &mut *EXTERNAL_TRACKING_MANAGER.lock() {
+                        manager.handle_packet(packet);
+                    }
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
+    if let Some(config) = settings.headset.controllers.as_option() {
+        if !config.custom_hand_gestures.is_empty() {
+            *CUSTOM_HAND_GESTURE_MANAGER.lock() = Some(CustomHandGestureManager::new(
+                &config.custom_hand_gestures,
+                &FILESYSTEM_LAYOUT.hand_gestures_dir(),
+            ));
+        }
+    }
+
     let tracking_manager = Arc::new(Mutex::new(TrackingManager::new()));
     let hand_gesture_manager = Arc::new(Mutex::new(HandGestureManager::new()));
 
+    // Tracking is processed from this channel. During playback, the recorded tracking is sent by
+    // the playback thread with the recording timing, in place of the headset tracking
+    let (tracking_sender, tracking_channel_receiver) = mpsc::channel::<Tracking>();
+    // Last target timestamp received from the headset and when it was received. During playback
+    // the stream is still synchronized to the headset timestamps
+    let last_headset_timestamp = Arc::new(Mutex::new(None::<(Duration, Instant)>));
+
+    let tracking_socket_thread = thread::spawn({
+        let tracking_sender = tracking_sender.clone();
+        let last_headset_timestamp = Arc::clone(&last_headset_timestamp);
+        let client_hostname = client_hostname.clone();
+        move || {
+            while is_streaming(&client_hostname) {
+                let data = match tracking_receiver.recv(STREAMING_RECV_TIMEOUT) {
+                    Ok(tracking) => tracking,
+                    Err(ConnectionError::TryAgain(_)) => continue,
+                    Err(ConnectionError::Other(_)) => return,
+                };
+                let Ok(tracking) = data.get_header() else {
+                    return;
+                };
+
+                if let Some(recorder) = &mut *TRACKING_RECORDER.lock() {
+                    recorder.record(RecordedInput::Tracking(Box::new(tracking.clone())));
+                }
+
+                if is_playing_back {
+                    *last_headset_timestamp.lock() =
+                        Some((tracking.target_timestamp, Instant::now()));
+                } else if tracking_sender.send(tracking).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let tracking_playback_thread = if let Some(mut player) = tracking_player {
+        thread::spawn({
+            let controller_button_mapping_manager = Arc::clone(&controller_button_mapping_manager);
+            let client_hostname = client_hostname.clone();
+            move || {
+                let mut last_tracking = None;
+                let mut last_sent_instant = Instant::now();
+                while is_streaming(&client_hostname) {
+                    let now = Instant::now();
+
+                    let mut tracking_updated = false;
+                    for input in player.advance(now) {
+                        match input {
+                            RecordedInput::Tracking(tracking) => {
+                                last_tracking = Some(*tracking);
+                                tracking_updated = true;
+                            }
+                            RecordedInput::Buttons(entries) => {
+                                if let Some(manager) =
+                                    &mut *controller_button_mapping_manager.lock()
+                                {
+                                    for entry in entries {
+                                        manager.report_button(entry.path_id, entry.value);
+                                    }
+                                }
+                            }
+                            RecordedInput::Haptics(_) => (),
+                        }
+                    }
+
+                    // The last pose is sent again periodically, so the devices stay tracked at the
+                    // end of the recording
+                    let headset_timestamp = *last_headset_timestamp.lock();
+                    if let (Some(tracking), Some((timestamp, instant))) =
+                        (&last_tracking, headset_timestamp)
+                    {
+                        if tracking_updated || now >= last_sent_instant + PLAYBACK_REPEAT_INTERVAL {
+                            let tracking = Tracking {
+                                target_timestamp: timestamp + instant.elapsed(),
+                                ..tracking.clone()
+                            };
+                            if tracking_sender.send(tracking).is_err() {
+                                return;
+                            }
+
+                            last_sent_instant = now;
+                        }
+                    }
+
+                    let wake_instant = player
+                        .next_event_instant()
+                        .map_or(now + PLAYBACK_REPEAT_INTERVAL, |instant| {
+                            instant.min(now + PLAYBACK_REPEAT_INTERVAL)
+                        });
+                    thread::sleep(wake_instant.saturating_duration_since(Instant::now()));
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
     let tracking_receive_thread = thread::spawn({
         let tracking_manager = Arc::clone(&tracking_manager);
         let hand_gesture_manager = Arc::clone(&hand_gesture_manager);
+        let controller_button_mapping_manager = Arc::clone(&controller_button_mapping_manager);
 
         let mut gestures_button_mapping_manager =
             settings.headset.controllers.as_option().map(|config| {
@@ -694,6 +1137,18 @@ fn connection_pipeline(
                 )
             });
 
+        let mut hand_pointer = settings
+            .headset
+            .controllers
+            .as_option()
+            .and_then(|config| config.hand_pointer.as_option())
+            .and_then(|config| {
+                hand_pointer::create_pointer_sink(config.output.clone())
+                    .map(|sink| HandPointer::new(config.clone(), sink))
+                    .map_err(|e| warn!("Failed to create hand pointer: {e:?}"))
+                    .ok()
+            });
+
         let client_hostname = client_hostname.clone();
         move || {
             let mut face_tracking_sink =
@@ -702,29 +1157,52 @@ fn connection_pipeline(
                     .face_tracking
                     .into_option()
                     .and_then(|config| {
-                        FaceTrackingSink::new(config.sink, settings.connection.osc_local_port).ok()
+                        face_tracking::create_face_tracking_sink(
+                            config.sink,
+                            settings.connection.osc_local_port,
+                        )
+                        .map_err(|e| warn!("Failed to create face tracking sink: {e:?}"))
+                        .ok()
                     });
 
             while is_streaming(&client_hostname) {
-                let data = match tracking_receiver.recv(STREAMING_RECV_TIMEOUT) {
-                    Ok(tracking) => tracking,
-                    Err(ConnectionError::TryAgain(_)) => continue,
-                    Err(ConnectionError::Other(_)) => return,
-                };
-                let Ok(tracking) = data.get_header() else {
-                    return;
-                };
+                let mut tracking =
+                    match tracking_channel_receiver.recv_timeout(STREAMING_RECV_TIMEOUT) {
+                        Ok(tracking) => tracking,
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    };
+
+                let finished_calibration =
+                    if let Some(manager) = &mut *EXTERNAL_TRACKING_MANAGER.lock() {
+                        manager.merge_motions(&mut tracking.device_motions)
+                    } else {
+                        None
+                    };
+                if let Some((source_name, calibration)) = finished_calibration {
+                    external_tracking::save_calibration(&source_name, calibration);
+                }
 
                 let controllers_config = {
                     let data_lock = SERVER_DATA_MANAGER.read();
                     data_lock
-                        .settings()
+                        .client_settings(&client_hostname)
                         .headset
                         .controllers
                         .clone()
                         .into_option()
                 };
 
+                if let Some(manager) = &mut *controller_button_mapping_manager.lock() {
+                    manager.set_macros(
+                        controllers_config
+                            .as_ref()
+                            .map(|c| c.button_mapping_macros.as_slice())
+                            .unwrap_or_default(),
+                    );
+                    manager.update_macros();
+                }
+
                 let track_controllers = controllers_config
                     .as_ref()
                     .map(|c| c.tracked)
@@ -736,7 +1214,8 @@ fn connection_pipeline(
                 {
                     let mut tracking_manager_lock = tracking_manager.lock();
                     let data_manager_lock = SERVER_DATA_MANAGER.read();
-                    let headset_config = &data_manager_lock.settings().headset;
+                    let headset_config =
+                        &data_manager_lock.client_settings(&client_hostname).headset;
 
                     motions = tracking_manager_lock.transform_motions(
                         headset_config,
@@ -745,6 +1224,7 @@ fn connection_pipeline(
                             tracking.hand_skeletons[0].is_some(),
                             tracking.hand_skeletons[1].is_some(),
                         ],
+                        tracking.target_timestamp,
                     );
 
                     left_hand_skeleton = tracking.hand_skeletons[0].map(|s| {
@@ -786,6 +1266,10 @@ fn connection_pipeline(
                             fb_face_expression: tracking.face_data.fb_face_expression.clone(),
                             htc_eye_expression: tracking.face_data.htc_eye_expression.clone(),
                             htc_lip_expression: tracking.face_data.htc_lip_expression.clone(),
+                            arkit_blendshapes: tracking
+                                .face_data
+                                .arkit_blendshapes()
+                                .map(|blendshapes| blendshapes.to_vec()),
                         })))
                     }
                 }
@@ -794,7 +1278,7 @@ fn connection_pipeline(
                     let mut face_data = tracking.face_data;
                     face_data.eye_gazes = local_eye_gazes;
 
-                    sink.send_tracking(face_data);
+                    sink.send_tracking(&face_data);
                 }
 
                 let ffi_motions = motions
@@ -823,31 +1307,66 @@ fn connection_pipeline(
                     &mut gestures_button_mapping_manager,
                 ) {
                     let mut hand_gesture_manager_lock = hand_gesture_manager.lock();
+                    let mut gesture_events = vec![];
 
                     if let Some(hand_skeleton) = tracking.hand_skeletons[0] {
+                        let gestures = hand_gesture_manager_lock.get_active_gestures(
+                            hand_skeleton,
+                            gestures_config,
+                            *LEFT_HAND_ID,
+                        );
                         trigger_hand_gesture_actions(
                             gestures_button_mapping_manager,
                             *LEFT_HAND_ID,
-                            &hand_gesture_manager_lock.get_active_gestures(
-                                hand_skeleton,
-                                gestures_config,
-                                *LEFT_HAND_ID,
-                            ),
+                            &gestures,
                             gestures_config.only_touch,
                         );
+                        gesture_events.extend(hand_gestures::to_events(LEFT_HAND_PATH, &gestures));
                     }
                     if let Some(hand_skeleton) = tracking.hand_skeletons[1] {
+                        let gestures = hand_gesture_manager_lock.get_active_gestures(
+                            hand_skeleton,
+                            gestures_config,
+                            *RIGHT_HAND_ID,
+                        );
                         trigger_hand_gesture_actions(
                             gestures_button_mapping_manager,
                             *RIGHT_HAND_ID,
-                            &hand_gesture_manager_lock.get_active_gestures(
-                                hand_skeleton,
-                                gestures_config,
-                                *RIGHT_HAND_ID,
-                            ),
+                            &gestures,
                             gestures_config.only_touch,
                         );
+                        gesture_events.extend(hand_gestures::to_events(RIGHT_HAND_PATH, &gestures));
                     }
+
+                    if SERVER_DATA_MANAGER
+                        .read()
+                        .settings()
+                        .logging
+                        .log_button_mappings
+                    {
+                        alvr_events::send_event(EventType::HandGestures(gesture_events));
+                    }
+                }
+
+                let gesture_buttons =
+                    if let Some(manager) = &mut *CUSTOM_HAND_GESTURE_MANAGER.lock() {
+                        manager.process(&tracking.hand_skeletons, Instant::now())
+                    } else {
+                        vec![]
+                    };
+                if let Some(manager) = &mut *controller_button_mapping_manager.lock() {
+                    for entry in gesture_buttons {
+                        manager.report_button(entry.path_id, entry.value);
+                    }
+                }
+
+                if let Some(pointer) = &mut hand_pointer {
+                    let head_pose = tracking
+                        .device_motions
+                        .iter()
+                        .find(|(id, _)| *id == *HEAD_ID)
+                        .map(|(_, motion)| motion.pose);
+                    pointer.update(head_pose, &tracking.hand_skeletons);
                 }
 
                 if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
@@ -897,7 +1416,11 @@ fn connection_pipeline(
 
                     let server_data_lock = SERVER_DATA_MANAGER.read();
                     BITRATE_MANAGER.lock().report_frame_latencies(
-                        &server_data_lock.settings().video.bitrate.mode,
+                        &server_data_lock
+                            .client_settings(&client_hostname)
+                            .video
+                            .bitrate
+                            .mode,
                         timestamp,
                         network_latency,
                         decoder_latency,
@@ -929,25 +1452,7 @@ fn connection_pipeline(
     });
 
     let control_receive_thread = thread::spawn({
-        let mut controller_button_mapping_manager = server_data_lock
-            .settings()
-            .headset
-            .controllers
-            .as_option()
-            .map(|config| {
-                if let Some(mappings) = &config.button_mappings {
-                    ButtonMappingManager::new_manual(mappings)
-                } else {
-                    ButtonMappingManager::new_automatic(
-                        &CONTROLLER_PROFILE_INFO
-                            .get(&alvr_common::hash_string(QUEST_CONTROLLER_PROFILE_PATH))
-                            .unwrap()
-                            .button_set,
-                        &config.button_mapping_config,
-                    )
-                }
-            });
-
+        let controller_button_mapping_manager = Arc::clone(&controller_button_mapping_manager);
         let disconnect_notif = Arc::clone(&disconnect_notif);
         let control_sender = Arc::clone(&control_sender);
         let client_hostname = client_hostname.clone();
@@ -976,7 +1481,8 @@ fn connection_pipeline(
                     ClientControlPacket::PlayspaceSync(packet) => {
                         if !settings.headset.tracking_ref_only {
                             let data_manager_lock = SERVER_DATA_MANAGER.read();
-                            let config = &data_manager_lock.settings().headset;
+                            let config =
+                                &data_manager_lock.client_settings(&client_hostname).headset;
                             tracking_manager.lock().recenter(
                                 config.position_recentering_mode,
                                 config.rotation_recentering_mode,
@@ -996,10 +1502,10 @@ fn connection_pipeline(
                         unsafe { crate::RequestIDR() }
                     }
                     ClientControlPacket::VideoErrorReport => {
-                        // legacy endpoint. todo: remove
                         if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
                             stats.report_packet_loss();
                         }
+                        BITRATE_MANAGER.lock().report_packet_loss();
                         unsafe { crate::VideoErrorReportReceive() };
                     }
                     ClientControlPacket::ViewsConfig(config) => unsafe {
@@ -1033,6 +1539,10 @@ fn connection_pipeline(
                         }
                     },
                     ClientControlPacket::Buttons(entries) => {
+                        if let Some(recorder) = &mut *TRACKING_RECORDER.lock() {
+                            recorder.record(RecordedInput::Buttons(entries.clone()));
+                        }
+
                         {
                             let data_manager_lock = SERVER_DATA_MANAGER.read();
                             if data_manager_lock.settings().logging.log_button_presses {
@@ -1040,12 +1550,7 @@ fn connection_pipeline(
                                     entries
                                         .iter()
                                         .map(|e| ButtonEvent {
-                                            path: BUTTON_INFO
-                                                .get(&e.path_id)
-                                                .map(|info| info.path.to_owned())
-                                                .unwrap_or_else(|| {
-                                                    format!("Unknown (ID: {:#16x})", e.path_id)
-                                                }),
+                                            path: input_mapping::button_path(e.path_id),
                                             value: e.value,
                                         })
                                         .collect(),
@@ -1053,29 +1558,32 @@ fn connection_pipeline(
                             }
                         }
 
-                        if let Some(manager) = &mut controller_button_mapping_manager {
-                            for entry in entries {
-                                manager.report_button(entry.path_id, entry.value);
+                        // Buttons are driven by the recording during playback
+                        if !is_playing_back {
+                            if let Some(manager) = &mut *controller_button_mapping_manager.lock() {
+                                for entry in entries {
+                                    manager.report_button(entry.path_id, entry.value);
+                                }
                             }
-                        };
+                        }
                     }
                     ClientControlPacket::ActiveInteractionProfile {
                         device_id: _,
                         profile_id,
                     } => {
-                        controller_button_mapping_manager =
-                            if let (Switch::Enabled(config), Some(profile_info)) = (
-                                &SERVER_DATA_MANAGER.read().settings().headset.controllers,
-                                CONTROLLER_PROFILE_INFO.get(&profile_id),
+                        *controller_button_mapping_manager.lock() =
+                            if let (Switch::Enabled(config), Some(button_set)) = (
+                                &SERVER_DATA_MANAGER
+                                    .read()
+                                    .client_settings(&client_hostname)
+                                    .headset
+                                    .controllers,
+                                input_mapping::profile_button_set(profile_id),
                             ) {
-                                if let Some(mappings) = &config.button_mappings {
-                                    Some(ButtonMappingManager::new_manual(mappings))
-                                } else {
-                                    Some(ButtonMappingManager::new_automatic(
-                                        &profile_info.button_set,
-                                        &config.button_mapping_config,
-                                    ))
-                                }
+                                Some(create_controller_button_mapping_manager(
+                                    config,
+                                    &button_set,
+                                ))
                             } else {
                                 None
                             };
@@ -1148,7 +1656,7 @@ fn connection_pipeline(
     }
 
     if settings.capture.startup_video_recording {
-        crate::create_recording_file(server_data_lock.settings());
+        crate::create_recording_file(server_data_lock.client_settings(&client_hostname));
     }
 
     unsafe { crate::InitializeStreaming() };
@@ -1163,6 +1671,9 @@ fn connection_pipeline(
     // This requests shutdown from threads
     *VIDEO_CHANNEL_SENDER.lock() = None;
     *HAPTICS_SENDER.lock() = None;
+    *TRACKING_RECORDER.lock() = None;
+    *EXTERNAL_TRACKING_MANAGER.lock() = None;
+    *CUSTOM_HAND_GESTURE_MANAGER.lock() = None;
 
     *VIDEO_RECORDING_FILE.lock() = None;
 
@@ -1174,7 +1685,7 @@ fn connection_pipeline(
     );
 
     let on_disconnect_script = server_data_lock
-        .settings()
+        .client_settings(&client_hostname)
         .connection
         .on_disconnect_script
         .clone();
@@ -1195,7 +1706,10 @@ fn connection_pipeline(
     video_send_thread.join().ok();
     game_audio_thread.join().ok();
     microphone_thread.join().ok();
+    tracking_socket_thread.join().ok();
+    tracking_playback_thread.join().ok();
     tracking_receive_thread.join().ok();
+    external_tracking_thread.join().ok();
     statistics_thread.join().ok();
     control_receive_thread.join().ok();
     stream_receive_thread.join().ok();
@@ -1219,7 +1733,7 @@ pub extern "C" fn send_video(timestamp_ns: u64, buffer_ptr: *mut u8, len: i32, i
 
         if let Switch::Enabled(config) = &SERVER_DATA_MANAGER
             .read()
-            .settings()
+            .connected_client_settings()
             .capture
             .rolling_video_files
         {
@@ -1227,7 +1741,9 @@ pub extern "C" fn send_video(timestamp_ns: u64, buffer_ptr: *mut u8, len: i32, i
                 unsafe { crate::RequestIDR() };
 
                 if is_idr {
-                    crate::create_recording_file(SERVER_DATA_MANAGER.read().settings());
+                    crate::create_recording_file(
+                        SERVER_DATA_MANAGER.read().connected_client_settings(),
+                    );
                     *LAST_IDR_INSTANT.lock() = Instant::now();
                 }
             }
@@ -1245,7 +1761,7 @@ pub extern "C" fn send_video(timestamp_ns: u64, buffer_ptr: *mut u8, len: i32, i
         if !STREAM_CORRUPTED.load(Ordering::SeqCst)
             || !SERVER_DATA_MANAGER
                 .read()
-                .settings()
+                .connected_client_settings()
                 .connection
                 .avoid_video_glitching
         {
@@ -1291,6 +1807,10 @@ pub extern "C" fn send_haptics(device_id: u64, duration_s: f32, frequency: f32,
         amplitude,
     };
 
+    if let Some(recorder) = &mut *TRACKING_RECORDER.lock() {
+        recorder.record(RecordedInput::Haptics(haptics.clone()));
+    }
+
     let haptics_config = {
         let data_manager_lock = SERVER_DATA_MANAGER.read();
 
@@ -1307,7 +1827,7 @@ pub extern "C" fn send_haptics(device_id: u64, duration_s: f32, frequency: f32,
         }
 
         data_manager_lock
-            .settings()
+            .connected_client_settings()
             .headset
             .controllers
             .as_option()
@@ -0,0 +1,707 @@
+// User-defined hand gestures. A template is recorded from the skeleton of one hand: a single
+// averaged pose for static gestures, or a trajectory sampled at fixed intervals for dynamic ones.
+// Joints are expressed in the palm space and mirrored for the left hand, so templates do not depend
+// on where the hand is or on which hand recorded them. Static gestures are matched by the average
+// joint distance, dynamic gestures by dynamic time warping against the recent history of the hand.
+
+use alvr_common::{
+    anyhow::{bail, Result},
+    error,
+    glam::Vec3,
+    info, warn, Pose,
+};
+use alvr_packets::{ButtonEntry, ButtonValue};
+use alvr_session::{
+    ButtonBindingTarget, CustomHandGestureConfig, CustomHandGestureHand, CustomHandGestureType,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+// Time given to the user to get the hand in position after requesting a recording
+const RECORDING_DELAY: Duration = Duration::from_secs(3);
+const STATIC_RECORDING_DURATION: Duration = Duration::from_secs(1);
+
+// Dynamic gestures can be performed up to this many times slower than recorded
+const HISTORY_LENGTH_FACTOR: usize = 2;
+// Dynamic gestures must span at least this fraction of the template length
+const MIN_DYNAMIC_SPAN_FACTOR: f32 = 0.5;
+const DYNAMIC_PRESS_DURATION: Duration = Duration::from_millis(200);
+
+// Static gestures are released only when the distance exceeds the match distance by this factor
+const STATIC_RELEASE_FACTOR: f32 = 1.25;
+
+const LEFT_HAND: usize = 0;
+const RIGHT_HAND: usize = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct HandFrame {
+    // Joint positions in the palm space, as seen on a right hand
+    joints: [Vec3; 26],
+    // Palm displacement since the previous sample, in the palm space
+    palm_motion: Vec3,
+}
+
+impl HandFrame {
+    fn new(skeleton: &[Pose; 26], previous_palm: Option<Pose>, is_left: bool) -> Self {
+        let palm = skeleton[0];
+        let inverse_orientation = palm.orientation.inverse();
+        let mirror = if is_left {
+            Vec3::new(-1.0, 1.0, 1.0)
+        } else {
+            Vec3::ONE
+        };
+
+        Self {
+            joints: skeleton
+                .map(|joint| inverse_orientation * (joint.position - palm.position) * mirror),
+            palm_motion: previous_palm
+                .map(|previous| inverse_orientation * (palm.position - previous.position) * mirror)
+                .unwrap_or(Vec3::ZERO),
+        }
+    }
+
+    // Average distance of the joints
+    fn joints_distance(&self, other: &Self) -> f32 {
+        self.joints
+            .iter()
+            .zip(&other.joints)
+            .map(|(a, b)| a.distance(*b))
+            .sum::<f32>()
+            / self.joints.len() as f32
+    }
+
+    fn distance(&self, other: &Self) -> f32 {
+        self.joints_distance(other) + self.palm_motion.distance(other.palm_motion)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GestureTemplate {
+    frames: Vec<HandFrame>,
+}
+
+impl GestureTemplate {
+    fn new_static(frames: &[HandFrame]) -> Self {
+        let mut joints = [Vec3::ZERO; 26];
+        for frame in frames {
+            for (sum, joint) in joints.iter_mut().zip(frame.joints) {
+                *sum += joint / frames.len() as f32;
+            }
+        }
+
+        Self {
+            frames: vec![HandFrame {
+                joints,
+                palm_motion: Vec3::ZERO,
+            }],
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let template: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if template.frames.is_empty() {
+            bail!("Empty gesture template");
+        }
+
+        Ok(template)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+}
+
+// Subsequence dynamic time warping: the template can start anywhere in the history but must end on
+// the latest frame. Returns the average frame distance along the best path, or infinity if the
+// matched part of the history is too short compared to the template.
+fn dynamic_time_warping_distance(template: &[HandFrame], history: &[HandFrame]) -> f32 {
+    if template.is_empty() || history.is_empty() {
+        return f32::INFINITY;
+    }
+
+    #[derive(Clone, Copy, Default)]
+    struct Cell {
+        cost: f32,
+        length: usize,
+        start: usize,
+    }
+
+    let mut previous_row = vec![Cell::default(); history.len()];
+    let mut row = vec![Cell::default(); history.len()];
+    for (i, template_frame) in template.iter().enumerate() {
+        for (j, frame) in history.iter().enumerate() {
+            let best = if i == 0 {
+                // Free start
+                Cell {
+                    cost: 0.0,
+                    length: 0,
+                    start: j,
+                }
+            } else if j == 0 {
+                previous_row[0]
+            } else {
+                [previous_row[j], previous_row[j - 1], row[j - 1]]
+                    .into_iter()
+                    .min_by(|a, b| a.cost.total_cmp(&b.cost))
+                    .unwrap()
+            };
+
+            row[j] = Cell {
+                cost: best.cost + template_frame.distance(frame),
+                length: best.length + 1,
+                start: best.start,
+            };
+        }
+
+        std::mem::swap(&mut previous_row, &mut row);
+    }
+
+    let end = previous_row[history.len() - 1];
+    let span = history.len() - end.start;
+    if (span as f32) < template.len() as f32 * MIN_DYNAMIC_SPAN_FACTOR {
+        return f32::INFINITY;
+    }
+
+    end.cost / end.length as f32
+}
+
+#[derive(Default)]
+struct HandState {
+    history: VecDeque<HandFrame>,
+    last_palm: Option<Pose>,
+    last_sample_instant: Option<Instant>,
+    samples_count: usize,
+}
+
+impl HandState {
+    // Returns the new frame if a sample was taken
+    fn sample(
+        &mut self,
+        skeleton: &[Pose; 26],
+        is_left: bool,
+        capacity: usize,
+        now: Instant,
+    ) -> Option<HandFrame> {
+        if self
+            .last_sample_instant
+            .is_some_and(|instant| now < instant + SAMPLE_INTERVAL)
+        {
+            return None;
+        }
+
+        let frame = HandFrame::new(skeleton, self.last_palm, is_left);
+        self.history.push_back(frame);
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+        self.last_palm = Some(skeleton[0]);
+        self.last_sample_instant = Some(now);
+        self.samples_count += 1;
+
+        Some(frame)
+    }
+
+    fn reset(&mut self) {
+        self.history.clear();
+        self.last_palm = None;
+        self.last_sample_instant = None;
+    }
+}
+
+struct CustomGesture {
+    config: CustomHandGestureConfig,
+    source_id: u64,
+    template: Option<GestureTemplate>,
+    active: [bool; 2],
+    // Dynamic gestures only
+    release_instants: [Option<Instant>; 2],
+    // Dynamic gestures are matched only against samples taken after the last activation
+    trigger_samples: [usize; 2],
+}
+
+impl CustomGesture {
+    fn update(
+        &mut self,
+        hand_index: usize,
+        current_frame: Option<&HandFrame>,
+        hand: &mut HandState,
+        new_sample: bool,
+        now: Instant,
+    ) {
+        let hand_enabled = match self.config.hand {
+            CustomHandGestureHand::Left => hand_index == LEFT_HAND,
+            CustomHandGestureHand::Right => hand_index == RIGHT_HAND,
+            CustomHandGestureHand::Both => true,
+        };
+        let (true, Some(template), Some(current_frame)) =
+            (hand_enabled, &self.template, current_frame)
+        else {
+            self.active[hand_index] = false;
+            self.release_instants[hand_index] = None;
+
+            return;
+        };
+
+        let match_distance = self.config.match_distance * 0.01;
+
+        match self.config.gesture_type {
+            CustomHandGestureType::Static => {
+                let threshold = if self.active[hand_index] {
+                    match_distance * STATIC_RELEASE_FACTOR
+                } else {
+                    match_distance
+                };
+
+                self.active[hand_index] =
+                    current_frame.joints_distance(&template.frames[0]) < threshold;
+            }
+            CustomHandGestureType::Dynamic { .. } => {
+                if let Some(instant) = self.release_instants[hand_index] {
+                    if now < instant {
+                        return;
+                    }
+                    self.release_instants[hand_index] = None;
+                    self.active[hand_index] = false;
+                }
+
+                if !new_sample {
+                    return;
+                }
+
+                let new_samples = hand.samples_count - self.trigger_samples[hand_index];
+                let history = hand.history.make_contiguous();
+                let history = &history[history.len().saturating_sub(new_samples)..];
+
+                if dynamic_time_warping_distance(&template.frames, history) < match_distance {
+                    self.active[hand_index] = true;
+                    self.release_instants[hand_index] = Some(now + DYNAMIC_PRESS_DURATION);
+                    self.trigger_samples[hand_index] = hand.samples_count;
+                }
+            }
+        }
+    }
+}
+
+struct Recording {
+    gesture_index: usize,
+    hand_index: usize,
+    start_instant: Instant,
+    duration: Duration,
+    frames: Vec<HandFrame>,
+}
+
+pub struct CustomHandGestureManager {
+    gestures: Vec<CustomGesture>,
+    hands: [HandState; 2],
+    history_capacity: usize,
+    recording: Option<Recording>,
+    templates_dir: PathBuf,
+}
+
+impl CustomHandGestureManager {
+    pub fn new(configs: &[CustomHandGestureConfig], templates_dir: &Path) -> Self {
+        let gestures = configs
+            .iter()
+            .map(|config| {
+                let source_path = gesture_source_path(&config.name);
+
+                let template = match template_path(templates_dir, &config.name) {
+                    Ok(path) if path.exists() => GestureTemplate::load(&path)
+                        .map_err(|e| {
+                            error!(
+                                "Failed to load template of gesture \"{}\": {e}",
+                                config.name
+                            )
+                        })
+                        .ok(),
+                    Err(e) => {
+                        error!("{e}");
+                        None
+                    }
+                    Ok(_) => {
+                        warn!(
+                            "Gesture \"{}\" has no template. Record it from the Debug tab",
+                            config.name
+                        );
+                        None
+                    }
+                };
+
+                CustomGesture {
+                    config: config.clone(),
+                    source_id: alvr_common::hash_string(&source_path),
+                    template,
+                    active: [false; 2],
+                    release_instants: [None; 2],
+                    trigger_samples: [0; 2],
+                }
+            })
+            .collect();
+
+        let mut manager = Self {
+            gestures,
+            hands: Default::default(),
+            history_capacity: 1,
+            recording: None,
+            templates_dir: templates_dir.to_owned(),
+        };
+        manager.update_history_capacity();
+
+        manager
+    }
+
+    fn update_history_capacity(&mut self) {
+        self.history_capacity = self
+            .gestures
+            .iter()
+            .filter(|g| matches!(g.config.gesture_type, CustomHandGestureType::Dynamic { .. }))
+            .filter_map(|g| g.template.as_ref())
+            .map(|t| t.frames.len() * HISTORY_LENGTH_FACTOR)
+            .max()
+            .unwrap_or(1);
+    }
+
+    pub fn start_recording(&mut self, name: &str) -> Result<()> {
+        let Some(gesture_index) = self.gestures.iter().position(|g| g.config.name == name) else {
+            bail!("Custom hand gesture \"{name}\" not found");
+        };
+        let config = &self.gestures[gesture_index].config;
+        template_path(&self.templates_dir, name)?;
+
+        let (hand_index, hand_name) = if config.hand == CustomHandGestureHand::Left {
+            (LEFT_HAND, "left")
+        } else {
+            (RIGHT_HAND, "right")
+        };
+        let duration = match config.gesture_type {
+            CustomHandGestureType::Static => STATIC_RECORDING_DURATION,
+            CustomHandGestureType::Dynamic { duration_s } => Duration::from_secs_f32(duration_s),
+        };
+
+        info!(
+            "Recording gesture \"{name}\" with the {hand_name} hand in {} seconds, for {:.1} seconds",
+            RECORDING_DELAY.as_secs(),
+            duration.as_secs_f32()
+        );
+
+        self.recording = Some(Recording {
+            gesture_index,
+            hand_index,
+            start_instant: Instant::now() + RECORDING_DELAY,
+            duration,
+            frames: vec![],
+        });
+
+        Ok(())
+    }
+
+    // Returns the state of every gesture. They are sources of the controller button mappings, so
+    // the gesture bindings can have conditions on the controller buttons
+    pub fn process(
+        &mut self,
+        hand_skeletons: &[Option<[Pose; 26]>; 2],
+        now: Instant,
+    ) -> Vec<ButtonEntry> {
+        for (hand_index, skeleton) in hand_skeletons.iter().enumerate() {
+            let hand = &mut self.hands[hand_index];
+
+            let (current_frame, new_sample) = if let Some(skeleton) = skeleton {
+                let is_left = hand_index == LEFT_HAND;
+                let new_sample = hand.sample(skeleton, is_left, self.history_capacity, now);
+
+                if let (Some(recording), Some(frame)) = (&mut self.recording, new_sample) {
+                    if recording.hand_index == hand_index && now >= recording.start_instant {
+                        recording.frames.push(frame);
+                    }
+                }
+
+                (
+                    Some(HandFrame::new(skeleton, None, is_left)),
+                    new_sample.is_some(),
+                )
+            } else {
+                hand.reset();
+
+                (None, false)
+            };
+
+            for gesture in &mut self.gestures {
+                gesture.update(hand_index, current_frame.as_ref(), hand, new_sample, now);
+            }
+        }
+
+        if self
+            .recording
+            .as_ref()
+            .is_some_and(|r| now >= r.start_instant + r.duration)
+        {
+            if let Some(recording) = self.recording.take() {
+                self.finish_recording(recording);
+            }
+        }
+
+        self.gestures
+            .iter()
+            .map(|gesture| ButtonEntry {
+                path_id: gesture.source_id,
+                value: ButtonValue::Binary(gesture.active.contains(&true)),
+            })
+            .collect()
+    }
+
+    fn finish_recording(&mut self, recording: Recording) {
+        let gesture = &mut self.gestures[recording.gesture_index];
+        let name = &gesture.config.name;
+
+        if recording.frames.is_empty() {
+            error!("Recording of gesture \"{name}\" failed: the hand was not tracked");
+            return;
+        }
+
+        let template = match gesture.config.gesture_type {
+            CustomHandGestureType::Static => GestureTemplate::new_static(&recording.frames),
+            CustomHandGestureType::Dynamic { .. } => GestureTemplate {
+                frames: recording.frames,
+            },
+        };
+
+        if let Err(e) =
+            template_path(&self.templates_dir, name).and_then(|path| template.save(&path))
+        {
+            error!("Failed to save template of gesture \"{name}\": {e}");
+        } else {
+            info!("Gesture \"{name}\" recorded");
+        }
+
+        gesture.template = Some(template);
+        gesture.trigger_samples = [self.hands[0].samples_count, self.hands[1].samples_count];
+        self.update_history_capacity();
+    }
+}
+
+fn gesture_source_path(name: &str) -> String {
+    format!("/alvr/custom_hand_gesture/{name}")
+}
+
+// Bindings of the gestures, to be added to the controller button mappings
+pub fn gesture_mappings(
+    configs: &[CustomHandGestureConfig],
+) -> Vec<(String, Vec<ButtonBindingTarget>)> {
+    configs
+        .iter()
+        .map(|config| (gesture_source_path(&config.name), config.bindings.clone()))
+        .collect()
+}
+
+// The gesture name is used as file name, it must not escape the templates directory
+fn template_path(templates_dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        bail!("Invalid gesture name \"{name}\": it must be a valid file name");
+    }
+
+    Ok(templates_dir.join(format!("{name}.json")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::glam::Quat;
+
+    // Fingers along -Z from the palm, curled towards +Y by the curl factor. The thumb is on the -X
+    // side of a right hand
+    fn skeleton(curl: f32, palm: Pose, is_left: bool) -> [Pose; 26] {
+        let mut joints = [Pose::default(); 26];
+        for finger in 0..5 {
+            let x = (finger as f32 - 2.0) * if is_left { -0.02 } else { 0.02 };
+            for segment in 0..5 {
+                let angle = curl * segment as f32 * 0.4;
+                let local = Vec3::new(
+                    x,
+                    angle.sin() * 0.02,
+                    -0.02 - angle.cos() * 0.02 * segment as f32,
+                );
+                joints[1 + finger * 5 + segment] = Pose {
+                    orientation: Quat::IDENTITY,
+                    position: local,
+                };
+            }
+        }
+
+        joints.map(|joint| palm * joint)
+    }
+
+    fn config(gesture_type: CustomHandGestureType) -> CustomHandGestureConfig {
+        CustomHandGestureConfig {
+            name: "test".into(),
+            hand: CustomHandGestureHand::Both,
+            gesture_type,
+            match_distance: 0.75,
+            bindings: vec![],
+        }
+    }
+
+    fn new_gesture(
+        gesture_type: CustomHandGestureType,
+        template: GestureTemplate,
+    ) -> CustomGesture {
+        CustomGesture {
+            config: config(gesture_type),
+            source_id: 0,
+            template: Some(template),
+            active: [false; 2],
+            release_instants: [None; 2],
+            trigger_samples: [0; 2],
+        }
+    }
+
+    #[test]
+    fn test_template_path() {
+        let dir = Path::new("hand_gestures");
+
+        assert_eq!(
+            template_path(dir, "thumbs up").unwrap(),
+            dir.join("thumbs up.json")
+        );
+        assert!(template_path(dir, "").is_err());
+        assert!(template_path(dir, "..").is_err());
+        assert!(template_path(dir, "../session").is_err());
+        assert!(template_path(dir, "a/b").is_err());
+        assert!(template_path(dir, "..\\session").is_err());
+    }
+
+    #[test]
+    fn test_frame_invariance() {
+        let palm = Pose {
+            orientation: Quat::from_rotation_y(1.0) * Quat::from_rotation_x(0.5),
+            position: Vec3::new(0.3, 1.2, -0.4),
+        };
+
+        let reference = HandFrame::new(&skeleton(0.5, Pose::default(), false), None, false);
+        let moved = HandFrame::new(&skeleton(0.5, palm, false), None, false);
+        assert!(reference.joints_distance(&moved) < 1e-5);
+
+        let left = HandFrame::new(&skeleton(0.5, palm, true), None, true);
+        assert!(reference.joints_distance(&left) < 1e-5);
+
+        let other = HandFrame::new(&skeleton(1.5, Pose::default(), false), None, false);
+        assert!(reference.joints_distance(&other) > 0.005);
+    }
+
+    #[test]
+    fn test_static_gesture() {
+        let template_frames = [0.9, 1.0, 1.1]
+            .map(|curl| HandFrame::new(&skeleton(curl, Pose::default(), false), None, false));
+        let mut gesture = new_gesture(
+            CustomHandGestureType::Static,
+            GestureTemplate::new_static(&template_frames),
+        );
+
+        let mut hand = HandState::default();
+        let now = Instant::now();
+        let palm = Pose {
+            orientation: Quat::from_rotation_z(0.7),
+            position: Vec3::new(-0.2, 1.0, 0.1),
+        };
+
+        let frame = HandFrame::new(&skeleton(1.0, palm, true), None, true);
+        gesture.update(LEFT_HAND, Some(&frame), &mut hand, false, now);
+        assert!(gesture.active[LEFT_HAND]);
+
+        let frame = HandFrame::new(&skeleton(0.0, palm, true), None, true);
+        gesture.update(LEFT_HAND, Some(&frame), &mut hand, false, now);
+        assert!(!gesture.active[LEFT_HAND]);
+
+        // Only the configured hand is matched
+        gesture.config.hand = CustomHandGestureHand::Right;
+        let frame = HandFrame::new(&skeleton(1.0, palm, true), None, true);
+        gesture.update(LEFT_HAND, Some(&frame), &mut hand, false, now);
+        assert!(!gesture.active[LEFT_HAND]);
+    }
+
+    // Opens and closes the hand while moving it sideways
+    fn run_trajectory(
+        gesture: &mut CustomGesture,
+        curls: impl Iterator<Item = f32>,
+        start: Instant,
+    ) -> bool {
+        let mut hand = HandState::default();
+        let mut triggered = false;
+        for (i, curl) in curls.enumerate() {
+            let now = start + SAMPLE_INTERVAL * i as u32;
+            let palm = Pose {
+                position: Vec3::new(i as f32 * 0.01, 0.0, 0.0),
+                ..Default::default()
+            };
+            let joints = skeleton(curl, palm, false);
+
+            let new_sample = hand.sample(&joints, false, 40, now).is_some();
+            let frame = HandFrame::new(&joints, None, false);
+            gesture.update(RIGHT_HAND, Some(&frame), &mut hand, new_sample, now);
+            triggered |= gesture.active[RIGHT_HAND];
+        }
+
+        triggered
+    }
+
+    #[test]
+    fn test_dynamic_gesture() {
+        let curls = |steps: usize| {
+            (0..steps).map(move |i| (i as f32 / steps as f32 * std::f32::consts::TAU).sin() + 1.0)
+        };
+
+        let mut hand = HandState::default();
+        let start = Instant::now();
+        let frames = curls(20)
+            .enumerate()
+            .filter_map(|(i, curl)| {
+                let palm = Pose {
+                    position: Vec3::new(i as f32 * 0.01, 0.0, 0.0),
+                    ..Default::default()
+                };
+                hand.sample(
+                    &skeleton(curl, palm, false),
+                    false,
+                    20,
+                    start + SAMPLE_INTERVAL * i as u32,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(frames.len(), 20);
+
+        let dynamic_type = CustomHandGestureType::Dynamic { duration_s: 1.0 };
+
+        // Same trajectory performed slower
+        let mut gesture = new_gesture(
+            dynamic_type.clone(),
+            GestureTemplate {
+                frames: frames.clone(),
+            },
+        );
+        assert!(run_trajectory(&mut gesture, curls(30), start));
+
+        // Holding a pose of the trajectory does not trigger the gesture
+        let mut gesture = new_gesture(
+            dynamic_type.clone(),
+            GestureTemplate {
+                frames: frames.clone(),
+            },
+        );
+        assert!(!run_trajectory(&mut gesture, [1.0; 30].into_iter(), start));
+
+        // Reversed trajectory
+        let mut gesture = new_gesture(dynamic_type, GestureTemplate { frames });
+        let reversed = curls(20).collect::<Vec<_>>().into_iter().rev();
+        assert!(!run_trajectory(&mut gesture, reversed, start));
+    }
+}
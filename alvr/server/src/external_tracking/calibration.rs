@@ -0,0 +1,254 @@
+// Alignment of the space of an external tracking system with the headset space. During the
+// calibration one tracker of the external system is rigidly attached to the headset (or it
+// estimates the head pose), while the user rotates their head in all directions. Both spaces are
+// assumed to be gravity aligned, so only the yaw and the translation are estimated.
+//
+// Model: head = calibration * tracker * mount, where mount is the unknown fixed offset of the
+// tracker relative to the headset.
+
+use alvr_common::{
+    anyhow::{bail, Result},
+    glam::{Mat3, Quat, Vec3},
+    Pose,
+};
+
+// A new sample is kept only if the headset rotated at least this much since the last kept one
+const MIN_SAMPLE_ROTATION_RAD: f32 = 5.0 * std::f32::consts::PI / 180.0;
+const MIN_SAMPLES: usize = 20;
+const MAX_SAMPLES: usize = 200;
+
+// Minimum average horizontal component of the rotations between samples. Rotations around the
+// vertical axis alone do not give any information about the yaw
+const MIN_TILT_RAD: f32 = 10.0 * std::f32::consts::PI / 180.0;
+
+const MAX_RMS_ERROR_M: f32 = 0.05;
+
+pub struct CalibrationResult {
+    pub yaw_rad: f32,
+    pub position: Vec3,
+    // Residual of the position fit
+    pub rms_error_m: f32,
+}
+
+// Rotation vector of the shortest rotation
+fn rotation_vector(rotation: Quat) -> Vec3 {
+    if rotation.w < 0.0 {
+        (-rotation).to_scaled_axis()
+    } else {
+        rotation.to_scaled_axis()
+    }
+}
+
+// Gaussian elimination with partial pivoting. Returns None if the system is singular
+fn solve_linear_system<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        for row in col + 1..N {
+            let pivot_row = a[col];
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot_value) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let sum = (row + 1..N).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Some(x)
+}
+
+#[derive(Default)]
+pub struct CalibrationSampler {
+    // (head, tracker)
+    samples: Vec<(Pose, Pose)>,
+}
+
+impl CalibrationSampler {
+    pub fn push(&mut self, head: Pose, tracker: Pose) {
+        if self.samples.len() >= MAX_SAMPLES {
+            return;
+        }
+
+        if let Some((last_head, _)) = self.samples.last() {
+            if last_head.orientation.angle_between(head.orientation) < MIN_SAMPLE_ROTATION_RAD {
+                return;
+            }
+        }
+
+        self.samples.push((head, tracker));
+    }
+
+    pub fn samples_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn solve(&self) -> Result<CalibrationResult> {
+        let samples = &self.samples;
+        if samples.len() < MIN_SAMPLES {
+            bail!(
+                "Not enough movement during the calibration ({} samples, {MIN_SAMPLES} needed)",
+                samples.len()
+            );
+        }
+
+        // The rotations between two samples are the same in both spaces, apart from the
+        // calibration rotation: head_j * head_i^-1 = R * (tracker_j * tracker_i^-1) * R^-1
+        let mut sin_sum = 0.0;
+        let mut cos_sum = 0.0;
+        let mut tilt_sum = 0.0;
+        let mut pairs_count = 0;
+        for (i, (head_i, tracker_i)) in samples.iter().enumerate() {
+            for (head_j, tracker_j) in &samples[i + 1..] {
+                let head_delta = rotation_vector(head_j.orientation * head_i.orientation.inverse());
+                let tracker_delta =
+                    rotation_vector(tracker_j.orientation * tracker_i.orientation.inverse());
+
+                sin_sum += tracker_delta.z * head_delta.x - tracker_delta.x * head_delta.z;
+                cos_sum += tracker_delta.x * head_delta.x + tracker_delta.z * head_delta.z;
+                tilt_sum += (tracker_delta.x.powi(2) + tracker_delta.z.powi(2)).sqrt();
+                pairs_count += 1;
+            }
+        }
+        if tilt_sum / (pairs_count as f32) < MIN_TILT_RAD {
+            bail!("Not enough rotation during the calibration. Tilt the head in all directions");
+        }
+        let yaw_rad = f32::atan2(sin_sum, cos_sum);
+        let rotation = Quat::from_rotation_y(yaw_rad);
+
+        // Least squares on the unknown translation t and mount position m:
+        // rotation * tracker_pos + t = head_pos + head_rot * m
+        let mut ata = [[0.0; 6]; 6];
+        let mut atb = [0.0; 6];
+        for (head, tracker) in samples {
+            let head_rot = Mat3::from_quat(head.orientation).as_dmat3();
+            let rhs = (head.position - rotation * tracker.position).as_dvec3();
+
+            // Rows of A: [I, -head_rot]
+            for r in 0..3 {
+                let mut row = [0.0; 6];
+                row[r] = 1.0;
+                for c in 0..3 {
+                    row[3 + c] = -head_rot.col(c)[r];
+                }
+
+                for i in 0..6 {
+                    for j in 0..6 {
+                        ata[i][j] += row[i] * row[j];
+                    }
+                    atb[i] += row[i] * rhs[r];
+                }
+            }
+        }
+        let Some(solution) = solve_linear_system(ata, atb) else {
+            bail!("Not enough rotation during the calibration. Tilt the head in all directions");
+        };
+        let position = Vec3::new(solution[0] as f32, solution[1] as f32, solution[2] as f32);
+        let mount_position = Vec3::new(solution[3] as f32, solution[4] as f32, solution[5] as f32);
+
+        let squared_error_sum = samples
+            .iter()
+            .map(|(head, tracker)| {
+                let predicted = rotation * tracker.position + position;
+                let actual = head.position + head.orientation * mount_position;
+
+                predicted.distance_squared(actual)
+            })
+            .sum::<f32>();
+        let rms_error_m = (squared_error_sum / samples.len() as f32).sqrt();
+        if rms_error_m > MAX_RMS_ERROR_M {
+            bail!(
+                "Calibration is inaccurate (error {:.1} cm). Make sure the tracker does not move relative to the headset",
+                rms_error_m * 100.0
+            );
+        }
+
+        Ok(CalibrationResult {
+            yaw_rad,
+            position,
+            rms_error_m,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::glam::EulerRot;
+
+    fn inverse(pose: Pose) -> Pose {
+        let orientation = pose.orientation.inverse();
+
+        Pose {
+            orientation,
+            position: -(orientation * pose.position),
+        }
+    }
+
+    #[test]
+    fn test_linear_system() {
+        let a = [[2.0, 1.0, 0.0], [0.0, 0.0, 3.0], [1.0, 4.0, 1.0]];
+        let x = solve_linear_system(a, [4.0, 9.0, 12.0]).unwrap();
+        for (value, expected) in x.iter().zip([1.0, 2.0, 3.0]) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+
+        assert!(solve_linear_system([[1.0, 2.0], [2.0, 4.0]], [1.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn test_calibration() {
+        let calibration = Pose {
+            orientation: Quat::from_rotation_y(1.2),
+            position: Vec3::new(0.5, -0.1, 2.0),
+        };
+        let mount = Pose {
+            orientation: Quat::from_rotation_x(0.3),
+            position: Vec3::new(0.0, 0.08, 0.05),
+        };
+
+        let mut sampler = CalibrationSampler::default();
+        for i in 0..100 {
+            let t = i as f32 * 0.1;
+            let head = Pose {
+                orientation: Quat::from_euler(
+                    EulerRot::YXZ,
+                    t.sin() * 1.5,
+                    (t * 1.3).sin() * 0.6,
+                    (t * 0.7).cos() * 0.4,
+                ),
+                position: Vec3::new(t.cos() * 0.2, 1.6, (t * 0.5).sin() * 0.3),
+            };
+            let tracker = inverse(calibration) * head * inverse(mount);
+
+            sampler.push(head, tracker);
+        }
+        assert!(sampler.samples_count() >= MIN_SAMPLES);
+
+        let result = sampler.solve().unwrap();
+        assert!((result.yaw_rad - 1.2).abs() < 1e-3);
+        assert!(result.position.distance(calibration.position) < 1e-3);
+        assert!(result.rms_error_m < 1e-3);
+
+        // Rotating only around the vertical axis is not enough
+        let mut sampler = CalibrationSampler::default();
+        for i in 0..100 {
+            let head = Pose {
+                orientation: Quat::from_rotation_y(i as f32 * 0.1),
+                ..Default::default()
+            };
+            sampler.push(head, inverse(calibration) * head);
+        }
+        assert!(sampler.solve().is_err());
+    }
+}
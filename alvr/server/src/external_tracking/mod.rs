@@ -0,0 +1,373 @@
+// Poses of trackers of external systems (IMU based trackers, mocap), received through OSC. They are
+// transformed to the headset tracking space using the calibration of their source and merged with
+// the motions received from the client.
+
+mod calibration;
+
+use crate::SERVER_DATA_MANAGER;
+use alvr_common::{
+    anyhow::{bail, Result},
+    error,
+    glam::{EulerRot, Quat, Vec3},
+    info, warn, DeviceMotion, Pose, HEAD_ID,
+};
+use alvr_session::{
+    ExternalTrackingCalibration, ExternalTrackingConfig, ExternalTrackingSourceConfig,
+};
+use calibration::CalibrationSampler;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::{
+    collections::HashMap,
+    net::UdpSocket,
+    time::{Duration, Instant},
+};
+
+// Trackers that did not send updates for this long are considered lost
+const POSE_TIMEOUT: Duration = Duration::from_millis(500);
+const CALIBRATION_DURATION: Duration = Duration::from_secs(15);
+const OSC_BUFFER_SIZE: usize = 8192;
+
+pub struct ExternalTrackingReceiver {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl ExternalTrackingReceiver {
+    pub fn new(bind_address: &str, port: u16, timeout: Duration) -> Result<Self> {
+        let socket = UdpSocket::bind((bind_address, port))?;
+        socket.set_read_timeout(Some(timeout))?;
+
+        Ok(Self {
+            socket,
+            buffer: vec![0; OSC_BUFFER_SIZE],
+        })
+    }
+
+    // Returns None on timeout or if the packet is malformed
+    pub fn recv(&mut self) -> Option<OscPacket> {
+        let size = self.socket.recv(&mut self.buffer).ok()?;
+
+        rosc::decoder::decode_udp(&self.buffer[..size])
+            .map(|(_, packet)| packet)
+            .ok()
+    }
+}
+
+fn to_floats(args: &[OscType]) -> Vec<f32> {
+    args.iter()
+        .filter_map(|arg| match arg {
+            OscType::Float(value) => Some(*value),
+            OscType::Double(value) => Some(*value as f32),
+            OscType::Int(value) => Some(*value as f32),
+            _ => None,
+        })
+        .collect()
+}
+
+// Unity is left-handed (Z forward), ALVR is right-handed (Z backward)
+fn position_from_unity(x: f32, y: f32, z: f32) -> Vec3 {
+    Vec3::new(x, y, -z)
+}
+
+// Unity Euler angles in degrees, applied in Z, X, Y order
+fn orientation_from_unity(x: f32, y: f32, z: f32) -> Quat {
+    Quat::from_euler(
+        EulerRot::YXZ,
+        -y.to_radians(),
+        -x.to_radians(),
+        z.to_radians(),
+    )
+}
+
+fn calibration_pose(calibration: &ExternalTrackingCalibration) -> Pose {
+    Pose {
+        orientation: Quat::from_rotation_y(calibration.yaw_deg.to_radians()),
+        position: Vec3::from(calibration.position),
+    }
+}
+
+#[derive(Default)]
+struct TrackerState {
+    pose: Pose,
+    last_update: Option<Instant>,
+}
+
+struct Source {
+    config: ExternalTrackingSourceConfig,
+    // tracker name -> device ID
+    device_ids: HashMap<String, u64>,
+    // Also contains trackers that are not mapped to any device, like the calibration tracker
+    trackers: HashMap<String, TrackerState>,
+}
+
+impl Source {
+    fn fresh_pose(&self, tracker: &str, now: Instant) -> Option<Pose> {
+        let state = self.trackers.get(tracker)?;
+
+        (now.saturating_duration_since(state.last_update?) < POSE_TIMEOUT).then_some(state.pose)
+    }
+}
+
+struct CalibrationState {
+    source_index: usize,
+    start_instant: Instant,
+    sampler: CalibrationSampler,
+}
+
+pub struct ExternalTrackingManager {
+    sources: Vec<Source>,
+    calibration: Option<CalibrationState>,
+}
+
+impl ExternalTrackingManager {
+    pub fn new(config: ExternalTrackingConfig) -> Self {
+        let sources = config
+            .sources
+            .into_iter()
+            .map(|config| Source {
+                device_ids: config
+                    .trackers
+                    .iter()
+                    .map(|(tracker, path)| (tracker.clone(), alvr_common::hash_string(path)))
+                    .collect(),
+                trackers: HashMap::new(),
+                config,
+            })
+            .collect();
+
+        Self {
+            sources,
+            calibration: None,
+        }
+    }
+
+    pub fn handle_packet(&mut self, packet: OscPacket) {
+        match packet {
+            OscPacket::Message(message) => self.handle_message(message),
+            OscPacket::Bundle(bundle) => {
+                for packet in bundle.content {
+                    self.handle_packet(packet);
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: OscMessage) {
+        let values = to_floats(&message.args);
+
+        for source in &mut self.sources {
+            let Some(tracker_address) = message.addr.strip_prefix(&source.config.address_prefix)
+            else {
+                continue;
+            };
+            let Some((tracker, kind)) = tracker_address.rsplit_once('/') else {
+                continue;
+            };
+
+            let state = source.trackers.entry(tracker.to_owned()).or_default();
+            match (kind, values.as_slice()) {
+                ("position", &[x, y, z]) => state.pose.position = position_from_unity(x, y, z),
+                ("rotation", &[x, y, z]) => {
+                    state.pose.orientation = orientation_from_unity(x, y, z)
+                }
+                ("pose", &[px, py, pz, qx, qy, qz, qw]) => {
+                    state.pose = Pose {
+                        orientation: Quat::from_xyzw(qx, qy, qz, qw).normalize(),
+                        position: Vec3::new(px, py, pz),
+                    }
+                }
+                _ => continue,
+            }
+            state.last_update = Some(Instant::now());
+        }
+    }
+
+    pub fn start_calibration(&mut self, source_name: &str) -> Result<()> {
+        let Some(source_index) = self
+            .sources
+            .iter()
+            .position(|source| source.config.name == source_name)
+        else {
+            bail!("External tracking source \"{source_name}\" not found");
+        };
+
+        info!(
+            "Calibrating \"{source_name}\": keep the tracker \"{}\" firmly attached to the headset and slowly tilt and turn the head in all directions for {} seconds",
+            self.sources[source_index].config.calibration_tracker,
+            CALIBRATION_DURATION.as_secs()
+        );
+
+        self.calibration = Some(CalibrationState {
+            source_index,
+            start_instant: Instant::now(),
+            sampler: CalibrationSampler::default(),
+        });
+
+        Ok(())
+    }
+
+    // device_motions must contain the raw head motion, before recentering. Returns the name and the
+    // new calibration of a source when its calibration completes. It must be saved with
+    // save_calibration() after releasing the manager, since saving locks the session.
+    pub fn merge_motions(
+        &mut self,
+        device_motions: &mut Vec<(u64, DeviceMotion)>,
+    ) -> Option<(String, ExternalTrackingCalibration)> {
+        let now = Instant::now();
+
+        let mut finished_calibration = None;
+
+        if let Some(calibration) = &mut self.calibration {
+            let source = &self.sources[calibration.source_index];
+
+            let head_pose = device_motions
+                .iter()
+                .find(|(id, _)| *id == *HEAD_ID)
+                .map(|(_, motion)| motion.pose);
+            if let (Some(head_pose), Some(tracker_pose)) = (
+                head_pose,
+                source.fresh_pose(&source.config.calibration_tracker, now),
+            ) {
+                calibration.sampler.push(head_pose, tracker_pose);
+            }
+
+            if now.saturating_duration_since(calibration.start_instant) > CALIBRATION_DURATION {
+                finished_calibration = self.finish_calibration();
+            }
+        }
+
+        for source in &self.sources {
+            let calibration = calibration_pose(&source.config.calibration);
+
+            for (tracker, id) in &source.device_ids {
+                let Some(pose) = source.fresh_pose(tracker, now) else {
+                    continue;
+                };
+                let motion = DeviceMotion {
+                    pose: calibration * pose,
+                    ..Default::default()
+                };
+
+                if let Some((_, existing)) = device_motions.iter_mut().find(|(i, _)| i == id) {
+                    *existing = motion;
+                } else {
+                    device_motions.push((*id, motion));
+                }
+            }
+        }
+
+        finished_calibration
+    }
+
+    fn finish_calibration(&mut self) -> Option<(String, ExternalTrackingCalibration)> {
+        let calibration = self.calibration.take()?;
+        let source = &mut self.sources[calibration.source_index];
+
+        match calibration.sampler.solve() {
+            Ok(result) => {
+                source.config.calibration = ExternalTrackingCalibration {
+                    yaw_deg: result.yaw_rad.to_degrees(),
+                    position: result.position.to_array(),
+                };
+
+                info!(
+                    "Calibration of \"{}\" succeeded with {} samples (error {:.1} cm)",
+                    source.config.name,
+                    calibration.sampler.samples_count(),
+                    result.rms_error_m * 100.0
+                );
+
+                Some((source.config.name.clone(), source.config.calibration))
+            }
+            Err(e) => {
+                error!("Calibration of \"{}\" failed: {e}", source.config.name);
+
+                None
+            }
+        }
+    }
+}
+
+pub fn save_calibration(source_name: &str, calibration: ExternalTrackingCalibration) {
+    let mut data_manager_lock = SERVER_DATA_MANAGER.write();
+    let mut session = data_manager_lock.session_mut();
+
+    let sources = &mut session
+        .session_settings
+        .headset
+        .external_tracking
+        .content
+        .sources
+        .content;
+    if let Some(source) = sources.iter_mut().find(|source| source.name == source_name) {
+        source.calibration.yaw_deg = calibration.yaw_deg;
+        source.calibration.position.content = calibration.position;
+    } else {
+        warn!("External tracking source \"{source_name}\" not found in the settings");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unity_conversion() {
+        assert_eq!(
+            position_from_unity(1.0, 2.0, 3.0),
+            Vec3::new(1.0, 2.0, -3.0)
+        );
+
+        // Unity yaw is clockwise when seen from above
+        let orientation = orientation_from_unity(0.0, 90.0, 0.0);
+        assert!((orientation * -Vec3::Z).distance(Vec3::X) < 1e-5);
+
+        // The forward direction of a rotated object must match in both conventions
+        let unity_forward = Quat::from_euler(
+            EulerRot::YXZ,
+            45_f32.to_radians(),
+            30_f32.to_radians(),
+            10_f32.to_radians(),
+        ) * Vec3::Z;
+        let forward = orientation_from_unity(30.0, 45.0, 10.0) * -Vec3::Z;
+        let expected = position_from_unity(unity_forward.x, unity_forward.y, unity_forward.z);
+        assert!(forward.distance(expected) < 1e-5);
+    }
+
+    #[test]
+    fn test_merge_motions() {
+        let mut manager = ExternalTrackingManager::new(ExternalTrackingConfig {
+            bind_address: "127.0.0.1".into(),
+            port: 0,
+            sources: vec![ExternalTrackingSourceConfig {
+                name: "test".into(),
+                address_prefix: "/tracking/trackers/".into(),
+                trackers: vec![("1".into(), "/waist".into())],
+                calibration_tracker: "head".into(),
+                calibration: ExternalTrackingCalibration {
+                    yaw_deg: 90.0,
+                    position: [0.0, 0.0, 1.0],
+                },
+            }],
+        });
+
+        manager.handle_packet(OscPacket::Message(OscMessage {
+            addr: "/tracking/trackers/1/pose".into(),
+            args: [1.0, 2.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+                .map(OscType::Float)
+                .to_vec(),
+        }));
+        manager.handle_packet(OscPacket::Message(OscMessage {
+            addr: "/tracking/trackers/2/position".into(),
+            args: vec![OscType::Float(1.0); 3],
+        }));
+
+        let mut motions = vec![];
+        assert!(manager.merge_motions(&mut motions).is_none());
+
+        assert_eq!(motions.len(), 1);
+        let (id, motion) = motions[0];
+        assert_eq!(id, alvr_common::hash_string("/waist"));
+        assert!(motion.pose.position.distance(Vec3::new(0.0, 2.0, 0.0)) < 1e-5);
+    }
+}
@@ -0,0 +1,210 @@
+mod osc_mapping;
+
+use crate::FILESYSTEM_LAYOUT;
+use alvr_common::{anyhow::Result, glam::EulerRot, Pose};
+use alvr_packets::{ArkitBlendshape, FaceData, ARKIT_BLENDSHAPES_COUNT};
+use alvr_session::FaceTrackingSinkConfig;
+use osc_mapping::{OscMappingFile, OscMappingSink};
+use rosc::{OscMessage, OscPacket, OscType};
+use std::{f32::consts::PI, mem, net::UdpSocket, path::Path};
+
+const RAD_TO_DEG: f32 = 180.0 / PI;
+
+const VRCFT_PORT: u16 = 0xA1F7;
+
+pub trait FaceTrackingSink {
+    fn send_tracking(&mut self, face_data: &FaceData);
+}
+
+pub fn create_face_tracking_sink(
+    config: FaceTrackingSinkConfig,
+    local_osc_port: u16,
+) -> Result<Box<dyn FaceTrackingSink>> {
+    Ok(match config {
+        FaceTrackingSinkConfig::VrchatEyeOsc { port } => Box::new(VrchatEyeOscSink {
+            socket: OscSocket::new(local_osc_port, port)?,
+        }),
+        FaceTrackingSinkConfig::VrcFaceTracking => Box::new(VrcFaceTrackingSink {
+            socket: connect_local_socket(local_osc_port, VRCFT_PORT)?,
+            packet_buffer: vec![],
+            packet_cursor: 0,
+        }),
+        FaceTrackingSinkConfig::OscMapping { port, mapping_file } => {
+            // Relative paths are resolved from the configuration directory
+            let path = FILESYSTEM_LAYOUT.config_dir.join(Path::new(&mapping_file));
+
+            Box::new(OscMappingSink::new(
+                OscSocket::new(local_osc_port, port)?,
+                OscMappingFile::load(&path)?,
+            ))
+        }
+        FaceTrackingSinkConfig::ArkitOsc {
+            port,
+            address_prefix,
+        } => Box::new(ArkitOscSink {
+            socket: OscSocket::new(local_osc_port, port)?,
+            addresses: ArkitBlendshape::ALL
+                .map(|shape| format!("{address_prefix}{}", shape.name())),
+        }),
+    })
+}
+
+fn connect_local_socket(local_port: u16, port: u16) -> Result<UdpSocket> {
+    let socket = UdpSocket::bind(format!("127.0.0.1:{local_port}"))?;
+    socket.connect(format!("127.0.0.1:{port}"))?;
+
+    Ok(socket)
+}
+
+// Returns (pitch, yaw) in degrees, with the sign convention of VRChat
+fn gaze_pitch_yaw(pose: Pose) -> (f32, f32) {
+    let (pitch, yaw, _) = pose.orientation.to_euler(EulerRot::XYZ);
+
+    (-pitch * RAD_TO_DEG, -yaw * RAD_TO_DEG)
+}
+
+pub struct OscSocket(UdpSocket);
+
+impl OscSocket {
+    pub fn new(local_port: u16, port: u16) -> Result<Self> {
+        Ok(Self(connect_local_socket(local_port, port)?))
+    }
+
+    pub fn send_message(&self, path: &str, args: Vec<OscType>) {
+        self.0
+            .send(
+                &rosc::encoder::encode(&OscPacket::Message(OscMessage {
+                    addr: path.into(),
+                    args,
+                }))
+                .unwrap(),
+            )
+            .ok();
+    }
+}
+
+struct VrchatEyeOscSink {
+    socket: OscSocket,
+}
+
+impl FaceTrackingSink for VrchatEyeOscSink {
+    fn send_tracking(&mut self, face_data: &FaceData) {
+        if let [Some(left), Some(right)] = face_data.eye_gazes {
+            let (left_pitch, left_yaw) = gaze_pitch_yaw(left);
+            let (right_pitch, right_yaw) = gaze_pitch_yaw(right);
+
+            self.socket.send_message(
+                "/tracking/eye/LeftRightPitchYaw",
+                vec![
+                    OscType::Float(left_pitch),
+                    OscType::Float(left_yaw),
+                    OscType::Float(right_pitch),
+                    OscType::Float(right_yaw),
+                ],
+            );
+        } else if let Some(pose) = face_data.eye_gazes[0].or(face_data.eye_gazes[1]) {
+            let (pitch, yaw) = gaze_pitch_yaw(pose);
+
+            self.socket.send_message(
+                "/tracking/eye/CenterPitchYaw",
+                vec![OscType::Float(pitch), OscType::Float(yaw)],
+            );
+        }
+
+        let left_eye_blink = face_data
+            .fb_face_expression
+            .as_ref()
+            .map(|v| v[12])
+            .or_else(|| face_data.htc_eye_expression.as_ref().map(|v| v[0]));
+        let right_eye_blink = face_data
+            .fb_face_expression
+            .as_ref()
+            .map(|v| v[13])
+            .or_else(|| face_data.htc_eye_expression.as_ref().map(|v| v[2]));
+
+        if let (Some(left), Some(right)) = (left_eye_blink, right_eye_blink) {
+            self.socket.send_message(
+                "/tracking/eye/EyesClosedAmount",
+                vec![OscType::Float((left + right) / 2.0)],
+            );
+        } else if let Some(blink) = left_eye_blink.or(right_eye_blink) {
+            self.socket.send_message(
+                "/tracking/eye/EyesClosedAmount",
+                vec![OscType::Float(blink)],
+            );
+        }
+    }
+}
+
+struct VrcFaceTrackingSink {
+    socket: UdpSocket,
+    packet_buffer: Vec<u8>,
+    packet_cursor: usize,
+}
+
+impl VrcFaceTrackingSink {
+    fn append_packet(&mut self, prefix: &[u8; 8], data: &[f32]) {
+        let new_buffer_len = self.packet_cursor + prefix.len() + data.len() * 4;
+        if self.packet_buffer.len() < new_buffer_len {
+            self.packet_buffer.resize(new_buffer_len, 0);
+        }
+
+        self.packet_buffer[self.packet_cursor..][..prefix.len()].copy_from_slice(prefix.as_slice());
+        self.packet_cursor += prefix.len();
+
+        for val in data {
+            self.packet_buffer[self.packet_cursor..][..mem::size_of::<f32>()]
+                .copy_from_slice(&val.to_le_bytes());
+            self.packet_cursor += mem::size_of::<f32>();
+        }
+    }
+}
+
+impl FaceTrackingSink for VrcFaceTrackingSink {
+    fn send_tracking(&mut self, face_data: &FaceData) {
+        self.packet_cursor = 0;
+
+        match face_data.eye_gazes {
+            [Some(left_quat), Some(right_quat)] => {
+                let mut vec = left_quat.orientation.to_array().to_vec();
+                vec.extend_from_slice(&right_quat.orientation.to_array());
+                self.append_packet(b"EyesQuat", &vec);
+            }
+            // todo: use separate field for combined eye data
+            [Some(combined_quat), None] => {
+                self.append_packet(b"CombQuat", &combined_quat.orientation.to_array());
+            }
+            _ => (),
+        }
+
+        if let Some(arr) = &face_data.fb_face_expression {
+            self.append_packet(b"Face2Fb\0", arr);
+        }
+
+        if let Some(arr) = &face_data.htc_eye_expression {
+            self.append_packet(b"EyesHtc\0", arr);
+        }
+
+        if let Some(arr) = &face_data.htc_lip_expression {
+            self.append_packet(b"LipHtc\0\0", arr);
+        }
+
+        self.socket.send(&self.packet_buffer).ok();
+    }
+}
+
+struct ArkitOscSink {
+    socket: OscSocket,
+    addresses: [String; ARKIT_BLENDSHAPES_COUNT],
+}
+
+impl FaceTrackingSink for ArkitOscSink {
+    fn send_tracking(&mut self, face_data: &FaceData) {
+        if let Some(blendshapes) = face_data.arkit_blendshapes() {
+            for (address, value) in self.addresses.iter().zip(blendshapes) {
+                self.socket
+                    .send_message(address, vec![OscType::Float(value)]);
+            }
+        }
+    }
+}
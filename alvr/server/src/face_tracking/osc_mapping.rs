@@ -0,0 +1,325 @@
+// Data-driven OSC output. A mapping file lists OSC addresses together with the FaceData value to
+// send to each of them. Example (TOML):
+//
+// [[mappings]]
+// address = "/avatar/parameters/LeftEyeLid"
+// source = { fb_face_expression = 12 }
+// scale = -1.0
+// offset = 1.0
+// smoothing = 0.5
+//
+// [[mappings]]
+// address = "/avatar/parameters/EyesY"
+// source = { eye_gaze = { eye = "combined", axis = "pitch" } }
+// scale = 0.02
+// range = [-1.0, 1.0]
+//
+// [[mappings]]
+// address = "/avatar/parameters/JawOpen"
+// source = { arkit = "jawOpen" }
+//
+// [[mappings]]
+// address = "/avatar/parameters/MouthSmile"
+// source.combined = [
+//     { source = { fb_face_expression = 32 }, weight = 0.5 },
+//     { source = { fb_face_expression = 33 }, weight = 0.5 },
+// ]
+//
+// The same structure can be written in JSON: {"mappings": [{"address": ..., "source": ...}]}
+
+use super::{gaze_pitch_yaw, FaceTrackingSink, OscSocket};
+use alvr_common::anyhow::{bail, Context, Result};
+use alvr_packets::{ArkitBlendshape, FaceData};
+use rosc::OscType;
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Eye {
+    Left,
+    Right,
+    // Average of the two eyes, or the only available one
+    Combined,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GazeAxis {
+    Pitch,
+    Yaw,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WeightedSource {
+    pub source: FaceDataSource,
+    pub weight: f32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FaceDataSource {
+    // Degrees, with the sign convention of VRChat (positive when looking up or left)
+    EyeGaze { eye: Eye, axis: GazeAxis },
+    FbFaceExpression(usize),
+    HtcEyeExpression(usize),
+    HtcLipExpression(usize),
+    // Converted from any of the expression formats
+    Arkit(ArkitBlendshape),
+    // Weighted sum. Sources that are not available are skipped
+    Combined(Vec<WeightedSource>),
+}
+
+impl FaceDataSource {
+    fn evaluate(&self, face_data: &FaceData) -> Option<f32> {
+        match self {
+            FaceDataSource::EyeGaze { eye, axis } => {
+                let [left, right] = face_data.eye_gazes.map(|pose| pose.map(gaze_pitch_yaw));
+                let (pitch, yaw) = match (eye, left, right) {
+                    (Eye::Left, left, _) => left?,
+                    (Eye::Right, _, right) => right?,
+                    (Eye::Combined, Some(left), Some(right)) => {
+                        ((left.0 + right.0) / 2.0, (left.1 + right.1) / 2.0)
+                    }
+                    (Eye::Combined, left, right) => left.or(right)?,
+                };
+
+                Some(match axis {
+                    GazeAxis::Pitch => pitch,
+                    GazeAxis::Yaw => yaw,
+                })
+            }
+            FaceDataSource::FbFaceExpression(index) => {
+                face_data.fb_face_expression.as_ref()?.get(*index).copied()
+            }
+            FaceDataSource::HtcEyeExpression(index) => {
+                face_data.htc_eye_expression.as_ref()?.get(*index).copied()
+            }
+            FaceDataSource::HtcLipExpression(index) => {
+                face_data.htc_lip_expression.as_ref()?.get(*index).copied()
+            }
+            FaceDataSource::Arkit(shape) => Some(face_data.arkit_blendshapes()?[*shape as usize]),
+            FaceDataSource::Combined(sources) => sources
+                .iter()
+                .filter_map(|s| Some(s.source.evaluate(face_data)? * s.weight))
+                .reduce(|sum, value| sum + value),
+        }
+    }
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OscMapping {
+    pub address: String,
+    pub source: FaceDataSource,
+    // value * scale + offset
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub offset: f32,
+    // Weight of the previous value, from 0 (no smoothing) to 1 (excluded)
+    #[serde(default)]
+    pub smoothing: f32,
+    // Applied after scaling and smoothing
+    #[serde(default)]
+    pub range: Option<[f32; 2]>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OscMappingFile {
+    pub mappings: Vec<OscMapping>,
+}
+
+impl OscMappingFile {
+    pub fn parse(text: &str, is_json: bool) -> Result<Self> {
+        let file: Self = if is_json {
+            serde_json::from_str(text)?
+        } else {
+            toml::from_str(text)?
+        };
+
+        for mapping in &file.mappings {
+            if !mapping.address.starts_with('/') {
+                bail!("Invalid OSC address \"{}\"", mapping.address);
+            }
+            if !(0.0..1.0).contains(&mapping.smoothing) {
+                bail!("Smoothing of \"{}\" must be in [0, 1)", mapping.address);
+            }
+        }
+
+        Ok(file)
+    }
+
+    // The format is chosen by the file extension, TOML if it is not .json
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read OSC mapping file {}", path.display()))?;
+        let is_json = path.extension().is_some_and(|ext| ext == "json");
+
+        Self::parse(&text, is_json)
+            .with_context(|| format!("Invalid OSC mapping file {}", path.display()))
+    }
+}
+
+// Computes the values to send, applying scaling and smoothing
+struct OscMapper {
+    mappings: Vec<OscMapping>,
+    last_values: Vec<Option<f32>>,
+}
+
+impl OscMapper {
+    fn new(file: OscMappingFile) -> Self {
+        Self {
+            last_values: vec![None; file.mappings.len()],
+            mappings: file.mappings,
+        }
+    }
+
+    fn map(&mut self, face_data: &FaceData) -> Vec<(&str, f32)> {
+        let mut values = vec![];
+        for (mapping, last_value) in self.mappings.iter().zip(&mut self.last_values) {
+            let Some(value) = mapping.source.evaluate(face_data) else {
+                continue;
+            };
+
+            let mut value = value * mapping.scale + mapping.offset;
+            if let Some(last_value) = *last_value {
+                value = last_value * mapping.smoothing + value * (1.0 - mapping.smoothing);
+            }
+            *last_value = Some(value);
+
+            if let Some([min, max]) = mapping.range {
+                value = value.clamp(min, max);
+            }
+
+            values.push((mapping.address.as_str(), value));
+        }
+
+        values
+    }
+}
+
+pub struct OscMappingSink {
+    socket: OscSocket,
+    mapper: OscMapper,
+}
+
+impl OscMappingSink {
+    pub fn new(socket: OscSocket, file: OscMappingFile) -> Self {
+        Self {
+            socket,
+            mapper: OscMapper::new(file),
+        }
+    }
+}
+
+impl FaceTrackingSink for OscMappingSink {
+    fn send_tracking(&mut self, face_data: &FaceData) {
+        for (address, value) in self.mapper.map(face_data) {
+            self.socket
+                .send_message(address, vec![OscType::Float(value)]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::{
+        glam::{EulerRot, Quat},
+        Pose,
+    };
+
+    const MAPPING_TOML: &str = r#"
+        [[mappings]]
+        address = "/avatar/parameters/LeftEyeLid"
+        source = { fb_face_expression = 12 }
+        scale = -1.0
+        offset = 1.0
+        smoothing = 0.5
+
+        [[mappings]]
+        address = "/avatar/parameters/EyesY"
+        source = { eye_gaze = { eye = "combined", axis = "pitch" } }
+        range = [-10.0, 10.0]
+
+        [[mappings]]
+        address = "/avatar/parameters/JawOpen"
+        source = { arkit = "jawOpen" }
+
+        [[mappings]]
+        address = "/avatar/parameters/LipFunnel"
+        source.combined = [
+            { source = { htc_lip_expression = 0 }, weight = 0.5 },
+            { source = { fb_face_expression = 1 }, weight = 0.25 },
+        ]
+    "#;
+
+    fn face_data(expression: f32, pitch_deg: f32) -> FaceData {
+        let pose = Pose {
+            orientation: Quat::from_euler(EulerRot::XYZ, -pitch_deg.to_radians(), 0.0, 0.0),
+            ..Default::default()
+        };
+
+        FaceData {
+            eye_gazes: [Some(pose), None],
+            fb_face_expression: Some(vec![expression; 63]),
+            htc_eye_expression: None,
+            htc_lip_expression: None,
+        }
+    }
+
+    #[test]
+    fn test_json_and_toml_are_equivalent() {
+        let json = r#"{"mappings": [{
+            "address": "/avatar/parameters/LeftEyeLid",
+            "source": {"fb_face_expression": 12},
+            "scale": -1.0,
+            "offset": 1.0,
+            "smoothing": 0.5
+        }]}"#;
+
+        let from_json = OscMappingFile::parse(json, true).unwrap();
+        let from_toml = OscMappingFile::parse(MAPPING_TOML, false).unwrap();
+        assert_eq!(from_toml.mappings.len(), 4);
+        assert_eq!(
+            format!("{:?}", from_json.mappings[0]),
+            format!("{:?}", from_toml.mappings[0])
+        );
+
+        let invalid_address = r#"{"mappings": [{
+            "address": "avatar",
+            "source": {"fb_face_expression": 0}
+        }]}"#;
+        assert!(OscMappingFile::parse(invalid_address, true).is_err());
+    }
+
+    #[test]
+    fn test_mapping() {
+        let mut mapper = OscMapper::new(OscMappingFile::parse(MAPPING_TOML, false).unwrap());
+
+        let values = mapper.map(&face_data(1.0, 20.0));
+        assert_eq!(values[0], ("/avatar/parameters/LeftEyeLid", 0.0));
+        // Only one eye is available, and the value is clamped
+        assert_eq!(values[1], ("/avatar/parameters/EyesY", 10.0));
+        // JAW_DROP
+        assert_eq!(values[2], ("/avatar/parameters/JawOpen", 1.0));
+        // The lip expression is missing
+        assert_eq!(values[3], ("/avatar/parameters/LipFunnel", 0.25));
+
+        // Smoothing
+        let values = mapper.map(&face_data(0.0, 5.0));
+        assert_eq!(values[0].1, 0.5);
+        assert!((values[1].1 - 5.0).abs() < 1e-4);
+
+        // Missing sources are not sent
+        let values = mapper.map(&FaceData::default());
+        assert!(values.is_empty());
+    }
+}
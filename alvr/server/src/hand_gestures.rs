@@ -10,6 +10,7 @@ use alvr_common::{
     *,
 };
 
+use alvr_events::HandGestureEvent;
 use alvr_packets::ButtonValue;
 use alvr_session::HandGestureConfig;
 
@@ -563,6 +564,20 @@ fn get_hover_bind_for_gesture(device_id: u64, gesture_id: HandGestureId) -> Opti
     }
 }
 
+pub fn to_events(hand_path: &str, gestures: &[HandGesture]) -> Vec<HandGestureEvent> {
+    gestures
+        .iter()
+        .map(|gesture| HandGestureEvent {
+            hand_path: hand_path.into(),
+            name: format!("{:?}", gesture.id),
+            active: gesture.active,
+            clicked: gesture.clicked,
+            touching: gesture.touching,
+            value: gesture.value,
+        })
+        .collect()
+}
+
 pub fn trigger_hand_gesture_actions(
     button_mapping_manager: &mut ButtonMappingManager,
     device_id: u64,
@@ -0,0 +1,234 @@
+// Mouse and keyboard events of the operating system
+
+use super::{PointerSink, VirtualKey};
+use alvr_common::{anyhow::Result, glam::Vec2};
+
+#[cfg(windows)]
+pub fn create_desktop_sink() -> Result<Box<dyn PointerSink + Send>> {
+    Ok(Box::new(windows::SendInputSink))
+}
+
+#[cfg(target_os = "linux")]
+pub fn create_desktop_sink() -> Result<Box<dyn PointerSink + Send>> {
+    Ok(Box::new(linux::XdotoolSink::new()?))
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn create_desktop_sink() -> Result<Box<dyn PointerSink + Send>> {
+    alvr_common::anyhow::bail!("Desktop pointer emulation is not supported on this platform")
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::*;
+    use std::mem;
+
+    const INPUT_MOUSE: u32 = 0;
+    const INPUT_KEYBOARD: u32 = 1;
+
+    const MOUSEEVENTF_MOVE: u32 = 0x0001;
+    const MOUSEEVENTF_LEFTDOWN: u32 = 0x0002;
+    const MOUSEEVENTF_LEFTUP: u32 = 0x0004;
+    const MOUSEEVENTF_VIRTUALDESK: u32 = 0x4000;
+    const MOUSEEVENTF_ABSOLUTE: u32 = 0x8000;
+
+    const KEYEVENTF_KEYUP: u32 = 0x0002;
+    const KEYEVENTF_UNICODE: u32 = 0x0004;
+
+    const VK_BACK: u16 = 0x08;
+    const VK_RETURN: u16 = 0x0D;
+    const VK_SPACE: u16 = 0x20;
+
+    // Absolute coordinates are normalized to this range
+    const ABSOLUTE_RANGE: f32 = 65535.0;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct MouseInput {
+        dx: i32,
+        dy: i32,
+        mouse_data: u32,
+        flags: u32,
+        time: u32,
+        extra_info: usize,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct KeyboardInput {
+        virtual_key: u16,
+        scan_code: u16,
+        flags: u32,
+        time: u32,
+        extra_info: usize,
+    }
+
+    #[repr(C)]
+    union InputData {
+        mouse: MouseInput,
+        keyboard: KeyboardInput,
+    }
+
+    #[repr(C)]
+    struct Input {
+        type_: u32,
+        data: InputData,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn SendInput(inputs_count: u32, inputs: *const Input, input_size: i32) -> u32;
+    }
+
+    fn send(inputs: &[Input]) {
+        unsafe {
+            SendInput(
+                inputs.len() as u32,
+                inputs.as_ptr(),
+                mem::size_of::<Input>() as i32,
+            )
+        };
+    }
+
+    fn mouse(dx: i32, dy: i32, flags: u32) -> Input {
+        Input {
+            type_: INPUT_MOUSE,
+            data: InputData {
+                mouse: MouseInput {
+                    dx,
+                    dy,
+                    mouse_data: 0,
+                    flags,
+                    time: 0,
+                    extra_info: 0,
+                },
+            },
+        }
+    }
+
+    fn keyboard(virtual_key: u16, scan_code: u16, flags: u32) -> Input {
+        Input {
+            type_: INPUT_KEYBOARD,
+            data: InputData {
+                keyboard: KeyboardInput {
+                    virtual_key,
+                    scan_code,
+                    flags,
+                    time: 0,
+                    extra_info: 0,
+                },
+            },
+        }
+    }
+
+    pub struct SendInputSink;
+
+    impl PointerSink for SendInputSink {
+        fn move_pointer(&mut self, position: Vec2) {
+            send(&[mouse(
+                (position.x * ABSOLUTE_RANGE) as i32,
+                (position.y * ABSOLUTE_RANGE) as i32,
+                MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK,
+            )]);
+        }
+
+        fn set_button(&mut self, pressed: bool) {
+            let flags = if pressed {
+                MOUSEEVENTF_LEFTDOWN
+            } else {
+                MOUSEEVENTF_LEFTUP
+            };
+            send(&[mouse(0, 0, flags)]);
+        }
+
+        fn tap_key(&mut self, key: VirtualKey) {
+            let (virtual_key, scan_code, flags) = match key {
+                VirtualKey::Character(character) => (0, character as u16, KEYEVENTF_UNICODE),
+                VirtualKey::Space => (VK_SPACE, 0, 0),
+                VirtualKey::Backspace => (VK_BACK, 0, 0),
+                VirtualKey::Enter => (VK_RETURN, 0, 0),
+            };
+
+            send(&[
+                keyboard(virtual_key, scan_code, flags),
+                keyboard(virtual_key, scan_code, flags | KEYEVENTF_KEYUP),
+            ]);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use alvr_common::anyhow::{bail, Context};
+    use std::{
+        io::Write,
+        process::{Child, ChildStdin, Command, Stdio},
+    };
+
+    // A single xdotool process reads the commands from its standard input
+    pub struct XdotoolSink {
+        process: Child,
+        stdin: ChildStdin,
+        screen_size: Vec2,
+    }
+
+    impl XdotoolSink {
+        pub fn new() -> Result<Self> {
+            let output = Command::new("xdotool")
+                .arg("getdisplaygeometry")
+                .output()
+                .context("xdotool not found")?;
+            let geometry = String::from_utf8_lossy(&output.stdout);
+            let Some((width, height)) = geometry.trim().split_once(' ') else {
+                bail!("Failed to get the display size. Only X11 is supported");
+            };
+            let screen_size = Vec2::new(width.parse()?, height.parse()?);
+
+            let mut process = Command::new("xdotool")
+                .arg("-")
+                .stdin(Stdio::piped())
+                .spawn()?;
+            let stdin = process.stdin.take().context("Missing xdotool stdin")?;
+
+            Ok(Self {
+                process,
+                stdin,
+                screen_size,
+            })
+        }
+
+        fn command(&mut self, command: &str) {
+            writeln!(self.stdin, "{command}").ok();
+            self.stdin.flush().ok();
+        }
+    }
+
+    impl PointerSink for XdotoolSink {
+        fn move_pointer(&mut self, position: Vec2) {
+            let pixel = position * self.screen_size;
+            self.command(&format!("mousemove {} {}", pixel.x as i32, pixel.y as i32));
+        }
+
+        fn set_button(&mut self, pressed: bool) {
+            self.command(if pressed { "mousedown 1" } else { "mouseup 1" });
+        }
+
+        fn tap_key(&mut self, key: VirtualKey) {
+            let keysym = match key {
+                VirtualKey::Character(character) => character.to_string(),
+                VirtualKey::Space => "space".into(),
+                VirtualKey::Backspace => "BackSpace".into(),
+                VirtualKey::Enter => "Return".into(),
+            };
+            self.command(&format!("key {keysym}"));
+        }
+    }
+
+    impl Drop for XdotoolSink {
+        fn drop(&mut self) {
+            self.process.kill().ok();
+            self.process.wait().ok();
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// Mouse and keyboard emulation with hand tracking. A ray is cast along the palm of the pointing
+// hand against a virtual screen, placed in front of the head when the hand starts being tracked.
+// Pinching the thumb and index tips clicks. The events are sent to a PointerSink, so the same logic
+// drives the desktop or an overlay.
+
+mod desktop;
+
+use crate::face_tracking::OscSocket;
+use alvr_common::{
+    anyhow::Result,
+    glam::{Quat, Vec2, Vec3},
+    Pose,
+};
+use alvr_session::{HandPointerConfig, HandPointerHand, HandPointerMode, HandPointerOutput};
+use rosc::OscType;
+
+// The pinch is released only when the distance exceeds the pinch distance by this factor
+const PINCH_RELEASE_FACTOR: f32 = 1.5;
+
+const PALM: usize = 0;
+const THUMB_TIP: usize = 5;
+const INDEX_TIP: usize = 10;
+
+const KEYBOARD_CHARACTER_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VirtualKey {
+    // Lowercase letters and digits
+    Character(char),
+    Space,
+    Backspace,
+    Enter,
+}
+
+impl VirtualKey {
+    fn name(self) -> String {
+        match self {
+            VirtualKey::Character(character) => character.to_string(),
+            VirtualKey::Space => "space".into(),
+            VirtualKey::Backspace => "backspace".into(),
+            VirtualKey::Enter => "enter".into(),
+        }
+    }
+}
+
+pub trait PointerSink {
+    // Position normalized to the virtual screen, (0, 0) is the top left corner
+    fn move_pointer(&mut self, position: Vec2);
+    fn set_button(&mut self, pressed: bool);
+    // Press and release
+    fn tap_key(&mut self, key: VirtualKey);
+    // Used by overlays to highlight the key under the pointer
+    fn hover_key(&mut self, _key: Option<VirtualKey>) {}
+}
+
+pub fn create_pointer_sink(output: HandPointerOutput) -> Result<Box<dyn PointerSink + Send>> {
+    Ok(match output {
+        HandPointerOutput::Desktop => desktop::create_desktop_sink()?,
+        // The OSC local port is reserved to the face tracking sink
+        HandPointerOutput::Osc { port } => Box::new(OscPointerSink(OscSocket::new(0, port)?)),
+    })
+}
+
+struct OscPointerSink(OscSocket);
+
+impl PointerSink for OscPointerSink {
+    fn move_pointer(&mut self, position: Vec2) {
+        self.0.send_message(
+            "/alvr/pointer/position",
+            vec![OscType::Float(position.x), OscType::Float(position.y)],
+        );
+    }
+
+    fn set_button(&mut self, pressed: bool) {
+        self.0
+            .send_message("/alvr/pointer/click", vec![OscType::Bool(pressed)]);
+    }
+
+    fn tap_key(&mut self, key: VirtualKey) {
+        self.0
+            .send_message("/alvr/pointer/key", vec![OscType::String(key.name())]);
+    }
+
+    fn hover_key(&mut self, key: Option<VirtualKey>) {
+        self.0.send_message(
+            "/alvr/pointer/hovered_key",
+            vec![OscType::String(
+                key.map(VirtualKey::name).unwrap_or_default(),
+            )],
+        );
+    }
+}
+
+// Position normalized to the keyboard area. The last row contains Backspace, Space and Enter
+fn keyboard_key(position: Vec2) -> Option<VirtualKey> {
+    let rows_count = KEYBOARD_CHARACTER_ROWS.len() + 1;
+    let row_index = (position.y * rows_count as f32) as usize;
+
+    if let Some(row) = KEYBOARD_CHARACTER_ROWS.get(row_index) {
+        let column_index = (position.x * row.len() as f32) as usize;
+        row.chars().nth(column_index).map(VirtualKey::Character)
+    } else if row_index == KEYBOARD_CHARACTER_ROWS.len() {
+        Some(if position.x < 0.25 {
+            VirtualKey::Backspace
+        } else if position.x < 0.75 {
+            VirtualKey::Space
+        } else {
+            VirtualKey::Enter
+        })
+    } else {
+        None
+    }
+}
+
+pub struct HandPointer {
+    config: HandPointerConfig,
+    sink: Box<dyn PointerSink + Send>,
+    // Center of the virtual screen, facing +Z. None while the hand is not tracked
+    screen: Option<Pose>,
+    pinching: bool,
+    button_pressed: bool,
+    hovered_key: Option<VirtualKey>,
+}
+
+impl HandPointer {
+    pub fn new(config: HandPointerConfig, sink: Box<dyn PointerSink + Send>) -> Self {
+        Self {
+            config,
+            sink,
+            screen: None,
+            pinching: false,
+            button_pressed: false,
+            hovered_key: None,
+        }
+    }
+
+    // Poses must be in the same space, usually the raw tracking space of the client
+    pub fn update(&mut self, head_pose: Option<Pose>, hand_skeletons: &[Option<[Pose; 26]>; 2]) {
+        let hand_skeleton = match self.config.hand {
+            HandPointerHand::Left => hand_skeletons[0],
+            HandPointerHand::Right => hand_skeletons[1],
+        };
+        let Some(skeleton) = hand_skeleton else {
+            self.screen = None;
+            self.pinching = false;
+            self.release();
+
+            return;
+        };
+
+        let screen = match self.screen {
+            Some(screen) => screen,
+            None => {
+                let Some(head_pose) = head_pose else {
+                    return;
+                };
+                let screen = self.place_screen(head_pose);
+                self.screen = Some(screen);
+
+                screen
+            }
+        };
+
+        let pinch_distance = skeleton[THUMB_TIP]
+            .position
+            .distance(skeleton[INDEX_TIP].position);
+        let pinch_threshold = if self.pinching {
+            self.config.pinch_distance * 0.01 * PINCH_RELEASE_FACTOR
+        } else {
+            self.config.pinch_distance * 0.01
+        };
+        let pinch_started = !self.pinching && pinch_distance < pinch_threshold;
+        self.pinching = pinch_distance < pinch_threshold;
+
+        let position = self.screen_position(screen, skeleton[PALM]);
+
+        match self.config.mode {
+            HandPointerMode::Mouse => {
+                if let Some(position) = position {
+                    self.sink.move_pointer(position);
+
+                    if pinch_started {
+                        self.button_pressed = true;
+                        self.sink.set_button(true);
+                    }
+                }
+                if !self.pinching {
+                    self.release();
+                }
+            }
+            HandPointerMode::Keyboard => {
+                let hovered_key = position.and_then(keyboard_key);
+                if hovered_key != self.hovered_key {
+                    self.hovered_key = hovered_key;
+                    self.sink.hover_key(hovered_key);
+                }
+
+                if let (true, Some(key)) = (pinch_started, hovered_key) {
+                    self.sink.tap_key(key);
+                }
+            }
+        }
+    }
+
+    fn place_screen(&self, head_pose: Pose) -> Pose {
+        let forward = head_pose.orientation * -Vec3::Z;
+        let orientation = Quat::from_rotation_y(f32::atan2(-forward.x, -forward.z));
+
+        Pose {
+            orientation,
+            position: head_pose.position
+                + orientation * Vec3::new(0.0, 0.0, -self.config.screen_distance),
+        }
+    }
+
+    // Intersection of the palm ray with the screen, normalized. None if outside of the screen
+    fn screen_position(&self, screen: Pose, palm: Pose) -> Option<Vec2> {
+        let inverse_orientation = screen.orientation.inverse();
+        let origin = inverse_orientation * (palm.position - screen.position);
+        let direction = inverse_orientation * (palm.orientation * -Vec3::Z);
+
+        // The ray must travel towards the screen, from its front side
+        if origin.z <= 0.0 || direction.z >= 0.0 {
+            return None;
+        }
+        let hit = origin + direction * (-origin.z / direction.z);
+
+        let position = Vec2::new(
+            hit.x / self.config.screen_width + 0.5,
+            0.5 - hit.y / self.config.screen_height,
+        );
+
+        (position.cmpge(Vec2::ZERO).all() && position.cmplt(Vec2::ONE).all()).then_some(position)
+    }
+
+    fn release(&mut self) {
+        if self.button_pressed {
+            self.button_pressed = false;
+            self.sink.set_button(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Move(Vec2),
+        Button(bool),
+        Key(VirtualKey),
+        Hover(Option<VirtualKey>),
+    }
+
+    struct StubSink(Arc<Mutex<Vec<Event>>>);
+
+    impl PointerSink for StubSink {
+        fn move_pointer(&mut self, position: Vec2) {
+            self.0.lock().unwrap().push(Event::Move(position));
+        }
+
+        fn set_button(&mut self, pressed: bool) {
+            self.0.lock().unwrap().push(Event::Button(pressed));
+        }
+
+        fn tap_key(&mut self, key: VirtualKey) {
+            self.0.lock().unwrap().push(Event::Key(key));
+        }
+
+        fn hover_key(&mut self, key: Option<VirtualKey>) {
+            self.0.lock().unwrap().push(Event::Hover(key));
+        }
+    }
+
+    fn pointer(mode: HandPointerMode) -> (HandPointer, Arc<Mutex<Vec<Event>>>) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let config = HandPointerConfig {
+            hand: HandPointerHand::Right,
+            mode,
+            output: HandPointerOutput::Desktop,
+            screen_distance: 1.0,
+            screen_width: 1.0,
+            screen_height: 1.0,
+            pinch_distance: 1.5,
+        };
+
+        (
+            HandPointer::new(config, Box::new(StubSink(Arc::clone(&events)))),
+            events,
+        )
+    }
+
+    // Right hand at 1.5m height, pointing at the given point of the screen when the head looks
+    // towards -Z
+    fn skeleton(target: Vec3, pinch: bool) -> [Option<[Pose; 26]>; 2] {
+        let position = Vec3::new(0.2, 1.3, -0.3);
+        let mut joints = [Pose {
+            orientation: Quat::from_rotation_arc(-Vec3::Z, (target - position).normalize()),
+            position,
+        }; 26];
+        joints[THUMB_TIP].position = position + Vec3::new(0.0, 0.0, -0.1);
+        joints[INDEX_TIP].position =
+            joints[THUMB_TIP].position + Vec3::new(if pinch { 0.005 } else { 0.05 }, 0.0, 0.0);
+
+        [None, Some(joints)]
+    }
+
+    const HEAD: Pose = Pose {
+        orientation: Quat::IDENTITY,
+        position: Vec3::new(0.0, 1.5, 0.0),
+    };
+
+    #[test]
+    fn test_mouse() {
+        let (mut pointer, events) = pointer(HandPointerMode::Mouse);
+
+        // Center of the screen
+        pointer.update(Some(HEAD), &skeleton(Vec3::new(0.0, 1.5, -1.0), false));
+        // Top left quadrant, then click
+        pointer.update(Some(HEAD), &skeleton(Vec3::new(-0.25, 1.75, -1.0), false));
+        pointer.update(Some(HEAD), &skeleton(Vec3::new(-0.25, 1.75, -1.0), true));
+        // Outside of the screen: the pointer does not move, but the button can be released
+        pointer.update(Some(HEAD), &skeleton(Vec3::new(2.0, 1.5, -1.0), false));
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 5);
+        let Event::Move(center) = events[0] else {
+            panic!()
+        };
+        assert!(center.distance(Vec2::new(0.5, 0.5)) < 1e-4);
+        let Event::Move(corner) = events[1] else {
+            panic!()
+        };
+        assert!(corner.distance(Vec2::new(0.25, 0.25)) < 1e-4);
+        assert!(matches!(events[2], Event::Move(_)));
+        assert_eq!(events[3], Event::Button(true));
+        assert_eq!(events[4], Event::Button(false));
+    }
+
+    #[test]
+    fn test_screen_follows_head() {
+        let (mut pointer, events) = pointer(HandPointerMode::Mouse);
+
+        // Looking towards +X, the screen is placed there
+        let head = Pose {
+            orientation: Quat::from_rotation_y(-std::f32::consts::FRAC_PI_2),
+            ..HEAD
+        };
+        pointer.update(Some(head), &skeleton(Vec3::new(0.0, 1.5, -1.0), false));
+        assert!(events.lock().unwrap().is_empty());
+
+        pointer.update(Some(head), &[None, None]);
+        pointer.update(Some(HEAD), &skeleton(Vec3::new(0.0, 1.5, -1.0), false));
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_keyboard() {
+        let (mut pointer, events) = pointer(HandPointerMode::Keyboard);
+
+        // First key of the second row
+        let target = Vec3::new(-0.45, 1.5 + 0.5 - 0.3, -1.0);
+        pointer.update(Some(HEAD), &skeleton(target, false));
+        pointer.update(Some(HEAD), &skeleton(target, true));
+        // Holding the pinch does not repeat the key
+        pointer.update(Some(HEAD), &skeleton(target, true));
+
+        // Space bar
+        let target = Vec3::new(0.0, 1.5 - 0.45, -1.0);
+        pointer.update(Some(HEAD), &skeleton(target, false));
+        pointer.update(Some(HEAD), &skeleton(target, true));
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                Event::Hover(Some(VirtualKey::Character('q'))),
+                Event::Key(VirtualKey::Character('q')),
+                Event::Hover(Some(VirtualKey::Space)),
+                Event::Key(VirtualKey::Space),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyboard_layout() {
+        assert_eq!(
+            keyboard_key(Vec2::new(0.0, 0.0)),
+            Some(VirtualKey::Character('1'))
+        );
+        assert_eq!(
+            keyboard_key(Vec2::new(0.99, 0.7)),
+            Some(VirtualKey::Character('m'))
+        );
+        assert_eq!(
+            keyboard_key(Vec2::new(0.1, 0.9)),
+            Some(VirtualKey::Backspace)
+        );
+        assert_eq!(keyboard_key(Vec2::new(0.9, 0.9)), Some(VirtualKey::Enter));
+    }
+}
@@ -0,0 +1,689 @@
+// Button mapping macros: a small expression language evaluated on top of the regular mappings.
+// Each macro has the form `<destination path> = <expression>`, for example:
+//
+// /user/hand/right/input/b/click = long_press(/user/hand/right/input/a/click, 500)
+// /user/hand/left/input/menu/click = /user/hand/left/input/x/click & /user/hand/left/input/y/click
+//
+// Operators, from lowest to highest precedence: `|`, `&`, `>` and `<` (compare to scalars), `!`.
+// Functions (durations are in milliseconds):
+// * long_press(input, ms): active while the input is held for longer than ms
+// * short_press(input, ms): short pulse when the input is released before ms
+// * double_tap(input, ms): short pulse when the input is pressed twice within ms
+// * toggle(input): switches state on every press
+// * sequence(ms, input1, input2, ...): short pulse when the inputs are pressed in order, each within
+//   ms from the previous one
+// * dpad_up/dpad_down/dpad_left/dpad_right(x, y, threshold): active while the axes point in that
+//   direction further than threshold
+
+use super::INTERACTION_PROFILES;
+use alvr_common::{
+    anyhow::{anyhow, bail, Result},
+    glam::Vec2,
+    hash_string, ButtonType,
+};
+use alvr_packets::ButtonValue;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+// Duration of the presses generated by short_press, double_tap and sequence
+const PULSE_DURATION: Duration = Duration::from_millis(100);
+const BINARY_THRESHOLD: f32 = 0.5;
+
+fn to_binary(value: ButtonValue) -> bool {
+    match value {
+        ButtonValue::Binary(value) => value,
+        ButtonValue::Scalar(value) => value > BINARY_THRESHOLD,
+    }
+}
+
+pub(super) fn to_scalar(value: ButtonValue) -> f32 {
+    match value {
+        ButtonValue::Binary(value) => {
+            if value {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        ButtonValue::Scalar(value) => value,
+    }
+}
+
+fn is_pulse_active(pulse_end: Option<Instant>, now: Instant) -> bool {
+    pulse_end.is_some_and(|end| now < end)
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Path(String),
+    Number(f32),
+    Identifier(String),
+    Not,
+    And,
+    Or,
+    Greater,
+    Less,
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let mut take_while = |predicate: fn(char) -> bool| {
+            let mut string = String::new();
+            while let Some(c) = chars.next_if(|c| predicate(*c)) {
+                string.push(c);
+            }
+            string
+        };
+
+        let token = match c {
+            c if c.is_whitespace() => {
+                take_while(char::is_whitespace);
+                continue;
+            }
+            '/' => Token::Path(take_while(|c| {
+                c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '.')
+            })),
+            '0'..='9' | '-' | '.' => {
+                let number = take_while(|c| c.is_ascii_digit() || matches!(c, '-' | '.'));
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid number \"{number}\""))?,
+                )
+            }
+            c if c.is_ascii_alphabetic() => {
+                Token::Identifier(take_while(|c| c.is_ascii_alphanumeric() || c == '_'))
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '!' => Token::Not,
+                    '&' => Token::And,
+                    '|' => Token::Or,
+                    '>' => Token::Greater,
+                    '<' => Token::Less,
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    ',' => Token::Comma,
+                    _ => bail!("Unexpected character '{c}'"),
+                }
+            }
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+enum Expression {
+    Constant(f32),
+    Input(u64),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Greater(Box<Expression>, Box<Expression>),
+    Less(Box<Expression>, Box<Expression>),
+    LongPress {
+        input: Box<Expression>,
+        duration: Duration,
+        press_start: Option<Instant>,
+    },
+    ShortPress {
+        input: Box<Expression>,
+        duration: Duration,
+        press_start: Option<Instant>,
+        pulse_end: Option<Instant>,
+    },
+    DoubleTap {
+        input: Box<Expression>,
+        interval: Duration,
+        was_pressed: bool,
+        last_press: Option<Instant>,
+        pulse_end: Option<Instant>,
+    },
+    Toggle {
+        input: Box<Expression>,
+        was_pressed: bool,
+        state: bool,
+    },
+    Sequence {
+        interval: Duration,
+        // expression, was pressed
+        steps: Vec<(Expression, bool)>,
+        progress: usize,
+        last_step: Option<Instant>,
+        pulse_end: Option<Instant>,
+    },
+    Dpad {
+        x: Box<Expression>,
+        y: Box<Expression>,
+        direction: Vec2,
+        threshold: f32,
+    },
+}
+
+impl Expression {
+    // All subexpressions are always evaluated, to keep the state of the timed functions updated
+    fn evaluate(&mut self, inputs: &HashMap<u64, ButtonValue>, now: Instant) -> ButtonValue {
+        let binary = |expression: &mut Expression| to_binary(expression.evaluate(inputs, now));
+
+        match self {
+            Expression::Constant(value) => ButtonValue::Scalar(*value),
+            Expression::Input(id) => inputs
+                .get(id)
+                .copied()
+                .unwrap_or(ButtonValue::Binary(false)),
+            Expression::Not(expression) => ButtonValue::Binary(!binary(expression)),
+            Expression::And(left, right) => {
+                let left = binary(left);
+                ButtonValue::Binary(binary(right) && left)
+            }
+            Expression::Or(left, right) => {
+                let left = binary(left);
+                ButtonValue::Binary(binary(right) || left)
+            }
+            Expression::Greater(left, right) => ButtonValue::Binary(
+                to_scalar(left.evaluate(inputs, now)) > to_scalar(right.evaluate(inputs, now)),
+            ),
+            Expression::Less(left, right) => ButtonValue::Binary(
+                to_scalar(left.evaluate(inputs, now)) < to_scalar(right.evaluate(inputs, now)),
+            ),
+            Expression::LongPress {
+                input,
+                duration,
+                press_start,
+            } => {
+                let active = if binary(input) {
+                    now.saturating_duration_since(*press_start.get_or_insert(now)) >= *duration
+                } else {
+                    *press_start = None;
+                    false
+                };
+
+                ButtonValue::Binary(active)
+            }
+            Expression::ShortPress {
+                input,
+                duration,
+                press_start,
+                pulse_end,
+            } => {
+                if binary(input) {
+                    press_start.get_or_insert(now);
+                } else if let Some(start) = press_start.take() {
+                    if now.saturating_duration_since(start) < *duration {
+                        *pulse_end = Some(now + PULSE_DURATION);
+                    }
+                }
+
+                ButtonValue::Binary(is_pulse_active(*pulse_end, now))
+            }
+            Expression::DoubleTap {
+                input,
+                interval,
+                was_pressed,
+                last_press,
+                pulse_end,
+            } => {
+                let pressed = binary(input);
+                if pressed && !*was_pressed {
+                    if last_press
+                        .is_some_and(|last| now.saturating_duration_since(last) <= *interval)
+                    {
+                        *pulse_end = Some(now + PULSE_DURATION);
+                        *last_press = None;
+                    } else {
+                        *last_press = Some(now);
+                    }
+                }
+                *was_pressed = pressed;
+
+                ButtonValue::Binary(is_pulse_active(*pulse_end, now))
+            }
+            Expression::Toggle {
+                input,
+                was_pressed,
+                state,
+            } => {
+                let pressed = binary(input);
+                if pressed && !*was_pressed {
+                    *state = !*state;
+                }
+                *was_pressed = pressed;
+
+                ButtonValue::Binary(*state)
+            }
+            Expression::Sequence {
+                interval,
+                steps,
+                progress,
+                last_step,
+                pulse_end,
+            } => {
+                let mut pressed_steps = HashSet::new();
+                for (index, (step, was_pressed)) in steps.iter_mut().enumerate() {
+                    let pressed = binary(step);
+                    if pressed && !*was_pressed {
+                        pressed_steps.insert(index);
+                    }
+                    *was_pressed = pressed;
+                }
+
+                if last_step.is_some_and(|last| now.saturating_duration_since(last) > *interval) {
+                    *progress = 0;
+                }
+
+                // Only one step is taken per evaluation, in case the same input is used for
+                // consecutive steps
+                if pressed_steps.contains(progress) {
+                    *progress += 1;
+                } else if !pressed_steps.is_empty() {
+                    *progress = if pressed_steps.contains(&0) { 1 } else { 0 };
+                }
+                if !pressed_steps.is_empty() {
+                    *last_step = Some(now);
+                }
+
+                if *progress == steps.len() {
+                    *pulse_end = Some(now + PULSE_DURATION);
+                    *progress = 0;
+                }
+
+                ButtonValue::Binary(is_pulse_active(*pulse_end, now))
+            }
+            Expression::Dpad {
+                x,
+                y,
+                direction,
+                threshold,
+            } => {
+                let axes = Vec2::new(
+                    to_scalar(x.evaluate(inputs, now)),
+                    to_scalar(y.evaluate(inputs, now)),
+                );
+
+                // Each direction covers a 90° sector
+                ButtonValue::Binary(
+                    axes.length() > *threshold
+                        && axes.dot(*direction) >= axes.length() * std::f32::consts::FRAC_1_SQRT_2,
+                )
+            }
+        }
+    }
+}
+
+fn to_duration(expression: Expression) -> Result<Duration> {
+    match expression {
+        Expression::Constant(milliseconds) if milliseconds >= 0.0 => {
+            Ok(Duration::from_secs_f32(milliseconds / 1000.0))
+        }
+        _ => bail!("Durations must be positive numbers of milliseconds"),
+    }
+}
+
+fn to_constant(expression: Expression) -> Result<f32> {
+    match expression {
+        Expression::Constant(value) => Ok(value),
+        _ => bail!("Thresholds must be numbers"),
+    }
+}
+
+fn take_arguments<const N: usize>(
+    function: &str,
+    arguments: Vec<Expression>,
+) -> Result<[Expression; N]> {
+    let count = arguments.len();
+    arguments
+        .try_into()
+        .map_err(|_| anyhow!("{function}() takes {N} arguments, {count} given"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    inputs: HashSet<u64>,
+}
+
+impl Parser {
+    fn next_if(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.position) == Some(token);
+        if found {
+            self.position += 1;
+        }
+
+        found
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        if !self.next_if(&token) {
+            bail!("Expected {token:?}");
+        }
+
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Expression> {
+        let mut expression = self.parse_and()?;
+        while self.next_if(&Token::Or) {
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression> {
+        let mut expression = self.parse_comparison()?;
+        while self.next_if(&Token::And) {
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_comparison()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression> {
+        let left = self.parse_unary()?;
+
+        if self.next_if(&Token::Greater) {
+            Ok(Expression::Greater(
+                Box::new(left),
+                Box::new(self.parse_unary()?),
+            ))
+        } else if self.next_if(&Token::Less) {
+            Ok(Expression::Less(
+                Box::new(left),
+                Box::new(self.parse_unary()?),
+            ))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression> {
+        if self.next_if(&Token::Not) {
+            Ok(Expression::Not(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression> {
+        let Some(token) = self.tokens.get(self.position) else {
+            bail!("Unexpected end of expression");
+        };
+        self.position += 1;
+
+        match token {
+            Token::Path(path) => {
+                let id = hash_string(path);
+                self.inputs.insert(id);
+
+                Ok(Expression::Input(id))
+            }
+            Token::Number(value) => Ok(Expression::Constant(*value)),
+            Token::OpenParen => {
+                let expression = self.parse_or()?;
+                self.expect(Token::CloseParen)?;
+
+                Ok(expression)
+            }
+            Token::Identifier(function) => {
+                let function = function.clone();
+                self.parse_function(&function)
+            }
+            token => bail!("Unexpected {token:?}"),
+        }
+    }
+
+    fn parse_function(&mut self, function: &str) -> Result<Expression> {
+        self.expect(Token::OpenParen)?;
+        let mut arguments = vec![];
+        if !self.next_if(&Token::CloseParen) {
+            loop {
+                arguments.push(self.parse_or()?);
+                if self.next_if(&Token::CloseParen) {
+                    break;
+                }
+                self.expect(Token::Comma)?;
+            }
+        }
+
+        let expression = match function {
+            "long_press" => {
+                let [input, duration] = take_arguments(function, arguments)?;
+                Expression::LongPress {
+                    input: Box::new(input),
+                    duration: to_duration(duration)?,
+                    press_start: None,
+                }
+            }
+            "short_press" => {
+                let [input, duration] = take_arguments(function, arguments)?;
+                Expression::ShortPress {
+                    input: Box::new(input),
+                    duration: to_duration(duration)?,
+                    press_start: None,
+                    pulse_end: None,
+                }
+            }
+            "double_tap" => {
+                let [input, interval] = take_arguments(function, arguments)?;
+                Expression::DoubleTap {
+                    input: Box::new(input),
+                    interval: to_duration(interval)?,
+                    was_pressed: false,
+                    last_press: None,
+                    pulse_end: None,
+                }
+            }
+            "toggle" => {
+                let [input] = take_arguments(function, arguments)?;
+                Expression::Toggle {
+                    input: Box::new(input),
+                    was_pressed: false,
+                    state: false,
+                }
+            }
+            "sequence" => {
+                if arguments.len() < 2 {
+                    bail!("sequence() takes an interval and at least one input");
+                }
+                let mut arguments = arguments.into_iter();
+                Expression::Sequence {
+                    interval: to_duration(arguments.next().unwrap())?,
+                    steps: arguments.map(|step| (step, false)).collect(),
+                    progress: 0,
+                    last_step: None,
+                    pulse_end: None,
+                }
+            }
+            "dpad_up" | "dpad_down" | "dpad_left" | "dpad_right" => {
+                let [x, y, threshold] = take_arguments(function, arguments)?;
+                Expression::Dpad {
+                    x: Box::new(x),
+                    y: Box::new(y),
+                    direction: match function {
+                        "dpad_up" => Vec2::Y,
+                        "dpad_down" => Vec2::NEG_Y,
+                        "dpad_left" => Vec2::NEG_X,
+                        _ => Vec2::X,
+                    },
+                    threshold: to_constant(threshold)?,
+                }
+            }
+            _ => bail!("Unknown function {function}()"),
+        };
+
+        Ok(expression)
+    }
+}
+
+pub struct MappingMacro {
+    destination: u64,
+    expression: Expression,
+    inputs: HashSet<u64>,
+    last_value: f32,
+}
+
+impl MappingMacro {
+    pub fn parse(text: &str) -> Result<Self> {
+        let Some((destination, expression)) = text.split_once('=') else {
+            bail!("Expected \"<destination path> = <expression>\"");
+        };
+        let destination = destination.trim();
+        if !destination.starts_with('/') {
+            bail!("Invalid destination path \"{destination}\"");
+        }
+
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            position: 0,
+            inputs: HashSet::new(),
+        };
+        let expression = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            bail!("Unexpected {token:?}");
+        }
+
+        Ok(Self {
+            destination: hash_string(destination),
+            expression,
+            inputs: parser.inputs,
+            last_value: 0.0,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MappingMacros {
+    macros: Vec<MappingMacro>,
+    inputs: HashSet<u64>,
+    destinations: HashSet<u64>,
+}
+
+impl MappingMacros {
+    pub fn new(macros: Vec<MappingMacro>) -> Self {
+        Self {
+            inputs: macros.iter().flat_map(|m| m.inputs.clone()).collect(),
+            destinations: macros.iter().map(|m| m.destination).collect(),
+            macros,
+        }
+    }
+
+    pub fn uses_input(&self, id: u64) -> bool {
+        self.inputs.contains(&id)
+    }
+
+    // The regular mappings must not set these destinations
+    pub fn overrides(&self, destination: u64) -> bool {
+        self.destinations.contains(&destination)
+    }
+
+    // Returns the destinations whose value changed. Values are converted to the destination type.
+    pub fn evaluate(
+        &mut self,
+        inputs: &HashMap<u64, ButtonValue>,
+        now: Instant,
+    ) -> Vec<(u64, ButtonValue)> {
+        let mut changes = vec![];
+        for mapping_macro in &mut self.macros {
+            let value = mapping_macro.expression.evaluate(inputs, now);
+            if to_scalar(value) == mapping_macro.last_value {
+                continue;
+            }
+            mapping_macro.last_value = to_scalar(value);
+
+            changes.push((
+                mapping_macro.destination,
+                destination_value(mapping_macro.destination, value),
+            ));
+        }
+
+        changes
+    }
+
+    // Returns the destinations left pressed, with their released value. Used before the macros are
+    // replaced, since the new macros or mappings may never set them again.
+    pub fn release(&self) -> Vec<(u64, ButtonValue)> {
+        self.macros
+            .iter()
+            .filter(|mapping_macro| mapping_macro.last_value != 0.0)
+            .map(|mapping_macro| {
+                (
+                    mapping_macro.destination,
+                    destination_value(mapping_macro.destination, ButtonValue::Scalar(0.0)),
+                )
+            })
+            .collect()
+    }
+}
+
+fn destination_value(destination: u64, value: ButtonValue) -> ButtonValue {
+    let button_type = INTERACTION_PROFILES
+        .read()
+        .buttons
+        .get(&destination)
+        .map(|info| info.button_type);
+
+    match button_type {
+        Some(ButtonType::Binary) => ButtonValue::Binary(to_binary(value)),
+        Some(ButtonType::Scalar) => ButtonValue::Scalar(to_scalar(value)),
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        for text in [
+            "/user/hand/right/input/a/click",
+            "a/click = /user/hand/right/input/b/click",
+            "/user/hand/right/input/a/click = ",
+            "/user/hand/right/input/a/click = (/user/hand/right/input/b/click",
+            "/user/hand/right/input/a/click = /user/hand/right/input/b/click /user/head",
+            "/user/hand/right/input/a/click = long_press(/user/hand/right/input/b/click)",
+            "/user/hand/right/input/a/click = long_press(/user/hand/right/input/b/click, /user/head)",
+            "/user/hand/right/input/a/click = hold(/user/hand/right/input/b/click, 500)",
+            "/user/hand/right/input/a/click = /user/hand/right/input/b/click # comment",
+        ] {
+            assert!(MappingMacro::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mapping_macro = MappingMacro::parse("/a = !/b & /c | /d > 0.5").unwrap();
+        assert_eq!(
+            mapping_macro.inputs,
+            ["/b", "/c", "/d"].into_iter().map(hash_string).collect()
+        );
+
+        let mut macros = MappingMacros::new(vec![mapping_macro]);
+        let now = Instant::now();
+        let mut evaluate = |values: [f32; 3]| {
+            let inputs = ["/b", "/c", "/d"]
+                .into_iter()
+                .zip(values)
+                .map(|(path, value)| (hash_string(path), ButtonValue::Scalar(value)))
+                .collect();
+            macros.evaluate(&inputs, now)
+        };
+
+        // (!b & c) | (d > 0.5)
+        assert_eq!(evaluate([0.0, 1.0, 0.0]).len(), 1);
+        assert_eq!(evaluate([1.0, 1.0, 0.0]).len(), 1);
+        assert_eq!(evaluate([1.0, 0.0, 0.7]).len(), 1);
+        assert!(evaluate([0.0, 1.0, 0.9]).is_empty());
+    }
+}
@@ -0,0 +1,951 @@
+mod macros;
+
+use crate::{bindings::FfiButtonValue, SERVER_DATA_MANAGER};
+use alvr_common::{once_cell::sync::Lazy, parking_lot::RwLock, settings_schema::Switch, *};
+use alvr_events::{ButtonEvent, EventType};
+use alvr_packets::ButtonValue;
+use alvr_session::{
+    AutomaticButtonMappingConfig, BinaryToScalarStates, ButtonBindingTarget, ButtonMappingType,
+    ControllersEmulationMode, HysteresisThreshold, Range,
+};
+use macros::{MappingMacro, MappingMacros};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, iter,
+    path::Path,
+    time::Instant,
+};
+
+// Built-in profiles, extended with the user profiles on startup
+pub static INTERACTION_PROFILES: Lazy<RwLock<InteractionProfiles>> =
+    Lazy::new(|| RwLock::new(BUILTIN_INTERACTION_PROFILES.clone()));
+
+// Destination buttons to try in order when the destination profile lacks the source button. The
+// keys are button identifiers, like "thumbstick" in "/user/hand/left/input/thumbstick/click"
+static AUTOMATIC_BINDING_FALLBACKS: Lazy<HashMap<String, Vec<String>>> = Lazy::new(|| {
+    serde_json::from_str(include_str!(
+        "../../resources/automatic_binding_fallbacks.json"
+    ))
+    .unwrap()
+});
+
+pub static REGISTERED_BUTTON_SET: Lazy<HashSet<u64>> = Lazy::new(|| {
+    let data_manager_lock = SERVER_DATA_MANAGER.read();
+    let Switch::Enabled(controllers_config) = &data_manager_lock
+        .connected_client_settings()
+        .headset
+        .controllers
+    else {
+        return HashSet::new();
+    };
+
+    let profile_id = match &controllers_config.emulation_mode {
+        ControllersEmulationMode::RiftSTouch
+        | ControllersEmulationMode::Quest2Touch
+        | ControllersEmulationMode::Quest3Plus => *QUEST_CONTROLLER_PROFILE_ID,
+        ControllersEmulationMode::ValveIndex => *INDEX_CONTROLLER_PROFILE_ID,
+        ControllersEmulationMode::ViveWand => *VIVE_CONTROLLER_PROFILE_ID,
+        ControllersEmulationMode::ViveTracker => return HashSet::new(),
+        ControllersEmulationMode::Custom { button_set, .. } => {
+            return button_set
+                .iter()
+                .map(|b| alvr_common::hash_string(b))
+                .collect()
+        }
+    };
+
+    profile_button_set(profile_id).unwrap_or_default()
+});
+
+// User profiles can describe new controllers or replace the built-in ones with the same path
+pub fn load_user_interaction_profiles(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut profiles = INTERACTION_PROFILES.write();
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let result = fs::read_to_string(&path)
+                .map_err(Into::into)
+                .and_then(|json| profiles.add_from_json(&json));
+            match result {
+                Ok(id) => info!("Loaded interaction profile {}", profiles.profiles[&id].name),
+                Err(e) => error!("Invalid interaction profile {}: {e}", path.display()),
+            }
+        }
+    }
+}
+
+pub fn profile_button_set(profile_id: u64) -> Option<HashSet<u64>> {
+    INTERACTION_PROFILES
+        .read()
+        .profiles
+        .get(&profile_id)
+        .map(|info| info.button_set.clone())
+}
+
+pub struct BindingTarget {
+    destination: u64,
+    mapping_type: ButtonMappingType,
+    binary_conditions: Vec<u64>,
+}
+
+// Inputs relative to the same physical button
+#[derive(Clone, Copy, Default)]
+pub struct ButtonInputs {
+    click: Option<u64>,
+    touch: Option<u64>,
+    value: Option<u64>,
+    force: Option<u64>,
+}
+
+#[derive(Default)]
+struct ButtonComponents {
+    inputs: ButtonInputs,
+    x: Option<u64>,
+    y: Option<u64>,
+}
+
+// Groups the buttons by device and button identifier. For example,
+// "/user/hand/left/input/trackpad/x" is the "x" component of ("/user/hand/left", "trackpad")
+fn button_components<'a>(
+    profiles: &'a InteractionProfiles,
+    set: &HashSet<u64>,
+) -> HashMap<(&'a str, &'a str), ButtonComponents> {
+    let mut buttons = HashMap::<_, ButtonComponents>::new();
+    for id in set {
+        let Some((device, input)) = profiles
+            .buttons
+            .get(id)
+            .and_then(|info| info.path.split_once("/input/"))
+        else {
+            continue;
+        };
+        let Some((identifier, component)) = input.rsplit_once('/') else {
+            continue;
+        };
+
+        let button = buttons.entry((device, identifier)).or_default();
+        match component {
+            "click" => button.inputs.click = Some(*id),
+            "touch" => button.inputs.touch = Some(*id),
+            "value" => button.inputs.value = Some(*id),
+            "force" => button.inputs.force = Some(*id),
+            "x" => button.x = Some(*id),
+            "y" => button.y = Some(*id),
+            _ => (),
+        }
+    }
+
+    buttons
+}
+
+fn passthrough(target: u64) -> BindingTarget {
+    BindingTarget {
+        destination: target,
+        mapping_type: ButtonMappingType::Passthrough,
+        binary_conditions: vec![],
+    }
+}
+
+fn binary_to_scalar(target: u64, map: BinaryToScalarStates) -> BindingTarget {
+    BindingTarget {
+        destination: target,
+        mapping_type: ButtonMappingType::BinaryToScalar(map),
+        binary_conditions: vec![],
+    }
+}
+
+fn hysteresis_threshold(target: u64, map: HysteresisThreshold) -> BindingTarget {
+    BindingTarget {
+        destination: target,
+        mapping_type: ButtonMappingType::HysteresisThreshold(map),
+        binary_conditions: vec![],
+    }
+}
+
+fn remap(target: u64, map: Range) -> BindingTarget {
+    BindingTarget {
+        destination: target,
+        mapping_type: ButtonMappingType::Remap(map),
+        binary_conditions: vec![],
+    }
+}
+
+// Map two buttons with eterogeneous inputs
+fn map_button_pair_automatic(
+    source: ButtonInputs,
+    destination: ButtonInputs,
+    config: &AutomaticButtonMappingConfig,
+) -> impl Iterator<Item = (u64, Vec<BindingTarget>)> {
+    let click_to_value = BinaryToScalarStates { off: 0.0, on: 1.0 };
+
+    let mut entries = vec![];
+    if let Some(source_click) = source.click {
+        let mut targets = vec![];
+
+        if let Some(destination_click) = destination.click {
+            targets.push(passthrough(destination_click));
+        }
+        if source.touch.is_none() {
+            if let Some(destination_touch) = destination.touch {
+                targets.push(passthrough(destination_touch));
+            }
+        }
+        if source.value.is_none() {
+            if let Some(destination_value) = destination.value {
+                targets.push(binary_to_scalar(destination_value, click_to_value));
+            }
+        }
+
+        entries.push((source_click, targets));
+    }
+    if let Some(source_touch) = source.touch {
+        let mut targets = vec![];
+        if let Some(destination_touch) = destination.touch {
+            targets.push(passthrough(destination_touch));
+        }
+        entries.push((source_touch, targets));
+    }
+    if let Some(source_value) = source.value {
+        let mut targets = vec![];
+        let mut remap_for_touch = false;
+        let mut remap_for_force = false;
+
+        if source.click.is_none() {
+            if let Some(destination_click) = destination.click {
+                targets.push(hysteresis_threshold(
+                    destination_click,
+                    config.click_threshold,
+                ));
+            }
+        }
+        if source.touch.is_none() {
+            if let Some(destination_touch) = destination.touch {
+                targets.push(hysteresis_threshold(
+                    destination_touch,
+                    config.touch_threshold,
+                ));
+                remap_for_touch = true;
+            }
+        }
+        if source.force.is_none() {
+            if let Some(destination_force) = destination.force {
+                targets.push(remap(
+                    destination_force,
+                    Range {
+                        min: config.force_threshold,
+                        max: 1.0,
+                    },
+                ));
+                remap_for_force = true;
+            }
+        }
+        if let Some(destination_value) = destination.value {
+            if !remap_for_touch && !remap_for_force {
+                targets.push(passthrough(destination_value));
+            } else {
+                let low = if remap_for_touch {
+                    config.touch_threshold.value
+                } else {
+                    0.0
+                };
+                let high = if remap_for_force {
+                    config.force_threshold
+                } else {
+                    1.0
+                };
+                targets.push(remap(
+                    destination_value,
+                    Range {
+                        min: low,
+                        max: high,
+                    },
+                ));
+            }
+        }
+
+        entries.push((source_value, targets));
+    }
+
+    entries.into_iter()
+}
+
+// Each source button is bound to the destination button with the same identifier or, if missing,
+// to the first available fallback
+pub fn automatic_bindings(
+    profiles: &InteractionProfiles,
+    source_set: &HashSet<u64>,
+    destination_set: &HashSet<u64>,
+    config: &AutomaticButtonMappingConfig,
+) -> HashMap<u64, Vec<BindingTarget>> {
+    let destination_buttons = button_components(profiles, destination_set);
+
+    let mut bindings = HashMap::new();
+    for ((device, identifier), source) in button_components(profiles, source_set) {
+        let fallbacks = AUTOMATIC_BINDING_FALLBACKS
+            .get(identifier)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let Some(destination) = iter::once(identifier)
+            .chain(fallbacks.iter().map(String::as_str))
+            .find_map(|identifier| destination_buttons.get(&(device, identifier)))
+        else {
+            continue;
+        };
+
+        bindings.extend(map_button_pair_automatic(
+            source.inputs,
+            destination.inputs,
+            config,
+        ));
+        for axes in [(source.x, destination.x), (source.y, destination.y)] {
+            if let (Some(source_axis), Some(destination_axis)) = axes {
+                bindings.insert(source_axis, vec![passthrough(destination_axis)]);
+            }
+        }
+    }
+
+    bindings
+}
+
+pub extern "C" fn register_buttons(device_id: u64) {
+    let profiles = INTERACTION_PROFILES.read();
+    for id in &*REGISTERED_BUTTON_SET {
+        if let Some(info) = profiles.buttons.get(id) {
+            if info.device_id == device_id {
+                unsafe { crate::RegisterButton(*id) };
+            }
+        } else {
+            error!("Cannot register unrecognized button ID {id}");
+        }
+    }
+}
+
+pub fn button_path(id: u64) -> String {
+    INTERACTION_PROFILES
+        .read()
+        .buttons
+        .get(&id)
+        .map(|info| info.path.clone())
+        .unwrap_or_else(|| format!("Unknown (ID: {id:#16x})"))
+}
+
+fn set_openvr_button(id: u64, value: ButtonValue) {
+    if SERVER_DATA_MANAGER
+        .read()
+        .settings()
+        .logging
+        .log_button_mappings
+    {
+        alvr_events::send_event(EventType::MappedButton(ButtonEvent {
+            path: button_path(id),
+            value,
+        }));
+    }
+
+    let value = match value {
+        ButtonValue::Binary(value) => FfiButtonValue {
+            type_: crate::FfiButtonType_BUTTON_TYPE_BINARY,
+            __bindgen_anon_1: crate::FfiButtonValue__bindgen_ty_1 {
+                binary: value.into(),
+            },
+        },
+
+        ButtonValue::Scalar(value) => FfiButtonValue {
+            type_: crate::FfiButtonType_BUTTON_TYPE_SCALAR,
+            __bindgen_anon_1: crate::FfiButtonValue__bindgen_ty_1 { scalar: value },
+        },
+    };
+    unsafe { crate::SetButton(id, value) };
+}
+
+fn manual_bindings(
+    mappings: &[(String, Vec<ButtonBindingTarget>)],
+) -> HashMap<u64, Vec<BindingTarget>> {
+    mappings
+        .iter()
+        .map(|(key, value)| {
+            (
+                alvr_common::hash_string(key),
+                value
+                    .iter()
+                    .map(|b| BindingTarget {
+                        destination: alvr_common::hash_string(&b.destination),
+                        mapping_type: b.mapping_type.clone(),
+                        binary_conditions: b
+                            .binary_conditions
+                            .iter()
+                            .map(|c| alvr_common::hash_string(c))
+                            .collect(),
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+pub struct ButtonMappingManager {
+    mappings: HashMap<u64, Vec<BindingTarget>>,
+    binary_source_states: HashMap<u64, bool>,
+    hysteresis_states: HashMap<u64, HashMap<u64, bool>>,
+    source_values: HashMap<u64, ButtonValue>,
+    macro_sources: Vec<String>,
+    macros: MappingMacros,
+    set_button: Box<dyn FnMut(u64, ButtonValue) + Send>,
+}
+
+impl ButtonMappingManager {
+    fn new(mappings: HashMap<u64, Vec<BindingTarget>>) -> Self {
+        Self {
+            mappings,
+            binary_source_states: HashMap::new(),
+            hysteresis_states: HashMap::new(),
+            source_values: HashMap::new(),
+            macro_sources: vec![],
+            macros: MappingMacros::default(),
+            set_button: Box::new(set_openvr_button),
+        }
+    }
+
+    pub fn new_automatic(source: &HashSet<u64>, config: &AutomaticButtonMappingConfig) -> Self {
+        Self::new(automatic_bindings(
+            &INTERACTION_PROFILES.read(),
+            source,
+            &REGISTERED_BUTTON_SET,
+            config,
+        ))
+    }
+
+    pub fn new_manual(mappings: &[(String, Vec<ButtonBindingTarget>)]) -> Self {
+        Self::new(manual_bindings(mappings))
+    }
+
+    // Map extra sources, like custom hand gestures. Their bindings can have conditions on the
+    // sources already mapped
+    pub fn add_manual_mappings(&mut self, mappings: &[(String, Vec<ButtonBindingTarget>)]) {
+        self.mappings.extend(manual_bindings(mappings));
+    }
+
+    // Recompiles the macros only if they changed. Invalid macros are logged and ignored
+    pub fn set_macros(&mut self, sources: &[String]) {
+        if self.macro_sources == sources {
+            return;
+        }
+        self.macro_sources = sources.to_vec();
+
+        for (destination, value) in self.macros.release() {
+            (self.set_button)(destination, value);
+        }
+
+        let macros = sources
+            .iter()
+            .filter_map(|source| {
+                MappingMacro::parse(source)
+                    .map_err(|e| error!("Invalid button mapping macro \"{source}\": {e}"))
+                    .ok()
+            })
+            .collect();
+        self.macros = MappingMacros::new(macros);
+    }
+
+    // Must be called periodically to advance timed macros, like long presses
+    pub fn update_macros(&mut self) {
+        self.update_macros_at(Instant::now());
+    }
+
+    fn update_macros_at(&mut self, now: Instant) {
+        for (destination, value) in self.macros.evaluate(&self.source_values, now) {
+            (self.set_button)(destination, value);
+        }
+    }
+
+    // Apply any button changes that are mapped to this specific button
+    pub fn report_button(&mut self, source_id: u64, source_value: ButtonValue) {
+        self.report_button_at(source_id, source_value, Instant::now());
+    }
+
+    fn report_button_at(&mut self, source_id: u64, source_value: ButtonValue, now: Instant) {
+        if let ButtonValue::Binary(value) = source_value {
+            let val_ref = self.binary_source_states.entry(source_id).or_default();
+
+            if value != *val_ref {
+                *val_ref = value;
+            } else {
+                return;
+            }
+        }
+
+        self.source_values.insert(source_id, source_value);
+        if self.macros.uses_input(source_id) {
+            self.update_macros_at(now);
+        }
+
+        if let Some(mappings) = self.mappings.get(&source_id) {
+            'mapping: for mapping in mappings {
+                if self.macros.overrides(mapping.destination) {
+                    continue;
+                }
+
+                let destination_value = match (&mapping.mapping_type, source_value) {
+                    (ButtonMappingType::Passthrough, value) => value,
+                    (
+                        ButtonMappingType::HysteresisThreshold(threshold),
+                        ButtonValue::Scalar(value),
+                    ) => {
+                        let state = self
+                            .hysteresis_states
+                            .entry(source_id)
+                            .or_default()
+                            .entry(mapping.destination)
+                            .or_default();
+
+                        if *state && value < threshold.value - threshold.deviation {
+                            *state = false;
+                        } else if !*state && value > threshold.value + threshold.deviation {
+                            *state = true;
+                        } else {
+                            // No change needed
+                            continue;
+                        }
+
+                        ButtonValue::Binary(*state)
+                    }
+                    (ButtonMappingType::BinaryToScalar(levels), ButtonValue::Binary(value)) => {
+                        if value {
+                            ButtonValue::Scalar(levels.on)
+                        } else {
+                            ButtonValue::Scalar(levels.off)
+                        }
+                    }
+                    (ButtonMappingType::Remap(range), ButtonValue::Scalar(value)) => {
+                        let value = (value - range.min) / (range.max - range.min);
+                        ButtonValue::Scalar(value.clamp(0.0, 1.0))
+                    }
+                    _ => {
+                        error!("Failed to map button!");
+                        continue;
+                    }
+                };
+
+                for source_id in &mapping.binary_conditions {
+                    if !self
+                        .binary_source_states
+                        .get(source_id)
+                        .copied()
+                        .unwrap_or(false)
+                    {
+                        continue 'mapping;
+                    }
+                }
+
+                (self.set_button)(mapping.destination, destination_value);
+            }
+        } else if !self.macros.uses_input(source_id) {
+            info!("Received button not mapped: {}", button_path(source_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    const A: &str = RIGHT_A_CLICK_PATH;
+    const B: &str = RIGHT_B_CLICK_PATH;
+    const X: &str = LEFT_X_CLICK_PATH;
+    const Y: &str = LEFT_Y_CLICK_PATH;
+    const STICK_X: &str = RIGHT_THUMBSTICK_X_PATH;
+    const STICK_Y: &str = RIGHT_THUMBSTICK_Y_PATH;
+    const TRIGGER: &str = RIGHT_TRIGGER_VALUE_PATH;
+
+    const TICK_MS: u64 = 10;
+
+    fn binary(ms: u64, path: &'static str, value: bool) -> (u64, &'static str, ButtonValue) {
+        (ms, path, ButtonValue::Binary(value))
+    }
+
+    fn scalar(ms: u64, path: &'static str, value: f32) -> (u64, &'static str, ButtonValue) {
+        (ms, path, ButtonValue::Scalar(value))
+    }
+
+    // Feeds the inputs at their time in milliseconds, updating the macros every tick, and returns
+    // the outputs with their time
+    fn run(
+        manager: &mut ButtonMappingManager,
+        inputs: &[(u64, &'static str, ButtonValue)],
+    ) -> Vec<(u64, u64, f32)> {
+        let (sender, receiver) = mpsc::channel();
+        let start = Instant::now();
+        let end_ms = inputs.iter().map(|(ms, ..)| ms).max().unwrap_or(&0) + 500;
+
+        for ms in (0..=end_ms).step_by(TICK_MS as usize) {
+            let now = start + Duration::from_millis(ms);
+
+            let sender = sender.clone();
+            manager.set_button =
+                Box::new(move |id, value| sender.send((ms, id, macros::to_scalar(value))).unwrap());
+
+            for (_, path, value) in inputs.iter().filter(|(t, ..)| *t == ms) {
+                manager.report_button_at(hash_string(path), *value, now);
+            }
+            manager.update_macros_at(now);
+        }
+
+        receiver.try_iter().collect()
+    }
+
+    #[test]
+    fn test_macros() {
+        #[allow(clippy::type_complexity)]
+        let cases: &[(&str, &[(u64, &str, ButtonValue)], &[(u64, &str, f32)])] = &[
+            (
+                "/user/hand/right/input/menu/click = /user/hand/left/input/x/click & /user/hand/left/input/y/click",
+                &[binary(0, X, true), binary(50, Y, true), binary(100, X, false)],
+                &[(50, RIGHT_MENU_CLICK_PATH, 1.0), (100, RIGHT_MENU_CLICK_PATH, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/b/click = long_press(/user/hand/right/input/a/click, 500)",
+                &[
+                    binary(0, A, true),
+                    binary(300, A, false),
+                    binary(400, A, true),
+                    binary(1100, A, false),
+                ],
+                &[(900, B, 1.0), (1100, B, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/b/click = short_press(/user/hand/right/input/a/click, 300)",
+                &[
+                    binary(0, A, true),
+                    binary(200, A, false),
+                    binary(400, A, true),
+                    binary(1000, A, false),
+                ],
+                &[(200, B, 1.0), (300, B, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/b/click = double_tap(/user/hand/right/input/a/click, 300)",
+                &[
+                    binary(0, A, true),
+                    binary(50, A, false),
+                    binary(200, A, true),
+                    binary(250, A, false),
+                    binary(1000, A, true),
+                    binary(1050, A, false),
+                    binary(1400, A, true),
+                ],
+                &[(200, B, 1.0), (300, B, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/b/click = toggle(/user/hand/right/input/a/click)",
+                &[
+                    binary(0, A, true),
+                    binary(50, A, false),
+                    binary(100, A, true),
+                    binary(150, A, false),
+                ],
+                &[(0, B, 1.0), (100, B, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/b/click = sequence(300, /user/hand/left/input/x/click, /user/hand/left/input/x/click, /user/hand/left/input/y/click)",
+                &[
+                    // Too slow
+                    binary(0, X, true),
+                    binary(50, X, false),
+                    binary(400, X, true),
+                    binary(450, X, false),
+                    binary(800, Y, true),
+                    binary(850, Y, false),
+                    // Wrong order
+                    binary(1000, Y, true),
+                    binary(1050, Y, false),
+                    binary(1100, X, true),
+                    binary(1150, X, false),
+                    binary(1200, Y, true),
+                    binary(1250, Y, false),
+                    // Correct
+                    binary(1300, X, true),
+                    binary(1350, X, false),
+                    binary(1400, X, true),
+                    binary(1450, X, false),
+                    binary(1500, Y, true),
+                ],
+                &[(1500, B, 1.0), (1600, B, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/a/click = dpad_right(/user/hand/right/input/thumbstick/x, /user/hand/right/input/thumbstick/y, 0.5)",
+                &[
+                    scalar(0, STICK_X, 0.3),
+                    scalar(100, STICK_X, 0.8),
+                    scalar(200, STICK_Y, 0.6),
+                    scalar(300, STICK_Y, 0.9),
+                    scalar(400, STICK_X, 0.0),
+                    scalar(400, STICK_Y, 0.0),
+                ],
+                &[(100, A, 1.0), (300, A, 0.0)],
+            ),
+            (
+                "/user/hand/right/input/trigger/value = /user/hand/right/input/a/click | /user/hand/right/input/squeeze/value > 0.8",
+                &[
+                    binary(0, A, true),
+                    binary(100, A, false),
+                    scalar(200, RIGHT_SQUEEZE_VALUE_PATH, 0.9),
+                ],
+                &[(0, TRIGGER, 1.0), (100, TRIGGER, 0.0), (200, TRIGGER, 1.0)],
+            ),
+        ];
+
+        for (macro_source, inputs, expected) in cases {
+            let mut manager = ButtonMappingManager::new_manual(&[]);
+            manager.set_macros(&[macro_source.to_string()]);
+
+            let outputs = run(&mut manager, inputs);
+            let expected = expected
+                .iter()
+                .map(|(ms, path, value)| (*ms, hash_string(path), *value))
+                .collect::<Vec<_>>();
+            assert_eq!(outputs, expected, "{macro_source}");
+        }
+    }
+
+    #[test]
+    fn test_macros_override_mappings() {
+        let mut manager = ButtonMappingManager::new_manual(&[
+            (
+                A.into(),
+                vec![ButtonBindingTarget {
+                    destination: A.into(),
+                    mapping_type: ButtonMappingType::Passthrough,
+                    binary_conditions: vec![],
+                }],
+            ),
+            (
+                B.into(),
+                vec![ButtonBindingTarget {
+                    destination: B.into(),
+                    mapping_type: ButtonMappingType::Passthrough,
+                    binary_conditions: vec![],
+                }],
+            ),
+        ]);
+        manager.set_macros(&[format!("{A} = toggle({B})")]);
+
+        let outputs = run(
+            &mut manager,
+            &[
+                binary(0, B, true),
+                binary(100, B, false),
+                binary(200, A, true),
+            ],
+        );
+        assert_eq!(
+            outputs,
+            vec![
+                (0, hash_string(A), 1.0),
+                (0, hash_string(B), 1.0),
+                (100, hash_string(B), 0.0),
+            ]
+        );
+
+        // Hot reload, the destinations of the previous macros are released and the previous source
+        // state is kept
+        let (sender, receiver) = mpsc::channel();
+        manager.set_button =
+            Box::new(move |id, value| sender.send((id, macros::to_scalar(value))).unwrap());
+        manager.set_macros(&[]);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![(hash_string(A), 0.0)]
+        );
+
+        let outputs = run(&mut manager, &[binary(0, A, false)]);
+        assert_eq!(outputs, vec![(0, hash_string(A), 0.0)]);
+    }
+
+    #[test]
+    fn test_added_mappings_conditions() {
+        const GESTURE: &str = "/alvr/custom_hand_gesture/pinch";
+
+        let mut manager = ButtonMappingManager::new_manual(&[]);
+        manager.add_manual_mappings(&[(
+            GESTURE.into(),
+            vec![ButtonBindingTarget {
+                destination: B.into(),
+                mapping_type: ButtonMappingType::Passthrough,
+                binary_conditions: vec![X.into()],
+            }],
+        )]);
+
+        let outputs = run(
+            &mut manager,
+            &[
+                binary(0, GESTURE, true),
+                binary(50, GESTURE, false),
+                binary(100, X, true),
+                binary(150, GESTURE, true),
+                binary(200, GESTURE, false),
+            ],
+        );
+        assert_eq!(
+            outputs,
+            vec![(150, hash_string(B), 1.0), (200, hash_string(B), 0.0)]
+        );
+    }
+
+    fn automatic_destinations(
+        source_profile: u64,
+        destination_profile: u64,
+    ) -> HashMap<u64, Vec<u64>> {
+        let profiles = &*BUILTIN_INTERACTION_PROFILES;
+        let config = AutomaticButtonMappingConfig {
+            click_threshold: HysteresisThreshold {
+                value: 0.5,
+                deviation: 0.05,
+            },
+            touch_threshold: HysteresisThreshold {
+                value: 0.1,
+                deviation: 0.05,
+            },
+            force_threshold: 0.8,
+        };
+
+        automatic_bindings(
+            profiles,
+            &profiles.profiles[&source_profile].button_set,
+            &profiles.profiles[&destination_profile].button_set,
+            &config,
+        )
+        .into_iter()
+        .map(|(source, targets)| {
+            (
+                source,
+                targets.iter().map(|target| target.destination).collect(),
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_builtin_interaction_profiles() {
+        let profiles = &*BUILTIN_INTERACTION_PROFILES;
+        for path in [
+            "/interaction_profiles/facebook/touch_controller_pro",
+            "/interaction_profiles/khr/simple_controller",
+        ] {
+            assert!(profiles.profiles.contains_key(&hash_string(path)));
+        }
+
+        let info = &profiles.buttons[&*LEFT_THUMBSTICK_X_ID];
+        assert_eq!(info.button_type, ButtonType::Scalar);
+        assert_eq!(info.device_id, *LEFT_HAND_ID);
+        assert_eq!(
+            profiles.buttons[&*RIGHT_A_TOUCH_ID].button_type,
+            ButtonType::Binary
+        );
+
+        // Buttons of the base table that no controller profile exposes
+        for id in [
+            *HEAD_ENTER_CLICK_ID,
+            *LEFT_SQUEEZE_TOUCH_ID,
+            *RIGHT_SQUEEZE_TOUCH_ID,
+            *LEFT_BACK_CLICK_ID,
+            *RIGHT_BACK_CLICK_ID,
+        ] {
+            assert_eq!(profiles.buttons[&id].button_type, ButtonType::Binary);
+        }
+        assert_eq!(profiles.buttons[&*HEAD_ENTER_CLICK_ID].device_id, *HEAD_ID);
+    }
+
+    #[test]
+    fn test_user_interaction_profile() {
+        let mut profiles = BUILTIN_INTERACTION_PROFILES.clone();
+
+        // Same path of a built-in button but different type
+        assert!(profiles
+            .add_from_json(
+                r#"{
+                    "name": "Invalid",
+                    "path": "/interaction_profiles/test/invalid",
+                    "buttons": { "/user/hand/right/input/a/click": "scalar" }
+                }"#
+            )
+            .is_err());
+        assert!(profiles
+            .add_from_json(
+                r#"{
+                    "name": "Invalid",
+                    "path": "/interaction_profiles/test/invalid",
+                    "buttons": { "/user/hand/right/a/click": "binary" }
+                }"#
+            )
+            .is_err());
+
+        let id = profiles
+            .add_from_json(
+                r#"{
+                    "name": "Replaced Vive",
+                    "path": "/interaction_profiles/htc/vive_controller",
+                    "buttons": {
+                        "/user/hand/right/input/trigger/value": "scalar",
+                        "/user/hand/right/input/dial/click": "binary"
+                    }
+                }"#,
+            )
+            .unwrap();
+        assert_eq!(id, *VIVE_CONTROLLER_PROFILE_ID);
+        assert_eq!(profiles.profiles[&id].button_set.len(), 2);
+        assert_eq!(
+            profiles.buttons[&hash_string("/user/hand/right/input/dial/click")].device_id,
+            *RIGHT_HAND_ID
+        );
+    }
+
+    #[test]
+    fn test_automatic_bindings() {
+        let to_vive =
+            automatic_destinations(*QUEST_CONTROLLER_PROFILE_ID, *VIVE_CONTROLLER_PROFILE_ID);
+        assert_eq!(to_vive[&*LEFT_X_CLICK_ID], vec![*LEFT_TRACKPAD_CLICK_ID]);
+        assert_eq!(to_vive[&*RIGHT_A_TOUCH_ID], vec![*RIGHT_TRACKPAD_TOUCH_ID]);
+        assert_eq!(to_vive[&*LEFT_THUMBSTICK_X_ID], vec![*LEFT_TRACKPAD_X_ID]);
+        assert_eq!(
+            to_vive[&*RIGHT_THUMBREST_TOUCH_ID],
+            vec![*RIGHT_TRACKPAD_TOUCH_ID]
+        );
+        assert_eq!(
+            to_vive[&*RIGHT_TRIGGER_VALUE_ID],
+            vec![*RIGHT_TRIGGER_CLICK_ID, *RIGHT_TRIGGER_VALUE_ID]
+        );
+        assert!(!to_vive.contains_key(&*LEFT_Y_CLICK_ID));
+
+        let to_index =
+            automatic_destinations(*QUEST_CONTROLLER_PROFILE_ID, *INDEX_CONTROLLER_PROFILE_ID);
+        assert_eq!(to_index[&*LEFT_X_CLICK_ID], vec![*LEFT_A_CLICK_ID]);
+        assert_eq!(to_index[&*LEFT_Y_TOUCH_ID], vec![*LEFT_B_TOUCH_ID]);
+        assert_eq!(
+            to_index[&*LEFT_MENU_CLICK_ID],
+            vec![*LEFT_SYSTEM_CLICK_ID, *LEFT_SYSTEM_TOUCH_ID]
+        );
+        assert_eq!(
+            to_index[&*RIGHT_SQUEEZE_VALUE_ID],
+            vec![*RIGHT_SQUEEZE_FORCE_ID, *RIGHT_SQUEEZE_VALUE_ID]
+        );
+        assert_eq!(
+            to_index[&*RIGHT_THUMBSTICK_Y_ID],
+            vec![*RIGHT_THUMBSTICK_Y_ID]
+        );
+
+        let generic = hash_string("/interaction_profiles/khr/simple_controller");
+        let from_generic = automatic_destinations(generic, *INDEX_CONTROLLER_PROFILE_ID);
+        assert_eq!(
+            from_generic[&hash_string("/user/hand/right/input/select/click")],
+            vec![
+                *RIGHT_TRIGGER_CLICK_ID,
+                *RIGHT_TRIGGER_TOUCH_ID,
+                *RIGHT_TRIGGER_VALUE_ID
+            ]
+        );
+    }
+}
@@ -1,15 +1,22 @@
 mod bitrate;
 mod c_api;
 mod connection;
+mod custom_hand_gestures;
+mod external_tracking;
 mod face_tracking;
 mod hand_gestures;
+mod hand_pointer;
 mod haptics;
 mod input_mapping;
 mod logging_backend;
+mod metrics;
 mod openvr_props;
+mod rest_api;
 mod sockets;
 mod statistics;
+mod statistics_log;
 mod tracking;
+mod tracking_recording;
 mod web_server;
 
 #[allow(
@@ -235,6 +242,8 @@ fn init() {
 
     SERVER_DATA_MANAGER.write().clean_client_list();
 
+    input_mapping::load_user_interaction_profiles(&FILESYSTEM_LAYOUT.interaction_profiles_dir());
+
     if let Some(runtime) = WEBSERVER_RUNTIME.lock().as_mut() {
         runtime.spawn(async { alvr_common::show_err(web_server::web_server(events_sender).await) });
     }
@@ -381,9 +390,13 @@ pub unsafe extern "C" fn HmdDriverFactory(
         }
 
         let server_data_lock = SERVER_DATA_MANAGER.read();
-        BITRATE_MANAGER
-            .lock()
-            .report_frame_present(&server_data_lock.settings().video.bitrate.adapt_to_framerate);
+        BITRATE_MANAGER.lock().report_frame_present(
+            &server_data_lock
+                .connected_client_settings()
+                .video
+                .bitrate
+                .adapt_to_framerate,
+        );
     }
 
     extern "C" fn report_composed(timestamp_ns: u64, offset_ns: u64) {
@@ -400,7 +413,7 @@ pub unsafe extern "C" fn HmdDriverFactory(
             let server_data_lock = SERVER_DATA_MANAGER.read();
             BITRATE_MANAGER
                 .lock()
-                .get_encoder_params(&server_data_lock.settings().video.bitrate)
+                .get_encoder_params(&server_data_lock.connected_client_settings().video.bitrate)
         };
 
         if let Some(stats) = stats {
@@ -415,7 +428,7 @@ pub unsafe extern "C" fn HmdDriverFactory(
     extern "C" fn wait_for_vsync() {
         if SERVER_DATA_MANAGER
             .read()
-            .settings()
+            .connected_client_settings()
             .video
             .optimize_game_render_latency
         {
@@ -0,0 +1,184 @@
+// Minimal encoder for the Prometheus text exposition format:
+// https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+
+use std::fmt::Write;
+
+pub const LATENCY_BUCKETS_S: &[f64] = &[
+    0.001, 0.002, 0.005, 0.01, 0.015, 0.02, 0.03, 0.05, 0.075, 0.1, 0.15, 0.2, 0.5,
+];
+
+#[derive(Clone)]
+pub struct Histogram {
+    upper_bounds: &'static [f64],
+    // Not cumulative, one more than upper_bounds for the +Inf bucket
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(upper_bounds: &'static [f64]) -> Self {
+        Self {
+            upper_bounds,
+            bucket_counts: vec![0; upper_bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let index = self
+            .upper_bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.upper_bounds.len());
+
+        self.bucket_counts[index] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let labels = labels
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("{{{labels}}}")
+}
+
+#[derive(Default)]
+pub struct MetricsWriter {
+    buffer: String,
+}
+
+impl MetricsWriter {
+    fn header(&mut self, name: &str, help: &str, metric_type: &str) {
+        writeln!(self.buffer, "# HELP {name} {help}").ok();
+        writeln!(self.buffer, "# TYPE {name} {metric_type}").ok();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        writeln!(self.buffer, "{name}{} {value}", format_labels(labels)).ok();
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, help, "counter");
+        self.sample(name, &[], value);
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, help, "gauge");
+        self.sample(name, &[], value);
+    }
+
+    // Gauge with one sample per label value
+    pub fn labeled_gauge<'a>(
+        &mut self,
+        name: &str,
+        help: &str,
+        label_name: &str,
+        samples: impl IntoIterator<Item = (&'a str, f64)>,
+    ) {
+        self.header(name, help, "gauge");
+        for (label_value, value) in samples {
+            self.sample(name, &[(label_name, label_value)], value);
+        }
+    }
+
+    // Histogram with one series per label value
+    pub fn labeled_histogram<'a>(
+        &mut self,
+        name: &str,
+        help: &str,
+        label_name: &str,
+        histograms: impl IntoIterator<Item = (&'a str, &'a Histogram)>,
+    ) {
+        self.header(name, help, "histogram");
+
+        for (label_value, histogram) in histograms {
+            let mut cumulative_count = 0;
+            for (bound, count) in histogram
+                .upper_bounds
+                .iter()
+                .map(|bound| bound.to_string())
+                .chain(["+Inf".into()])
+                .zip(&histogram.bucket_counts)
+            {
+                cumulative_count += count;
+                self.sample(
+                    &format!("{name}_bucket"),
+                    &[(label_name, label_value), ("le", &bound)],
+                    cumulative_count as f64,
+                );
+            }
+
+            let labels = [(label_name, label_value)];
+            self.sample(&format!("{name}_sum"), &labels, histogram.sum);
+            self.sample(&format!("{name}_count"), &labels, histogram.count as f64);
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_format() {
+        let mut histogram = Histogram::new(&[0.25, 1.0]);
+        histogram.observe(0.125);
+        histogram.observe(0.5);
+        histogram.observe(2.0);
+
+        let mut writer = MetricsWriter::default();
+        writer.counter("alvr_packets_lost_total", "Lost packets", 3.0);
+        writer.labeled_gauge(
+            "alvr_battery_ratio",
+            "Battery",
+            "device",
+            [("/user/\"head\"", 0.5)],
+        );
+        writer.labeled_histogram(
+            "alvr_latency_seconds",
+            "Latency",
+            "stage",
+            [("network", &histogram)],
+        );
+
+        assert_eq!(
+            writer.finish(),
+            r#"# HELP alvr_packets_lost_total Lost packets
+# TYPE alvr_packets_lost_total counter
+alvr_packets_lost_total 3
+# HELP alvr_battery_ratio Battery
+# TYPE alvr_battery_ratio gauge
+alvr_battery_ratio{device="/user/\"head\""} 0.5
+# HELP alvr_latency_seconds Latency
+# TYPE alvr_latency_seconds histogram
+alvr_latency_seconds_bucket{stage="network",le="0.25"} 1
+alvr_latency_seconds_bucket{stage="network",le="1"} 2
+alvr_latency_seconds_bucket{stage="network",le="+Inf"} 3
+alvr_latency_seconds_sum{stage="network"} 2.625
+alvr_latency_seconds_count{stage="network"} 3
+"#
+        );
+    }
+}
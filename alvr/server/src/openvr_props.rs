@@ -2,10 +2,11 @@
 // todo: fill out more properties for headset and controllers
 // todo: add more emulation modes
 
-use crate::{FfiOpenvrProperty, FfiOpenvrPropertyValue, SERVER_DATA_MANAGER};
+use crate::{tracking, FfiOpenvrProperty, FfiOpenvrPropertyValue, SERVER_DATA_MANAGER};
 use alvr_common::{info, settings_schema::Switch, HEAD_ID, LEFT_HAND_ID, RIGHT_HAND_ID};
 use alvr_session::{
-    ControllersEmulationMode, HeadsetEmulationMode, OpenvrPropValue, OpenvrProperty,
+    BodyTrackerConfig, BodyTrackerRole, ControllersEmulationMode, HeadsetEmulationMode,
+    OpenvrPropValue, OpenvrProperty, Settings,
 };
 use std::{
     ffi::{c_char, CString},
@@ -53,9 +54,111 @@ pub fn to_ffi_openvr_prop(prop: OpenvrProperty) -> FfiOpenvrProperty {
     FfiOpenvrProperty { key, type_, value }
 }
 
+// Properties shared by all emulated Vive trackers
+fn set_vive_tracker_props(set_prop: impl Fn(OpenvrProperty)) {
+    use OpenvrProperty::*;
+
+    set_prop(TrackingSystemName("lighthouse".into()));
+    set_prop(RenderModelName("{htc}vr_tracker_vive_1_0".into()));
+    set_prop(InputProfilePath(
+        "{htc}/input/vive_tracker_profile.json".into(),
+    ));
+
+    // All of these property values were dumped from real a vive tracker via
+    // https://github.com/SDraw/openvr_dumper and were copied from
+    // https://github.com/SDraw/driver_kinectV2
+    set_prop(ResourceRoot("htc".into()));
+    set_prop(WillDriftInYaw(false));
+    set_prop(TrackingFirmwareVersion(
+        "1541800000 RUNNER-WATCHMAN$runner-watchman@runner-watchman 2018-01-01 FPGA 512(2.56/0/0) BL 0 VRC 1541800000 Radio 1518800000".into(),
+    ));
+    set_prop(HardwareRevisionString(
+        "product 128 rev 2.5.6 lot 2000/0/0 0".into(),
+    ));
+    set_prop(ConnectedWirelessDongle("D0000BE000".into()));
+    set_prop(DeviceIsWireless(true));
+    set_prop(DeviceIsCharging(false));
+    set_prop(ControllerHandSelectionPriority(-1));
+    // vr::HmdMatrix34_t l_transform = {
+    //     {{-1.f, 0.f, 0.f, 0.f}, {0.f, 0.f, -1.f, 0.f}, {0.f, -1.f, 0.f, 0.f}}};
+    // vr_properties->SetProperty(this->prop_container,
+    //                            vr::Prop_StatusDisplayTransform_Matrix34,
+    //                            &l_transform,
+    //                            sizeof(vr::HmdMatrix34_t),
+    //                            vr::k_unHmdMatrix34PropertyTag);
+    set_prop(FirmwareUpdateAvailable(false));
+    set_prop(FirmwareManualUpdate(false));
+    set_prop(FirmwareManualUpdateURL(
+        "https://developer.valvesoftware.com/wiki/SteamVR/HowTo_Update_Firmware".into(),
+    ));
+    set_prop(HardwareRevisionUint64(2214720000));
+    set_prop(FirmwareVersion(1541800000));
+    set_prop(FPGAVersion(512));
+    set_prop(VRCVersion(1514800000));
+    set_prop(RadioVersion(1518800000));
+    set_prop(DongleVersion(8933539758));
+    set_prop(DeviceCanPowerOff(true));
+    // vr_properties->SetStringProperty(this->prop_container,
+    //                                  vr::Prop_Firmware_ProgrammingTarget_String,
+    //                                  GetSerialNumber().c_str());
+    set_prop(FirmwareForceUpdateRequired(false));
+    set_prop(FirmwareRemindUpdate(false));
+    set_prop(HasDisplayComponent(false));
+    set_prop(HasCameraComponent(false));
+    set_prop(HasDriverDirectModeComponent(false));
+    set_prop(HasVirtualDisplayComponent(false));
+
+    // icons
+    set_prop(NamedIconPathDeviceOff(
+        "{htc}/icons/tracker_status_off.png".into(),
+    ));
+    set_prop(NamedIconPathDeviceSearching(
+        "{htc}/icons/tracker_status_searching.gif".into(),
+    ));
+    set_prop(NamedIconPathDeviceSearchingAlert(
+        "{htc}/icons/tracker_status_searching_alert.gif".into(),
+    ));
+    set_prop(NamedIconPathDeviceReady(
+        "{htc}/icons/tracker_status_ready.png".into(),
+    ));
+    set_prop(NamedIconPathDeviceReadyAlert(
+        "{htc}/icons/tracker_status_ready_alert.png".into(),
+    ));
+    set_prop(NamedIconPathDeviceNotReady(
+        "{htc}/icons/tracker_status_error.png".into(),
+    ));
+    set_prop(NamedIconPathDeviceStandby(
+        "{htc}/icons/tracker_status_standby.png".into(),
+    ));
+    set_prop(NamedIconPathDeviceAlertLow(
+        "{htc}/icons/tracker_status_ready_low.png".into(),
+    ));
+}
+
+fn steamvr_tracker_role(role: BodyTrackerRole) -> &'static str {
+    match role {
+        BodyTrackerRole::Waist => "waist",
+        BodyTrackerRole::Chest => "chest",
+        BodyTrackerRole::LeftFoot => "left_foot",
+        BodyTrackerRole::RightFoot => "right_foot",
+        BodyTrackerRole::LeftKnee => "left_knee",
+        BodyTrackerRole::RightKnee => "right_knee",
+        BodyTrackerRole::LeftElbow => "left_elbow",
+        BodyTrackerRole::RightElbow => "right_elbow",
+    }
+}
+
+fn find_body_tracker(settings: &Settings, device_id: u64) -> Option<&BodyTrackerConfig> {
+    settings
+        .headset
+        .body_trackers
+        .iter()
+        .find(|tracker| tracking::body_tracker_id(tracker.role) == device_id)
+}
+
 fn serial_number(device_id: u64) -> String {
     let data_manager_lock = SERVER_DATA_MANAGER.read();
-    let settings = data_manager_lock.settings();
+    let settings = data_manager_lock.connected_client_settings();
 
     if device_id == *HEAD_ID {
         match &settings.headset.emulation_mode {
@@ -84,6 +187,8 @@ fn serial_number(device_id: u64) -> String {
         } else {
             "Unknown".into()
         }
+    } else if let Some(tracker) = find_body_tracker(settings, device_id) {
+        format!("ALVR Tracker {}", steamvr_tracker_role(tracker.role))
     } else {
         "Unknown".into()
     }
@@ -109,7 +214,7 @@ pub extern "C" fn set_device_openvr_props(device_id: u64) {
     use OpenvrProperty::*;
 
     let data_manager_lock = SERVER_DATA_MANAGER.read();
-    let settings = data_manager_lock.settings();
+    let settings = data_manager_lock.connected_client_settings();
 
     if device_id == *HEAD_ID {
         fn set_prop(prop: OpenvrProperty) {
@@ -459,8 +564,6 @@ pub extern "C" fn set_device_openvr_props(device_id: u64) {
                     set_prop(InputProfilePath("{oculus}/input/touch_profile.json".into()));
                 }
                 ControllersEmulationMode::ViveTracker => {
-                    set_prop(TrackingSystemName("lighthouse".into()));
-                    set_prop(RenderModelName("{htc}vr_tracker_vive_1_0".into()));
                     if device_id == *LEFT_HAND_ID {
                         set_prop(ModelNumber("Vive Tracker Pro MV (Left Controller)".into()));
                         set_prop(RegisteredDeviceType("ALVR/tracker/left_foot".into()));
@@ -470,80 +573,8 @@ pub extern "C" fn set_device_openvr_props(device_id: u64) {
                         set_prop(RegisteredDeviceType("ALVR/tracker/right_foot".into()));
                         set_prop(ControllerType("vive_tracker_right_foot".into()));
                     }
-                    set_prop(InputProfilePath(
-                        "{htc}/input/vive_tracker_profile.json".into(),
-                    ));
 
-                    // All of these property values were dumped from real a vive tracker via
-                    // https://github.com/SDraw/openvr_dumper and were copied from
-                    // https://github.com/SDraw/driver_kinectV2
-                    set_prop(ResourceRoot("htc".into()));
-                    set_prop(WillDriftInYaw(false));
-                    set_prop(TrackingFirmwareVersion(
-                        "1541800000 RUNNER-WATCHMAN$runner-watchman@runner-watchman 2018-01-01 FPGA 512(2.56/0/0) BL 0 VRC 1541800000 Radio 1518800000".into(),
-                    ));
-                    set_prop(HardwareRevisionString(
-                        "product 128 rev 2.5.6 lot 2000/0/0 0".into(),
-                    ));
-                    set_prop(ConnectedWirelessDongle("D0000BE000".into()));
-                    set_prop(DeviceIsWireless(true));
-                    set_prop(DeviceIsCharging(false));
-                    set_prop(ControllerHandSelectionPriority(-1));
-                    // vr::HmdMatrix34_t l_transform = {
-                    //     {{-1.f, 0.f, 0.f, 0.f}, {0.f, 0.f, -1.f, 0.f}, {0.f, -1.f, 0.f, 0.f}}};
-                    // vr_properties->SetProperty(this->prop_container,
-                    //                            vr::Prop_StatusDisplayTransform_Matrix34,
-                    //                            &l_transform,
-                    //                            sizeof(vr::HmdMatrix34_t),
-                    //                            vr::k_unHmdMatrix34PropertyTag);
-                    set_prop(FirmwareUpdateAvailable(false));
-                    set_prop(FirmwareManualUpdate(false));
-                    set_prop(FirmwareManualUpdateURL(
-                        "https://developer.valvesoftware.com/wiki/SteamVR/HowTo_Update_Firmware"
-                            .into(),
-                    ));
-                    set_prop(HardwareRevisionUint64(2214720000));
-                    set_prop(FirmwareVersion(1541800000));
-                    set_prop(FPGAVersion(512));
-                    set_prop(VRCVersion(1514800000));
-                    set_prop(RadioVersion(1518800000));
-                    set_prop(DongleVersion(8933539758));
-                    set_prop(DeviceCanPowerOff(true));
-                    // vr_properties->SetStringProperty(this->prop_container,
-                    //                                  vr::Prop_Firmware_ProgrammingTarget_String,
-                    //                                  GetSerialNumber().c_str());
-                    set_prop(FirmwareForceUpdateRequired(false));
-                    set_prop(FirmwareRemindUpdate(false));
-                    set_prop(HasDisplayComponent(false));
-                    set_prop(HasCameraComponent(false));
-                    set_prop(HasDriverDirectModeComponent(false));
-                    set_prop(HasVirtualDisplayComponent(false));
-
-                    // icons
-                    set_prop(NamedIconPathDeviceOff(
-                        "{htc}/icons/tracker_status_off.png".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceSearching(
-                        "{htc}/icons/tracker_status_searching.gif".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceSearchingAlert(
-                        "{htc}/icons/tracker_status_searching_alert.gif".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceReady(
-                        "{htc}/icons/tracker_status_ready.png".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceReadyAlert(
-                        "{htc}/icons/tracker_status_ready_alert.png".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceNotReady(
-                        "{htc}/icons/tracker_status_error.png".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceStandby(
-                        "{htc}/icons/tracker_status_standby.png".into(),
-                    ));
-                    set_prop(NamedIconPathDeviceAlertLow(
-                        "{htc}/icons/tracker_status_ready_low.png".into(),
-                    ));
+                    set_vive_tracker_props(set_prop);
                 }
                 ControllersEmulationMode::Custom { .. } => todo!(),
             }
@@ -574,5 +605,69 @@ pub extern "C" fn set_device_openvr_props(device_id: u64) {
                 set_prop(prop.clone());
             }
         }
+    } else if let Some(tracker) = find_body_tracker(settings, device_id) {
+        let role = steamvr_tracker_role(tracker.role);
+
+        let set_prop = |prop| {
+            info!("Setting {role} tracker OpenVR prop: {prop:?}");
+            unsafe {
+                crate::SetOpenvrProperty(device_id, to_ffi_openvr_prop(prop));
+            }
+        };
+
+        set_prop(ManufacturerName("HTC".into()));
+        set_prop(ModelNumber(format!("Vive Tracker Pro MV ({role})")));
+        set_prop(RegisteredDeviceType(format!("ALVR/tracker/{role}")));
+        set_prop(ControllerType(format!("vive_tracker_{role}")));
+        set_vive_tracker_props(set_prop);
+
+        set_prop(SerialNumber(serial_number(device_id)));
+        set_prop(DeviceProvidesBatteryStatus(false));
+
+        // TrackedControllerRole_Invalid. The role is assigned through the controller type
+        set_prop(ControllerRoleHint(0));
+
+        for prop in &tracker.extra_openvr_props {
+            set_prop(prop.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::DEVICE_ID_TO_PATH;
+    use std::collections::HashSet;
+
+    const BODY_TRACKER_ROLES: [BodyTrackerRole; 8] = [
+        BodyTrackerRole::Waist,
+        BodyTrackerRole::Chest,
+        BodyTrackerRole::LeftFoot,
+        BodyTrackerRole::RightFoot,
+        BodyTrackerRole::LeftKnee,
+        BodyTrackerRole::RightKnee,
+        BodyTrackerRole::LeftElbow,
+        BodyTrackerRole::RightElbow,
+    ];
+
+    #[test]
+    fn test_body_tracker_roles() {
+        let mut ids = HashSet::from([*HEAD_ID, *LEFT_HAND_ID, *RIGHT_HAND_ID]);
+
+        for role in BODY_TRACKER_ROLES {
+            let id = tracking::body_tracker_id(role);
+            let path = tracking::body_tracker_path(role);
+
+            assert_eq!(id, alvr_common::hash_string(path));
+            assert!(ids.insert(id), "{path} has a duplicated ID");
+            assert_eq!(DEVICE_ID_TO_PATH.get(&id), Some(&path));
+            assert_eq!(
+                path,
+                format!(
+                    "/user/vive_tracker_htcx/role/{}",
+                    steamvr_tracker_role(role)
+                )
+            );
+        }
     }
 }
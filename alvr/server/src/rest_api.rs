@@ -0,0 +1,452 @@
+// Resource oriented HTTP API, meant for scripting servers without the dashboard. Unlike
+// /api/dashboard-request, every call replies synchronously: the result is returned as JSON in the
+// response body and errors are returned as {"error": "<message>"} with a 4xx status code.
+// The API is described by resources/openapi.json, which is served at /api/v1/openapi.json.
+
+use crate::{web_server, SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_RECORDING_FILE};
+use alvr_common::anyhow::Result;
+use alvr_packets::{ClientListAction, PathSegment, PathValuePair};
+use alvr_session::{ClientConnectionConfig, SessionConfig};
+use bytes::Buf;
+use hyper::{header::CONTENT_TYPE, Body, Method, Request, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json as json;
+use std::net::IpAddr;
+
+pub const API_PREFIX: &str = "/api/v1/";
+
+const OPENAPI_DESCRIPTION: &str = include_str!("../resources/openapi.json");
+
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+}
+
+type ApiResult = std::result::Result<(StatusCode, json::Value), ApiError>;
+
+fn ok(value: impl Serialize) -> ApiResult {
+    Ok((StatusCode::OK, json::to_value(value).unwrap_or_default()))
+}
+
+fn json_response(status: StatusCode, body: String) -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())?)
+}
+
+async fn parse_body<T: DeserializeOwned>(request: Request<Body>) -> Result<T, ApiError> {
+    let body = hyper::body::aggregate(request)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Failed to read request body: {e}")))?;
+
+    json::from_reader(body.reader())
+        .map_err(|e| ApiError::bad_request(format!("Invalid request body: {e}")))
+}
+
+// Distinguishes a missing field (None) from a field explicitly set to null (Some(None))
+fn nullable<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewClient {
+    hostname: String,
+    display_name: Option<String>,
+    #[serde(default)]
+    manual_ips: Vec<IpAddr>,
+}
+
+// Only the specified fields are updated
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientUpdate {
+    display_name: Option<String>,
+    manual_ips: Option<Vec<IpAddr>>,
+    trusted: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
+    settings_profile: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    settings_override: Option<Option<json::Value>>,
+}
+
+#[derive(Debug)]
+enum Route {
+    OpenApi,
+    GetSession,
+    SetSession,
+    GetSetting(Vec<String>),
+    SetSetting(Vec<String>),
+    GetClients,
+    AddClient,
+    GetClient(String),
+    UpdateClient(String),
+    RemoveClient(String),
+    GetRecording,
+    StartRecording,
+    StopRecording,
+    GetStatistics,
+}
+
+// Path segments are percent-decoded, so hostnames and setting names can contain any character
+fn parse_route(method: &Method, path: &str) -> Result<Route, ApiError> {
+    let no_route = || ApiError::not_found(format!("No route for {method} {path}"));
+
+    let segments = path
+        .strip_prefix(API_PREFIX)
+        .ok_or_else(no_route)?
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            percent_decode_str(segment)
+                .decode_utf8()
+                .map(|segment| segment.into_owned())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError::bad_request(format!("Invalid path {path}: {e}")))?;
+    let segments_ref = segments.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let route = match (method, segments_ref.as_slice()) {
+        (&Method::GET, ["openapi.json"]) => Route::OpenApi,
+        (&Method::GET, ["session"]) => Route::GetSession,
+        (&Method::PUT, ["session"]) => Route::SetSession,
+        (&Method::GET, ["settings", ..]) => Route::GetSetting(segments[1..].to_vec()),
+        (&Method::PUT, ["settings", ..]) => Route::SetSetting(segments[1..].to_vec()),
+        (&Method::GET, ["clients"]) => Route::GetClients,
+        (&Method::POST, ["clients"]) => Route::AddClient,
+        (&Method::GET, ["clients", hostname]) => Route::GetClient(hostname.to_string()),
+        (&Method::PATCH, ["clients", hostname]) => Route::UpdateClient(hostname.to_string()),
+        (&Method::DELETE, ["clients", hostname]) => Route::RemoveClient(hostname.to_string()),
+        (&Method::GET, ["recording"]) => Route::GetRecording,
+        (&Method::POST, ["recording", "start"]) => Route::StartRecording,
+        (&Method::POST, ["recording", "stop"]) => Route::StopRecording,
+        (&Method::GET, ["statistics"]) => Route::GetStatistics,
+        _ => return Err(no_route()),
+    };
+
+    Ok(route)
+}
+
+#[derive(Serialize)]
+struct RecordingState {
+    active: bool,
+}
+
+fn recording_state() -> ApiResult {
+    ok(RecordingState {
+        active: VIDEO_RECORDING_FILE.lock().is_some(),
+    })
+}
+
+// Convert the URL segments of a settings path, relative to session_settings, to a session path.
+// Numeric segments are indices only if the corresponding node is an array.
+fn settings_path(segments: &[String]) -> Result<(Vec<PathSegment>, json::Value), ApiError> {
+    let session_json = json::to_value(SERVER_DATA_MANAGER.read().session()).unwrap_or_default();
+
+    let mut path = vec![PathSegment::Name("session_settings".into())];
+    let mut node = &session_json["session_settings"];
+    for segment in segments {
+        let (path_segment, child) = match (node, segment.parse::<usize>()) {
+            (json::Value::Array(array), Ok(index)) => (PathSegment::Index(index), array.get(index)),
+            _ => (PathSegment::Name(segment.clone()), node.get(segment)),
+        };
+
+        node =
+            child.ok_or_else(|| ApiError::not_found(format!("Setting {segments:?} not found")))?;
+        path.push(path_segment);
+    }
+
+    Ok((path, node.clone()))
+}
+
+fn get_client(hostname: &str) -> ApiResult {
+    if let Some(entry) = SERVER_DATA_MANAGER.read().client_list().get(hostname) {
+        ok(entry)
+    } else {
+        Err(ApiError::not_found(format!("Client {hostname} not found")))
+    }
+}
+
+// Trusting a client requires the pairing flow: the client must be showing its pairing code, which
+// the user confirmed by reading it on the headset. Returns false if there is nothing to trust.
+fn check_trust_update(
+    hostname: &str,
+    client: &ClientConnectionConfig,
+    trusted: Option<bool>,
+) -> Result<bool, ApiError> {
+    match trusted {
+        None => Ok(false),
+        Some(false) => Err(ApiError::bad_request(
+            "A client can be untrusted only by removing it",
+        )),
+        Some(true) if client.trusted && client.identity_key.is_some() => Ok(false),
+        Some(true) if client.pending_pairing.is_some() => Ok(true),
+        Some(true) => Err(ApiError::conflict(format!(
+            "Client {hostname} can be trusted only while it is showing a pairing code"
+        ))),
+    }
+}
+
+fn update_client(hostname: &str, update: ClientUpdate) -> ApiResult {
+    let trust = {
+        let data_manager = SERVER_DATA_MANAGER.read();
+        let client = data_manager
+            .client_list()
+            .get(hostname)
+            .ok_or_else(|| ApiError::not_found(format!("Client {hostname} not found")))?;
+
+        check_trust_update(hostname, client, update.trusted)?
+    };
+
+    if let Some(Some(profile)) = &update.settings_profile {
+        if !SERVER_DATA_MANAGER
+            .read()
+            .session()
+            .settings_profiles
+            .contains_key(profile)
+        {
+            return Err(ApiError::not_found(format!(
+                "Settings profile {profile} not found"
+            )));
+        }
+    }
+
+    let actions = [
+        update.display_name.map(ClientListAction::SetDisplayName),
+        update.manual_ips.map(ClientListAction::SetManualIps),
+        trust.then_some(ClientListAction::Trust),
+        update
+            .settings_profile
+            .map(ClientListAction::SetSettingsProfile),
+        update
+            .settings_override
+            .map(ClientListAction::SetSettingsOverride),
+    ];
+    for action in actions.into_iter().flatten() {
+        web_server::update_client_list(hostname.to_owned(), action);
+    }
+
+    get_client(hostname)
+}
+
+async fn route(request: Request<Body>) -> ApiResult {
+    match parse_route(request.method(), request.uri().path())? {
+        Route::OpenApi => Ok((
+            StatusCode::OK,
+            json::from_str(OPENAPI_DESCRIPTION).unwrap_or_default(),
+        )),
+        Route::GetSession => ok(SERVER_DATA_MANAGER.read().session()),
+        Route::SetSession => {
+            let session = parse_body::<SessionConfig>(request).await?;
+
+            let mut data_manager = SERVER_DATA_MANAGER.write();
+            *data_manager.session_mut() = session;
+
+            ok(data_manager.session())
+        }
+        Route::GetSetting(segments) => ok(settings_path(&segments)?.1),
+        Route::SetSetting(segments) => {
+            let (path, _) = settings_path(&segments)?;
+            let value = parse_body::<json::Value>(request).await?;
+
+            SERVER_DATA_MANAGER
+                .write()
+                .set_values(vec![PathValuePair { path, value }])
+                .map_err(|e| ApiError::bad_request(format!("Invalid setting value: {e}")))?;
+
+            ok(settings_path(&segments)?.1)
+        }
+        Route::GetClients => ok(SERVER_DATA_MANAGER.read().client_list()),
+        Route::AddClient => {
+            let client = parse_body::<NewClient>(request).await?;
+
+            if SERVER_DATA_MANAGER
+                .read()
+                .client_list()
+                .contains_key(&client.hostname)
+            {
+                return Err(ApiError::conflict(format!(
+                    "Client {} already exists",
+                    client.hostname
+                )));
+            }
+
+            // The client is trusted later, by pairing it
+            web_server::update_client_list(
+                client.hostname.clone(),
+                ClientListAction::AddIfMissing {
+                    trusted: false,
+                    manual_ips: client.manual_ips,
+                },
+            );
+            if let Some(display_name) = client.display_name {
+                web_server::update_client_list(
+                    client.hostname.clone(),
+                    ClientListAction::SetDisplayName(display_name),
+                );
+            }
+
+            get_client(&client.hostname).map(|(_, value)| (StatusCode::CREATED, value))
+        }
+        Route::GetClient(hostname) => get_client(&hostname),
+        Route::UpdateClient(hostname) => {
+            let update = parse_body::<ClientUpdate>(request).await?;
+
+            update_client(&hostname, update)
+        }
+        Route::RemoveClient(hostname) => {
+            get_client(&hostname)?;
+
+            web_server::update_client_list(hostname, ClientListAction::RemoveEntry);
+
+            Ok((StatusCode::OK, json::Value::Null))
+        }
+        Route::GetRecording => recording_state(),
+        Route::StartRecording => {
+            crate::create_recording_file(SERVER_DATA_MANAGER.read().connected_client_settings());
+
+            recording_state()
+        }
+        Route::StopRecording => {
+            *VIDEO_RECORDING_FILE.lock() = None;
+
+            recording_state()
+        }
+        Route::GetStatistics => {
+            if let Some(summary) = STATISTICS_MANAGER
+                .lock()
+                .as_ref()
+                .and_then(|manager| manager.last_summary().cloned())
+            {
+                ok(summary)
+            } else {
+                Err(ApiError::not_found("No client is streaming"))
+            }
+        }
+    }
+}
+
+pub async fn handle(request: Request<Body>) -> Result<Response<Body>> {
+    match route(request).await {
+        Ok((status, value)) => json_response(status, json::to_string(&value)?),
+        Err(e) => json_response(e.status, json::json!({ "error": e.message }).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_session::ClientPairing;
+
+    #[test]
+    fn test_openapi_description_is_valid_json() {
+        let description = json::from_str::<json::Value>(OPENAPI_DESCRIPTION).unwrap();
+
+        assert!(description["paths"]["/session"].is_object());
+    }
+
+    #[test]
+    fn test_parse_route() {
+        assert!(matches!(
+            parse_route(&Method::GET, "/api/v1/clients/My%20Quest%2F2"),
+            Ok(Route::GetClient(hostname)) if hostname == "My Quest/2"
+        ));
+        assert!(matches!(
+            parse_route(&Method::PATCH, "/api/v1/clients/quest.client/"),
+            Ok(Route::UpdateClient(hostname)) if hostname == "quest.client"
+        ));
+        assert!(matches!(
+            parse_route(&Method::PUT, "/api/v1/settings/video/preferred_fps"),
+            Ok(Route::SetSetting(segments)) if segments == ["video", "preferred_fps"]
+        ));
+        assert!(matches!(
+            parse_route(&Method::POST, "/api/v1/recording/start"),
+            Ok(Route::StartRecording)
+        ));
+
+        let error = parse_route(&Method::GET, "/api/v1/clients/%FF").unwrap_err();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+
+        let error = parse_route(&Method::DELETE, "/api/v1/session").unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+
+        let error = parse_route(&Method::GET, "/api/v1/clients/a/b").unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_new_client_cannot_be_trusted() {
+        assert!(json::from_str::<NewClient>(r#"{ "hostname": "quest.client" }"#).is_ok());
+        assert!(
+            json::from_str::<NewClient>(r#"{ "hostname": "quest.client", "trusted": true }"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_trust_requires_pairing() {
+        let mut client = json::from_value::<ClientConnectionConfig>(json::json!({
+            "display_name": "Quest",
+            "current_ip": null,
+            "manual_ips": [],
+            "trusted": false,
+            "connection_state": "Disconnected",
+            "cabled": false,
+        }))
+        .unwrap();
+
+        assert!(!check_trust_update("quest", &client, None).unwrap());
+        assert_eq!(
+            check_trust_update("quest", &client, Some(false))
+                .unwrap_err()
+                .status,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            check_trust_update("quest", &client, Some(true))
+                .unwrap_err()
+                .status,
+            StatusCode::CONFLICT
+        );
+
+        client.pending_pairing = Some(ClientPairing {
+            identity_key: [1; 32],
+            code: "123456".into(),
+        });
+        assert!(check_trust_update("quest", &client, Some(true)).unwrap());
+
+        // Manually trusted clients without a key still need pairing
+        client.trusted = true;
+        client.pending_pairing = None;
+        assert!(check_trust_update("quest", &client, Some(true)).is_err());
+
+        client.identity_key = Some([1; 32]);
+        assert!(!check_trust_update("quest", &client, Some(true)).unwrap());
+    }
+}
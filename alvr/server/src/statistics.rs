@@ -1,4 +1,8 @@
-use alvr_common::{SlidingWindowAverage, HEAD_ID};
+use crate::{
+    metrics::{Histogram, MetricsWriter, LATENCY_BUCKETS_S},
+    statistics_log::{FrameStatisticsLog, FrameStatisticsRow},
+};
+use alvr_common::{warn, SlidingWindowAverage, DEVICE_ID_TO_PATH, HEAD_ID};
 use alvr_events::{EventType, GraphStatistics, NominalBitrateStats, StatisticsSummary};
 use alvr_packets::ClientStatistics;
 use std::{
@@ -8,6 +12,18 @@ use std::{
 
 const FULL_REPORT_INTERVAL: Duration = Duration::from_millis(500);
 
+const LATENCY_STAGES: [&str; 9] = [
+    "total_pipeline",
+    "game_time",
+    "server_compositor",
+    "encoder",
+    "network",
+    "decoder",
+    "decoder_queue",
+    "client_compositor",
+    "vsync_queue",
+];
+
 pub struct HistoryFrame {
     target_timestamp: Duration,
     tracking_received: Instant,
@@ -57,6 +73,15 @@ pub struct StatisticsManager {
     last_vsync_time: Instant,
     frame_interval: Duration,
     last_nominal_bitrate_stats: NominalBitrateStats,
+    last_actual_bitrate_bps: f32,
+    last_client_fps: f32,
+    last_server_fps: f32,
+    // One histogram for each of LATENCY_STAGES
+    latency_histograms: [Histogram; LATENCY_STAGES.len()],
+    last_summary: Option<StatisticsSummary>,
+    // Target timestamp and present instant of the last presented frame
+    last_presented_frame: Option<(Duration, Instant)>,
+    frame_log: Option<FrameStatisticsLog>,
 }
 
 impl StatisticsManager {
@@ -65,6 +90,7 @@ impl StatisticsManager {
         max_history_size: usize,
         nominal_server_frame_interval: Duration,
         steamvr_pipeline_frames: f32,
+        frame_log: Option<FrameStatisticsLog>,
     ) -> Self {
         Self {
             history_buffer: VecDeque::new(),
@@ -89,6 +115,13 @@ impl StatisticsManager {
             last_vsync_time: Instant::now(),
             frame_interval: nominal_server_frame_interval,
             last_nominal_bitrate_stats: NominalBitrateStats::default(),
+            last_actual_bitrate_bps: 0.0,
+            last_client_fps: 0.0,
+            last_server_fps: 0.0,
+            latency_histograms: std::array::from_fn(|_| Histogram::new(LATENCY_BUCKETS_S)),
+            last_summary: None,
+            last_presented_frame: None,
+            frame_log,
         }
     }
 
@@ -123,9 +156,23 @@ impl StatisticsManager {
             self.last_frame_present_instant = now;
 
             frame.frame_present = now;
+
+            self.last_presented_frame = Some((target_timestamp, now));
         }
     }
 
+    // Maps an instant to the timeline of the video timestamps, extrapolating from the last
+    // presented frame. Used to timestamp game audio
+    pub fn stream_timestamp(&self, instant: Instant) -> Option<Duration> {
+        let (target_timestamp, present_instant) = self.last_presented_frame?;
+
+        Some(if instant >= present_instant {
+            target_timestamp + (instant - present_instant)
+        } else {
+            target_timestamp.saturating_sub(present_instant - instant)
+        })
+    }
+
     pub fn report_frame_composed(&mut self, target_timestamp: Duration, offset: Duration) {
         if let Some(frame) = self
             .history_buffer
@@ -234,7 +281,7 @@ impl StatisticsManager {
 
                 let interval_secs = FULL_REPORT_INTERVAL.as_secs_f32();
 
-                alvr_events::send_event(EventType::StatisticsSummary(StatisticsSummary {
+                let summary = StatisticsSummary {
                     video_packets_total: self.video_packets_total,
                     video_packets_per_sec: (self.video_packets_partial_sum as f32 / interval_secs)
                         as _,
@@ -264,11 +311,20 @@ impl StatisticsManager {
                         .cloned()
                         .unwrap_or_default()
                         .is_plugged,
-                }));
+                    audio_video_offset_ms: client_stats
+                        .audio_video_offset
+                        .map(|offset| offset * 1000.),
+                };
+                self.last_summary = Some(summary.clone());
+                alvr_events::send_event(EventType::StatisticsSummary(summary));
 
                 self.video_packets_partial_sum = 0;
                 self.video_bytes_partial_sum = 0;
                 self.packets_lost_partial_sum = 0;
+
+                if let Some(log) = &mut self.frame_log {
+                    log.flush().ok();
+                }
             }
 
             // While not accurate, this prevents NaNs and zeros that would cause a crash or pollute
@@ -279,6 +335,48 @@ impl StatisticsManager {
                 0.0
             };
 
+            for (histogram, latency) in self.latency_histograms.iter_mut().zip([
+                client_stats.total_pipeline_latency,
+                game_time_latency,
+                server_compositor_latency,
+                encoder_latency,
+                network_latency,
+                client_stats.video_decode,
+                client_stats.video_decoder_queue,
+                client_stats.rendering,
+                client_stats.vsync_queue,
+            ]) {
+                histogram.observe(latency.as_secs_f64());
+            }
+            self.last_actual_bitrate_bps = bitrate_bps;
+            self.last_client_fps = client_fps;
+            self.last_server_fps = server_fps;
+
+            if let Some(log) = &mut self.frame_log {
+                let res = log.write_frame(&FrameStatisticsRow {
+                    target_timestamp: client_stats.target_timestamp,
+                    total_pipeline_latency: client_stats.total_pipeline_latency,
+                    game_time_latency,
+                    server_compositor_latency,
+                    encoder_latency,
+                    network_latency,
+                    decoder_latency: client_stats.video_decode,
+                    decoder_queue_latency: client_stats.video_decoder_queue,
+                    client_compositor_latency: client_stats.rendering,
+                    vsync_queue_latency: client_stats.vsync_queue,
+                    video_packet_bytes: frame.video_packet_bytes,
+                    packets_lost_total: self.packets_lost_total,
+                    client_fps,
+                    server_fps,
+                    requested_bitrate_bps: self.last_nominal_bitrate_stats.requested_bps,
+                    actual_bitrate_bps: bitrate_bps,
+                });
+                if let Err(e) = res {
+                    warn!("Failed to write frame statistics log, stopping: {e}");
+                    self.frame_log = None;
+                }
+            }
+
             // todo: use target timestamp in nanoseconds. the dashboard needs to use the first
             // timestamp as the graph time origin.
             alvr_events::send_event(EventType::GraphStatistics(GraphStatistics {
@@ -303,6 +401,88 @@ impl StatisticsManager {
         }
     }
 
+    pub fn prometheus_metrics(&self) -> String {
+        let mut writer = MetricsWriter::default();
+
+        writer.labeled_histogram(
+            "alvr_latency_seconds",
+            "Latency of each stage of the video pipeline",
+            "stage",
+            LATENCY_STAGES.iter().copied().zip(&self.latency_histograms),
+        );
+        writer.counter(
+            "alvr_video_packets_total",
+            "Number of encoded video frames",
+            self.video_packets_total as f64,
+        );
+        writer.counter(
+            "alvr_video_bytes_total",
+            "Size of the encoded video frames",
+            self.video_bytes_total as f64,
+        );
+        writer.counter(
+            "alvr_packets_lost_total",
+            "Number of video packets lost by the client",
+            self.packets_lost_total as f64,
+        );
+        writer.gauge(
+            "alvr_requested_bitrate_bps",
+            "Bitrate requested to the encoder",
+            self.last_nominal_bitrate_stats.requested_bps as f64,
+        );
+        writer.gauge(
+            "alvr_actual_bitrate_bps",
+            "Bitrate of the last frame, calculated from its size and network latency",
+            self.last_actual_bitrate_bps as f64,
+        );
+        writer.gauge(
+            "alvr_client_fps",
+            "Framerate of the client",
+            self.last_client_fps as f64,
+        );
+        writer.gauge(
+            "alvr_server_fps",
+            "Framerate of the server",
+            self.last_server_fps as f64,
+        );
+
+        let batteries = self
+            .battery_gauges
+            .iter()
+            .map(|(id, data)| {
+                let device = DEVICE_ID_TO_PATH
+                    .get(id)
+                    .map(|path| path.to_string())
+                    .unwrap_or_else(|| id.to_string());
+
+                (device, data.clone())
+            })
+            .collect::<Vec<_>>();
+        writer.labeled_gauge(
+            "alvr_battery_ratio",
+            "Battery charge of the device, from 0 to 1",
+            "device",
+            batteries
+                .iter()
+                .map(|(device, data)| (device.as_str(), data.gauge_value as f64)),
+        );
+        writer.labeled_gauge(
+            "alvr_battery_plugged",
+            "Whether the device is charging",
+            "device",
+            batteries
+                .iter()
+                .map(|(device, data)| (device.as_str(), data.is_plugged as u8 as f64)),
+        );
+
+        writer.finish()
+    }
+
+    // Latest summary sent to the dashboard, if any
+    pub fn last_summary(&self) -> Option<&StatisticsSummary> {
+        self.last_summary.as_ref()
+    }
+
     pub fn video_pipeline_latency_average(&self) -> Duration {
         self.total_pipeline_latency_average.get_average()
     }
@@ -0,0 +1,227 @@
+use alvr_common::anyhow::Result;
+use alvr_session::FrameStatisticsLogConfig;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const CSV_HEADER: &str = "time,target_timestamp_ms,total_pipeline_latency_ms,game_time_ms,\
+server_compositor_ms,encoder_ms,network_ms,decoder_ms,decoder_queue_ms,client_compositor_ms,\
+vsync_queue_ms,video_packet_bytes,packets_lost_total,client_fps,server_fps,requested_bitrate_mbps,\
+actual_bitrate_mbps\n";
+
+pub struct FrameStatisticsRow {
+    pub target_timestamp: Duration,
+    pub total_pipeline_latency: Duration,
+    pub game_time_latency: Duration,
+    pub server_compositor_latency: Duration,
+    pub encoder_latency: Duration,
+    pub network_latency: Duration,
+    pub decoder_latency: Duration,
+    pub decoder_queue_latency: Duration,
+    pub client_compositor_latency: Duration,
+    pub vsync_queue_latency: Duration,
+    pub video_packet_bytes: usize,
+    pub packets_lost_total: usize,
+    pub client_fps: f32,
+    pub server_fps: f32,
+    pub requested_bitrate_bps: f32,
+    pub actual_bitrate_bps: f32,
+}
+
+impl FrameStatisticsRow {
+    fn to_csv_line(&self) -> String {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            chrono::Local::now().format("%F %T%.3f"),
+            ms(self.target_timestamp),
+            ms(self.total_pipeline_latency),
+            ms(self.game_time_latency),
+            ms(self.server_compositor_latency),
+            ms(self.encoder_latency),
+            ms(self.network_latency),
+            ms(self.decoder_latency),
+            ms(self.decoder_queue_latency),
+            ms(self.client_compositor_latency),
+            ms(self.vsync_queue_latency),
+            self.video_packet_bytes,
+            self.packets_lost_total,
+            self.client_fps,
+            self.server_fps,
+            self.requested_bitrate_bps / 1e6,
+            self.actual_bitrate_bps / 1e6,
+        )
+    }
+}
+
+// frame_statistics.csv -> frame_statistics.1.csv
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_extension(format!("{index}.{extension}"))
+}
+
+// Shift the existing files by one position, deleting the oldest one
+fn rotate(path: &Path, max_rotated_files: usize) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    if max_rotated_files == 0 {
+        fs::remove_file(path)?;
+
+        return Ok(());
+    }
+
+    let oldest_path = rotated_path(path, max_rotated_files);
+    if oldest_path.exists() {
+        fs::remove_file(oldest_path)?;
+    }
+    for index in (1..max_rotated_files).rev() {
+        let source = rotated_path(path, index);
+        if source.exists() {
+            fs::rename(source, rotated_path(path, index + 1))?;
+        }
+    }
+
+    fs::rename(path, rotated_path(path, 1))?;
+
+    Ok(())
+}
+
+// CSV log of the statistics of every frame. A new file is started for every connection and when the
+// current file reaches the size limit.
+pub struct FrameStatisticsLog {
+    path: PathBuf,
+    max_file_size: u64,
+    max_rotated_files: usize,
+    writer: BufWriter<File>,
+    file_size: u64,
+}
+
+impl FrameStatisticsLog {
+    pub fn new(path: PathBuf, config: &FrameStatisticsLogConfig) -> Result<Self> {
+        let max_rotated_files = config.max_rotated_files;
+        let (writer, file_size) = Self::open_new_file(&path, max_rotated_files)?;
+
+        Ok(Self {
+            path,
+            max_file_size: config.max_file_size_mb * 1024 * 1024,
+            max_rotated_files,
+            writer,
+            file_size,
+        })
+    }
+
+    fn open_new_file(path: &Path, max_rotated_files: usize) -> Result<(BufWriter<File>, u64)> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        rotate(path, max_rotated_files)?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(CSV_HEADER.as_bytes())?;
+
+        Ok((writer, CSV_HEADER.len() as u64))
+    }
+
+    pub fn write_frame(&mut self, row: &FrameStatisticsRow) -> Result<()> {
+        let line = row.to_csv_line();
+
+        if self.file_size + line.len() as u64 > self.max_file_size {
+            self.writer.flush()?;
+            (self.writer, self.file_size) =
+                Self::open_new_file(&self.path, self.max_rotated_files)?;
+        }
+
+        self.writer.write_all(line.as_bytes())?;
+        self.file_size += line.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn row(index: u64) -> FrameStatisticsRow {
+        FrameStatisticsRow {
+            target_timestamp: Duration::from_millis(index),
+            total_pipeline_latency: Duration::ZERO,
+            game_time_latency: Duration::ZERO,
+            server_compositor_latency: Duration::ZERO,
+            encoder_latency: Duration::ZERO,
+            network_latency: Duration::ZERO,
+            decoder_latency: Duration::ZERO,
+            decoder_queue_latency: Duration::ZERO,
+            client_compositor_latency: Duration::ZERO,
+            vsync_queue_latency: Duration::ZERO,
+            video_packet_bytes: 0,
+            packets_lost_total: 0,
+            client_fps: 0.0,
+            server_fps: 0.0,
+            requested_bitrate_bps: 0.0,
+            actual_bitrate_bps: 0.0,
+        }
+    }
+
+    // Values of the target_timestamp_ms column
+    fn target_timestamps(path: &Path) -> Vec<u64> {
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.starts_with(CSV_HEADER));
+
+        content
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = env::temp_dir().join(format!("alvr_statistics_test_{}", std::process::id()));
+        let path = dir.join("frame_statistics.csv");
+
+        let mut log = FrameStatisticsLog::new(
+            path.clone(),
+            &FrameStatisticsLogConfig {
+                max_file_size_mb: 1,
+                max_rotated_files: 2,
+            },
+        )
+        .unwrap();
+        // Room for the header and two rows
+        let line_size = row(10).to_csv_line().len() as u64;
+        log.max_file_size = CSV_HEADER.len() as u64 + line_size * 2;
+
+        for index in 10..20 {
+            log.write_frame(&row(index)).unwrap();
+        }
+        log.flush().unwrap();
+
+        let current = target_timestamps(&path);
+        let rotated_1 = target_timestamps(&dir.join("frame_statistics.1.csv"));
+        let rotated_2 = target_timestamps(&dir.join("frame_statistics.2.csv"));
+        let oldest_exists = dir.join("frame_statistics.3.csv").exists();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(current, [18, 19]);
+        assert_eq!(rotated_1, [16, 17]);
+        assert_eq!(rotated_2, [14, 15]);
+        // The files with the first rows have been deleted
+        assert!(!oldest_exists);
+    }
+}
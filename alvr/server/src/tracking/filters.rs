@@ -0,0 +1,449 @@
+// Pose filters for tracked devices. They run on the transformed motion (recentered and with offsets
+// applied), using the tracking timestamps as sample times.
+
+use alvr_common::{
+    glam::{Quat, Vec3},
+    DeviceMotion, Pose,
+};
+use alvr_session::{
+    JitterRejectionConfig, KalmanPredictionConfig, MotionFiltersConfig, OneEuroFilterConfig,
+};
+use std::{f32::consts::PI, time::Duration};
+
+// Samples further apart than this (or out of order) restart the filters
+const MAX_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+// Initial uncertainty of the Kalman filter state, in m/s and m/s²
+const INITIAL_VELOCITY_STD_DEV: f64 = 1.0;
+const INITIAL_ACCELERATION_STD_DEV: f64 = 10.0;
+
+// Weight of the new sample for an exponential smoothing with the specified cutoff frequency
+fn smoothing_factor(cutoff_hz: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * PI * cutoff_hz);
+
+    1.0 / (1.0 + tau / dt)
+}
+
+// Keeps the last accepted pose until the device moves further than the thresholds. Returns true if
+// the pose is held
+fn reject_jitter(held_pose: &mut Pose, pose: &mut Pose, config: &JitterRejectionConfig) -> bool {
+    let moved = pose.position.distance(held_pose.position) > config.position_threshold_mm / 1000.0
+        || pose.orientation.angle_between(held_pose.orientation)
+            > config.rotation_threshold_deg.to_radians();
+
+    if moved {
+        *held_pose = *pose;
+    } else {
+        *pose = *held_pose;
+    }
+
+    !moved
+}
+
+// One Euro filter (Casiez et al. 2012). The cutoff frequency grows with the filtered speed, which
+// gives strong smoothing at rest and low lag during fast movements. Position and orientation are
+// filtered separately, each with its own speed.
+struct OneEuroFilter {
+    pose: Pose,
+    linear_speed: f32,
+    angular_speed: f32,
+}
+
+impl OneEuroFilter {
+    fn new(pose: Pose) -> Self {
+        Self {
+            pose,
+            linear_speed: 0.0,
+            angular_speed: 0.0,
+        }
+    }
+
+    fn filter(&mut self, pose: Pose, dt: f32, config: &OneEuroFilterConfig) -> Pose {
+        let derivative_factor = smoothing_factor(config.derivative_cutoff_hz, dt);
+
+        let linear_speed = pose.position.distance(self.pose.position) / dt;
+        self.linear_speed += (linear_speed - self.linear_speed) * derivative_factor;
+        let cutoff_hz = config.min_cutoff_hz + config.linear_beta * self.linear_speed;
+        self.pose.position = self
+            .pose
+            .position
+            .lerp(pose.position, smoothing_factor(cutoff_hz, dt));
+
+        let angular_speed = pose.orientation.angle_between(self.pose.orientation) / dt;
+        self.angular_speed += (angular_speed - self.angular_speed) * derivative_factor;
+        let cutoff_hz = config.min_cutoff_hz + config.angular_beta * self.angular_speed;
+        self.pose.orientation = self
+            .pose
+            .orientation
+            .slerp(pose.orientation, smoothing_factor(cutoff_hz, dt))
+            .normalize();
+
+        self.pose
+    }
+}
+
+// Kalman filter with a constant acceleration model, driven by white jerk noise. The three axes are
+// independent and share the same dynamics and noise, so they also share the covariance matrix.
+struct KalmanFilter {
+    // Position, velocity and acceleration
+    state: [Vec3; 3],
+    covariance: [[f64; 3]; 3],
+}
+
+impl KalmanFilter {
+    fn new(position: Vec3, config: &KalmanPredictionConfig) -> Self {
+        let measurement_std_dev = config.measurement_noise_mm as f64 / 1000.0;
+
+        Self {
+            state: [position, Vec3::ZERO, Vec3::ZERO],
+            covariance: [
+                [measurement_std_dev.powi(2), 0.0, 0.0],
+                [0.0, INITIAL_VELOCITY_STD_DEV.powi(2), 0.0],
+                [0.0, 0.0, INITIAL_ACCELERATION_STD_DEV.powi(2)],
+            ],
+        }
+    }
+
+    fn update(&mut self, position: Vec3, dt: f32, config: &KalmanPredictionConfig) {
+        let [p, v, a] = self.state;
+        let dt64 = dt as f64;
+
+        // Predict: x = F x, P = F P Fᵀ + Q
+        self.state = [p + v * dt + a * (dt * dt / 2.0), v + a * dt, a];
+
+        let f = [
+            [1.0, dt64, dt64 * dt64 / 2.0],
+            [0.0, 1.0, dt64],
+            [0.0, 0.0, 1.0],
+        ];
+        let q = config.process_noise as f64 * config.process_noise as f64;
+        let noise = [
+            [dt64.powi(5) / 20.0, dt64.powi(4) / 8.0, dt64.powi(3) / 6.0],
+            [dt64.powi(4) / 8.0, dt64.powi(3) / 3.0, dt64.powi(2) / 2.0],
+            [dt64.powi(3) / 6.0, dt64.powi(2) / 2.0, dt64],
+        ];
+        let mut covariance = [[0.0; 3]; 3];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                for k in 0..3 {
+                    for l in 0..3 {
+                        *value += f[i][k] * self.covariance[k][l] * f[j][l];
+                    }
+                }
+                *value += q * noise[i][j];
+            }
+        }
+
+        // Correct with the measured position: K = P Hᵀ / (H P Hᵀ + R), with H = [1, 0, 0]
+        let measurement_variance = (config.measurement_noise_mm as f64 / 1000.0).powi(2);
+        let innovation_variance = covariance[0][0] + measurement_variance;
+        let gain = [0, 1, 2].map(|i| covariance[i][0] / innovation_variance);
+
+        let innovation = position - self.state[0];
+        for (value, gain) in self.state.iter_mut().zip(gain) {
+            *value += innovation * gain as f32;
+        }
+
+        for (i, row) in self.covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = covariance[i][j] - gain[i] * covariance[0][j];
+            }
+        }
+    }
+
+    fn predict_position(&self, horizon: f32) -> Vec3 {
+        let [p, v, a] = self.state;
+
+        p + v * horizon + a * (horizon * horizon / 2.0)
+    }
+}
+
+// State of the filters of one device. Filters that are disabled in the settings lose their state.
+#[derive(Default)]
+pub struct MotionFilter {
+    last_timestamp: Option<Duration>,
+    last_output: Option<DeviceMotion>,
+    held_pose: Option<Pose>,
+    one_euro: Option<OneEuroFilter>,
+    kalman: Option<KalmanFilter>,
+}
+
+impl MotionFilter {
+    pub fn filter(
+        &mut self,
+        mut motion: DeviceMotion,
+        timestamp: Duration,
+        config: &MotionFiltersConfig,
+    ) -> DeviceMotion {
+        // Repeated timestamp, there is no new information to filter
+        if self.last_timestamp == Some(timestamp) {
+            if let Some(last_output) = self.last_output {
+                return last_output;
+            }
+        }
+
+        let dt = self
+            .last_timestamp
+            .filter(|last| timestamp > *last && timestamp - *last <= MAX_SAMPLE_INTERVAL)
+            .map(|last| (timestamp - last).as_secs_f32());
+        if dt.is_none() {
+            *self = Self::default();
+        }
+        self.last_timestamp = Some(timestamp);
+
+        if let Some(config) = config.jitter_rejection.as_option() {
+            let held_pose = self.held_pose.get_or_insert(motion.pose);
+            if reject_jitter(held_pose, &mut motion.pose, config) {
+                // The device must look still to SteamVR too
+                motion.linear_velocity = Vec3::ZERO;
+                motion.angular_velocity = Vec3::ZERO;
+            }
+        } else {
+            self.held_pose = None;
+        }
+
+        if let Some(config) = config.one_euro_filter.as_option() {
+            match (&mut self.one_euro, dt) {
+                (Some(filter), Some(dt)) => motion.pose = filter.filter(motion.pose, dt, config),
+                _ => self.one_euro = Some(OneEuroFilter::new(motion.pose)),
+            }
+        } else {
+            self.one_euro = None;
+        }
+
+        if let Some(config) = config.prediction.as_option() {
+            let horizon = config.horizon_ms / 1000.0;
+
+            match (&mut self.kalman, dt) {
+                (Some(filter), Some(dt)) => {
+                    filter.update(motion.pose.position, dt, config);
+                    motion.pose.position = filter.predict_position(horizon);
+                }
+                _ => self.kalman = Some(KalmanFilter::new(motion.pose.position, config)),
+            }
+
+            // The angular velocity is in the local frame of the device
+            motion.pose.orientation = (motion.pose.orientation
+                * Quat::from_scaled_axis(motion.angular_velocity * horizon))
+            .normalize();
+        } else {
+            self.kalman = None;
+        }
+
+        self.last_output = Some(motion);
+
+        motion
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::glam::EulerRot;
+    use alvr_session::settings_schema::Switch;
+
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+    // Deterministic uniform noise in [-amplitude, amplitude]
+    struct Noise(u64);
+
+    impl Noise {
+        fn next(&mut self, amplitude: f32) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+
+            ((self.0 >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * amplitude
+        }
+
+        fn vec3(&mut self, amplitude: f32) -> Vec3 {
+            Vec3::new(
+                self.next(amplitude),
+                self.next(amplitude),
+                self.next(amplitude),
+            )
+        }
+    }
+
+    fn filters_config() -> MotionFiltersConfig {
+        MotionFiltersConfig {
+            jitter_rejection: Switch::Disabled,
+            one_euro_filter: Switch::Disabled,
+            prediction: Switch::Disabled,
+        }
+    }
+
+    fn one_euro_config() -> OneEuroFilterConfig {
+        OneEuroFilterConfig {
+            min_cutoff_hz: 1.0,
+            linear_beta: 10.0,
+            angular_beta: 1.0,
+            derivative_cutoff_hz: 1.0,
+        }
+    }
+
+    fn motion(position: Vec3, orientation: Quat) -> DeviceMotion {
+        DeviceMotion {
+            pose: Pose {
+                orientation,
+                position,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn rms(errors: &[f32]) -> f32 {
+        (errors.iter().map(|e| e * e).sum::<f32>() / errors.len() as f32).sqrt()
+    }
+
+    // Runs the filter on a noisy trajectory, returning the RMS error of the noisy and of the
+    // filtered positions against the position at the time of each sample plus the time offset
+    fn position_errors(
+        config: &MotionFiltersConfig,
+        trajectory: impl Fn(f32) -> Vec3,
+        noise_m: f32,
+        time_offset: f32,
+    ) -> (f32, f32) {
+        let mut noise = Noise(1);
+        let mut filter = MotionFilter::default();
+
+        let mut raw_errors = vec![];
+        let mut filtered_errors = vec![];
+        for i in 0..500 {
+            let timestamp = SAMPLE_INTERVAL * i;
+            let t = timestamp.as_secs_f32();
+            let measured = trajectory(t) + noise.vec3(noise_m);
+
+            let filtered = filter.filter(motion(measured, Quat::IDENTITY), timestamp, config);
+
+            // Skip the convergence time
+            if i >= 100 {
+                let target = trajectory(t + time_offset);
+                raw_errors.push(measured.distance(target));
+                filtered_errors.push(filtered.pose.position.distance(target));
+            }
+        }
+
+        (rms(&raw_errors), rms(&filtered_errors))
+    }
+
+    #[test]
+    fn test_one_euro_filter() {
+        let config = MotionFiltersConfig {
+            one_euro_filter: Switch::Enabled(one_euro_config()),
+            ..filters_config()
+        };
+
+        // Still device: the noise is reduced
+        let (raw, filtered) = position_errors(&config, |_| Vec3::new(0.0, 1.0, 0.0), 0.002, 0.0);
+        assert!(filtered < raw / 2.0, "{filtered} {raw}");
+
+        // Fast movement: the lag stays small compared to the traveled distance
+        let (_, filtered) = position_errors(&config, |t| Vec3::new(t * 2.0, 1.0, 0.0), 0.002, 0.0);
+        assert!(filtered < 0.03, "{filtered}");
+
+        // Orientation noise is reduced too
+        let mut noise = Noise(2);
+        let mut filter = MotionFilter::default();
+        let mut errors = vec![];
+        for i in 0..200 {
+            let orientation = Quat::from_euler(
+                EulerRot::XYZ,
+                noise.next(0.02),
+                noise.next(0.02),
+                noise.next(0.02),
+            );
+            let filtered = filter.filter(
+                motion(Vec3::ZERO, orientation),
+                SAMPLE_INTERVAL * i,
+                &config,
+            );
+            if i >= 100 {
+                errors.push(filtered.pose.orientation.angle_between(Quat::IDENTITY));
+            }
+        }
+        assert!(rms(&errors) < 0.01, "{}", rms(&errors));
+    }
+
+    #[test]
+    fn test_kalman_prediction() {
+        let horizon = 0.05;
+        let config = MotionFiltersConfig {
+            prediction: Switch::Enabled(KalmanPredictionConfig {
+                horizon_ms: horizon * 1000.0,
+                process_noise: 50.0,
+                measurement_noise_mm: 1.0,
+            }),
+            ..filters_config()
+        };
+
+        // Accelerating device: the predicted position is closer to the future position than the
+        // last measurement
+        let trajectory = |t: f32| Vec3::new(0.5 * t * t, 1.0 + 0.2 * t, -0.3 * t);
+        let (raw, predicted) = position_errors(&config, trajectory, 0.001, horizon);
+        assert!(predicted < raw / 3.0, "{predicted} {raw}");
+
+        // Orientation is extrapolated with the angular velocity
+        let mut filter = MotionFilter::default();
+        let mut device_motion = motion(Vec3::ZERO, Quat::IDENTITY);
+        device_motion.angular_velocity = Vec3::new(0.0, 2.0, 0.0);
+        let filtered = filter.filter(device_motion, Duration::ZERO, &config);
+        assert!((filtered.pose.orientation.angle_between(Quat::IDENTITY) - 0.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_jitter_rejection() {
+        let config = MotionFiltersConfig {
+            jitter_rejection: Switch::Enabled(JitterRejectionConfig {
+                position_threshold_mm: 3.0,
+                rotation_threshold_deg: 1.0,
+            }),
+            ..filters_config()
+        };
+
+        let mut noise = Noise(3);
+        let mut filter = MotionFilter::default();
+        let start = Vec3::new(0.0, 1.0, 0.0);
+        let first = filter.filter(motion(start, Quat::IDENTITY), Duration::ZERO, &config);
+
+        // Noise smaller than the threshold is rejected completely
+        for i in 1..100 {
+            let mut device_motion = motion(start + noise.vec3(0.001), Quat::IDENTITY);
+            device_motion.linear_velocity = noise.vec3(0.1);
+
+            let filtered = filter.filter(device_motion, SAMPLE_INTERVAL * i, &config);
+            assert_eq!(filtered.pose.position, first.pose.position);
+            assert_eq!(filtered.linear_velocity, Vec3::ZERO);
+        }
+
+        // Real movements are followed
+        let moved = start + Vec3::new(0.01, 0.0, 0.0);
+        let filtered = filter.filter(
+            motion(moved, Quat::IDENTITY),
+            SAMPLE_INTERVAL * 100,
+            &config,
+        );
+        assert_eq!(filtered.pose.position, moved);
+    }
+
+    #[test]
+    fn test_restart() {
+        let config = MotionFiltersConfig {
+            one_euro_filter: Switch::Enabled(one_euro_config()),
+            ..filters_config()
+        };
+
+        let mut filter = MotionFilter::default();
+        filter.filter(motion(Vec3::ZERO, Quat::IDENTITY), Duration::ZERO, &config);
+
+        // After a long pause the old pose is not used anymore
+        let position = Vec3::new(1.0, 0.0, 0.0);
+        let filtered = filter.filter(
+            motion(position, Quat::IDENTITY),
+            Duration::from_secs(10),
+            &config,
+        );
+        assert_eq!(filtered.pose.position, position);
+    }
+}
@@ -1,14 +1,22 @@
+mod filters;
+
 use crate::{to_ffi_quat, FfiDeviceMotion, FfiHandSkeleton};
 use alvr_common::{
     glam::{EulerRot, Quat, Vec3},
-    DeviceMotion, Pose, HEAD_ID, LEFT_HAND_ID, RIGHT_HAND_ID,
+    DeviceMotion, Pose, CHEST_ID, CHEST_PATH, HEAD_ID, LEFT_ELBOW_ID, LEFT_ELBOW_PATH,
+    LEFT_FOOT_ID, LEFT_FOOT_PATH, LEFT_HAND_ID, LEFT_KNEE_ID, LEFT_KNEE_PATH, RIGHT_ELBOW_ID,
+    RIGHT_ELBOW_PATH, RIGHT_FOOT_ID, RIGHT_FOOT_PATH, RIGHT_HAND_ID, RIGHT_KNEE_ID,
+    RIGHT_KNEE_PATH, WAIST_ID, WAIST_PATH,
 };
 use alvr_session::{
-    settings_schema::Switch, HeadsetConfig, PositionRecenteringMode, RotationRecenteringMode,
+    settings_schema::Switch, BodyTrackerRole, HeadsetConfig, MotionFiltersConfig,
+    PositionRecenteringMode, RotationRecenteringMode,
 };
+use filters::MotionFilter;
 use std::{
     collections::HashMap,
     f32::consts::{FRAC_PI_2, PI},
+    time::Duration,
 };
 
 const DEG_TO_RAD: f32 = PI / 180.0;
@@ -46,18 +54,50 @@ fn get_hand_skeleton_offsets(config: &HeadsetConfig) -> (Pose, Pose) {
     (left_offset, right_offset)
 }
 
-// todo: Move this struct to Settings and use it for every tracked device
+pub fn body_tracker_path(role: BodyTrackerRole) -> &'static str {
+    match role {
+        BodyTrackerRole::Waist => WAIST_PATH,
+        BodyTrackerRole::Chest => CHEST_PATH,
+        BodyTrackerRole::LeftFoot => LEFT_FOOT_PATH,
+        BodyTrackerRole::RightFoot => RIGHT_FOOT_PATH,
+        BodyTrackerRole::LeftKnee => LEFT_KNEE_PATH,
+        BodyTrackerRole::RightKnee => RIGHT_KNEE_PATH,
+        BodyTrackerRole::LeftElbow => LEFT_ELBOW_PATH,
+        BodyTrackerRole::RightElbow => RIGHT_ELBOW_PATH,
+    }
+}
+
+pub fn body_tracker_id(role: BodyTrackerRole) -> u64 {
+    match role {
+        BodyTrackerRole::Waist => *WAIST_ID,
+        BodyTrackerRole::Chest => *CHEST_ID,
+        BodyTrackerRole::LeftFoot => *LEFT_FOOT_ID,
+        BodyTrackerRole::RightFoot => *RIGHT_FOOT_ID,
+        BodyTrackerRole::LeftKnee => *LEFT_KNEE_ID,
+        BodyTrackerRole::RightKnee => *RIGHT_KNEE_ID,
+        BodyTrackerRole::LeftElbow => *LEFT_ELBOW_ID,
+        BodyTrackerRole::RightElbow => *RIGHT_ELBOW_ID,
+    }
+}
+
+// Transformations applied to the motion of a tracked device, derived from the settings of the device
 #[derive(Default)]
-struct MotionConfig {
+struct MotionConfig<'a> {
     // Position offset applied after rotation offset
     pose_offset: Pose,
     linear_velocity_cutoff: f32,
     angular_velocity_cutoff: f32,
+    // Controller emulated with hand tracking. Velocities are discarded
+    hand_tracked: bool,
+    motion_filters: Option<&'a MotionFiltersConfig>,
 }
 
 pub struct TrackingManager {
     last_head_pose: Pose,     // client's reference space
     recentering_origin: Pose, // client's reference space
+    // Keyed by device ID and whether the device is a tracked hand, so that switching between
+    // controllers and hand tracking does not mix the filter state
+    motion_filters: HashMap<(u64, bool), MotionFilter>,
 }
 
 impl TrackingManager {
@@ -65,6 +105,7 @@ impl TrackingManager {
         TrackingManager {
             last_head_pose: Pose::default(),
             recentering_origin: Pose::default(),
+            motion_filters: HashMap::new(),
         }
     }
 
@@ -99,6 +140,9 @@ impl TrackingManager {
             }
             RotationRecenteringMode::Tilted => self.last_head_pose.orientation,
         };
+
+        // The filtered poses would jump to the new reference space
+        self.motion_filters.clear();
     }
 
     pub fn recenter_pose(&self, pose: Pose) -> Pose {
@@ -117,59 +161,86 @@ impl TrackingManager {
         config: &HeadsetConfig,
         device_motions: &[(u64, DeviceMotion)],
         hand_skeletons_enabled: [bool; 2],
+        timestamp: Duration,
     ) -> Vec<(u64, DeviceMotion)> {
         let mut device_motion_configs = HashMap::new();
-        device_motion_configs.insert(*HEAD_ID, MotionConfig::default());
+        device_motion_configs.insert(
+            *HEAD_ID,
+            MotionConfig {
+                motion_filters: Some(&config.head_motion_filters),
+                ..Default::default()
+            },
+        );
 
         if let Switch::Enabled(controllers) = &config.controllers {
             let t = controllers.left_controller_position_offset;
             let r = controllers.left_controller_rotation_offset;
 
-            device_motion_configs.insert(
-                *LEFT_HAND_ID,
-                MotionConfig {
-                    pose_offset: Pose {
-                        orientation: Quat::from_euler(
-                            EulerRot::XYZ,
-                            r[0] * DEG_TO_RAD,
-                            r[1] * DEG_TO_RAD,
-                            r[2] * DEG_TO_RAD,
-                        ),
-                        position: Vec3::new(t[0], t[1], t[2]),
-                    },
-                    linear_velocity_cutoff: controllers.linear_velocity_cutoff,
-                    angular_velocity_cutoff: controllers.angular_velocity_cutoff * DEG_TO_RAD,
-                },
-            );
+            let (left_hand_skeleton_offset, right_hand_skeleton_offset) =
+                get_hand_skeleton_offsets(config);
+
+            // The right controller offset is mirrored
+            for (device_id, hand_tracked, hand_skeleton_offset, sign) in [
+                (
+                    *LEFT_HAND_ID,
+                    hand_skeletons_enabled[0],
+                    left_hand_skeleton_offset,
+                    1.0,
+                ),
+                (
+                    *RIGHT_HAND_ID,
+                    hand_skeletons_enabled[1],
+                    right_hand_skeleton_offset,
+                    -1.0,
+                ),
+            ] {
+                let motion_config = if hand_tracked {
+                    MotionConfig {
+                        pose_offset: hand_skeleton_offset,
+                        hand_tracked: true,
+                        motion_filters: Some(&controllers.hand_tracking_motion_filters),
+                        ..Default::default()
+                    }
+                } else {
+                    MotionConfig {
+                        pose_offset: Pose {
+                            orientation: Quat::from_euler(
+                                EulerRot::XYZ,
+                                r[0] * DEG_TO_RAD,
+                                sign * r[1] * DEG_TO_RAD,
+                                sign * r[2] * DEG_TO_RAD,
+                            ),
+                            position: Vec3::new(sign * t[0], t[1], t[2]),
+                        },
+                        linear_velocity_cutoff: controllers.linear_velocity_cutoff,
+                        angular_velocity_cutoff: controllers.angular_velocity_cutoff * DEG_TO_RAD,
+                        hand_tracked: false,
+                        motion_filters: Some(&controllers.controller_motion_filters),
+                    }
+                };
+
+                device_motion_configs.insert(device_id, motion_config);
+            }
+        }
 
+        // Body trackers are only recentered and filtered
+        for tracker in &config.body_trackers {
             device_motion_configs.insert(
-                *RIGHT_HAND_ID,
+                body_tracker_id(tracker.role),
                 MotionConfig {
-                    pose_offset: Pose {
-                        orientation: Quat::from_euler(
-                            EulerRot::XYZ,
-                            r[0] * DEG_TO_RAD,
-                            -r[1] * DEG_TO_RAD,
-                            -r[2] * DEG_TO_RAD,
-                        ),
-                        position: Vec3::new(-t[0], t[1], t[2]),
-                    },
-                    linear_velocity_cutoff: controllers.linear_velocity_cutoff,
-                    angular_velocity_cutoff: controllers.angular_velocity_cutoff * DEG_TO_RAD,
+                    motion_filters: Some(&tracker.motion_filters),
+                    ..Default::default()
                 },
             );
         }
 
-        let (left_hand_skeleton_offset, right_hand_skeleton_offset) =
-            get_hand_skeleton_offsets(config);
-
         let mut transformed_motions = vec![];
         for &(device_id, mut motion) in device_motions {
             if device_id == *HEAD_ID {
                 self.last_head_pose = motion.pose;
             }
 
-            if let Some(config) = device_motion_configs.get(&device_id) {
+            if let Some(motion_config) = device_motion_configs.get(&device_id) {
                 // Recenter
                 motion.pose = self.recenter_pose(motion.pose);
 
@@ -178,13 +249,7 @@ impl TrackingManager {
                 motion.angular_velocity = inverse_origin_orientation * motion.angular_velocity;
 
                 // Apply custom transform
-                let pose_offset = if device_id == *LEFT_HAND_ID && hand_skeletons_enabled[0] {
-                    left_hand_skeleton_offset
-                } else if device_id == *RIGHT_HAND_ID && hand_skeletons_enabled[1] {
-                    right_hand_skeleton_offset
-                } else {
-                    config.pose_offset
-                };
+                let pose_offset = motion_config.pose_offset;
                 motion.pose.orientation *= pose_offset.orientation;
                 motion.pose.position += motion.pose.orientation * pose_offset.position;
 
@@ -202,17 +267,25 @@ impl TrackingManager {
                     }
                 }
 
-                if (device_id == *LEFT_HAND_ID && hand_skeletons_enabled[0])
-                    || (device_id == *RIGHT_HAND_ID && hand_skeletons_enabled[1])
-                {
+                if motion_config.hand_tracked {
                     // On hand tracking, velocities seem to make hands overly jittery
                     motion.linear_velocity = Vec3::ZERO;
                     motion.angular_velocity = Vec3::ZERO;
                 } else {
                     motion.linear_velocity =
-                        cutoff(motion.linear_velocity, config.linear_velocity_cutoff);
-                    motion.angular_velocity =
-                        cutoff(motion.angular_velocity, config.angular_velocity_cutoff);
+                        cutoff(motion.linear_velocity, motion_config.linear_velocity_cutoff);
+                    motion.angular_velocity = cutoff(
+                        motion.angular_velocity,
+                        motion_config.angular_velocity_cutoff,
+                    );
+                }
+
+                if let Some(filters_config) = motion_config.motion_filters {
+                    motion = self
+                        .motion_filters
+                        .entry((device_id, motion_config.hand_tracked))
+                        .or_default()
+                        .filter(motion, timestamp, filters_config);
                 }
 
                 transformed_motions.push((device_id, motion));
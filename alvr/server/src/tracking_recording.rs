@@ -0,0 +1,326 @@
+// Recording of the input received from the headset, used to get repeatable motion when testing
+// games and for regression tests of the tracking transformations. Tracking packets are recorded as
+// received, before any transformation, so a recording can be played back with different settings.
+//
+// File layout: same as packet captures. Magic bytes, length-prefixed RecordingHeader, then a
+// sequence of length-prefixed RecordedEvent.
+
+use alvr_common::{
+    anyhow::{bail, Context, Result},
+    warn,
+};
+use alvr_packets::{ButtonEntry, Haptics, Tracking};
+use alvr_sockets::{read_record, write_record};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const RECORDING_MAGIC: &[u8; 8] = b"ALVRTRK1";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordingHeader {
+    // Packets are serialized with bincode, their layout depends on the protocol version
+    pub protocol_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum RecordedInput {
+    Tracking(Box<Tracking>),
+    Buttons(Vec<ButtonEntry>),
+    // Sent to the headset. Only recorded for reference, it is not played back
+    Haptics(Haptics),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecordedEvent {
+    // Time since the start of the recording
+    pub timestamp: Duration,
+    pub input: RecordedInput,
+}
+
+pub struct TrackingRecorder {
+    start_instant: Instant,
+    writer: BufWriter<File>,
+    failed: bool,
+}
+
+impl TrackingRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(RECORDING_MAGIC)?;
+        write_record(
+            &mut writer,
+            &RecordingHeader {
+                protocol_id: alvr_common::protocol_id(),
+            },
+        )?;
+
+        Ok(Self {
+            start_instant: Instant::now(),
+            writer,
+            failed: false,
+        })
+    }
+
+    pub fn record(&mut self, input: RecordedInput) {
+        if self.failed {
+            return;
+        }
+
+        let event = RecordedEvent {
+            timestamp: self.start_instant.elapsed(),
+            input,
+        };
+        if let Err(e) = write_record(&mut self.writer, &event) {
+            warn!("Tracking recording stopped: {e}");
+            self.failed = true;
+        }
+    }
+}
+
+// The whole recording is loaded in memory, to allow looping it
+pub fn load_recording(path: &Path) -> Result<Vec<RecordedEvent>> {
+    let mut reader = BufReader::new(
+        File::open(path)
+            .with_context(|| format!("Cannot open tracking recording {}", path.display()))?,
+    );
+
+    let mut magic = [0; RECORDING_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != RECORDING_MAGIC {
+        bail!("{} is not an ALVR tracking recording", path.display());
+    }
+
+    let Some(header) = read_record::<RecordingHeader>(&mut reader)? else {
+        bail!("Tracking recording is truncated");
+    };
+    if header.protocol_id != alvr_common::protocol_id() {
+        bail!("Tracking recording made with an incompatible version of ALVR");
+    }
+
+    let mut events = vec![];
+    while let Some(event) = read_record(&mut reader)? {
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+// Replays recorded tracking and buttons with the original timing. The playback time starts at the
+// first call to advance().
+pub struct TrackingPlayer {
+    events: Vec<RecordedEvent>,
+    looped: bool,
+    start_instant: Option<Instant>,
+    cursor: usize,
+}
+
+impl TrackingPlayer {
+    pub fn new(events: Vec<RecordedEvent>, looped: bool) -> Self {
+        Self {
+            events,
+            looped,
+            start_instant: None,
+            cursor: 0,
+        }
+    }
+
+    // Moves the playback to the specified instant. Returns the tracking and buttons recorded since
+    // the previous call, in the recorded order
+    pub fn advance(&mut self, now: Instant) -> Vec<RecordedInput> {
+        let start_instant = self.start_instant.get_or_insert(now);
+
+        let mut inputs = vec![];
+        loop {
+            let Some(event) = self.events.get(self.cursor) else {
+                let duration = self.events.last().map(|e| e.timestamp).unwrap_or_default();
+
+                // A recording with no duration would be replayed endlessly in this loop
+                if self.looped && duration > Duration::ZERO {
+                    *start_instant += duration;
+                    self.cursor = 0;
+                    continue;
+                } else {
+                    break;
+                }
+            };
+
+            if *start_instant + event.timestamp > now {
+                break;
+            }
+
+            if !matches!(event.input, RecordedInput::Haptics(_)) {
+                inputs.push(event.input.clone());
+            }
+
+            self.cursor += 1;
+        }
+
+        inputs
+    }
+
+    // Instant at which advance() will return the next event. None before the playback started and
+    // at the end of the recording
+    pub fn next_event_instant(&self) -> Option<Instant> {
+        let start_instant = self.start_instant?;
+
+        self.events
+            .get(self.cursor)
+            .map(|event| start_instant + event.timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking::TrackingManager;
+    use alvr_common::{glam::Vec3, DeviceMotion, Pose, HEAD_ID, LEFT_HAND_ID};
+    use alvr_packets::ButtonValue;
+    use alvr_session::SessionConfig;
+    use std::{env, fs, thread};
+
+    fn tracking(x: f32) -> Tracking {
+        let motion = |position| DeviceMotion {
+            pose: Pose {
+                position,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        Tracking {
+            device_motions: vec![
+                (*HEAD_ID, motion(Vec3::new(x, 1.6, 0.0))),
+                (*LEFT_HAND_ID, motion(Vec3::new(x - 0.2, 1.0, -0.3))),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn button(value: bool) -> RecordedInput {
+        RecordedInput::Buttons(vec![ButtonEntry {
+            path_id: 1,
+            value: ButtonValue::Binary(value),
+        }])
+    }
+
+    fn head_x(input: &RecordedInput) -> Option<f32> {
+        if let RecordedInput::Tracking(tracking) = input {
+            Some(tracking.device_motions[0].1.pose.position.x)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_recording_roundtrip() {
+        let path = env::temp_dir().join(format!("alvr_tracking_test_{}", std::process::id()));
+
+        {
+            let mut recorder = TrackingRecorder::create(&path).unwrap();
+            recorder.record(RecordedInput::Tracking(Box::new(tracking(0.5))));
+            thread::sleep(Duration::from_millis(20));
+            recorder.record(button(true));
+            thread::sleep(Duration::from_millis(20));
+            recorder.record(RecordedInput::Tracking(Box::new(tracking(1.0))));
+        }
+
+        let events = load_recording(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(events.len(), 3);
+        assert!(events[1].timestamp >= events[0].timestamp + Duration::from_millis(20));
+        assert!(events[2].timestamp >= events[1].timestamp + Duration::from_millis(20));
+
+        // Each event is played back in order, at its recorded time relative to the first call
+        let start = Instant::now();
+        let mut player = TrackingPlayer::new(events.clone(), false);
+        assert!(player.next_event_instant().is_none());
+
+        let mut inputs = player.advance(start);
+        inputs.extend(player.advance(start + events[0].timestamp));
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(head_x(&inputs[0]), Some(0.5));
+
+        let next_instant = player.next_event_instant().unwrap();
+        assert_eq!(next_instant, start + events[1].timestamp);
+        assert!(player
+            .advance(next_instant - Duration::from_millis(1))
+            .is_empty());
+        let inputs = player.advance(next_instant);
+        assert!(matches!(&inputs[..], [RecordedInput::Buttons(entries)] if entries.len() == 1));
+
+        let next_instant = player.next_event_instant().unwrap();
+        assert_eq!(next_instant, start + events[2].timestamp);
+        let inputs = player.advance(next_instant);
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(head_x(&inputs[0]), Some(1.0));
+        assert!(player.next_event_instant().is_none());
+
+        // The played back motion goes through the same transformations of live tracking
+        let config = SessionConfig::default().to_settings().headset;
+        let RecordedInput::Tracking(recorded) = &events[0].input else {
+            panic!()
+        };
+        let expected = TrackingManager::new().transform_motions(
+            &config,
+            &tracking(0.5).device_motions,
+            [false, false],
+            Duration::ZERO,
+        );
+        let actual = TrackingManager::new().transform_motions(
+            &config,
+            &recorded.device_motions,
+            [false, false],
+            Duration::ZERO,
+        );
+        assert_eq!(format!("{actual:?}"), format!("{expected:?}"));
+    }
+
+    #[test]
+    fn test_playback_timing() {
+        let event = |ms, input| RecordedEvent {
+            timestamp: Duration::from_millis(ms),
+            input,
+        };
+        let events = vec![
+            event(0, RecordedInput::Tracking(Box::new(tracking(0.0)))),
+            event(10, button(true)),
+            event(20, RecordedInput::Tracking(Box::new(tracking(1.0)))),
+            event(30, button(false)),
+        ];
+        let head_positions =
+            |inputs: &[RecordedInput]| inputs.iter().filter_map(head_x).collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let mut player = TrackingPlayer::new(events.clone(), false);
+        assert_eq!(head_positions(&player.advance(start)), [0.0]);
+
+        let inputs = player.advance(start + Duration::from_millis(25));
+        assert!(matches!(inputs[0], RecordedInput::Buttons(_)));
+        assert_eq!(head_positions(&inputs), [1.0]);
+
+        // Nothing is played back after the end of the recording
+        let inputs = player.advance(start + Duration::from_millis(100));
+        assert!(matches!(&inputs[..], [RecordedInput::Buttons(_)]));
+        assert!(player
+            .advance(start + Duration::from_millis(200))
+            .is_empty());
+        assert!(player.next_event_instant().is_none());
+
+        // When looping, the first event is played again one recording duration later
+        let mut player = TrackingPlayer::new(events, true);
+        player.advance(start);
+        let inputs = player.advance(start + Duration::from_millis(45));
+        assert_eq!(inputs.len(), 5);
+        assert_eq!(head_positions(&inputs), [1.0, 0.0]);
+        assert_eq!(
+            player.next_event_instant(),
+            Some(start + Duration::from_millis(50))
+        );
+    }
+}
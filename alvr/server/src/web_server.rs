@@ -1,6 +1,8 @@
 use crate::{
-    bindings::FfiButtonValue, connection::CLIENTS_TO_BE_REMOVED, DECODER_CONFIG, FILESYSTEM_LAYOUT,
-    SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
+    bindings::FfiButtonValue,
+    connection::{CLIENTS_TO_BE_REMOVED, CUSTOM_HAND_GESTURE_MANAGER, EXTERNAL_TRACKING_MANAGER},
+    rest_api, DECODER_CONFIG, FILESYSTEM_LAYOUT, SERVER_DATA_MANAGER, STATISTICS_MANAGER,
+    VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
 };
 use alvr_common::{
     anyhow::{self, Result},
@@ -87,11 +89,28 @@ async fn websocket<T: Clone + Send + 'static>(
     }
 }
 
+// Clients that are still connected are disconnected before being removed
+pub fn update_client_list(hostname: String, mut action: ClientListAction) {
+    let mut data_manager = SERVER_DATA_MANAGER.write();
+    if matches!(action, ClientListAction::RemoveEntry) {
+        if let Some(entry) = data_manager.client_list().get(&hostname) {
+            if entry.connection_state != ConnectionState::Disconnected {
+                CLIENTS_TO_BE_REMOVED.lock().insert(hostname.clone());
+
+                action = ClientListAction::SetConnectionState(ConnectionState::Disconnecting)
+            };
+        }
+    }
+
+    data_manager.update_client_list(hostname, action);
+}
+
 async fn http_api(
     request: Request<Body>,
     events_sender: broadcast::Sender<Event>,
 ) -> Result<Response<Body>> {
     let mut response = match request.uri().path() {
+        path if path.starts_with(rest_api::API_PREFIX) => rest_api::handle(request).await?,
         // New unified requests
         "/api/dashboard-request" => {
             if let Ok(request) = from_request_body::<ServerRequest>(request).await {
@@ -111,24 +130,8 @@ async fn http_api(
                     ServerRequest::SetValues(descs) => {
                         SERVER_DATA_MANAGER.write().set_values(descs).ok();
                     }
-                    ServerRequest::UpdateClientList {
-                        hostname,
-                        mut action,
-                    } => {
-                        let mut data_manager = SERVER_DATA_MANAGER.write();
-                        if matches!(action, ClientListAction::RemoveEntry) {
-                            if let Some(entry) = data_manager.client_list().get(&hostname) {
-                                if entry.connection_state != ConnectionState::Disconnected {
-                                    CLIENTS_TO_BE_REMOVED.lock().insert(hostname.clone());
-
-                                    action = ClientListAction::SetConnectionState(
-                                        ConnectionState::Disconnecting,
-                                    )
-                                };
-                            }
-                        }
-
-                        data_manager.update_client_list(hostname, action);
+                    ServerRequest::UpdateClientList { hostname, action } => {
+                        update_client_list(hostname, action)
                     }
                     ServerRequest::GetAudioDevices => {
                         if let Ok(list) = SERVER_DATA_MANAGER.read().get_audio_devices_list() {
@@ -137,10 +140,30 @@ async fn http_api(
                     }
                     ServerRequest::CaptureFrame => unsafe { crate::CaptureFrame() },
                     ServerRequest::InsertIdr => unsafe { crate::RequestIDR() },
-                    ServerRequest::StartRecording => {
-                        crate::create_recording_file(SERVER_DATA_MANAGER.read().settings())
-                    }
+                    ServerRequest::StartRecording => crate::create_recording_file(
+                        SERVER_DATA_MANAGER.read().connected_client_settings(),
+                    ),
                     ServerRequest::StopRecording => *VIDEO_RECORDING_FILE.lock() = None,
+                    ServerRequest::StartExternalTrackingCalibration(source_name) => {
+                        if let Some(manager) = &mut *EXTERNAL_TRACKING_MANAGER.lock() {
+                            if let Err(e) = manager.start_calibration(&source_name) {
+                                error!("{e}");
+                            }
+                        } else {
+                            warn!("External tracking is not active. Enable it and start streaming");
+                        }
+                    }
+                    ServerRequest::RecordCustomHandGesture(name) => {
+                        if let Some(manager) = &mut *CUSTOM_HAND_GESTURE_MANAGER.lock() {
+                            if let Err(e) = manager.start_recording(&name) {
+                                error!("{e}");
+                            }
+                        } else {
+                            warn!(
+                                "Custom hand gestures are not active. Add one and start streaming"
+                            );
+                        }
+                    }
                     ServerRequest::FirewallRules(action) => {
                         if alvr_server_io::firewall_rules(action).is_ok() {
                             info!("Setting firewall rules succeeded!");
@@ -250,6 +273,17 @@ async fn http_api(
                 .body(latency.to_string().into())?
         }
         "/api/ping" => reply(StatusCode::OK)?,
+        "/metrics" => {
+            let metrics = STATISTICS_MANAGER
+                .lock()
+                .as_ref()
+                .map(|manager| manager.prometheus_metrics())
+                .unwrap_or_default();
+
+            Response::builder()
+                .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(metrics.into())?
+        }
         other_uri => {
             if other_uri.contains("..") {
                 // Attempted tree traversal
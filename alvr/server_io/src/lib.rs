@@ -33,6 +33,7 @@ pub struct SessionLock<'a> {
     session_desc: &'a mut SessionConfig,
     session_path: &'a Path,
     settings: &'a mut Settings,
+    client_settings: &'a mut HashMap<String, Settings>,
 }
 
 impl Deref for SessionLock<'_> {
@@ -52,10 +53,25 @@ impl Drop for SessionLock<'_> {
     fn drop(&mut self) {
         save_session(self.session_desc, self.session_path).unwrap();
         *self.settings = self.session_desc.to_settings();
+        *self.client_settings = compute_client_settings(self.session_desc);
         alvr_events::send_event(EventType::Session(Box::new(self.session_desc.clone())));
     }
 }
 
+// Only connected clients and the client the driver is configured for are cached, since computing
+// the overrides is expensive
+fn compute_client_settings(session: &SessionConfig) -> HashMap<String, Settings> {
+    session
+        .client_connections
+        .iter()
+        .filter(|(hostname, connection)| {
+            connection.connection_state != ConnectionState::Disconnected
+                || session.streaming_client_hostname.as_ref() == Some(*hostname)
+        })
+        .map(|(hostname, _)| (hostname.clone(), session.to_client_settings(hostname)))
+        .collect()
+}
+
 // Correct usage:
 // SessionManager should be used behind a Mutex. Each write of the session should be preceded by a
 // read, within the same lock.
@@ -65,6 +81,8 @@ pub struct ServerDataManager {
     session: SessionConfig,
     settings: Settings,
     session_path: PathBuf,
+    // Settings of the connected clients, with their profile and override applied
+    client_settings: HashMap<String, Settings>,
 }
 
 impl ServerDataManager {
@@ -77,6 +95,7 @@ impl ServerDataManager {
             session: session_desc.clone(),
             settings: session_desc.to_settings(),
             session_path: session_path.to_owned(),
+            client_settings: compute_client_settings(&session_desc),
         }
     }
 
@@ -87,7 +106,7 @@ impl ServerDataManager {
             return SessionConfig::default();
         }
 
-        let session_json = json::from_str::<json::Value>(&session_string)
+        let mut session_json = json::from_str::<json::Value>(&session_string)
             .unwrap_or_else(|e| {
                 error!(
                     "{} {} {}\n{}",
@@ -104,7 +123,24 @@ impl ServerDataManager {
             return SessionConfig::default();
         }
 
-        json::from_value(session_json.clone()).unwrap_or_else(|_| {
+        let old_version = session_json["server_version"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_owned();
+        let migrated = alvr_session::migrate_session_json(&mut session_json);
+        if migrated {
+            let backup_name = format!("session_backup_v{old_version}.json");
+            fs::write(config_dir.join(&backup_name), &session_string).ok();
+            info!("Session migrated. Old session.json is stored as {backup_name}");
+        }
+
+        if let Ok(session_desc) = json::from_value(session_json.clone()) {
+            if migrated {
+                save_session(&session_desc, session_path).ok();
+            }
+
+            session_desc
+        } else {
             fs::write(config_dir.join("session_old.json"), &session_string).ok();
             let mut session_desc = SessionConfig::default();
             match session_desc.merge_from_json(&session_json) {
@@ -124,7 +160,7 @@ impl ServerDataManager {
             save_session(&session_desc, session_path).ok();
 
             session_desc
-        })
+        }
     }
 
     // prefer settings()
@@ -137,6 +173,7 @@ impl ServerDataManager {
             session_desc: &mut self.session,
             session_path: &self.session_path,
             settings: &mut self.settings,
+            client_settings: &mut self.client_settings,
         }
     }
 
@@ -144,6 +181,23 @@ impl ServerDataManager {
         &self.settings
     }
 
+    // Settings with the profile and override of a connected client applied. Falls back to the
+    // global settings if the client is not connected.
+    pub fn client_settings(&self, hostname: &str) -> &Settings {
+        self.client_settings.get(hostname).unwrap_or(&self.settings)
+    }
+
+    // Settings of the client the driver is configured for, which is the client it streams to. Only
+    // one client at a time can stream. Falls back to the global settings if no client has streamed
+    // yet.
+    pub fn connected_client_settings(&self) -> &Settings {
+        self.session
+            .streaming_client_hostname
+            .as_ref()
+            .and_then(|hostname| self.client_settings.get(hostname))
+            .unwrap_or(&self.settings)
+    }
+
     // Note: "value" can be any session subtree, in json format.
     pub fn set_values(&mut self, descs: Vec<PathValuePair>) -> Result<()> {
         let mut session_json = serde_json::to_value(self.session.clone()).unwrap();
@@ -174,6 +228,7 @@ impl ServerDataManager {
         // session_json has been updated
         self.session = serde_json::from_value(session_json)?;
         self.settings = self.session.to_settings();
+        self.client_settings = compute_client_settings(&self.session);
 
         save_session(&self.session, &self.session_path).unwrap();
         alvr_events::send_event(EventType::Session(Box::new(self.session.clone())));
@@ -204,6 +259,10 @@ impl ServerDataManager {
                         trusted,
                         connection_state: ConnectionState::Disconnected,
                         cabled: false,
+                        identity_key: None,
+                        pending_pairing: None,
+                        settings_profile: None,
+                        settings_override: None,
                     };
                     new_entry.insert(client_connection_desc);
 
@@ -217,9 +276,21 @@ impl ServerDataManager {
                     updated = true;
                 }
             }
+            // A client can be trusted only once its identity key is known through pairing
             ClientListAction::Trust => {
                 if let Entry::Occupied(mut entry) = maybe_client_entry {
-                    entry.get_mut().trusted = true;
+                    let entry = entry.get_mut();
+                    if let Some(pairing) = entry.pending_pairing.take() {
+                        entry.trusted = true;
+                        entry.identity_key = Some(pairing.identity_key);
+
+                        updated = true;
+                    }
+                }
+            }
+            ClientListAction::SetPendingPairing(pairing) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    entry.get_mut().pending_pairing = pairing;
 
                     updated = true;
                 }
@@ -231,6 +302,20 @@ impl ServerDataManager {
                     updated = true;
                 }
             }
+            ClientListAction::SetSettingsProfile(profile) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    entry.get_mut().settings_profile = profile;
+
+                    updated = true;
+                }
+            }
+            ClientListAction::SetSettingsOverride(settings_override) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    entry.get_mut().settings_override = settings_override;
+
+                    updated = true;
+                }
+            }
             ClientListAction::RemoveEntry => {
                 if let Entry::Occupied(entry) = maybe_client_entry {
                     entry.remove_entry();
@@ -260,6 +345,7 @@ impl ServerDataManager {
 
         if updated {
             self.session.client_connections = client_connections;
+            self.client_settings = compute_client_settings(&self.session);
 
             save_session(&self.session, &self.session_path).unwrap();
             alvr_events::send_event(EventType::Session(Box::new(self.session.clone())));
@@ -295,6 +381,7 @@ impl ServerDataManager {
         let host = match self.session.to_settings().audio.linux_backend {
             alvr_session::LinuxAudioBackend::Alsa => cpal::host_from_id(cpal::HostId::Alsa)?,
             alvr_session::LinuxAudioBackend::Jack => cpal::host_from_id(cpal::HostId::Jack)?,
+            alvr_session::LinuxAudioBackend::PipeWire => cpal::default_host(),
         };
         #[cfg(not(target_os = "linux"))]
         let host = cpal::default_host();
@@ -1,12 +1,15 @@
+mod migration;
 mod settings;
 
+pub use migration::migrate_session_json;
 pub use settings::*;
 pub use settings_schema;
 
 use alvr_common::{
     anyhow::{bail, Result},
+    error,
     semver::Version,
-    ConnectionState, ToAny, ALVR_VERSION,
+    warn, ConnectionState, ToAny, ALVR_VERSION,
 };
 use serde::{Deserialize, Serialize};
 use serde_json as json;
@@ -61,6 +64,7 @@ pub struct OpenvrConfig {
     pub sw_thread_count: u32,
     pub controller_is_tracker: bool,
     pub controllers_enabled: bool,
+    pub body_tracker_paths: Vec<String>,
     pub enable_foveated_encoding: bool,
     pub foveation_center_size_x: f32,
     pub foveation_center_size_y: f32,
@@ -102,6 +106,14 @@ pub struct OpenvrConfig {
     pub _controller_profile: i32,
 }
 
+// Identity key of a client that completed the handshake but is not trusted yet
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientPairing {
+    pub identity_key: [u8; 32],
+    // Shown also on the headset. The user must check that the two codes match before trusting
+    pub code: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ClientConnectionConfig {
     pub display_name: String,
@@ -110,6 +122,18 @@ pub struct ClientConnectionConfig {
     pub trusted: bool,
     pub connection_state: ConnectionState,
     pub cabled: bool,
+    // Trusted clients are identified by this key, not by hostname. If a client has no key yet
+    // (added manually or before pairing was introduced), it must be paired before streaming.
+    #[serde(default)]
+    pub identity_key: Option<[u8; 32]>,
+    #[serde(default)]
+    pub pending_pairing: Option<ClientPairing>,
+    // Name of an entry of SessionConfig::settings_profiles
+    #[serde(default)]
+    pub settings_profile: Option<String>,
+    // Sparse session settings, in the same format as session_settings. Applied after the profile
+    #[serde(default)]
+    pub settings_override: Option<json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -117,9 +141,17 @@ pub struct SessionConfig {
     pub server_version: Version,
     pub drivers_backup: Option<DriversBackup>,
     pub openvr_config: OpenvrConfig,
+    // Hostname of the client openvr_config was built for. The driver uses the settings of this
+    // client, also before the client connects again after a driver restart.
+    #[serde(default)]
+    pub streaming_client_hostname: Option<String>,
     // The hashmap key is the hostname
     pub client_connections: HashMap<String, ClientConnectionConfig>,
     pub session_settings: SessionSettings,
+    // Sparse session settings that clients can select by name. Only the specified fields override
+    // session_settings.
+    #[serde(default)]
+    pub settings_profiles: HashMap<String, json::Value>,
 }
 
 impl Default for SessionConfig {
@@ -143,8 +175,10 @@ impl Default for SessionConfig {
                 capture_frame_dir: "/tmp".into(),
                 ..<_>::default()
             },
+            streaming_client_hostname: None,
             client_connections: HashMap::new(),
             session_settings: settings::session_settings_default(),
+            settings_profiles: HashMap::new(),
         }
     }
 }
@@ -213,18 +247,70 @@ impl SessionConfig {
     }
 
     pub fn to_settings(&self) -> Settings {
-        let session_settings_json = json::to_value(&self.session_settings).unwrap();
+        session_settings_to_settings(&self.session_settings)
+    }
+
+    // Session settings with the profile and the override of the client applied on top. Fields of
+    // the overrides that don't exist or have the wrong type are ignored.
+    pub fn client_session_settings(&self, hostname: &str) -> SessionSettings {
+        let Some(connection) = self.client_connections.get(hostname) else {
+            return self.session_settings.clone();
+        };
+
+        let profile = connection.settings_profile.as_ref().and_then(|name| {
+            let profile = self.settings_profiles.get(name);
+            if profile.is_none() {
+                warn!("Settings profile \"{name}\" of client {hostname} not found");
+            }
+
+            profile
+        });
+
         let schema = Settings::schema(settings::session_settings_default());
+        let mut session_settings_json = json::to_value(&self.session_settings).unwrap();
+        for override_json in profile.into_iter().chain(&connection.settings_override) {
+            session_settings_json = extrapolate_session_settings_from_session_settings(
+                &session_settings_json,
+                override_json,
+                &schema,
+            );
+        }
 
-        json::from_value::<Settings>(json_session_settings_to_settings(
-            &session_settings_json,
-            &schema,
-        ))
-        .map_err(|e| dbg!(e))
-        .unwrap()
+        match json::from_value(session_settings_json) {
+            Ok(session_settings) => session_settings,
+            Err(e) => {
+                error!("Invalid settings override for client {hostname}, ignoring it: {e}");
+
+                self.session_settings.clone()
+            }
+        }
+    }
+
+    pub fn to_client_settings(&self, hostname: &str) -> Settings {
+        session_settings_to_settings(&self.client_session_settings(hostname))
+    }
+
+    // Copy of the session as seen by a client, with its settings overrides applied
+    pub fn to_client_session(&self, hostname: &str) -> SessionConfig {
+        SessionConfig {
+            session_settings: self.client_session_settings(hostname),
+            ..self.clone()
+        }
     }
 }
 
+fn session_settings_to_settings(session_settings: &SessionSettings) -> Settings {
+    let session_settings_json = json::to_value(session_settings).unwrap();
+    let schema = Settings::schema(settings::session_settings_default());
+
+    json::from_value::<Settings>(json_session_settings_to_settings(
+        &session_settings_json,
+        &schema,
+    ))
+    .map_err(|e| dbg!(e))
+    .unwrap()
+}
+
 // Current data extrapolation strategy: match both field name and value type exactly.
 // Integer bounds are not validated, if they do not match the schema, deserialization will fail and
 // all data is lost.
@@ -630,4 +716,96 @@ mod tests {
         assert_eq!(settings.video.preferred_fps, 60.0);
         assert!(settings.headset.controllers.as_option().is_none());
     }
+
+    #[test]
+    fn test_client_settings_overrides() {
+        let mut session = SessionConfig::default();
+        session.settings_profiles.insert(
+            "quest3".into(),
+            json::json!({
+                "video": {
+                    "preferred_fps": 120.0,
+                    "foveated_encoding": { "enabled": false }
+                },
+                "unknown_field": 1
+            }),
+        );
+        session.client_connections.insert(
+            "client.alvr".into(),
+            ClientConnectionConfig {
+                display_name: "Quest 3".into(),
+                current_ip: None,
+                manual_ips: HashSet::new(),
+                trusted: true,
+                connection_state: ConnectionState::Disconnected,
+                cabled: false,
+                identity_key: None,
+                pending_pairing: None,
+                settings_profile: Some("quest3".into()),
+                settings_override: Some(json::json!({
+                    "video": { "preferred_fps": 90.0 },
+                    "headset": { "controllers": { "enabled": "wrong type" } }
+                })),
+            },
+        );
+
+        let default_settings = session.to_settings();
+        let client_settings = session.to_client_settings("client.alvr");
+
+        // The override wins over the profile
+        assert_eq!(client_settings.video.preferred_fps, 90.0);
+        assert!(client_settings
+            .video
+            .foveated_encoding
+            .as_option()
+            .is_none());
+        assert_eq!(
+            client_settings.headset.controllers.as_option().is_some(),
+            default_settings.headset.controllers.as_option().is_some()
+        );
+
+        // Other clients and the global settings are not affected
+        assert_eq!(
+            session
+                .to_client_settings("other.client.alvr")
+                .video
+                .preferred_fps,
+            default_settings.video.preferred_fps
+        );
+    }
+
+    #[test]
+    fn test_invalid_client_settings_override() {
+        let mut session = SessionConfig::default();
+        session.client_connections.insert(
+            "client.alvr".into(),
+            ClientConnectionConfig {
+                display_name: "Quest 3".into(),
+                current_ip: None,
+                manual_ips: HashSet::new(),
+                trusted: true,
+                connection_state: ConnectionState::Disconnected,
+                cabled: false,
+                identity_key: None,
+                pending_pairing: None,
+                settings_profile: None,
+                settings_override: Some(json::json!({
+                    "video": { "preferred_fps": 90.0 },
+                    // Out of the range of u16, so the overridden settings cannot be deserialized
+                    "connection": { "stream_port": 100000 }
+                })),
+            },
+        );
+
+        let client_settings = session.to_client_settings("client.alvr");
+
+        assert_eq!(
+            client_settings.connection.stream_port,
+            session.to_settings().connection.stream_port
+        );
+        assert_eq!(
+            client_settings.video.preferred_fps,
+            session.to_settings().video.preferred_fps
+        );
+    }
 }
@@ -0,0 +1,280 @@
+// Explicit upgrades of session.json between releases. The extrapolation in merge_from_json only
+// keeps fields that did not change name or type, so any field that is renamed, moved or changed
+// shape must have a migration step here.
+//
+// Steps receive the raw session JSON and are applied in order to sessions saved by an older
+// version. Note: server_version is updated only by the dashboard, so a step may be applied more
+// than once to the same session. Steps must be idempotent; the helpers below are no-ops when the
+// old field is not found.
+
+use alvr_common::{info, semver::Version};
+use serde_json as json;
+
+pub struct MigrationStep {
+    // The step is applied to sessions saved by versions older than this one
+    pub version: Version,
+    pub description: &'static str,
+    pub apply: fn(&mut json::Value),
+}
+
+fn migration_steps() -> Vec<MigrationStep> {
+    vec![MigrationStep {
+        version: Version::new(20, 0, 0),
+        description: "Rename foveated_rendering to foveated_encoding",
+        apply: |session| {
+            rename_field(
+                session,
+                &["session_settings", "video"],
+                "foveated_rendering",
+                "foveated_encoding",
+            );
+        },
+    }]
+}
+
+fn get_path_mut<'a>(json: &'a mut json::Value, path: &[&str]) -> Option<&'a mut json::Value> {
+    path.iter()
+        .try_fold(json, |json, segment| json.get_mut(*segment))
+}
+
+// Returns true if the field was renamed. Existing fields with the new name are not overwritten
+pub fn rename_field(
+    json: &mut json::Value,
+    parent_path: &[&str],
+    old_name: &str,
+    new_name: &str,
+) -> bool {
+    let Some(json::Value::Object(parent)) = get_path_mut(json, parent_path) else {
+        return false;
+    };
+
+    if parent.contains_key(new_name) {
+        return false;
+    }
+
+    if let Some(value) = parent.remove(old_name) {
+        parent.insert(new_name.to_owned(), value);
+
+        true
+    } else {
+        false
+    }
+}
+
+// Insert a value creating the missing parent objects. The value is returned if a parent is not an
+// object
+// Note: this and the helpers below are not used by any step yet, they are available for the next
+// ones
+#[allow(dead_code)]
+fn insert_at_path(
+    json: &mut json::Value,
+    path: &[&str],
+    value: json::Value,
+) -> Result<(), json::Value> {
+    let Some((name, parent_path)) = path.split_last() else {
+        return Err(value);
+    };
+
+    let mut parent = json;
+    for segment in parent_path {
+        let json::Value::Object(object) = parent else {
+            return Err(value);
+        };
+        parent = object
+            .entry(segment.to_string())
+            .or_insert_with(|| json::Value::Object(json::Map::new()));
+    }
+
+    if let json::Value::Object(parent) = parent {
+        parent.insert(name.to_string(), value);
+
+        Ok(())
+    } else {
+        Err(value)
+    }
+}
+
+// Move a subtree to another place in the hierarchy, creating the missing parent objects. Returns
+// true if the subtree was moved. Existing fields at the destination are not overwritten
+#[allow(dead_code)]
+pub fn move_subtree(json: &mut json::Value, from_path: &[&str], to_path: &[&str]) -> bool {
+    let Some((from_name, from_parent_path)) = from_path.split_last() else {
+        return false;
+    };
+
+    if get_path_mut(json, to_path).is_some() {
+        return false;
+    }
+
+    let Some(json::Value::Object(from_parent)) = get_path_mut(json, from_parent_path) else {
+        return false;
+    };
+    let Some(value) = from_parent.remove(*from_name) else {
+        return false;
+    };
+
+    match insert_at_path(json, to_path, value) {
+        Ok(()) => true,
+        Err(value) => {
+            // Restore the subtree, the source parent still exists
+            insert_at_path(json, from_path, value).ok();
+
+            false
+        }
+    }
+}
+
+// Rename a variant of an enum stored in session settings format: the selected variant is in the
+// "variant" field and the content of each variant is stored in the field with the variant name.
+// Returns true if anything changed.
+#[allow(dead_code)]
+pub fn rename_variant(
+    json: &mut json::Value,
+    choice_path: &[&str],
+    old_variant: &str,
+    new_variant: &str,
+) -> bool {
+    let Some(json::Value::Object(choice)) = get_path_mut(json, choice_path) else {
+        return false;
+    };
+
+    let mut changed = false;
+
+    if choice.get("variant").and_then(|v| v.as_str()) == Some(old_variant) {
+        choice.insert("variant".into(), json::Value::String(new_variant.into()));
+        changed = true;
+    }
+
+    if !choice.contains_key(new_variant) {
+        if let Some(content) = choice.remove(old_variant) {
+            choice.insert(new_variant.into(), content);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn session_version(session_json: &json::Value) -> Version {
+    session_json["server_version"]
+        .as_str()
+        .and_then(|version| Version::parse(version).ok())
+        // Very old sessions are treated as older than any migration step
+        .unwrap_or(Version::new(0, 0, 0))
+}
+
+fn apply_steps(session_json: &mut json::Value, steps: &[MigrationStep]) -> bool {
+    let version = session_version(session_json);
+
+    let mut changed = false;
+    for step in steps.iter().filter(|step| version < step.version) {
+        let old_json = session_json.clone();
+        (step.apply)(session_json);
+
+        if *session_json != old_json {
+            info!("Session migrated to {}: {}", step.version, step.description);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+// Returns true if the session has been modified. In this case, the caller should back up the
+// original session.json.
+pub fn migrate_session_json(session_json: &mut json::Value) -> bool {
+    apply_steps(session_json, &migration_steps())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_are_ordered() {
+        let steps = migration_steps();
+
+        assert!(steps.windows(2).all(|w| w[0].version <= w[1].version));
+    }
+
+    #[test]
+    fn test_migrate_fixture() {
+        let mut session = json::json!({
+            "server_version": "19.1.1",
+            "session_settings": {
+                "video": {
+                    "preferred_fps": 90.0,
+                    "foveated_rendering": {
+                        "enabled": true,
+                        "content": { "center_size_x": 0.5 }
+                    }
+                }
+            }
+        });
+
+        assert!(migrate_session_json(&mut session));
+        assert_eq!(
+            session["session_settings"]["video"]["foveated_encoding"]["content"]["center_size_x"],
+            0.5
+        );
+        assert!(session["session_settings"]["video"]
+            .get("foveated_rendering")
+            .is_none());
+
+        // The steps are idempotent
+        assert!(!migrate_session_json(&mut session));
+    }
+
+    #[test]
+    fn test_newer_sessions_are_not_migrated() {
+        let steps = [MigrationStep {
+            version: Version::new(20, 5, 0),
+            description: "test",
+            apply: |session| {
+                rename_field(session, &[], "a", "b");
+            },
+        }];
+
+        let mut old_session = json::json!({ "server_version": "20.4.0", "a": 1 });
+        assert!(apply_steps(&mut old_session, &steps));
+        assert_eq!(
+            old_session,
+            json::json!({ "server_version": "20.4.0", "b": 1 })
+        );
+
+        let mut new_session = json::json!({ "server_version": "20.5.0", "a": 1 });
+        assert!(!apply_steps(&mut new_session, &steps));
+
+        // Sessions without a valid version are migrated
+        let mut invalid_session = json::json!({ "a": 1 });
+        assert!(apply_steps(&mut invalid_session, &steps));
+    }
+
+    #[test]
+    fn test_move_subtree() {
+        let mut session = json::json!({ "a": { "b": { "c": 1 } } });
+
+        assert!(move_subtree(&mut session, &["a", "b"], &["d", "e", "f"]));
+        assert_eq!(
+            session,
+            json::json!({ "a": {}, "d": { "e": { "f": { "c": 1 } } } })
+        );
+
+        assert!(!move_subtree(&mut session, &["a", "b"], &["d", "e", "f"]));
+    }
+
+    #[test]
+    fn test_rename_variant() {
+        let mut session = json::json!({
+            "mode": { "variant": "Old", "Old": { "x": 1 }, "Other": 2 }
+        });
+
+        assert!(rename_variant(&mut session, &["mode"], "Old", "New"));
+        assert_eq!(
+            session,
+            json::json!({ "mode": { "variant": "New", "New": { "x": 1 }, "Other": 2 } })
+        );
+
+        assert!(!rename_variant(&mut session, &["mode"], "Old", "New"));
+    }
+}
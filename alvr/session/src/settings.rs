@@ -298,6 +298,41 @@ pub enum BitrateMode {
         #[schema(flag = "real-time")]
         decoder_latency_limiter: Switch<DecoderLatencyLimiter>,
     },
+
+    #[schema(strings(
+        display_name = "Delay gradient",
+        help = "Congestion controller in the style of Google Congestion Control. The bitrate is increased while the network latency is stable and it is reduced as soon as the latency starts growing or packets are lost"
+    ))]
+    #[schema(collapsible)]
+    DelayGradient {
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "Mbps")]
+        initial_bitrate_mbps: u64,
+
+        #[schema(strings(display_name = "Maximum bitrate"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "Mbps")]
+        max_bitrate_mbps: u64,
+
+        #[schema(strings(display_name = "Minimum bitrate"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1, max = 100, logarithmic)), suffix = "Mbps")]
+        min_bitrate_mbps: u64,
+
+        #[schema(strings(
+            help = "Bitrate increase per second while the network is not congested"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1.01, max = 2.0, step = 0.01)))]
+        increase_multiplier_per_second: f32,
+
+        #[schema(strings(
+            help = "Fraction of the measured throughput used as the new bitrate when congestion is detected"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.5, max = 0.95, step = 0.01)))]
+        decrease_multiplier: f32,
+    },
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
@@ -516,6 +551,12 @@ pub enum LinuxAudioBackend {
     Alsa,
 
     Jack,
+
+    #[schema(strings(
+        display_name = "PipeWire",
+        help = "Creates the ALVR Game Audio sink and the ALVR Microphone source while streaming and sets them as default devices. Works also with PulseAudio. Custom devices are ignored"
+    ))]
+    PipeWire,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -539,12 +580,81 @@ pub struct AudioBufferingConfig {
     pub batch_ms: u64,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct AudioVideoSyncConfig {
+    #[schema(strings(
+        help = "Positive values delay audio with respect to video. Sound produced at a distance can be simulated with a small positive offset"
+    ))]
+    #[schema(gui(slider(min = -100.0, max = 100.0, step = 1.0)), suffix = "ms")]
+    pub target_offset_ms: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum AudioCodecConfig {
+    #[schema(strings(
+        display_name = "PCM",
+        help = "Uncompressed audio. Used also when the device sample rate is not supported by Opus"
+    ))]
+    Pcm,
+    Opus {
+        #[schema(gui(slider(min = 16, max = 256, step = 8)), suffix = "kbps")]
+        bitrate_kbps: u32,
+
+        #[schema(strings(
+            display_name = "In-band FEC",
+            help = "Adds redundancy to each packet so that a single lost packet can be recovered"
+        ))]
+        in_band_fec: bool,
+    },
+}
+
+// Coefficients used to fold surround channels into the left and right channels. The front left and
+// right channels are always mixed with level 1.
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum AudioDownmixConfig {
+    #[schema(strings(help = "ITU-R BS.775 coefficients, normalized to avoid clipping"))]
+    Standard,
+    Custom {
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+        center_level: f32,
+
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+        surround_level: f32,
+
+        #[schema(strings(display_name = "LFE level"))]
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+        lfe_level: f32,
+
+        #[schema(strings(help = "Scale the coefficients so that the output cannot clip"))]
+        normalize: bool,
+    },
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct GameAudioConfig {
     pub device: Option<CustomAudioDeviceConfig>,
     pub mute_when_streaming: bool,
+    pub codec: AudioCodecConfig,
+
+    #[schema(strings(
+        help = "Stream 5.1 or 7.1 audio if the device has these channels. Otherwise audio is downmixed to stereo by the streamer. Opus supports only stereo, PCM will be used for surround audio"
+    ))]
+    pub surround: bool,
+
+    #[schema(strings(
+        help = "Used to downmix surround audio, by the streamer or by clients that cannot play it"
+    ))]
+    pub downmix: AudioDownmixConfig,
+
     pub buffering: AudioBufferingConfig,
+
+    #[schema(strings(
+        display_name = "Audio/video synchronization",
+        help = "Measures the offset between audio and video on the client and slightly resamples audio to keep the target offset"
+    ))]
+    pub audio_video_sync: Switch<AudioVideoSyncConfig>,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -572,13 +682,16 @@ pub enum MicrophoneDevicesConfig {
 #[schema(collapsible)]
 pub struct MicrophoneConfig {
     pub devices: MicrophoneDevicesConfig,
+    pub codec: AudioCodecConfig,
     pub buffering: AudioBufferingConfig,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct AudioConfig {
-    #[schema(strings(help = "ALSA is recommended for most PulseAudio or PipeWire-based setups"))]
+    #[schema(strings(
+        help = "ALSA and Jack require loopback devices to be configured manually. PipeWire creates the virtual devices automatically"
+    ))]
     pub linux_backend: LinuxAudioBackend,
 
     pub game_audio: Switch<GameAudioConfig>,
@@ -613,6 +726,16 @@ pub enum FaceTrackingSinkConfig {
     VrchatEyeOsc { port: u16 },
     #[schema(strings(display_name = "VRCFaceTracking"))]
     VrcFaceTracking,
+    #[schema(strings(
+        display_name = "OSC mapping file",
+        help = "Sends the face data fields listed in a TOML or JSON mapping file to custom OSC addresses, with scaling and smoothing. Relative paths start from the ALVR configuration directory"
+    ))]
+    OscMapping { port: u16, mapping_file: String },
+    #[schema(strings(
+        display_name = "ARKit OSC",
+        help = "Converts the face expressions to the 52 ARKit blendshapes and sends each of them to <address prefix><blendshape name>, for example /avatar/parameters/eyeBlinkLeft"
+    ))]
+    ArkitOsc { port: u16, address_prefix: String },
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -680,6 +803,49 @@ pub struct ButtonBindingTarget {
     pub binary_conditions: Vec<String>,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CustomHandGestureHand {
+    Left,
+    Right,
+    Both,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum CustomHandGestureType {
+    Static,
+    Dynamic {
+        #[schema(strings(help = "Length of the recorded trajectory"))]
+        #[schema(gui(slider(min = 0.5, max = 3.0, step = 0.1)), suffix = "s")]
+        duration_s: f32,
+    },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct CustomHandGestureConfig {
+    #[schema(strings(
+        help = "Name of the template. Record it from the Debug tab while streaming"
+    ))]
+    pub name: String,
+
+    #[schema(strings(
+        help = "Hand that performs the gesture. Templates recorded with one hand also match the other"
+    ))]
+    pub hand: CustomHandGestureHand,
+
+    #[schema(strings(help = r#"Static: a hand pose, active while it is held.
+Dynamic: a short movement of the fingers and the hand, triggers a short button press."#))]
+    pub gesture_type: CustomHandGestureType,
+
+    #[schema(strings(
+        help = "Maximum average distance of the joints from the template to recognize the gesture"
+    ))]
+    #[schema(gui(slider(min = 0.2, max = 5.0, step = 0.1)), suffix = "cm")]
+    pub match_distance: f32,
+
+    #[schema(strings(help = "The gesture is a binary input mapped to these buttons"))]
+    pub bindings: Vec<ButtonBindingTarget>,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct AutomaticButtonMappingConfig {
@@ -780,6 +946,144 @@ pub struct HapticsConfig {
     pub min_duration_s: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct OneEuroFilterConfig {
+    #[schema(strings(
+        display_name = "Minimum cutoff",
+        help = "Cutoff frequency when the device is still. Lower values remove more jitter"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.1, max = 10.0, step = 0.1)), suffix = "Hz")]
+    pub min_cutoff_hz: f32,
+
+    #[schema(strings(
+        help = "Increase of the cutoff frequency with speed. Higher values reduce lag"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 50.0, step = 0.5)), suffix = "Hz/(m/s)")]
+    pub linear_beta: f32,
+
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 10.0, step = 0.1)), suffix = "Hz/(rad/s)")]
+    pub angular_beta: f32,
+
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.1, max = 10.0, step = 0.1)), suffix = "Hz")]
+    pub derivative_cutoff_hz: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct KalmanPredictionConfig {
+    #[schema(strings(help = "How far ahead the position is predicted"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 100.0, step = 1.0)), suffix = "ms")]
+    pub horizon_ms: f32,
+
+    #[schema(strings(
+        help = "Expected variability of the acceleration. Higher values follow sudden movements faster, lower values reject more noise"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 1000.0, logarithmic)), suffix = "m/s³")]
+    pub process_noise: f32,
+
+    #[schema(strings(help = "Expected noise of the tracked position"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.1, max = 10.0, step = 0.1)), suffix = "mm")]
+    pub measurement_noise_mm: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct JitterRejectionConfig {
+    #[schema(strings(help = "Position changes smaller than this are ignored"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 10.0, step = 0.1)), suffix = "mm")]
+    pub position_threshold_mm: f32,
+
+    #[schema(strings(help = "Rotations smaller than this are ignored"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 5.0, step = 0.1)), suffix = "°")]
+    pub rotation_threshold_deg: f32,
+}
+
+// Filters are applied in order: jitter rejection, One Euro filter, prediction
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct MotionFiltersConfig {
+    #[schema(strings(
+        help = "Hold the pose until the device moves more than a threshold. Useful for hand tracking"
+    ))]
+    #[schema(flag = "real-time")]
+    pub jitter_rejection: Switch<JitterRejectionConfig>,
+
+    #[schema(strings(
+        display_name = "One Euro filter",
+        help = "Adaptive low-pass filter: smooths the pose when the device moves slowly and reduces lag when it moves fast"
+    ))]
+    #[schema(flag = "real-time")]
+    pub one_euro_filter: Switch<OneEuroFilterConfig>,
+
+    #[schema(strings(
+        display_name = "Kalman prediction",
+        help = "Predict the position with a constant acceleration model. SteamVR prediction is still applied on top of this"
+    ))]
+    #[schema(flag = "real-time")]
+    pub prediction: Switch<KalmanPredictionConfig>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HandPointerHand {
+    Left,
+    Right,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HandPointerMode {
+    Mouse,
+    Keyboard,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum HandPointerOutput {
+    #[schema(strings(
+        help = "Mouse and keyboard events of the operating system. On Linux this requires xdotool and X11"
+    ))]
+    Desktop,
+    #[schema(strings(
+        help = "/alvr/pointer/position (x, y), /alvr/pointer/click (bool), /alvr/pointer/hovered_key and /alvr/pointer/key (string), for overlays"
+    ))]
+    Osc { port: u16 },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct HandPointerConfig {
+    pub hand: HandPointerHand,
+
+    #[schema(strings(
+        help = r#"Mouse: the virtual screen maps to the desktop, pinch to click.
+Keyboard: the virtual screen is a keyboard with rows 1-0, Q-P, A-L, Z-M and Backspace/Space/Enter, pinch to type."#
+    ))]
+    pub mode: HandPointerMode,
+
+    pub output: HandPointerOutput,
+
+    #[schema(strings(
+        help = "The virtual screen is placed in front of the head when the hand starts being tracked"
+    ))]
+    #[schema(gui(slider(min = 0.2, max = 2.0, step = 0.05)), suffix = "m")]
+    pub screen_distance: f32,
+
+    #[schema(gui(slider(min = 0.1, max = 2.0, step = 0.05)), suffix = "m")]
+    pub screen_width: f32,
+
+    #[schema(gui(slider(min = 0.1, max = 2.0, step = 0.05)), suffix = "m")]
+    pub screen_height: f32,
+
+    #[schema(strings(help = "Distance between the thumb and index tips to register a click"))]
+    #[schema(gui(slider(min = 0.5, max = 5.0, step = 0.1)), suffix = "cm")]
+    pub pinch_distance: f32,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct ControllersConfig {
@@ -802,6 +1106,16 @@ pub struct ControllersConfig {
     #[schema(strings(help = "List of OpenXR-syle paths"))]
     pub button_mappings: Option<Vec<(String, Vec<ButtonBindingTarget>)>>,
 
+    #[schema(flag = "real-time")]
+    #[schema(strings(
+        help = r"One rule per entry, in the form `<destination path> = <expression>`. Rules override the mappings above for their destination.
+Operators: `|`, `&`, `!`, `>`, `<`, parentheses. Functions (durations in milliseconds): long_press(input, ms), short_press(input, ms), double_tap(input, ms), toggle(input), sequence(ms, input1, input2, ...), dpad_up/dpad_down/dpad_left/dpad_right(x, y, threshold)"
+    ))]
+    pub button_mapping_macros: Vec<String>,
+
+    #[schema(strings(help = "Hand gestures recorded by the user, bound to any button"))]
+    pub custom_hand_gestures: Vec<CustomHandGestureConfig>,
+
     pub button_mapping_config: AutomaticButtonMappingConfig,
 
     #[schema(flag = "real-time")]
@@ -810,6 +1124,11 @@ pub struct ControllersConfig {
     ))]
     pub gestures: Switch<HandGestureConfig>,
 
+    #[schema(strings(
+        help = "Point with the palm of one hand at a virtual screen to move the mouse or type, pinch to click. The pinch of that hand still triggers the gestures above"
+    ))]
+    pub hand_pointer: Switch<HandPointerConfig>,
+
     #[schema(strings(
         display_name = "Prediction",
         help = r"Higher values make the controllers track smoother.
@@ -829,6 +1148,12 @@ Currently this cannot be reliably estimated automatically. The correct value sho
     #[schema(gui(slider(min = 0.0, max = 100.0, step = 1.0)), suffix = "°/s")]
     pub angular_velocity_cutoff: f32,
 
+    #[schema(strings(help = "Filters for controllers tracked by the headset"))]
+    pub controller_motion_filters: MotionFiltersConfig,
+
+    #[schema(strings(help = "Filters for controllers emulated with hand tracking"))]
+    pub hand_tracking_motion_filters: MotionFiltersConfig,
+
     #[schema(flag = "real-time")]
     // note: logarithmic scale seems to be glitchy for this control
     #[schema(gui(slider(min = -0.5, max = 0.5, step = 0.001)), suffix = "m")]
@@ -868,6 +1193,75 @@ pub enum RotationRecenteringMode {
     Tilted,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BodyTrackerRole {
+    Waist,
+    Chest,
+    LeftFoot,
+    RightFoot,
+    LeftKnee,
+    RightKnee,
+    LeftElbow,
+    RightElbow,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct BodyTrackerConfig {
+    #[schema(strings(
+        help = "Tracked point that drives this tracker. It is also the tracker role in SteamVR"
+    ))]
+    pub role: BodyTrackerRole,
+
+    pub motion_filters: MotionFiltersConfig,
+
+    pub extra_openvr_props: Vec<OpenvrProperty>,
+}
+
+// Transform from the space of an external tracking system to the headset space
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct ExternalTrackingCalibration {
+    #[schema(gui(slider(min = -180.0, max = 180.0, step = 0.1)), suffix = "°")]
+    pub yaw_deg: f32,
+
+    #[schema(suffix = "m")]
+    pub position: [f32; 3],
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct ExternalTrackingSourceConfig {
+    #[schema(strings(help = "Used to identify the source when calibrating"))]
+    pub name: String,
+
+    #[schema(strings(
+        help = r#"Accepted messages are <prefix><tracker>/position and <prefix><tracker>/rotation (VRChat OSC trackers convention), or <prefix><tracker>/pose with 7 floats: position and orientation quaternion (x, y, z, w)."#
+    ))]
+    pub address_prefix: String,
+
+    #[schema(strings(help = "Tracker name -> OpenXR-style path of the driven device"))]
+    pub trackers: Vec<(String, String)>,
+
+    #[schema(strings(
+        help = "Tracker that is held against the headset (or estimates the head pose) during calibration"
+    ))]
+    pub calibration_tracker: String,
+
+    #[schema(strings(help = "Computed by the calibration from the Debug tab"))]
+    pub calibration: ExternalTrackingCalibration,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct ExternalTrackingConfig {
+    #[schema(strings(
+        help = "Local address that receives the OSC messages. Use 0.0.0.0 to receive them from other devices in the network"
+    ))]
+    pub bind_address: String,
+
+    #[schema(strings(help = "Local UDP port that receives the OSC messages"))]
+    pub port: u16,
+
+    pub sources: Vec<ExternalTrackingSourceConfig>,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct HeadsetConfig {
@@ -885,9 +1279,22 @@ pub struct HeadsetConfig {
 
     pub face_tracking: Switch<FaceTrackingConfig>,
 
+    pub head_motion_filters: MotionFiltersConfig,
+
     #[schema(flag = "steamvr-restart")]
     pub controllers: Switch<ControllersConfig>,
 
+    #[schema(strings(
+        help = "Extra tracked points exposed to SteamVR as Vive trackers, for full-body tracking. Their motion can come from the headset or from external sources"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub body_trackers: Vec<BodyTrackerConfig>,
+
+    #[schema(strings(
+        help = "Poses of devices received from external tracking systems (mocap, IMU trackers) through OSC. They replace the poses sent by the headset for the same devices"
+    ))]
+    pub external_tracking: Switch<ExternalTrackingConfig>,
+
     #[schema(strings(
         help = r#"Disabled: the playspace origin is determined by the room-scale guardian setup.
 Local floor: the origin is on the floor and resets when long pressing the oculus button.
@@ -912,6 +1319,8 @@ pub enum SocketProtocol {
     Udp,
     #[schema(strings(display_name = "TCP"))]
     Tcp,
+    #[schema(strings(display_name = "QUIC"))]
+    Quic,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -929,12 +1338,22 @@ pub enum SocketBufferSize {
     Custom(#[schema(suffix = "B")] u32),
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct ForwardErrorCorrectionConfig {
+    #[schema(strings(
+        help = "Number of video shards protected by one parity shard. At most one lost shard per group can be recovered. Lower values increase the bandwidth overhead."
+    ))]
+    #[schema(gui(slider(min = 1, max = 64)), suffix = " shards")]
+    pub group_size: u32,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct ConnectionConfig {
     #[schema(strings(
         help = r#"UDP: Faster, but less stable than TCP. Try this if your network is well optimized and free of interference.
-TCP: Slower than UDP, but more stable. Pick this if you experience video or audio stutters with UDP."#
+TCP: Slower than UDP, but more stable. Pick this if you experience video or audio stutters with UDP.
+QUIC: Encrypted and with congestion control. Video is sent unreliably like UDP, the rest reliably like TCP. Use a packet size of 1200B or less to avoid falling back to reliable video transmission."#
     ))]
     pub stream_protocol: SocketProtocol,
 
@@ -970,6 +1389,13 @@ For now works only on Windows+Nvidia"#
     ))]
     pub avoid_video_glitching: bool,
 
+    #[schema(strings(
+        display_name = "Video forward error correction",
+        help = r#"Send parity shards alongside the video shards, so that the client can rebuild video packets that lost some shards instead of requesting a IDR frame.
+Useful on UDP with packet loss."#
+    ))]
+    pub video_fec: Switch<ForwardErrorCorrectionConfig>,
+
     #[schema(strings(
         help = "Reduce minimum delay between IDR keyframes from 100ms to 5ms. Use on networks with high packet loss."
     ))]
@@ -1024,6 +1450,17 @@ pub struct RawEventsConfig {
     pub hide_spammy_events: bool,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct FrameStatisticsLogConfig {
+    #[schema(strings(help = "When the file reaches this size, a new file is started"))]
+    #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "MB")]
+    pub max_file_size_mb: u64,
+
+    #[schema(strings(help = "Number of rotated files to keep, the oldest ones are deleted"))]
+    #[schema(gui(slider(min = 1, max = 20)))]
+    pub max_rotated_files: usize,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct LoggingConfig {
@@ -1038,6 +1475,12 @@ pub struct LoggingConfig {
     #[schema(flag = "real-time")]
     pub log_button_presses: bool,
 
+    #[schema(flag = "real-time")]
+    #[schema(strings(
+        help = "Log the button values sent to SteamVR by the button mappings and the state of the hand gestures"
+    ))]
+    pub log_button_mappings: bool,
+
     #[schema(flag = "real-time")]
     pub log_haptics: bool,
 
@@ -1047,6 +1490,11 @@ pub struct LoggingConfig {
     #[schema(flag = "real-time")]
     pub show_raw_events: Switch<RawEventsConfig>,
 
+    #[schema(strings(
+        help = "Write the statistics of every frame into frame_statistics.csv in the log directory. A new file is started for every connection."
+    ))]
+    pub log_frame_statistics: Switch<FrameStatisticsLogConfig>,
+
     #[schema(strings(help = "This applies only to certain error or warning messages."))]
     #[schema(flag = "steamvr-restart")]
     pub prefer_backtrace: bool,
@@ -1085,6 +1533,14 @@ pub struct RollingVideoFilesConfig {
     pub duration_s: u64,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct TrackingPlaybackConfig {
+    #[schema(strings(help = "Relative paths start from the log folder"))]
+    pub recording_file: String,
+    #[schema(strings(display_name = "Loop"))]
+    pub looped: bool,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct CaptureConfig {
@@ -1093,6 +1549,23 @@ pub struct CaptureConfig {
 
     pub rolling_video_files: Switch<RollingVideoFilesConfig>,
 
+    #[schema(strings(
+        display_name = "Capture network packets at client connection",
+        help = "Save every control and stream packet to a capture file, on the streamer in the log folder and on the client in the app config folder. The capture can be replayed with alvr_replay"
+    ))]
+    pub packet_capture: bool,
+
+    #[schema(strings(
+        display_name = "Record tracking at client connection",
+        help = "Save tracking data, button presses and haptics received while streaming to a .alvrtrk file in the log folder"
+    ))]
+    pub tracking_recording: bool,
+
+    #[schema(strings(
+        help = "Replace the motion and buttons of the headset with a tracking recording. The headset is still needed to display the stream"
+    ))]
+    pub tracking_playback: Switch<TrackingPlaybackConfig>,
+
     #[schema(flag = "steamvr-restart")]
     pub capture_frame_dir: String,
 }
@@ -1112,6 +1585,88 @@ pub struct Patches {
     pub linux_async_reprojection: bool,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum JitterDistribution {
+    None,
+
+    #[schema(strings(help = "Delay varies uniformly in the range delay ± max jitter"))]
+    Uniform {
+        #[schema(gui(slider(min = 0, max = 100)), suffix = "ms")]
+        max_jitter_ms: u64,
+    },
+
+    #[schema(strings(help = "Delay follows a normal distribution centered on the delay"))]
+    Normal {
+        #[schema(gui(slider(min = 0.0, max = 50.0, step = 0.5)), suffix = "ms")]
+        std_dev_ms: f32,
+    },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct BandwidthLimitConfig {
+    #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "Mbps")]
+    pub mbps: u64,
+
+    #[schema(strings(
+        help = "Packets that would wait longer than this in the bottleneck queue are dropped"
+    ))]
+    #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "ms")]
+    pub max_queue_delay_ms: u64,
+}
+
+// Two-state Markov chain, evaluated for each packet
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct GilbertElliottConfig {
+    #[schema(strings(help = "Probability of entering the bad state after each packet"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub good_to_bad_probability: f32,
+
+    #[schema(strings(help = "Probability of returning to the good state after each packet"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub bad_to_good_probability: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub good_loss_probability: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub bad_loss_probability: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct ReorderingConfig {
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+    pub probability: f32,
+
+    #[schema(strings(help = "Additional delay of reordered packets"))]
+    #[schema(gui(slider(min = 1, max = 100)), suffix = "ms")]
+    pub extra_delay_ms: u64,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct NetworkEmulatorConfig {
+    #[schema(gui(slider(min = 0, max = 500, logarithmic)), suffix = "ms")]
+    pub delay_ms: u64,
+
+    pub jitter: JitterDistribution,
+
+    pub bandwidth_limit: Switch<BandwidthLimitConfig>,
+
+    #[schema(strings(help = "Gilbert-Elliott loss model"))]
+    pub burst_loss: Switch<GilbertElliottConfig>,
+
+    pub reordering: Switch<ReorderingConfig>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct DebugConfig {
+    #[schema(strings(
+        help = r#"Degrade the stream sockets on purpose, to test the behavior on bad networks. Applied to the packets sent by each peer.
+Control packets are not affected. DO NOT USE FOR NORMAL PLAY."#
+    ))]
+    pub network_emulator: Switch<NetworkEmulatorConfig>,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct Settings {
     pub video: VideoConfig,
@@ -1122,9 +1677,40 @@ pub struct Settings {
     pub steamvr_launcher: SteamvrLauncher,
     pub capture: CaptureConfig,
     pub patches: Patches,
+    pub debug: DebugConfig,
     pub open_setup_wizard: bool,
 }
 
+fn motion_filters_default() -> MotionFiltersConfigDefault {
+    MotionFiltersConfigDefault {
+        gui_collapsed: true,
+        jitter_rejection: SwitchDefault {
+            enabled: false,
+            content: JitterRejectionConfigDefault {
+                position_threshold_mm: 1.5,
+                rotation_threshold_deg: 1.0,
+            },
+        },
+        one_euro_filter: SwitchDefault {
+            enabled: false,
+            content: OneEuroFilterConfigDefault {
+                min_cutoff_hz: 3.0,
+                linear_beta: 10.0,
+                angular_beta: 1.0,
+                derivative_cutoff_hz: 1.0,
+            },
+        },
+        prediction: SwitchDefault {
+            enabled: false,
+            content: KalmanPredictionConfigDefault {
+                horizon_ms: 10.0,
+                process_noise: 50.0,
+                measurement_noise_mm: 1.0,
+            },
+        },
+    }
+}
+
 pub fn session_settings_default() -> SettingsDefault {
     let view_resolution = FrameSizeDefault {
         variant: FrameSizeDefaultVariant::Absolute,
@@ -1142,6 +1728,13 @@ pub fn session_settings_default() -> SettingsDefault {
         Index: 0,
         variant: CustomAudioDeviceConfigDefaultVariant::NameSubstring,
     };
+    let default_audio_codec = AudioCodecConfigDefault {
+        Opus: AudioCodecConfigOpusDefault {
+            bitrate_kbps: 128,
+            in_band_fec: true,
+        },
+        variant: AudioCodecConfigDefaultVariant::Opus,
+    };
     let default_custom_openvr_props = VectorDefault {
         gui_collapsed: true,
         element: OPENVR_PROPS_DEFAULT.clone(),
@@ -1197,6 +1790,14 @@ pub fn session_settings_default() -> SettingsDefault {
                             },
                         },
                     },
+                    DelayGradient: BitrateModeDelayGradientDefault {
+                        gui_collapsed: true,
+                        initial_bitrate_mbps: 30,
+                        max_bitrate_mbps: 200,
+                        min_bitrate_mbps: 5,
+                        increase_multiplier_per_second: 1.08,
+                        decrease_multiplier: 0.85,
+                    },
                     variant: BitrateModeDefaultVariant::ConstantMbps,
                 },
                 adapt_to_framerate: SwitchDefault {
@@ -1354,11 +1955,29 @@ pub fn session_settings_default() -> SettingsDefault {
                         content: default_custom_audio_device.clone(),
                     },
                     mute_when_streaming: true,
+                    codec: default_audio_codec.clone(),
+                    surround: false,
+                    downmix: AudioDownmixConfigDefault {
+                        Custom: AudioDownmixConfigCustomDefault {
+                            center_level: 0.71,
+                            surround_level: 0.71,
+                            lfe_level: 0.0,
+                            normalize: true,
+                        },
+                        variant: AudioDownmixConfigDefaultVariant::Standard,
+                    },
                     buffering: AudioBufferingConfigDefault {
                         gui_collapsed: true,
                         average_buffering_ms: 50,
                         batch_ms: 10,
                     },
+                    audio_video_sync: SwitchDefault {
+                        enabled: true,
+                        content: AudioVideoSyncConfigDefault {
+                            gui_collapsed: true,
+                            target_offset_ms: 0.0,
+                        },
+                    },
                 },
             },
             microphone: SwitchDefault {
@@ -1372,6 +1991,7 @@ pub fn session_settings_default() -> SettingsDefault {
                         },
                         variant: MicrophoneDevicesConfigDefaultVariant::Automatic,
                     },
+                    codec: default_audio_codec,
                     buffering: AudioBufferingConfigDefault {
                         gui_collapsed: true,
                         average_buffering_ms: 50,
@@ -1404,10 +2024,19 @@ pub fn session_settings_default() -> SettingsDefault {
                     },
                     sink: FaceTrackingSinkConfigDefault {
                         VrchatEyeOsc: FaceTrackingSinkConfigVrchatEyeOscDefault { port: 9000 },
+                        OscMapping: FaceTrackingSinkConfigOscMappingDefault {
+                            port: 9000,
+                            mapping_file: "face_tracking_osc.toml".into(),
+                        },
+                        ArkitOsc: FaceTrackingSinkConfigArkitOscDefault {
+                            port: 9000,
+                            address_prefix: "/avatar/parameters/".into(),
+                        },
                         variant: FaceTrackingSinkConfigDefaultVariant::VrchatEyeOsc,
                     },
                 },
             },
+            head_motion_filters: motion_filters_default(),
             controllers: SwitchDefault {
                 enabled: true,
                 content: ControllersConfigDefault {
@@ -1425,7 +2054,7 @@ pub fn session_settings_default() -> SettingsDefault {
                         },
                         variant: ControllersEmulationModeDefaultVariant::Quest2Touch,
                     },
-                    extra_openvr_props: default_custom_openvr_props,
+                    extra_openvr_props: default_custom_openvr_props.clone(),
                     button_mappings: OptionalDefault {
                         set: false,
                         content: DictionaryDefault {
@@ -1458,6 +2087,50 @@ pub fn session_settings_default() -> SettingsDefault {
                             content: vec![],
                         },
                     },
+                    button_mapping_macros: VectorDefault {
+                        gui_collapsed: false,
+                        element: "/user/hand/right/input/b/click = long_press(/user/hand/right/input/a/click, 500)".into(),
+                        content: vec![],
+                    },
+                    custom_hand_gestures: VectorDefault {
+                        gui_collapsed: false,
+                        element: CustomHandGestureConfigDefault {
+                            name: "gesture".into(),
+                            hand: CustomHandGestureHandDefault {
+                                variant: CustomHandGestureHandDefaultVariant::Both,
+                            },
+                            gesture_type: CustomHandGestureTypeDefault {
+                                Dynamic: CustomHandGestureTypeDynamicDefault { duration_s: 1.0 },
+                                variant: CustomHandGestureTypeDefaultVariant::Static,
+                            },
+                            match_distance: 1.5,
+                            bindings: VectorDefault {
+                                gui_collapsed: false,
+                                element: ButtonBindingTargetDefault {
+                                    destination: "/user/hand/right/input/a/click".into(),
+                                    mapping_type: ButtonMappingTypeDefault {
+                                        HysteresisThreshold: HysteresisThresholdDefault {
+                                            value: 0.5,
+                                            deviation: 0.05,
+                                        },
+                                        BinaryToScalar: BinaryToScalarStatesDefault {
+                                            off: 0.0,
+                                            on: 1.0,
+                                        },
+                                        Remap: RangeDefault { min: 0.0, max: 1.0 },
+                                        variant: ButtonMappingTypeDefaultVariant::Passthrough,
+                                    },
+                                    binary_conditions: VectorDefault {
+                                        gui_collapsed: true,
+                                        element: "/user/hand/right/input/trigger/touch".into(),
+                                        content: vec![],
+                                    },
+                                },
+                                content: vec![],
+                            },
+                        },
+                        content: vec![],
+                    },
                     button_mapping_config: AutomaticButtonMappingConfigDefault {
                         gui_collapsed: true,
                         click_threshold: HysteresisThresholdDefault {
@@ -1488,9 +2161,31 @@ pub fn session_settings_default() -> SettingsDefault {
                             deactivation_delay: 100,
                         },
                     },
+                    hand_pointer: SwitchDefault {
+                        enabled: false,
+                        content: HandPointerConfigDefault {
+                            gui_collapsed: true,
+                            hand: HandPointerHandDefault {
+                                variant: HandPointerHandDefaultVariant::Right,
+                            },
+                            mode: HandPointerModeDefault {
+                                variant: HandPointerModeDefaultVariant::Mouse,
+                            },
+                            output: HandPointerOutputDefault {
+                                Osc: HandPointerOutputOscDefault { port: 9003 },
+                                variant: HandPointerOutputDefaultVariant::Desktop,
+                            },
+                            screen_distance: 0.6,
+                            screen_width: 0.6,
+                            screen_height: 0.35,
+                            pinch_distance: 1.5,
+                        },
+                    },
                     steamvr_pipeline_frames: 3.0,
                     linear_velocity_cutoff: 0.05,
                     angular_velocity_cutoff: 10.0,
+                    controller_motion_filters: motion_filters_default(),
+                    hand_tracking_motion_filters: motion_filters_default(),
                     left_controller_position_offset: ArrayDefault {
                         gui_collapsed: true,
                         content: [0.0, 0.0, -0.11],
@@ -1518,6 +2213,46 @@ pub fn session_settings_default() -> SettingsDefault {
                     },
                 },
             },
+            body_trackers: VectorDefault {
+                gui_collapsed: true,
+                element: BodyTrackerConfigDefault {
+                    role: BodyTrackerRoleDefault {
+                        variant: BodyTrackerRoleDefaultVariant::Waist,
+                    },
+                    motion_filters: motion_filters_default(),
+                    extra_openvr_props: default_custom_openvr_props,
+                },
+                content: vec![],
+            },
+            external_tracking: SwitchDefault {
+                enabled: false,
+                content: ExternalTrackingConfigDefault {
+                    bind_address: "127.0.0.1".into(),
+                    port: 9002,
+                    sources: VectorDefault {
+                        gui_collapsed: false,
+                        element: ExternalTrackingSourceConfigDefault {
+                            name: "SlimeVR".into(),
+                            address_prefix: "/tracking/trackers/".into(),
+                            trackers: DictionaryDefault {
+                                gui_collapsed: false,
+                                key: "1".into(),
+                                value: "/user/vive_tracker_htcx/role/waist".into(),
+                                content: vec![],
+                            },
+                            calibration_tracker: "head".into(),
+                            calibration: ExternalTrackingCalibrationDefault {
+                                yaw_deg: 0.0,
+                                position: ArrayDefault {
+                                    gui_collapsed: true,
+                                    content: [0.0, 0.0, 0.0],
+                                },
+                            },
+                        },
+                        content: vec![],
+                    },
+                },
+            },
             position_recentering_mode: PositionRecenteringModeDefault {
                 Local: PositionRecenteringModeLocalDefault { view_height: 1.5 },
                 variant: PositionRecenteringModeDefaultVariant::LocalFloor,
@@ -1559,6 +2294,10 @@ pub fn session_settings_default() -> SettingsDefault {
             client_recv_buffer_bytes: socket_buffer,
             max_queued_server_video_frames: 1024,
             avoid_video_glitching: false,
+            video_fec: SwitchDefault {
+                enabled: false,
+                content: ForwardErrorCorrectionConfigDefault { group_size: 16 },
+            },
             aggressive_keyframe_resend: false,
             on_connect_script: "".into(),
             on_disconnect_script: "".into(),
@@ -1575,6 +2314,7 @@ pub fn session_settings_default() -> SettingsDefault {
             },
             log_to_disk: cfg!(debug_assertions),
             log_button_presses: false,
+            log_button_mappings: false,
             log_tracking: false,
             log_haptics: false,
             notification_level: LogSeverityDefault {
@@ -1590,6 +2330,13 @@ pub fn session_settings_default() -> SettingsDefault {
                     hide_spammy_events: false,
                 },
             },
+            log_frame_statistics: SwitchDefault {
+                enabled: false,
+                content: FrameStatisticsLogConfigDefault {
+                    max_file_size_mb: 100,
+                    max_rotated_files: 5,
+                },
+            },
             prefer_backtrace: false,
             show_notification_tip: true,
         },
@@ -1607,6 +2354,15 @@ pub fn session_settings_default() -> SettingsDefault {
                 enabled: false,
                 content: RollingVideoFilesConfigDefault { duration_s: 5 },
             },
+            packet_capture: false,
+            tracking_recording: false,
+            tracking_playback: SwitchDefault {
+                enabled: false,
+                content: TrackingPlaybackConfigDefault {
+                    recording_file: "".into(),
+                    looped: true,
+                },
+            },
             capture_frame_dir: if !cfg!(target_os = "linux") {
                 "/tmp".into()
             } else {
@@ -1618,6 +2374,43 @@ pub fn session_settings_default() -> SettingsDefault {
             linux_async_compute: false,
             linux_async_reprojection: false,
         },
+        debug: DebugConfigDefault {
+            gui_collapsed: true,
+            network_emulator: SwitchDefault {
+                enabled: false,
+                content: NetworkEmulatorConfigDefault {
+                    delay_ms: 20,
+                    jitter: JitterDistributionDefault {
+                        Uniform: JitterDistributionUniformDefault { max_jitter_ms: 5 },
+                        Normal: JitterDistributionNormalDefault { std_dev_ms: 5.0 },
+                        variant: JitterDistributionDefaultVariant::None,
+                    },
+                    bandwidth_limit: SwitchDefault {
+                        enabled: false,
+                        content: BandwidthLimitConfigDefault {
+                            mbps: 100,
+                            max_queue_delay_ms: 100,
+                        },
+                    },
+                    burst_loss: SwitchDefault {
+                        enabled: false,
+                        content: GilbertElliottConfigDefault {
+                            good_to_bad_probability: 0.01,
+                            bad_to_good_probability: 0.3,
+                            good_loss_probability: 0.0,
+                            bad_loss_probability: 0.5,
+                        },
+                    },
+                    reordering: SwitchDefault {
+                        enabled: false,
+                        content: ReorderingConfigDefault {
+                            probability: 0.01,
+                            extra_delay_ms: 10,
+                        },
+                    },
+                },
+            },
+        },
         open_setup_wizard: alvr_common::is_stable() || alvr_common::is_nightly(),
     }
 }
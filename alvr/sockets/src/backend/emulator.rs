@@ -0,0 +1,570 @@
+// Network condition emulator. The writer wrapper delays, drops and reorders the sent buffers
+// according to NetworkEmulatorConfig. Each buffer is treated as one network packet. Buffers sent
+// reliably over a stream transport are only delayed and rate limited, since the transport
+// retransmits lost packets and keeps them in order.
+//
+// The decisions are taken by NetworkEmulator, which does not read the clock and uses a seeded RNG,
+// so it can be used to write deterministic tests. VirtualLink is an in-process link with a manually
+// advanced clock, meant for tests too.
+
+use super::{SocketReader, SocketWriter};
+use alvr_common::{
+    anyhow::{bail, Result},
+    parking_lot::{Condvar, Mutex, MutexGuard},
+    ConResult,
+};
+use alvr_session::{settings_schema::Switch, JitterDistribution, NetworkEmulatorConfig};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    f64::consts::PI,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub struct NetworkEmulator {
+    config: NetworkEmulatorConfig,
+    rng: StdRng,
+    is_bad_state: bool,
+    // Instant at which the emulated bottleneck link finishes sending the queued packets
+    link_free_instant: Option<Instant>,
+    last_in_order_delivery: Option<Instant>,
+}
+
+impl NetworkEmulator {
+    pub fn new(config: NetworkEmulatorConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            is_bad_state: false,
+            link_free_instant: None,
+            last_in_order_delivery: None,
+        }
+    }
+
+    // Returns the instant when a packet of the specified size, sent at `now`, reaches the peer, or
+    // None if it is lost. Calls must be made with non decreasing `now`. Packets that are not
+    // `lossy` are never dropped and are delivered in order.
+    pub fn schedule(&mut self, now: Instant, size: usize, lossy: bool) -> Option<Instant> {
+        if let (true, Switch::Enabled(config)) = (lossy, &self.config.burst_loss) {
+            let transition_probability = if self.is_bad_state {
+                config.bad_to_good_probability
+            } else {
+                config.good_to_bad_probability
+            };
+            if self.rng.gen::<f32>() < transition_probability {
+                self.is_bad_state = !self.is_bad_state;
+            }
+
+            let loss_probability = if self.is_bad_state {
+                config.bad_loss_probability
+            } else {
+                config.good_loss_probability
+            };
+            if self.rng.gen::<f32>() < loss_probability {
+                return None;
+            }
+        }
+
+        let mut departure = now;
+        if let Switch::Enabled(config) = &self.config.bandwidth_limit {
+            let start = self
+                .link_free_instant
+                .map_or(now, |instant| instant.max(now));
+            if lossy && start - now > Duration::from_millis(config.max_queue_delay_ms) {
+                // Tail drop
+                return None;
+            }
+
+            let transmission_time =
+                Duration::from_secs_f64(size as f64 * 8.0 / (config.mbps as f64 * 1e6));
+            departure = start + transmission_time;
+            self.link_free_instant = Some(departure);
+        }
+
+        let delay_ms = self.config.delay_ms as f64
+            + match &self.config.jitter {
+                JitterDistribution::None => 0.0,
+                JitterDistribution::Uniform { max_jitter_ms } => {
+                    let max_jitter_ms = *max_jitter_ms as f64;
+                    self.rng.gen_range(-max_jitter_ms..=max_jitter_ms)
+                }
+                JitterDistribution::Normal { std_dev_ms } => {
+                    // Box-Muller transform
+                    let u1 = 1.0 - self.rng.gen::<f64>();
+                    let u2 = self.rng.gen::<f64>();
+                    let standard_normal = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+
+                    standard_normal * *std_dev_ms as f64
+                }
+            };
+        let delivery = departure + Duration::from_secs_f64(delay_ms.max(0.0) / 1000.0);
+
+        if let (true, Switch::Enabled(config)) = (lossy, &self.config.reordering) {
+            if self.rng.gen::<f32>() < config.probability {
+                // Later packets can overtake this one
+                return Some(delivery + Duration::from_millis(config.extra_delay_ms));
+            }
+        }
+
+        // Jitter alone does not reorder packets
+        let delivery = self
+            .last_in_order_delivery
+            .map_or(delivery, |last| delivery.max(last));
+        self.last_in_order_delivery = Some(delivery);
+
+        Some(delivery)
+    }
+}
+
+struct DelayedPacket {
+    delivery: Instant,
+    // Keeps the send order for packets with the same delivery instant
+    sequence: u64,
+    data: Vec<u8>,
+    unreliable: bool,
+}
+
+impl PartialEq for DelayedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DelayedPacket {}
+
+impl PartialOrd for DelayedPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DelayedPacket {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.delivery, self.sequence).cmp(&(other.delivery, other.sequence))
+    }
+}
+
+#[derive(Default)]
+struct DelayQueue {
+    packets: BinaryHeap<Reverse<DelayedPacket>>,
+    next_sequence: u64,
+}
+
+impl DelayQueue {
+    fn push(&mut self, delivery: Instant, data: Vec<u8>, unreliable: bool) {
+        self.packets.push(Reverse(DelayedPacket {
+            delivery,
+            sequence: self.next_sequence,
+            data,
+            unreliable,
+        }));
+        self.next_sequence += 1;
+    }
+
+    fn next_delivery(&self) -> Option<Instant> {
+        self.packets.peek().map(|Reverse(packet)| packet.delivery)
+    }
+
+    fn pop_ready(&mut self, now: Instant) -> Option<DelayedPacket> {
+        if self.next_delivery()? <= now {
+            self.packets.pop().map(|Reverse(packet)| packet)
+        } else {
+            None
+        }
+    }
+}
+
+struct WriterState {
+    queue: DelayQueue,
+    error: Option<String>,
+    running: bool,
+}
+
+// The inner socket is moved to a thread that sends the packets when they are due
+pub struct EmulatedSocketWriter {
+    emulator: NetworkEmulator,
+    // Whether send() is unreliable too, like on UDP
+    is_datagram: bool,
+    shared: Arc<(Mutex<WriterState>, Condvar)>,
+}
+
+impl EmulatedSocketWriter {
+    pub fn new(
+        mut inner: Box<dyn SocketWriter>,
+        config: NetworkEmulatorConfig,
+        seed: u64,
+        is_datagram: bool,
+    ) -> Self {
+        let shared = Arc::new((
+            Mutex::new(WriterState {
+                queue: DelayQueue::default(),
+                error: None,
+                running: true,
+            }),
+            Condvar::new(),
+        ));
+
+        thread::spawn({
+            let shared = Arc::clone(&shared);
+            move || {
+                let (state, condvar) = &*shared;
+                let mut state_lock = state.lock();
+                while state_lock.running {
+                    if let Some(packet) = state_lock.queue.pop_ready(Instant::now()) {
+                        let res = MutexGuard::unlocked(&mut state_lock, || {
+                            if packet.unreliable {
+                                inner.send_unreliable(&packet.data)
+                            } else {
+                                inner.send(&packet.data)
+                            }
+                        });
+                        if let Err(e) = res {
+                            state_lock.error = Some(e.to_string());
+                            return;
+                        }
+                    } else if let Some(delivery) = state_lock.queue.next_delivery() {
+                        condvar.wait_until(&mut state_lock, delivery);
+                    } else {
+                        condvar.wait(&mut state_lock);
+                    }
+                }
+            }
+        });
+
+        Self {
+            emulator: NetworkEmulator::new(config, seed),
+            is_datagram,
+            shared,
+        }
+    }
+
+    fn enqueue(&mut self, buffer: &[u8], unreliable: bool) -> Result<()> {
+        let (state, condvar) = &*self.shared;
+        let mut state_lock = state.lock();
+
+        if let Some(e) = &state_lock.error {
+            bail!("Emulated socket failed: {e}");
+        }
+
+        let lossy = unreliable || self.is_datagram;
+        if let Some(delivery) = self.emulator.schedule(Instant::now(), buffer.len(), lossy) {
+            state_lock.queue.push(delivery, buffer.to_vec(), unreliable);
+            condvar.notify_one();
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketWriter for EmulatedSocketWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        self.enqueue(buffer, false)
+    }
+
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        self.enqueue(buffer, true)
+    }
+}
+
+impl Drop for EmulatedSocketWriter {
+    fn drop(&mut self) {
+        let (state, condvar) = &*self.shared;
+        state.lock().running = false;
+        condvar.notify_one();
+    }
+}
+
+struct VirtualLinkState {
+    emulator: NetworkEmulator,
+    now: Instant,
+    queue: DelayQueue,
+}
+
+// In-process datagram link between a writer and a reader. Time does not flow by itself: packets
+// are delivered only when the clock is moved forward with advance().
+#[derive(Clone)]
+pub struct VirtualLink(Arc<Mutex<VirtualLinkState>>);
+
+impl VirtualLink {
+    pub fn new(config: NetworkEmulatorConfig, seed: u64) -> Self {
+        Self(Arc::new(Mutex::new(VirtualLinkState {
+            emulator: NetworkEmulator::new(config, seed),
+            now: Instant::now(),
+            queue: DelayQueue::default(),
+        })))
+    }
+
+    pub fn now(&self) -> Instant {
+        self.0.lock().now
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.0.lock().now += duration;
+    }
+
+    // Number of packets sent but not delivered yet
+    pub fn in_flight_count(&self) -> usize {
+        self.0.lock().queue.packets.len()
+    }
+}
+
+impl SocketWriter for VirtualLink {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        let state = &mut *self.0.lock();
+        if let Some(delivery) = state.emulator.schedule(state.now, buffer.len(), true) {
+            state.queue.push(delivery, buffer.to_vec(), false);
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketReader for VirtualLink {
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let state = &mut *self.0.lock();
+        let Some(packet) = state.queue.pop_ready(state.now) else {
+            return alvr_common::try_again();
+        };
+
+        let size = usize::min(buffer.len(), packet.data.len());
+        buffer[..size].copy_from_slice(&packet.data[..size]);
+
+        Ok(size)
+    }
+
+    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+        let state = self.0.lock();
+        let packet = match state.queue.packets.peek() {
+            Some(Reverse(packet)) if packet.delivery <= state.now => packet,
+            _ => return alvr_common::try_again(),
+        };
+
+        let size = usize::min(buffer.len(), packet.data.len());
+        buffer[..size].copy_from_slice(&packet.data[..size]);
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_session::{BandwidthLimitConfig, GilbertElliottConfig, ReorderingConfig};
+
+    fn ideal_config() -> NetworkEmulatorConfig {
+        NetworkEmulatorConfig {
+            delay_ms: 10,
+            jitter: JitterDistribution::None,
+            bandwidth_limit: Switch::Disabled,
+            burst_loss: Switch::Disabled,
+            reordering: Switch::Disabled,
+        }
+    }
+
+    fn schedule_all(config: NetworkEmulatorConfig, count: usize) -> Vec<Option<Duration>> {
+        let mut emulator = NetworkEmulator::new(config, 1);
+        let start = Instant::now();
+
+        (0..count)
+            .map(|i| {
+                let now = start + Duration::from_millis(i as u64);
+                emulator
+                    .schedule(now, 1000, true)
+                    .map(|delivery| delivery - start)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_constant_delay() {
+        let deliveries = schedule_all(ideal_config(), 3);
+
+        assert_eq!(
+            deliveries,
+            [10, 11, 12]
+                .map(|ms| Some(Duration::from_millis(ms)))
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let config = NetworkEmulatorConfig {
+            jitter: JitterDistribution::Normal { std_dev_ms: 5.0 },
+            burst_loss: Switch::Enabled(GilbertElliottConfig {
+                good_to_bad_probability: 0.1,
+                bad_to_good_probability: 0.3,
+                good_loss_probability: 0.0,
+                bad_loss_probability: 0.5,
+            }),
+            ..ideal_config()
+        };
+
+        assert_eq!(schedule_all(config.clone(), 100), schedule_all(config, 100));
+    }
+
+    #[test]
+    fn test_jitter_keeps_order() {
+        let deliveries = schedule_all(
+            NetworkEmulatorConfig {
+                jitter: JitterDistribution::Uniform { max_jitter_ms: 8 },
+                ..ideal_config()
+            },
+            100,
+        );
+
+        let deliveries = deliveries
+            .into_iter()
+            .map(Option::unwrap)
+            .collect::<Vec<_>>();
+        assert!(deliveries.windows(2).all(|w| w[0] <= w[1]));
+        assert!(deliveries.iter().any(|d| *d != deliveries[0]));
+    }
+
+    #[test]
+    fn test_burst_loss() {
+        let deliveries = schedule_all(
+            NetworkEmulatorConfig {
+                burst_loss: Switch::Enabled(GilbertElliottConfig {
+                    good_to_bad_probability: 0.05,
+                    bad_to_good_probability: 0.2,
+                    good_loss_probability: 0.0,
+                    bad_loss_probability: 1.0,
+                }),
+                ..ideal_config()
+            },
+            10000,
+        );
+
+        // Stationary probability of the bad state: 0.05 / (0.05 + 0.2) = 0.2
+        let lost_count = deliveries.iter().filter(|d| d.is_none()).count();
+        assert!((1500..2500).contains(&lost_count), "{lost_count}");
+
+        // Losses are bursty: the mean burst length is 1 / 0.2 = 5 packets
+        let bursts_count = deliveries
+            .windows(2)
+            .filter(|w| w[0].is_some() && w[1].is_none())
+            .count();
+        let mean_burst_length = lost_count as f32 / bursts_count as f32;
+        assert!(
+            (4.0..6.0).contains(&mean_burst_length),
+            "{mean_burst_length}"
+        );
+    }
+
+    #[test]
+    fn test_bandwidth_limit() {
+        // 1000 bytes at 4 Mbps take 2ms, while a packet is sent every 1ms: the queue grows by 1ms
+        // per packet
+        let deliveries = schedule_all(
+            NetworkEmulatorConfig {
+                delay_ms: 0,
+                bandwidth_limit: Switch::Enabled(BandwidthLimitConfig {
+                    mbps: 4,
+                    max_queue_delay_ms: 10,
+                }),
+                ..ideal_config()
+            },
+            20,
+        );
+
+        assert_eq!(deliveries[0], Some(Duration::from_millis(2)));
+        assert_eq!(deliveries[1], Some(Duration::from_millis(4)));
+        // Queue overflow
+        assert!(deliveries.iter().any(Option::is_none));
+    }
+
+    #[test]
+    fn test_reordering() {
+        let deliveries = schedule_all(
+            NetworkEmulatorConfig {
+                reordering: Switch::Enabled(ReorderingConfig {
+                    probability: 0.2,
+                    extra_delay_ms: 5,
+                }),
+                ..ideal_config()
+            },
+            100,
+        );
+
+        let deliveries = deliveries
+            .into_iter()
+            .map(Option::unwrap)
+            .collect::<Vec<_>>();
+        assert!(deliveries.windows(2).any(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn test_reliable_packets() {
+        let mut emulator = NetworkEmulator::new(
+            NetworkEmulatorConfig {
+                bandwidth_limit: Switch::Enabled(BandwidthLimitConfig {
+                    mbps: 4,
+                    max_queue_delay_ms: 10,
+                }),
+                burst_loss: Switch::Enabled(GilbertElliottConfig {
+                    good_to_bad_probability: 0.5,
+                    bad_to_good_probability: 0.5,
+                    good_loss_probability: 0.5,
+                    bad_loss_probability: 1.0,
+                }),
+                reordering: Switch::Enabled(ReorderingConfig {
+                    probability: 0.5,
+                    extra_delay_ms: 5,
+                }),
+                ..ideal_config()
+            },
+            1,
+        );
+        let now = Instant::now();
+
+        // Packets are only delayed by the link, even once the queue overflows
+        let deliveries = (0..100)
+            .map(|_| emulator.schedule(now, 1000, false).unwrap())
+            .collect::<Vec<_>>();
+        assert!(deliveries.windows(2).all(|w| w[0] < w[1]));
+        assert!(deliveries[99] - now >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_virtual_link() {
+        let mut link = VirtualLink::new(ideal_config(), 1);
+        let mut reader = link.clone();
+
+        link.send(b"hello").unwrap();
+        assert!(reader.recv(&mut [0; 16]).is_err());
+
+        link.advance(Duration::from_millis(10));
+        let mut buffer = [0; 16];
+        assert!(matches!(reader.peek(&mut buffer[..2]), Ok(2)));
+        let Ok(size) = reader.recv(&mut buffer) else {
+            panic!()
+        };
+        assert_eq!(&buffer[..size], b"hello");
+        assert_eq!(link.in_flight_count(), 0);
+    }
+
+    #[test]
+    fn test_emulated_writer() {
+        let link = VirtualLink::new(
+            NetworkEmulatorConfig {
+                delay_ms: 0,
+                ..ideal_config()
+            },
+            1,
+        );
+        let mut writer = EmulatedSocketWriter::new(Box::new(link.clone()), ideal_config(), 1, true);
+
+        let start = Instant::now();
+        writer.send(b"hello").unwrap();
+        assert_eq!(link.in_flight_count(), 0);
+
+        while link.in_flight_count() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,478 @@
+// Wrappers that encrypt and authenticate each buffer sent through another backend. Each buffer is
+// sent as a record: [u32 BE length of the rest][u64 BE counter][ciphertext][tag].
+//
+// The counter is sent explicitly since datagram backends can drop or reorder records. Replayed
+// records are rejected using a sliding window.
+
+use super::{SocketReader, SocketWriter};
+use crate::crypto::{Cipher, StreamKeys, TAG_SIZE};
+use alvr_common::{anyhow::Result, con_bail, parking_lot::Mutex, ConResult};
+use std::mem;
+
+const LENGTH_SIZE: usize = mem::size_of::<u32>();
+const COUNTER_SIZE: usize = mem::size_of::<u64>();
+const HEADER_SIZE: usize = LENGTH_SIZE + COUNTER_SIZE;
+pub const RECORD_OVERHEAD: usize = HEADER_SIZE + TAG_SIZE;
+
+const MAX_DATAGRAM_SIZE: usize = 65536;
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+pub struct EncryptedSocketWriter {
+    inner: Box<dyn SocketWriter>,
+    cipher: Cipher,
+    buffer: Vec<u8>,
+}
+
+impl EncryptedSocketWriter {
+    pub fn new(inner: Box<dyn SocketWriter>, keys: &StreamKeys) -> Self {
+        Self {
+            inner,
+            cipher: Cipher::new(&keys.send),
+            buffer: vec![],
+        }
+    }
+
+    fn seal(&mut self, buffer: &[u8]) {
+        let record_size = buffer.len() + RECORD_OVERHEAD;
+        self.buffer.resize(record_size, 0);
+
+        self.buffer[..LENGTH_SIZE]
+            .copy_from_slice(&((record_size - LENGTH_SIZE) as u32).to_be_bytes());
+        self.buffer[HEADER_SIZE..HEADER_SIZE + buffer.len()].copy_from_slice(buffer);
+
+        let counter = self.cipher.seal(&mut self.buffer[HEADER_SIZE..]);
+        self.buffer[LENGTH_SIZE..HEADER_SIZE].copy_from_slice(&counter.to_be_bytes());
+    }
+}
+
+impl SocketWriter for EncryptedSocketWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        self.seal(buffer);
+
+        self.inner.send(&self.buffer)
+    }
+
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        self.seal(buffer);
+
+        self.inner.send_unreliable(&self.buffer)
+    }
+}
+
+#[derive(Default)]
+struct ReplayWindow {
+    highest_counter: Option<u64>,
+    // bit N is set if the record with counter highest_counter - N has been received
+    received_mask: u64,
+}
+
+impl ReplayWindow {
+    fn is_new(&self, counter: u64) -> bool {
+        match self.highest_counter {
+            Some(highest) if counter <= highest => {
+                let offset = highest - counter;
+                offset < REPLAY_WINDOW_SIZE && self.received_mask & (1 << offset) == 0
+            }
+            _ => true,
+        }
+    }
+
+    fn mark(&mut self, counter: u64) {
+        match self.highest_counter {
+            Some(highest) if counter <= highest => {
+                self.received_mask |= 1 << (highest - counter);
+            }
+            Some(highest) => {
+                let shift = counter - highest;
+                self.received_mask = if shift < REPLAY_WINDOW_SIZE {
+                    (self.received_mask << shift) | 1
+                } else {
+                    1
+                };
+                self.highest_counter = Some(counter);
+            }
+            None => {
+                self.received_mask = 1;
+                self.highest_counter = Some(counter);
+            }
+        }
+    }
+}
+
+struct ReaderState {
+    inner: Box<dyn SocketReader>,
+    cipher: Cipher,
+    // If true, each recv() returns a whole record, and invalid records are dropped. Otherwise the
+    // inner socket is a byte stream and invalid records are fatal.
+    is_datagram: bool,
+    raw_buffer: Vec<u8>,
+    raw_length: usize,
+    record: Vec<u8>,
+    record_length: usize,
+    record_cursor: usize,
+    replay_window: ReplayWindow,
+}
+
+impl ReaderState {
+    // Make sure there is some decrypted data ready to be read
+    fn fill(&mut self) -> ConResult {
+        while self.record_cursor == self.record_length {
+            if self.raw_length >= LENGTH_SIZE {
+                let record_size = LENGTH_SIZE
+                    + u32::from_be_bytes(self.raw_buffer[..LENGTH_SIZE].try_into().unwrap())
+                        as usize;
+
+                if !(RECORD_OVERHEAD..=MAX_DATAGRAM_SIZE).contains(&record_size)
+                    || (self.is_datagram && record_size != self.raw_length)
+                {
+                    if self.is_datagram {
+                        self.raw_length = 0;
+                        continue;
+                    } else {
+                        con_bail!("Received malformed stream record");
+                    }
+                }
+
+                if self.raw_length >= record_size {
+                    self.open_record(record_size)?;
+
+                    self.raw_buffer.copy_within(record_size..self.raw_length, 0);
+                    self.raw_length -= record_size;
+
+                    continue;
+                }
+            } else if self.is_datagram {
+                // Drop leftovers of truncated datagrams
+                self.raw_length = 0;
+            }
+
+            // Note: for datagram sockets, raw_length is always 0 at this point
+            if self.raw_buffer.len() < self.raw_length + MAX_DATAGRAM_SIZE {
+                self.raw_buffer
+                    .resize(self.raw_length + MAX_DATAGRAM_SIZE, 0);
+            }
+
+            let size = self.inner.recv(&mut self.raw_buffer[self.raw_length..])?;
+            if size == 0 && !self.is_datagram {
+                con_bail!("Stream socket closed");
+            }
+            self.raw_length += size;
+        }
+
+        Ok(())
+    }
+
+    fn open_record(&mut self, record_size: usize) -> ConResult {
+        let counter = u64::from_be_bytes(
+            self.raw_buffer[LENGTH_SIZE..HEADER_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+
+        if !self.replay_window.is_new(counter) {
+            if self.is_datagram {
+                return Ok(());
+            } else {
+                con_bail!("Received replayed stream record");
+            }
+        }
+
+        let ciphertext_size = record_size - HEADER_SIZE;
+        if self.record.len() < ciphertext_size {
+            self.record.resize(ciphertext_size, 0);
+        }
+        self.record[..ciphertext_size].copy_from_slice(&self.raw_buffer[HEADER_SIZE..record_size]);
+
+        match self
+            .cipher
+            .open(counter, &mut self.record[..ciphertext_size])
+        {
+            Ok(size) => {
+                self.replay_window.mark(counter);
+                self.record_length = size;
+                self.record_cursor = 0;
+
+                Ok(())
+            }
+            Err(_) if self.is_datagram => Ok(()),
+            Err(_) => con_bail!("Stream record authentication failed"),
+        }
+    }
+
+    fn copy_to(&self, buffer: &mut [u8]) -> usize {
+        let size = usize::min(buffer.len(), self.record_length - self.record_cursor);
+        buffer[..size].copy_from_slice(&self.record[self.record_cursor..self.record_cursor + size]);
+
+        size
+    }
+}
+
+pub struct EncryptedSocketReader {
+    // Note: a mutex is needed since peek() needs to receive and decrypt a whole record
+    state: Mutex<ReaderState>,
+}
+
+impl EncryptedSocketReader {
+    pub fn new(inner: Box<dyn SocketReader>, keys: &StreamKeys, is_datagram: bool) -> Self {
+        Self {
+            state: Mutex::new(ReaderState {
+                inner,
+                cipher: Cipher::new(&keys.recv),
+                is_datagram,
+                raw_buffer: vec![],
+                raw_length: 0,
+                record: vec![],
+                record_length: 0,
+                record_cursor: 0,
+                replay_window: ReplayWindow::default(),
+            }),
+        }
+    }
+}
+
+impl SocketReader for EncryptedSocketReader {
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let state = self.state.get_mut();
+        state.fill()?;
+
+        let size = state.copy_to(buffer);
+        state.record_cursor += size;
+
+        Ok(size)
+    }
+
+    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+        let mut state = self.state.lock();
+        state.fill()?;
+
+        Ok(state.copy_to(buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::emulator::EmulatedSocketWriter;
+    use alvr_common::ConnectionError;
+    use alvr_session::{
+        settings_schema::Switch, GilbertElliottConfig, JitterDistribution, NetworkEmulatorConfig,
+        ReorderingConfig,
+    };
+    use std::{
+        collections::VecDeque,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    type Queue = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+    struct QueueWriter(Queue);
+
+    impl SocketWriter for QueueWriter {
+        fn send(&mut self, buffer: &[u8]) -> Result<()> {
+            self.0.lock().push_back(buffer.to_vec());
+
+            Ok(())
+        }
+    }
+
+    // If split_records is true, the queue is read as a byte stream in small chunks
+    struct QueueReader {
+        queue: Queue,
+        split_records: bool,
+    }
+
+    impl SocketReader for QueueReader {
+        fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let mut queue = self.queue.lock();
+            let Some(data) = queue.front_mut() else {
+                return alvr_common::try_again();
+            };
+
+            let size = if self.split_records {
+                usize::min(usize::min(buffer.len(), data.len()), 5)
+            } else {
+                usize::min(buffer.len(), data.len())
+            };
+            buffer[..size].copy_from_slice(&data[..size]);
+
+            if self.split_records && size < data.len() {
+                data.drain(..size);
+            } else {
+                queue.pop_front();
+            }
+
+            Ok(size)
+        }
+
+        fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+            let queue = self.queue.lock();
+            let Some(data) = queue.front() else {
+                return alvr_common::try_again();
+            };
+
+            let size = usize::min(buffer.len(), data.len());
+            buffer[..size].copy_from_slice(&data[..size]);
+
+            Ok(size)
+        }
+    }
+
+    fn keys() -> StreamKeys {
+        StreamKeys {
+            send: [1; 32],
+            recv: [1; 32],
+            identity_pkcs8: Arc::from([]),
+            peer_identity_key: [0; 32],
+        }
+    }
+
+    fn pair(split_records: bool) -> (EncryptedSocketWriter, EncryptedSocketReader, Queue) {
+        let queue = Queue::default();
+
+        (
+            EncryptedSocketWriter::new(Box::new(QueueWriter(Arc::clone(&queue))), &keys()),
+            EncryptedSocketReader::new(
+                Box::new(QueueReader {
+                    queue: Arc::clone(&queue),
+                    split_records,
+                }),
+                &keys(),
+                !split_records,
+            ),
+            queue,
+        )
+    }
+
+    fn recv_all(reader: &mut EncryptedSocketReader) -> Option<Vec<u8>> {
+        let mut buffer = [0; 64];
+        let peeked_size = reader.peek(&mut buffer[..2]).ok()?;
+        assert_eq!(peeked_size, 2);
+
+        let size = reader.recv(&mut buffer).ok()?;
+
+        Some(buffer[..size].to_vec())
+    }
+
+    #[test]
+    fn test_datagram_roundtrip() {
+        let (mut writer, mut reader, queue) = pair(false);
+
+        writer.send(b"hello").unwrap();
+        writer.send_unreliable(b"world").unwrap();
+        assert!(!queue.lock()[0].windows(5).any(|w| w == b"hello"));
+
+        assert_eq!(recv_all(&mut reader).unwrap(), b"hello");
+        assert_eq!(recv_all(&mut reader).unwrap(), b"world");
+        assert!(recv_all(&mut reader).is_none());
+    }
+
+    #[test]
+    fn test_stream_roundtrip() {
+        let (mut writer, mut reader, _) = pair(true);
+
+        writer.send(b"hello world").unwrap();
+        writer.send(b"second record").unwrap();
+
+        assert_eq!(recv_all(&mut reader).unwrap(), b"hello world");
+        assert_eq!(recv_all(&mut reader).unwrap(), b"second record");
+    }
+
+    #[test]
+    fn test_stream_through_lossy_emulator() {
+        let queue = Queue::default();
+        let config = NetworkEmulatorConfig {
+            delay_ms: 1,
+            jitter: JitterDistribution::Uniform { max_jitter_ms: 1 },
+            bandwidth_limit: Switch::Disabled,
+            burst_loss: Switch::Enabled(GilbertElliottConfig {
+                good_to_bad_probability: 0.3,
+                bad_to_good_probability: 0.3,
+                good_loss_probability: 0.1,
+                bad_loss_probability: 0.9,
+            }),
+            reordering: Switch::Enabled(ReorderingConfig {
+                probability: 0.3,
+                extra_delay_ms: 5,
+            }),
+        };
+        let emulated_writer =
+            EmulatedSocketWriter::new(Box::new(QueueWriter(Arc::clone(&queue))), config, 1, false);
+        let mut writer = EncryptedSocketWriter::new(Box::new(emulated_writer), &keys());
+        let mut reader = EncryptedSocketReader::new(
+            Box::new(QueueReader {
+                queue: Arc::clone(&queue),
+                split_records: true,
+            }),
+            &keys(),
+            false,
+        );
+
+        for i in 0..100_u8 {
+            writer.send(&[i; 20]).unwrap();
+        }
+
+        // Reliable sends are neither dropped nor reordered, otherwise the record counters would
+        // not match
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while queue.lock().len() < 100 {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(1));
+        }
+        for i in 0..100_u8 {
+            assert_eq!(recv_all(&mut reader).unwrap(), [i; 20]);
+        }
+    }
+
+    #[test]
+    fn test_datagram_replay_and_tampering() {
+        let (mut writer, mut reader, queue) = pair(false);
+
+        writer.send(b"first").unwrap();
+        writer.send(b"second").unwrap();
+
+        let first = queue.lock()[0].clone();
+        let mut tampered = queue.lock()[1].clone();
+        *tampered.last_mut().unwrap() ^= 1;
+
+        {
+            let mut queue = queue.lock();
+            queue.insert(1, first);
+            queue.insert(2, tampered);
+        }
+
+        // The replayed and the tampered records are skipped
+        assert_eq!(recv_all(&mut reader).unwrap(), b"first");
+        assert_eq!(recv_all(&mut reader).unwrap(), b"second");
+        assert!(recv_all(&mut reader).is_none());
+    }
+
+    #[test]
+    fn test_stream_tampering_is_fatal() {
+        let (mut writer, mut reader, queue) = pair(true);
+
+        writer.send(b"hello").unwrap();
+        queue.lock()[0][HEADER_SIZE] ^= 1;
+
+        assert!(matches!(
+            reader.recv(&mut [0; 64]),
+            Err(ConnectionError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+
+        for counter in [5, 3, 4, 100, 60] {
+            assert!(window.is_new(counter));
+            window.mark(counter);
+            assert!(!window.is_new(counter));
+        }
+
+        // Too old
+        assert!(!window.is_new(10));
+        assert!(window.is_new(99));
+    }
+}
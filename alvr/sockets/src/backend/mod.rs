@@ -1,3 +1,6 @@
+pub mod emulator;
+pub mod encrypted;
+pub mod quic;
 pub mod tcp;
 pub mod udp;
 
@@ -5,6 +8,12 @@ use alvr_common::{anyhow::Result, ConResult};
 
 pub trait SocketWriter: Send {
     fn send(&mut self, buffer: &[u8]) -> Result<()>;
+
+    // Send a buffer that is allowed to be dropped by the network. Only backends that support both
+    // reliable and unreliable transmission need to override this
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        self.send(buffer)
+    }
 }
 
 // Trait used to abstract different socket (or other input/output) implementations. The funtionality
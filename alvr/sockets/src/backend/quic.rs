@@ -0,0 +1,658 @@
+// QUIC backend. Shards sent through `send_unreliable()` are transmitted as QUIC datagrams, all
+// other shards are written on a single reliable unidirectional QUIC stream, preceded by their
+// length prefix (already part of the shard). This way video is not affected by head-of-line
+// blocking, while still getting congestion control and encryption.
+// Note: certificates are self-signed with the identity keys exchanged by the control socket
+// handshake. Each peer accepts only the identity key it paired with, in both directions.
+
+use crate::{crypto::StreamKeys, PublicKey, LOCAL_IP};
+
+use super::{SocketReader, SocketWriter};
+use alvr_common::{
+    anyhow::{anyhow, Result},
+    con_bail, debug,
+    parking_lot::Mutex,
+    AnyhowToCon, ConResult, ToCon,
+};
+use alvr_session::{DscpTos, SocketBufferSize};
+use quinn::{
+    crypto::rustls::{QuicClientConfig, QuicServerConfig},
+    ClientConfig, Connection, Endpoint, EndpointConfig, SendDatagramError, SendStream,
+    ServerConfig, TokioRuntime, TransportConfig,
+};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime},
+    server::danger::{ClientCertVerified, ClientCertVerifier},
+    CertificateError, DigitallySignedStruct, DistinguishedName, SignatureScheme,
+};
+use std::{
+    mem,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    runtime::Runtime,
+    sync::mpsc::{self, error::TrySendError},
+};
+
+const SERVER_NAME: &str = "alvr";
+
+// Shards are dropped if they are not read fast enough, like for a UDP socket buffer. Shards of the
+// reliable stream are never dropped, they are throttled by QUIC flow control instead.
+const MAX_QUEUED_SHARDS: usize = 1024;
+
+// Certificates are not signed by a CA. Instead, the TLS 1.3 handshake signature is checked against
+// the identity key pinned at pairing time, which proves that the peer owns it. The same verifier
+// is used by the client endpoint (the streamer) and the server endpoint (the headset).
+#[derive(Debug)]
+struct PinnedIdentityVerifier(PublicKey);
+
+impl PinnedIdentityVerifier {
+    fn verify_signature(
+        &self,
+        message: &[u8],
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        if dss.scheme == SignatureScheme::ED25519
+            && UnparsedPublicKey::new(&ED25519, self.0)
+                .verify(message, dss.signature())
+                .is_ok()
+        {
+            Ok(HandshakeSignatureValid::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::BadSignature,
+            ))
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedIdentityVerifier {
+    fn verify_server_cert(
+        &self,
+        _: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    // QUIC always uses TLS 1.3
+    fn verify_tls12_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::General("TLS 1.2 is not supported".into()))
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        _: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_signature(message, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+impl ClientCertVerifier for PinnedIdentityVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::General("TLS 1.2 is not supported".into()))
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        _: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_signature(message, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+// Self-signed certificate for the local identity key
+fn identity_certificate(
+    keys: &StreamKeys,
+) -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
+    let key_pair = rcgen::KeyPair::try_from(&*keys.identity_pkcs8)?;
+    let certificate =
+        rcgen::CertificateParams::new(vec![SERVER_NAME.into()])?.self_signed(&key_pair)?;
+
+    Ok((
+        certificate.der().clone(),
+        PrivatePkcs8KeyDer::from(keys.identity_pkcs8.to_vec()).into(),
+    ))
+}
+
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    // Both peers derive the shard size from the maximum datagram size, so it must not change
+    // during the connection
+    config.mtu_discovery_config(None);
+
+    Arc::new(config)
+}
+
+fn new_runtime() -> Result<Arc<Runtime>> {
+    Ok(Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?,
+    ))
+}
+
+fn bind_udp_socket(
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<UdpSocket> {
+    let socket = UdpSocket::bind((LOCAL_IP, port))?.into();
+
+    crate::set_socket_buffers(&socket, send_buffer_bytes, recv_buffer_bytes).ok();
+
+    crate::set_dscp(&socket, dscp);
+
+    Ok(socket.into())
+}
+
+pub struct QuicListener {
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+}
+
+// The client uses a QUIC server endpoint. Its certificate is configured only when accepting the
+// server, after the control socket handshake.
+pub fn bind(
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<QuicListener> {
+    let runtime = new_runtime()?;
+
+    let socket = bind_udp_socket(port, dscp, send_buffer_bytes, recv_buffer_bytes)?;
+
+    let endpoint = {
+        let _guard = runtime.enter();
+        Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            socket,
+            Arc::new(TokioRuntime),
+        )?
+    };
+
+    Ok(QuicListener { runtime, endpoint })
+}
+
+pub fn accept_from_server(
+    listener: QuicListener,
+    server_ip: IpAddr,
+    timeout: Duration,
+    keys: &StreamKeys,
+) -> ConResult<(QuicSocketWriter, QuicSocketReader)> {
+    let QuicListener { runtime, endpoint } = listener;
+
+    let server_config = {
+        let (certificate, private_key) = identity_certificate(keys).to_con()?;
+        let crypto = rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .to_con()?
+            .with_client_cert_verifier(Arc::new(PinnedIdentityVerifier(keys.peer_identity_key)))
+            .with_single_cert(vec![certificate], private_key)
+            .to_con()?;
+
+        let mut config =
+            ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(crypto).to_con()?));
+        config.transport_config(transport_config());
+
+        config
+    };
+    endpoint.set_server_config(Some(server_config));
+
+    let connection = runtime.block_on(async {
+        let incoming = tokio::time::timeout(timeout, endpoint.accept())
+            .await
+            .map_err(|_| alvr_common::ConnectionError::TryAgain(anyhow!("Accept timeout")))?
+            .to_con()?;
+
+        if incoming.remote_address().ip() != server_ip {
+            con_bail!(
+                "Connected to wrong client: Expected: {server_ip}, Found {}",
+                incoming.remote_address().ip()
+            );
+        }
+
+        incoming.await.to_con()
+    })?;
+
+    Ok(split_connection(runtime, endpoint, connection, timeout))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn connect_to_client(
+    timeout: Duration,
+    client_ip: IpAddr,
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+    keys: &StreamKeys,
+) -> ConResult<(QuicSocketWriter, QuicSocketReader)> {
+    // Note: the local port is the same as the one of the client, like for UDP
+    connect(
+        timeout,
+        port,
+        SocketAddr::new(client_ip, port),
+        dscp,
+        send_buffer_bytes,
+        recv_buffer_bytes,
+        keys,
+    )
+}
+
+fn connect(
+    timeout: Duration,
+    local_port: u16,
+    client_address: SocketAddr,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+    keys: &StreamKeys,
+) -> ConResult<(QuicSocketWriter, QuicSocketReader)> {
+    let runtime = new_runtime().to_con()?;
+
+    let client_config = {
+        let (certificate, private_key) = identity_certificate(keys).to_con()?;
+        let crypto = rustls::ClientConfig::builder_with_provider(crypto_provider())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .to_con()?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedIdentityVerifier(
+                keys.peer_identity_key,
+            )))
+            .with_client_auth_cert(vec![certificate], private_key)
+            .to_con()?;
+
+        let mut config =
+            ClientConfig::new(Arc::new(QuicClientConfig::try_from(crypto).to_con()?));
+        config.transport_config(transport_config());
+
+        config
+    };
+
+    let socket =
+        bind_udp_socket(local_port, dscp, send_buffer_bytes, recv_buffer_bytes).to_con()?;
+
+    let endpoint = {
+        let _guard = runtime.enter();
+        Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            socket,
+            Arc::new(TokioRuntime),
+        )
+        .to_con()?
+    };
+
+    let connection = runtime.block_on(async {
+        let connecting = endpoint
+            .connect_with(client_config, client_address, SERVER_NAME)
+            .to_con()?;
+
+        tokio::time::timeout(timeout, connecting)
+            .await
+            .map_err(|_| alvr_common::ConnectionError::TryAgain(anyhow!("Connect timeout")))?
+            .to_con()
+    })?;
+
+    Ok(split_connection(runtime, endpoint, connection, timeout))
+}
+
+fn split_connection(
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+    connection: Connection,
+    timeout: Duration,
+) -> (QuicSocketWriter, QuicSocketReader) {
+    let (shard_sender, shard_receiver) = mpsc::channel(MAX_QUEUED_SHARDS);
+
+    runtime.spawn({
+        let connection = connection.clone();
+        let shard_sender = shard_sender.clone();
+        async move {
+            while let Ok(datagram) = connection.read_datagram().await {
+                if let Err(TrySendError::Closed(_)) = shard_sender.try_send(datagram.to_vec()) {
+                    return;
+                }
+            }
+        }
+    });
+
+    runtime.spawn({
+        let connection = connection.clone();
+        async move {
+            while let Ok(mut stream) = connection.accept_uni().await {
+                let shard_sender = shard_sender.clone();
+                tokio::spawn(async move {
+                    let mut prefix = [0; mem::size_of::<u32>()];
+                    while stream.read_exact(&mut prefix).await.is_ok() {
+                        // The prefix does not count itself
+                        let mut shard = vec![0; prefix.len() + u32::from_be_bytes(prefix) as usize];
+                        shard[..prefix.len()].copy_from_slice(&prefix);
+
+                        if stream.read_exact(&mut shard[prefix.len()..]).await.is_err()
+                            || shard_sender.send(shard).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                });
+            }
+        }
+    });
+
+    (
+        QuicSocketWriter {
+            runtime: Arc::clone(&runtime),
+            connection: connection.clone(),
+            reliable_stream: None,
+        },
+        QuicSocketReader {
+            runtime,
+            _endpoint: endpoint,
+            connection,
+            shard_receiver: Mutex::new(shard_receiver),
+            peeked_shard: Mutex::new(None),
+            timeout,
+        },
+    )
+}
+
+pub struct QuicSocketWriter {
+    runtime: Arc<Runtime>,
+    connection: Connection,
+    reliable_stream: Option<SendStream>,
+}
+
+impl QuicSocketWriter {
+    // Bigger buffers cannot be sent as datagrams. None if the peer does not support datagrams.
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection.max_datagram_size()
+    }
+}
+
+impl SocketWriter for QuicSocketWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        let stream = if let Some(stream) = &mut self.reliable_stream {
+            stream
+        } else {
+            let stream = self.runtime.block_on(self.connection.open_uni())?;
+            self.reliable_stream.insert(stream)
+        };
+
+        self.runtime.block_on(stream.write_all(buffer))?;
+
+        Ok(())
+    }
+
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        match self
+            .connection
+            .send_datagram(bytes::Bytes::copy_from_slice(buffer))
+        {
+            Ok(()) => Ok(()),
+            Err(e @ (SendDatagramError::TooLarge | SendDatagramError::UnsupportedByPeer)) => {
+                debug!("Cannot send QUIC datagram ({e}), using reliable stream");
+
+                self.send(buffer)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+pub struct QuicSocketReader {
+    // The runtime and the endpoint must be kept alive for the background tasks to run
+    runtime: Arc<Runtime>,
+    _endpoint: Endpoint,
+    connection: Connection,
+    shard_receiver: Mutex<mpsc::Receiver<Vec<u8>>>,
+    peeked_shard: Mutex<Option<Vec<u8>>>,
+    timeout: Duration,
+}
+
+impl QuicSocketReader {
+    fn next_shard(&self) -> ConResult<Vec<u8>> {
+        if let Some(reason) = self.connection.close_reason() {
+            con_bail!("QUIC connection closed: {reason}");
+        }
+
+        let mut shard_receiver = self.shard_receiver.lock();
+        match self.runtime.block_on(async {
+            tokio::time::timeout(self.timeout, shard_receiver.recv()).await
+        }) {
+            Ok(Some(shard)) => Ok(shard),
+            Ok(None) => con_bail!("QUIC connection closed"),
+            Err(_) => alvr_common::try_again(),
+        }
+    }
+}
+
+// Shards are received whole, like for UDP
+impl SocketReader for QuicSocketReader {
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let shard = if let Some(shard) = self.peeked_shard.lock().take() {
+            shard
+        } else {
+            self.next_shard()?
+        };
+
+        let size = usize::min(buffer.len(), shard.len());
+        buffer[..size].copy_from_slice(&shard[..size]);
+
+        Ok(size)
+    }
+
+    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+        let mut peeked_shard_lock = self.peeked_shard.lock();
+        let shard = if let Some(shard) = &*peeked_shard_lock {
+            shard
+        } else {
+            peeked_shard_lock.insert(self.next_shard()?)
+        };
+
+        let size = usize::min(buffer.len(), shard.len());
+        buffer[..size].copy_from_slice(&shard[..size]);
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use std::{net::Ipv4Addr, thread};
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn identity() -> (Arc<[u8]>, PublicKey) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let public_key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+            .unwrap()
+            .public_key()
+            .as_ref()
+            .try_into()
+            .unwrap();
+
+        (pkcs8.as_ref().into(), public_key)
+    }
+
+    fn keys(identity_pkcs8: &Arc<[u8]>, peer_identity_key: PublicKey) -> StreamKeys {
+        StreamKeys {
+            send: [0; 32],
+            recv: [0; 32],
+            identity_pkcs8: Arc::clone(identity_pkcs8),
+            peer_identity_key,
+        }
+    }
+
+    fn shard(payload: &[u8]) -> Vec<u8> {
+        let mut shard = (payload.len() as u32).to_be_bytes().to_vec();
+        shard.extend_from_slice(payload);
+
+        shard
+    }
+
+    fn recv_shard(reader: &mut QuicSocketReader) -> Vec<u8> {
+        let mut buffer = [0; 64];
+        let size = reader.recv(&mut buffer).ok().unwrap();
+
+        buffer[..size].to_vec()
+    }
+
+    // Returns the client thread and the result of the server connection
+    #[allow(clippy::type_complexity)]
+    fn loopback(
+        client_keys: StreamKeys,
+        server_keys: StreamKeys,
+    ) -> (
+        thread::JoinHandle<ConResult<(QuicSocketWriter, QuicSocketReader)>>,
+        ConResult<(QuicSocketWriter, QuicSocketReader)>,
+    ) {
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let listener = bind(
+            0,
+            None,
+            SocketBufferSize::Default,
+            SocketBufferSize::Default,
+        )
+        .unwrap();
+        let port = listener.endpoint.local_addr().unwrap().port();
+
+        let client_thread = thread::spawn(move || {
+            accept_from_server(listener, localhost, TIMEOUT, &client_keys)
+        });
+
+        // The client and the server cannot bind the same port on the same machine
+        let server_result = connect(
+            TIMEOUT,
+            0,
+            SocketAddr::new(localhost, port),
+            None,
+            SocketBufferSize::Default,
+            SocketBufferSize::Default,
+            &server_keys,
+        );
+
+        (client_thread, server_result)
+    }
+
+    #[test]
+    fn test_loopback() {
+        let (client_pkcs8, client_key) = identity();
+        let (server_pkcs8, server_key) = identity();
+
+        let (client_thread, server_result) = loopback(
+            keys(&client_pkcs8, server_key),
+            keys(&server_pkcs8, client_key),
+        );
+        let (mut writer, mut reader) = server_result.ok().unwrap();
+        let (mut client_writer, mut client_reader) = client_thread.join().unwrap().ok().unwrap();
+
+        client_writer.send(&shard(b"tracking")).unwrap();
+
+        let mut prefix = [0; 4];
+        assert_eq!(reader.peek(&mut prefix).ok(), Some(4));
+        assert_eq!(u32::from_be_bytes(prefix), 8);
+        assert_eq!(recv_shard(&mut reader), shard(b"tracking"));
+
+        writer.send_unreliable(&shard(b"video")).unwrap();
+
+        assert_eq!(recv_shard(&mut client_reader), shard(b"video"));
+
+        // Without MTU discovery, datagrams are limited by the minimum QUIC MTU
+        let max_datagram_size = writer.max_datagram_size().unwrap();
+        assert!(max_datagram_size < 1200);
+        assert_eq!(client_writer.max_datagram_size(), Some(max_datagram_size));
+    }
+
+    // The TLS client can complete the handshake before the server rejects its certificate, in which
+    // case the connection is closed right after
+    fn is_rejected(result: ConResult<(QuicSocketWriter, QuicSocketReader)>) -> bool {
+        match result {
+            Ok((_, mut reader)) => matches!(
+                reader.recv(&mut [0; 64]),
+                Err(alvr_common::ConnectionError::Other(_))
+            ),
+            Err(_) => true,
+        }
+    }
+
+    #[test]
+    fn test_reject_unpaired_peer() {
+        let (client_pkcs8, client_key) = identity();
+        let (server_pkcs8, server_key) = identity();
+        let (impostor_pkcs8, _) = identity();
+
+        // The impostor pretends to be the server
+        let (client_thread, server_result) = loopback(
+            keys(&client_pkcs8, server_key),
+            keys(&impostor_pkcs8, client_key),
+        );
+        assert!(is_rejected(server_result));
+        assert!(is_rejected(client_thread.join().unwrap()));
+
+        // The impostor pretends to be the client
+        let (client_thread, server_result) = loopback(
+            keys(&impostor_pkcs8, server_key),
+            keys(&server_pkcs8, client_key),
+        );
+        assert!(is_rejected(server_result));
+        assert!(is_rejected(client_thread.join().unwrap()));
+    }
+}
@@ -0,0 +1,265 @@
+// Packet capture, used to debug sessions offline. Packets are recorded after decryption and before
+// sharding, so a capture can be replayed through any socket backend.
+//
+// File layout: magic bytes, length-prefixed CaptureHeader, then a sequence of length-prefixed
+// CapturedPacket. Length prefixes are u32 little endian.
+
+use alvr_common::{
+    anyhow::{bail, Result},
+    parking_lot::Mutex,
+    warn,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const CAPTURE_MAGIC: &[u8; 8] = b"ALVRCAP1";
+// Bigger packets are not captured. Video frames are well below this size
+const MAX_CAPTURED_PACKET_SIZE: usize = 32 * 1024 * 1024;
+// Size of a CapturedPacket without its data, rounded up
+const CAPTURED_PACKET_HEADER_SIZE: usize = 64;
+// Records above this size are rejected, to avoid allocating any size read from a corrupted file
+const MAX_RECORD_SIZE: usize = MAX_CAPTURED_PACKET_SIZE + CAPTURED_PACKET_HEADER_SIZE;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CaptureSide {
+    Server,
+    Client,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PacketDirection {
+    Sent,
+    Received,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaptureChannel {
+    Control,
+    Stream { stream_id: u16, packet_index: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CaptureHeader {
+    // Side of the connection where the capture has been recorded
+    pub side: CaptureSide,
+    pub protocol_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CapturedPacket {
+    // Time since the start of the capture
+    pub timestamp: Duration,
+    pub direction: PacketDirection,
+    pub channel: CaptureChannel,
+    // Packet serialized with bincode. For streams, the header is followed by the payload
+    pub data: Vec<u8>,
+}
+
+impl CapturedPacket {
+    // Returns the side of the connection that sent this packet
+    pub fn origin(&self, capture_side: CaptureSide) -> CaptureSide {
+        match (self.direction, capture_side) {
+            (PacketDirection::Sent, side) => side,
+            (PacketDirection::Received, CaptureSide::Server) => CaptureSide::Client,
+            (PacketDirection::Received, CaptureSide::Client) => CaptureSide::Server,
+        }
+    }
+}
+
+// Also used for other files with the same layout
+pub fn write_record<T: Serialize>(writer: &mut impl Write, record: &T) -> Result<()> {
+    let size = bincode::serialized_size(record)? as usize;
+    if size > MAX_RECORD_SIZE {
+        bail!("Record too big: {size} bytes");
+    }
+    writer.write_all(&(size as u32).to_le_bytes())?;
+    bincode::serialize_into(writer, record)?;
+
+    Ok(())
+}
+
+// Returns None at the end of the file
+pub fn read_record<T: for<'de> Deserialize<'de>>(reader: &mut impl Read) -> Result<Option<T>> {
+    let mut size_bytes = [0; 4];
+    match reader.read_exact(&mut size_bytes) {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let size = u32::from_le_bytes(size_bytes) as usize;
+    if size > MAX_RECORD_SIZE {
+        bail!("Record too big: {size} bytes. The file is corrupted");
+    }
+
+    let mut buffer = vec![0; size];
+    reader.read_exact(&mut buffer)?;
+
+    Ok(Some(bincode::deserialize(&buffer)?))
+}
+
+struct RecorderState {
+    writer: BufWriter<File>,
+    failed: bool,
+}
+
+// Shared by all sockets of a connection. The file is flushed when the last reference is dropped.
+pub struct PacketRecorder {
+    start_instant: Instant,
+    state: Mutex<RecorderState>,
+}
+
+impl PacketRecorder {
+    pub fn create(path: &Path, side: CaptureSide) -> Result<Arc<Self>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(CAPTURE_MAGIC)?;
+        write_record(
+            &mut writer,
+            &CaptureHeader {
+                side,
+                protocol_id: alvr_common::protocol_id(),
+            },
+        )?;
+
+        Ok(Arc::new(Self {
+            start_instant: Instant::now(),
+            state: Mutex::new(RecorderState {
+                writer,
+                failed: false,
+            }),
+        }))
+    }
+
+    // Used for control packets exchanged before the recorder could be attached to the socket
+    pub fn record_control<T: Serialize>(
+        &self,
+        direction: PacketDirection,
+        packet: &T,
+    ) -> Result<()> {
+        self.record(
+            direction,
+            CaptureChannel::Control,
+            &bincode::serialize(packet)?,
+        );
+
+        Ok(())
+    }
+
+    pub(crate) fn record(&self, direction: PacketDirection, channel: CaptureChannel, data: &[u8]) {
+        if data.len() > MAX_CAPTURED_PACKET_SIZE {
+            warn!("Packet of {} bytes not captured: too big", data.len());
+            return;
+        }
+
+        let packet = CapturedPacket {
+            timestamp: self.start_instant.elapsed(),
+            direction,
+            channel,
+            data: data.to_vec(),
+        };
+
+        let mut state = self.state.lock();
+        if !state.failed {
+            if let Err(e) = write_record(&mut state.writer, &packet) {
+                // Avoid flooding the log, the capture is useless from now on anyway
+                warn!("Packet capture stopped: {e}");
+                state.failed = true;
+            }
+        }
+    }
+}
+
+pub struct CaptureReader {
+    header: CaptureHeader,
+    reader: BufReader<File>,
+}
+
+impl CaptureReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; CAPTURE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            bail!("Not an ALVR capture file");
+        }
+
+        let Some(header) = read_record(&mut reader)? else {
+            bail!("Capture file is truncated");
+        };
+
+        Ok(Self { header, reader })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<CapturedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_record(&mut self.reader).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_capture_roundtrip() {
+        let path = env::temp_dir().join(format!("alvr_capture_test_{}", std::process::id()));
+
+        {
+            let recorder = PacketRecorder::create(&path, CaptureSide::Server).unwrap();
+            recorder.record(PacketDirection::Sent, CaptureChannel::Control, b"control");
+            recorder.record(
+                PacketDirection::Received,
+                CaptureChannel::Stream {
+                    stream_id: 3,
+                    packet_index: 42,
+                },
+                b"video",
+            );
+        }
+
+        let reader = CaptureReader::open(&path).unwrap();
+        assert_eq!(reader.header().side, CaptureSide::Server);
+
+        let packets = reader.collect::<Result<Vec<_>>>().unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].channel, CaptureChannel::Control);
+        assert_eq!(packets[0].data, b"control");
+        assert_eq!(packets[0].origin(CaptureSide::Server), CaptureSide::Server);
+        assert_eq!(
+            packets[1].channel,
+            CaptureChannel::Stream {
+                stream_id: 3,
+                packet_index: 42
+            }
+        );
+        assert_eq!(packets[1].data, b"video");
+        assert_eq!(packets[1].origin(CaptureSide::Server), CaptureSide::Client);
+        assert!(packets[0].timestamp <= packets[1].timestamp);
+    }
+
+    #[test]
+    fn test_record_size_limit() {
+        // A corrupted size must not be allocated
+        let mut record = u32::MAX.to_le_bytes().to_vec();
+        record.extend_from_slice(&[0; 16]);
+
+        assert!(read_record::<CaptureHeader>(&mut record.as_slice()).is_err());
+    }
+}
@@ -1,13 +1,21 @@
-use crate::backend::{tcp, SocketReader, SocketWriter};
+use crate::{
+    backend::{tcp, SocketReader, SocketWriter},
+    capture::{CaptureChannel, PacketDirection, PacketRecorder},
+    crypto::{self, Cipher, EphemeralKey, Identity, PublicKey, Role, StreamKeys},
+};
 
 use super::CONTROL_PORT;
-use alvr_common::{anyhow::Result, ConResult, HandleTryAgain, ToCon};
+use alvr_common::{
+    anyhow::{bail, Result},
+    con_bail, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+};
 use alvr_session::SocketBufferSize;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     marker::PhantomData,
     mem,
     net::{IpAddr, TcpListener, TcpStream},
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -19,20 +27,44 @@ struct RecvState {
     packet_cursor: usize, // counts also the length prefix bytes
 }
 
+// If a cipher is specified, the payload is encrypted and followed by the authentication tag
 fn framed_send<S: Serialize>(
     socket: &mut TcpStream,
     buffer: &mut Vec<u8>,
+    cipher: Option<&mut Cipher>,
+    recorder: Option<&PacketRecorder>,
     packet: &S,
 ) -> Result<()> {
     let serialized_size = bincode::serialized_size(&packet)? as usize;
-    let packet_size = serialized_size + FRAMED_PREFIX_LENGTH;
+    let payload_size = serialized_size
+        + if cipher.is_some() {
+            crypto::TAG_SIZE
+        } else {
+            0
+        };
+    let packet_size = payload_size + FRAMED_PREFIX_LENGTH;
 
     if buffer.len() < packet_size {
         buffer.resize(packet_size, 0);
     }
 
-    buffer[0..FRAMED_PREFIX_LENGTH].copy_from_slice(&(serialized_size as u32).to_be_bytes());
-    bincode::serialize_into(&mut buffer[FRAMED_PREFIX_LENGTH..packet_size], &packet)?;
+    buffer[0..FRAMED_PREFIX_LENGTH].copy_from_slice(&(payload_size as u32).to_be_bytes());
+    bincode::serialize_into(
+        &mut buffer[FRAMED_PREFIX_LENGTH..FRAMED_PREFIX_LENGTH + serialized_size],
+        &packet,
+    )?;
+
+    if let Some(recorder) = recorder {
+        recorder.record(
+            PacketDirection::Sent,
+            CaptureChannel::Control,
+            &buffer[FRAMED_PREFIX_LENGTH..FRAMED_PREFIX_LENGTH + serialized_size],
+        );
+    }
+
+    if let Some(cipher) = cipher {
+        cipher.seal(&mut buffer[FRAMED_PREFIX_LENGTH..packet_size]);
+    }
 
     socket.send(&buffer[0..packet_size])?;
 
@@ -43,6 +75,8 @@ fn framed_recv<R: DeserializeOwned>(
     socket: &mut TcpStream,
     buffer: &mut Vec<u8>,
     maybe_recv_state: &mut Option<RecvState>,
+    cipher: Option<&mut Cipher>,
+    recorder: Option<&PacketRecorder>,
     timeout: Duration,
 ) -> ConResult<R> {
     let deadline = Instant::now() + timeout;
@@ -85,8 +119,22 @@ fn framed_recv<R: DeserializeOwned>(
         }
     }
 
-    let packet = bincode::deserialize(&buffer[FRAMED_PREFIX_LENGTH..recv_state_mut.packet_length])
-        .to_con()?;
+    let payload = &mut buffer[FRAMED_PREFIX_LENGTH..recv_state_mut.packet_length];
+    let payload_size = if let Some(cipher) = cipher {
+        cipher.open_next(payload).to_con()?
+    } else {
+        payload.len()
+    };
+
+    if let Some(recorder) = recorder {
+        recorder.record(
+            PacketDirection::Received,
+            CaptureChannel::Control,
+            &payload[..payload_size],
+        );
+    }
+
+    let packet = bincode::deserialize(&payload[..payload_size]).to_con()?;
 
     *maybe_recv_state = None;
 
@@ -96,12 +144,20 @@ fn framed_recv<R: DeserializeOwned>(
 pub struct ControlSocketSender<T> {
     inner: TcpStream,
     buffer: Vec<u8>,
+    cipher: Cipher,
+    recorder: Option<Arc<PacketRecorder>>,
     _phantom: PhantomData<T>,
 }
 
 impl<S: Serialize> ControlSocketSender<S> {
     pub fn send(&mut self, packet: &S) -> Result<()> {
-        framed_send(&mut self.inner, &mut self.buffer, packet)
+        framed_send(
+            &mut self.inner,
+            &mut self.buffer,
+            Some(&mut self.cipher),
+            self.recorder.as_deref(),
+            packet,
+        )
     }
 }
 
@@ -109,6 +165,8 @@ pub struct ControlSocketReceiver<T> {
     inner: TcpStream,
     buffer: Vec<u8>,
     recv_state: Option<RecvState>,
+    cipher: Cipher,
+    recorder: Option<Arc<PacketRecorder>>,
     _phantom: PhantomData<T>,
 }
 
@@ -118,6 +176,8 @@ impl<R: DeserializeOwned> ControlSocketReceiver<R> {
             &mut self.inner,
             &mut self.buffer,
             &mut self.recv_state,
+            Some(&mut self.cipher),
+            self.recorder.as_deref(),
             timeout,
         )
     }
@@ -135,10 +195,50 @@ pub fn get_server_listener(timeout: Duration) -> Result<TcpListener> {
     Ok(listener)
 }
 
+#[derive(Serialize, Deserialize)]
+struct ClientHello {
+    identity_key: PublicKey,
+    // Hash of the ephemeral key, which is revealed only after receiving the server ephemeral key
+    ephemeral_key_commitment: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize)]
+struct ServerHello {
+    identity_key: PublicKey,
+    ephemeral_key: PublicKey,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClientAuth {
+    ephemeral_key: PublicKey,
+    signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ServerAuth {
+    signature: Vec<u8>,
+}
+
+pub struct HandshakeResult {
+    pub peer_identity_key: PublicKey,
+    // Must be compared by the user with the code shown by the peer before trusting it for the
+    // first time
+    pub pairing_code: String,
+    pub stream_keys: StreamKeys,
+}
+
+struct ControlCiphers {
+    send: Cipher,
+    recv: Cipher,
+}
+
 // Proto-control-socket that can send and receive any packet. After the split, only the packets of
-// the specified types can be exchanged
+// the specified types can be exchanged. A handshake is required before exchanging any packet.
 pub struct ProtoControlSocket {
     inner: TcpStream,
+    role: Role,
+    ciphers: Option<ControlCiphers>,
+    recorder: Option<Arc<PacketRecorder>>,
 }
 
 pub enum PeerType<'a> {
@@ -148,51 +248,287 @@ pub enum PeerType<'a> {
 
 impl ProtoControlSocket {
     pub fn connect_to(timeout: Duration, peer: PeerType<'_>) -> ConResult<(Self, IpAddr)> {
-        let socket = match peer {
+        let (socket, role) = match peer {
             PeerType::AnyClient(ips) => {
-                tcp::connect_to_client(
+                let socket = tcp::connect_to_client(
                     timeout,
                     &ips,
                     CONTROL_PORT,
                     SocketBufferSize::Default,
                     SocketBufferSize::Default,
                 )?
-                .0
+                .0;
+
+                (socket, Role::Server)
             }
-            PeerType::Server(listener) => tcp::accept_from_server(listener, None, timeout)?.0,
+            PeerType::Server(listener) => (
+                tcp::accept_from_server(listener, None, timeout)?.0,
+                Role::Client,
+            ),
         };
 
         let peer_ip = socket.peer_addr().to_con()?.ip();
 
-        Ok((Self { inner: socket }, peer_ip))
+        Ok((
+            Self {
+                inner: socket,
+                role,
+                ciphers: None,
+                recorder: None,
+            },
+            peer_ip,
+        ))
+    }
+
+    // Authenticate the peer and set up encryption. The caller is responsible for checking that
+    // the peer identity key is trusted.
+    pub fn handshake(
+        &mut self,
+        identity: &Identity,
+        timeout: Duration,
+    ) -> ConResult<HandshakeResult> {
+        let ephemeral_key = EphemeralKey::generate().to_con()?;
+
+        let (peer_identity_key, peer_ephemeral_key, transcript_hash) = match self.role {
+            Role::Client => {
+                self.send_plain(&ClientHello {
+                    identity_key: identity.public_key(),
+                    ephemeral_key_commitment: crypto::hash(&ephemeral_key.public_key()),
+                })?;
+                let server_hello = self.recv_plain::<ServerHello>(timeout)?;
+
+                let transcript_hash = crypto::transcript_hash(
+                    &identity.public_key(),
+                    &ephemeral_key.public_key(),
+                    &server_hello.identity_key,
+                    &server_hello.ephemeral_key,
+                );
+
+                self.send_plain(&ClientAuth {
+                    ephemeral_key: ephemeral_key.public_key(),
+                    signature: identity.sign(Role::Client, &transcript_hash),
+                })?;
+                let server_auth = self.recv_plain::<ServerAuth>(timeout)?;
+
+                crypto::verify_signature(
+                    &server_hello.identity_key,
+                    Role::Server,
+                    &transcript_hash,
+                    &server_auth.signature,
+                )
+                .to_con()?;
+
+                (
+                    server_hello.identity_key,
+                    server_hello.ephemeral_key,
+                    transcript_hash,
+                )
+            }
+            Role::Server => {
+                let client_hello = self.recv_plain::<ClientHello>(timeout)?;
+                self.send_plain(&ServerHello {
+                    identity_key: identity.public_key(),
+                    ephemeral_key: ephemeral_key.public_key(),
+                })?;
+                let client_auth = self.recv_plain::<ClientAuth>(timeout)?;
+
+                if crypto::hash(&client_auth.ephemeral_key) != client_hello.ephemeral_key_commitment
+                {
+                    con_bail!("Client ephemeral key does not match its commitment");
+                }
+
+                let transcript_hash = crypto::transcript_hash(
+                    &client_hello.identity_key,
+                    &client_auth.ephemeral_key,
+                    &identity.public_key(),
+                    &ephemeral_key.public_key(),
+                );
+
+                crypto::verify_signature(
+                    &client_hello.identity_key,
+                    Role::Client,
+                    &transcript_hash,
+                    &client_auth.signature,
+                )
+                .to_con()?;
+
+                self.send_plain(&ServerAuth {
+                    signature: identity.sign(Role::Server, &transcript_hash),
+                })?;
+
+                (
+                    client_hello.identity_key,
+                    client_auth.ephemeral_key,
+                    transcript_hash,
+                )
+            }
+        };
+
+        let keys = crypto::derive_session_keys(
+            identity,
+            &peer_identity_key,
+            ephemeral_key,
+            &peer_ephemeral_key,
+            &transcript_hash,
+            self.role,
+        )
+        .to_con()?;
+
+        self.ciphers = Some(ControlCiphers {
+            send: Cipher::new(&keys.control_send),
+            recv: Cipher::new(&keys.control_recv),
+        });
+
+        Ok(HandshakeResult {
+            peer_identity_key,
+            pairing_code: crypto::pairing_code(&transcript_hash),
+            stream_keys: keys.stream,
+        })
+    }
+
+    fn send_plain<S: Serialize>(&mut self, packet: &S) -> ConResult {
+        framed_send(&mut self.inner, &mut vec![], None, None, packet).to_con()
+    }
+
+    fn recv_plain<R: DeserializeOwned>(&mut self, timeout: Duration) -> ConResult<R> {
+        framed_recv(&mut self.inner, &mut vec![], &mut None, None, None, timeout)
     }
 
     pub fn send<S: Serialize>(&mut self, packet: &S) -> Result<()> {
-        framed_send(&mut self.inner, &mut vec![], packet)
+        let Some(ciphers) = &mut self.ciphers else {
+            bail!("Cannot send packets before the handshake");
+        };
+
+        framed_send(
+            &mut self.inner,
+            &mut vec![],
+            Some(&mut ciphers.send),
+            self.recorder.as_deref(),
+            packet,
+        )
     }
 
     pub fn recv<R: DeserializeOwned>(&mut self, timeout: Duration) -> ConResult<R> {
-        framed_recv(&mut self.inner, &mut vec![], &mut None, timeout)
+        let Some(ciphers) = &mut self.ciphers else {
+            con_bail!("Cannot receive packets before the handshake");
+        };
+
+        framed_recv(
+            &mut self.inner,
+            &mut vec![],
+            &mut None,
+            Some(&mut ciphers.recv),
+            self.recorder.as_deref(),
+            timeout,
+        )
+    }
+
+    // Record all packets exchanged from now on, including the ones exchanged after the split
+    pub fn set_recorder(&mut self, recorder: Arc<PacketRecorder>) {
+        self.recorder = Some(recorder);
     }
 
     pub fn split<S: Serialize, R: DeserializeOwned>(
         self,
         timeout: Duration,
     ) -> Result<(ControlSocketSender<S>, ControlSocketReceiver<R>)> {
+        let Some(ciphers) = self.ciphers else {
+            bail!("Cannot split the control socket before the handshake");
+        };
+
         self.inner.set_read_timeout(Some(timeout))?;
 
         Ok((
             ControlSocketSender {
                 inner: self.inner.try_clone()?,
                 buffer: vec![],
+                cipher: ciphers.send,
+                recorder: self.recorder.clone(),
                 _phantom: PhantomData,
             },
             ControlSocketReceiver {
                 inner: self.inner,
                 buffer: vec![],
                 recv_state: None,
+                cipher: ciphers.recv,
+                recorder: self.recorder,
                 _phantom: PhantomData,
             },
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::Ipv4Addr, thread};
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn socket_pair() -> (ProtoControlSocket, ProtoControlSocket) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let server_socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (client_socket, _) = listener.accept().unwrap();
+
+        (
+            ProtoControlSocket {
+                inner: client_socket,
+                role: Role::Client,
+                ciphers: None,
+                recorder: None,
+            },
+            ProtoControlSocket {
+                inner: server_socket,
+                role: Role::Server,
+                ciphers: None,
+                recorder: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_handshake() {
+        let (mut client_socket, mut server_socket) = socket_pair();
+        let client_identity = Identity::generate().unwrap();
+        let server_identity = Identity::generate().unwrap();
+        let client_key = client_identity.public_key();
+        let server_key = server_identity.public_key();
+
+        let server_thread = thread::spawn(move || {
+            let result = server_socket
+                .handshake(&server_identity, TIMEOUT)
+                .ok()
+                .unwrap();
+            server_socket.send(&"hello from server".to_owned()).unwrap();
+            let packet = server_socket.recv::<String>(TIMEOUT).ok().unwrap();
+
+            (result, packet)
+        });
+
+        let client_result = client_socket
+            .handshake(&client_identity, TIMEOUT)
+            .ok()
+            .unwrap();
+        let client_packet = client_socket.recv::<String>(TIMEOUT).ok().unwrap();
+        client_socket.send(&"hello from client".to_owned()).unwrap();
+
+        let (server_result, server_packet) = server_thread.join().unwrap();
+
+        assert_eq!(client_result.peer_identity_key, server_key);
+        assert_eq!(server_result.peer_identity_key, client_key);
+        assert_eq!(client_result.pairing_code, server_result.pairing_code);
+        assert_eq!(
+            client_result.stream_keys.send,
+            server_result.stream_keys.recv
+        );
+        assert_eq!(client_packet, "hello from server");
+        assert_eq!(server_packet, "hello from client");
+    }
+
+    #[test]
+    fn test_no_packets_before_handshake() {
+        let (mut client_socket, _server_socket) = socket_pair();
+
+        assert!(client_socket.send(&0_u32).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+// Cryptographic primitives used to authenticate peers and encrypt the connection.
+//
+// Each peer owns a long-term Ed25519 identity key, stored on disk. During the handshake, peers
+// exchange identity keys and ephemeral X25519 keys, then sign the transcript with the identity key.
+// The client commits to its ephemeral key before seeing the server one, so a man in the middle
+// cannot steer the pairing code derived from the transcript.
+
+use alvr_common::{anyhow::Result, warn};
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305},
+    agreement::{self, EphemeralPrivateKey, X25519},
+    digest::{self, SHA256},
+    hkdf::{self, HKDF_SHA256},
+    rand::SystemRandom,
+    signature::{self, Ed25519KeyPair, KeyPair, ED25519},
+};
+use std::{fs, io::Write, path::Path, sync::Arc};
+
+pub const TAG_SIZE: usize = 16;
+
+const TRANSCRIPT_LABEL: &[u8] = b"ALVR handshake v1";
+const CLIENT_SIGNATURE_LABEL: &[u8] = b"ALVR client signature";
+const SERVER_SIGNATURE_LABEL: &[u8] = b"ALVR server signature";
+const CLIENT_CONTROL_KEY_LABEL: &[u8] = b"ALVR client control key";
+const SERVER_CONTROL_KEY_LABEL: &[u8] = b"ALVR server control key";
+const CLIENT_STREAM_KEY_LABEL: &[u8] = b"ALVR client stream key";
+const SERVER_STREAM_KEY_LABEL: &[u8] = b"ALVR server stream key";
+
+pub type PublicKey = [u8; 32];
+
+// Long-term key pair that identifies a server or a client
+pub struct Identity {
+    key_pair: Ed25519KeyPair,
+    // Kept to sign the certificates of backends that run their own TLS handshake
+    pkcs8: Arc<[u8]>,
+}
+
+impl Identity {
+    fn from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        Ok(Self {
+            key_pair: Ed25519KeyPair::from_pkcs8(pkcs8)?,
+            pkcs8: pkcs8.into(),
+        })
+    }
+
+    pub fn generate() -> Result<Self> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())?;
+
+        Self::from_pkcs8(pkcs8.as_ref())
+    }
+
+    // Load the identity from the specified file. If the file does not exist or it is invalid, a
+    // new identity is generated and saved.
+    pub fn load_or_generate(path: &Path) -> Result<Self> {
+        if let Ok(pkcs8) = fs::read(path) {
+            if let Ok(identity) = Self::from_pkcs8(&pkcs8) {
+                return Ok(identity);
+            } else {
+                warn!("Invalid identity key file. Generating a new identity");
+            }
+        }
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // An invalid file is replaced instead of being truncated, so that it does not keep its
+        // permissions
+        fs::remove_file(path).ok();
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)?.write_all(pkcs8.as_ref())?;
+
+        Self::from_pkcs8(pkcs8.as_ref())
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.key_pair.public_key().as_ref().try_into().unwrap()
+    }
+
+    pub(crate) fn sign(&self, role: Role, transcript_hash: &[u8]) -> Vec<u8> {
+        self.key_pair
+            .sign(&signed_message(role, transcript_hash))
+            .as_ref()
+            .to_vec()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Role {
+    Client,
+    Server,
+}
+
+fn signed_message(role: Role, transcript_hash: &[u8]) -> Vec<u8> {
+    let label = match role {
+        Role::Client => CLIENT_SIGNATURE_LABEL,
+        Role::Server => SERVER_SIGNATURE_LABEL,
+    };
+
+    [label, transcript_hash].concat()
+}
+
+pub(crate) fn verify_signature(
+    public_key: &PublicKey,
+    role: Role,
+    transcript_hash: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    signature::UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&signed_message(role, transcript_hash), signature)?;
+
+    Ok(())
+}
+
+pub(crate) struct EphemeralKey {
+    private_key: EphemeralPrivateKey,
+    public_key: PublicKey,
+}
+
+impl EphemeralKey {
+    pub fn generate() -> Result<Self> {
+        let private_key = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new())?;
+        let public_key = private_key.compute_public_key()?.as_ref().try_into()?;
+
+        Ok(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+}
+
+pub(crate) fn hash(data: &[u8]) -> [u8; 32] {
+    digest::digest(&SHA256, data).as_ref().try_into().unwrap()
+}
+
+pub(crate) fn transcript_hash(
+    client_identity_key: &PublicKey,
+    client_ephemeral_key: &PublicKey,
+    server_identity_key: &PublicKey,
+    server_ephemeral_key: &PublicKey,
+) -> [u8; 32] {
+    hash(
+        &[
+            TRANSCRIPT_LABEL,
+            client_identity_key,
+            client_ephemeral_key,
+            server_identity_key,
+            server_ephemeral_key,
+        ]
+        .concat(),
+    )
+}
+
+// Six digit code shown on both the headset and the dashboard. Since it depends on both ephemeral
+// keys, it matches only if no one tampered with the handshake.
+pub(crate) fn pairing_code(transcript_hash: &[u8; 32]) -> String {
+    let value = u32::from_be_bytes(transcript_hash[0..4].try_into().unwrap());
+
+    format!("{:06}", value % 1_000_000)
+}
+
+// Raw keys used to encrypt the stream socket. The stream socket is created after the control
+// socket handshake, so the keys are handed over by the caller.
+#[derive(Clone)]
+pub struct StreamKeys {
+    pub(crate) send: [u8; 32],
+    pub(crate) recv: [u8; 32],
+    // Backends that run their own TLS handshake (QUIC) present a certificate signed with the local
+    // identity and accept only the peer identity authenticated by the control socket handshake
+    pub(crate) identity_pkcs8: Arc<[u8]>,
+    pub(crate) peer_identity_key: PublicKey,
+}
+
+pub(crate) struct SessionKeys {
+    pub control_send: [u8; 32],
+    pub control_recv: [u8; 32],
+    pub stream: StreamKeys,
+}
+
+pub(crate) fn derive_session_keys(
+    identity: &Identity,
+    peer_identity_key: &PublicKey,
+    ephemeral_key: EphemeralKey,
+    peer_ephemeral_key: &PublicKey,
+    transcript_hash: &[u8; 32],
+    role: Role,
+) -> Result<SessionKeys> {
+    let prk = agreement::agree_ephemeral(
+        ephemeral_key.private_key,
+        &agreement::UnparsedPublicKey::new(&X25519, peer_ephemeral_key),
+        |shared_secret| hkdf::Salt::new(HKDF_SHA256, transcript_hash).extract(shared_secret),
+    )?;
+
+    let expand = |label: &[u8]| -> Result<[u8; 32]> {
+        let mut key = [0; 32];
+        prk.expand(&[label], HKDF_SHA256)?.fill(&mut key)?;
+
+        Ok(key)
+    };
+
+    let client_control = expand(CLIENT_CONTROL_KEY_LABEL)?;
+    let server_control = expand(SERVER_CONTROL_KEY_LABEL)?;
+    let client_stream = expand(CLIENT_STREAM_KEY_LABEL)?;
+    let server_stream = expand(SERVER_STREAM_KEY_LABEL)?;
+
+    Ok(match role {
+        Role::Client => SessionKeys {
+            control_send: client_control,
+            control_recv: server_control,
+            stream: StreamKeys {
+                send: client_stream,
+                recv: server_stream,
+                identity_pkcs8: Arc::clone(&identity.pkcs8),
+                peer_identity_key: *peer_identity_key,
+            },
+        },
+        Role::Server => SessionKeys {
+            control_send: server_control,
+            control_recv: client_control,
+            stream: StreamKeys {
+                send: server_stream,
+                recv: client_stream,
+                identity_pkcs8: Arc::clone(&identity.pkcs8),
+                peer_identity_key: *peer_identity_key,
+            },
+        },
+    })
+}
+
+// ChaCha20-Poly1305 cipher for one direction of a connection. Nonces are generated from a counter,
+// so each key must be used by a single sender.
+pub(crate) struct Cipher {
+    key: LessSafeKey,
+    next_counter: u64,
+}
+
+impl Cipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            key: LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap()),
+            next_counter: 0,
+        }
+    }
+
+    fn nonce(counter: u64) -> Nonce {
+        let mut nonce = [0; aead::NONCE_LEN];
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+
+        Nonce::assume_unique_for_key(nonce)
+    }
+
+    // The last TAG_SIZE bytes of the buffer are overwritten with the authentication tag. Returns
+    // the counter used for the nonce.
+    pub fn seal(&mut self, buffer: &mut [u8]) -> u64 {
+        let counter = self.next_counter;
+        self.next_counter += 1;
+
+        let (plaintext, tag) = buffer.split_at_mut(buffer.len() - TAG_SIZE);
+        let computed_tag = self
+            .key
+            .seal_in_place_separate_tag(Self::nonce(counter), Aad::empty(), plaintext)
+            .unwrap();
+        tag.copy_from_slice(computed_tag.as_ref());
+
+        counter
+    }
+
+    // Returns the size of the plaintext, which is placed at the start of the buffer
+    pub fn open(&self, counter: u64, buffer: &mut [u8]) -> Result<usize> {
+        Ok(self
+            .key
+            .open_in_place(Self::nonce(counter), Aad::empty(), buffer)?
+            .len())
+    }
+
+    // Open a message from a reliable and ordered channel, where the counter is implicit
+    pub fn open_next(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let size = self.open(self.next_counter, buffer)?;
+        self.next_counter += 1;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_keys_pair() -> (SessionKeys, SessionKeys) {
+        let client_identity = Identity::generate().unwrap();
+        let server_identity = Identity::generate().unwrap();
+        let client_key = EphemeralKey::generate().unwrap();
+        let server_key = EphemeralKey::generate().unwrap();
+        let client_public = client_key.public_key();
+        let server_public = server_key.public_key();
+        let hash = [7; 32];
+
+        (
+            derive_session_keys(
+                &client_identity,
+                &server_identity.public_key(),
+                client_key,
+                &server_public,
+                &hash,
+                Role::Client,
+            )
+            .unwrap(),
+            derive_session_keys(
+                &server_identity,
+                &client_identity.public_key(),
+                server_key,
+                &client_public,
+                &hash,
+                Role::Server,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_derived_keys_match() {
+        let (client, server) = session_keys_pair();
+
+        assert_eq!(client.control_send, server.control_recv);
+        assert_eq!(client.control_recv, server.control_send);
+        assert_eq!(client.stream.send, server.stream.recv);
+        assert_eq!(client.stream.recv, server.stream.send);
+        assert_ne!(client.control_send, client.control_recv);
+        assert_ne!(client.control_send, client.stream.send);
+    }
+
+    #[test]
+    fn test_cipher_roundtrip() {
+        let (client, server) = session_keys_pair();
+        let mut sealer = Cipher::new(&client.control_send);
+        let mut opener = Cipher::new(&server.control_recv);
+
+        for message in [&b"hello"[..], b"world"] {
+            let mut buffer = [message, &[0; TAG_SIZE]].concat();
+            sealer.seal(&mut buffer);
+            assert_ne!(&buffer[..message.len()], message);
+
+            let size = opener.open_next(&mut buffer).unwrap();
+            assert_eq!(&buffer[..size], message);
+        }
+    }
+
+    #[test]
+    fn test_cipher_rejects_tampering() {
+        let (client, server) = session_keys_pair();
+        let mut sealer = Cipher::new(&client.control_send);
+        let opener = Cipher::new(&server.control_recv);
+
+        let mut buffer = [&b"hello"[..], &[0; TAG_SIZE]].concat();
+        let counter = sealer.seal(&mut buffer);
+        buffer[0] ^= 1;
+
+        assert!(opener.open(counter, &mut buffer).is_err());
+    }
+
+    #[test]
+    fn test_identity_key_file() {
+        let path = std::env::temp_dir()
+            .join(format!("alvr_identity_test_{}", std::process::id()))
+            .join("identity_key");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"invalid").unwrap();
+
+        let identity = Identity::load_or_generate(&path).unwrap();
+        assert_eq!(
+            Identity::load_or_generate(&path).unwrap().public_key(),
+            identity.public_key()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_signature() {
+        let identity = Identity::generate().unwrap();
+        let hash = [3; 32];
+        let signature = identity.sign(Role::Client, &hash);
+
+        assert!(verify_signature(&identity.public_key(), Role::Client, &hash, &signature).is_ok());
+        // A client signature cannot be replayed as a server signature
+        assert!(verify_signature(&identity.public_key(), Role::Server, &hash, &signature).is_err());
+        assert!(
+            verify_signature(&identity.public_key(), Role::Client, &[4; 32], &signature).is_err()
+        );
+    }
+}
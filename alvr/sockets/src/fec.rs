@@ -0,0 +1,166 @@
+// Forward error correction based on XOR parity.
+// The data shards of a packet are split into groups of `group_size` consecutive shards. For each
+// group a parity shard is sent after all the data shards. The parity shard payload contains the
+// total length of the packet followed by the XOR of the payloads of the data shards of the group
+// (shorter shards are zero padded). This allows to rebuild at most one lost data shard per group.
+// Parity shards reuse the shard prefix, and they are identified by a shard index greater or equal
+// than the data shards count.
+
+use crate::stream_socket::SHARD_PREFIX_SIZE;
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+// This corresponds to the total packet length, including the prefix of the first shard
+pub const PARITY_HEADER_SIZE: usize = mem::size_of::<u32>();
+
+// When FEC is enabled, data shards are shrunk so that parity shards can fit the parity header
+pub fn max_shard_data_size(max_packet_size: usize, fec_group_size: Option<usize>) -> usize {
+    if fec_group_size.is_some() {
+        max_packet_size - SHARD_PREFIX_SIZE - PARITY_HEADER_SIZE
+    } else {
+        max_packet_size - SHARD_PREFIX_SIZE
+    }
+}
+
+fn div_ceil(lhs: usize, rhs: usize) -> usize {
+    (lhs as f32 / rhs as f32).ceil() as usize
+}
+
+pub fn groups_count(shards_count: usize, group_size: usize) -> usize {
+    div_ceil(shards_count, group_size)
+}
+
+// Position of the data of a shard inside the packet buffer. The buffer layout is the one used by
+// StreamSender and StreamSocket: one shard prefix followed by the contiguous data of all shards.
+fn shard_data_range(
+    shard_index: usize,
+    max_shard_data_size: usize,
+    packet_length: usize,
+) -> (usize, usize) {
+    let start = SHARD_PREFIX_SIZE + shard_index * max_shard_data_size;
+    let length = usize::min(max_shard_data_size, packet_length - start);
+
+    (start, length)
+}
+
+/// Size of the parity shard of a group, prefix included. This is equal to the size of the first
+/// data shard of the group (which is the largest) plus the parity header.
+pub fn parity_shard_size(
+    group_index: usize,
+    group_size: usize,
+    max_shard_data_size: usize,
+    packet_length: usize,
+) -> usize {
+    let (_, length) =
+        shard_data_range(group_index * group_size, max_shard_data_size, packet_length);
+
+    SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE + length
+}
+
+/// Compute the parity shards of a packet. `packet` contains the prefix space followed by the data
+/// and must not have been sent yet (sending a packet overwrites data with the shard prefixes).
+/// Each parity shard is written in `parity_buffer` at multiples of the returned stride, leaving
+/// the space for the shard prefix at the start.
+pub fn encode_parity_shards(
+    packet: &[u8],
+    max_shard_data_size: usize,
+    group_size: usize,
+    parity_buffer: &mut Vec<u8>,
+) -> usize {
+    let packet_length = packet.len();
+    let shards_count = div_ceil(packet_length - SHARD_PREFIX_SIZE, max_shard_data_size);
+    let stride = SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE + max_shard_data_size;
+
+    parity_buffer.clear();
+    parity_buffer.resize(groups_count(shards_count, group_size) * stride, 0);
+
+    for (group_index, parity_shard) in parity_buffer.chunks_exact_mut(stride).enumerate() {
+        parity_shard[SHARD_PREFIX_SIZE..SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE]
+            .copy_from_slice(&(packet_length as u32).to_be_bytes());
+
+        let parity_payload = &mut parity_shard[SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE..];
+        let first_shard = group_index * group_size;
+        for shard_index in first_shard..usize::min(first_shard + group_size, shards_count) {
+            let (start, length) = shard_data_range(shard_index, max_shard_data_size, packet_length);
+
+            for (parity_byte, data_byte) in
+                parity_payload.iter_mut().zip(&packet[start..][..length])
+            {
+                *parity_byte ^= data_byte;
+            }
+        }
+    }
+
+    stride
+}
+
+/// Rebuild the missing data shards of a packet using the received parity shards.
+/// `parity_shards` maps the group index to the whole parity shard, prefix included.
+/// Returns true if all data shards are available. In this case `buffer_length` is updated to the
+/// full packet length.
+pub fn recover_missing_shards(
+    buffer: &mut Vec<u8>,
+    buffer_length: &mut usize,
+    received_shard_indices: &mut HashSet<usize>,
+    parity_shards: &HashMap<usize, Vec<u8>>,
+    shards_count: usize,
+    group_size: usize,
+    max_shard_data_size: usize,
+) -> bool {
+    // First check that every group is recoverable, before touching the buffer
+    let mut missing_shards = vec![];
+    for group_index in 0..groups_count(shards_count, group_size) {
+        let first_shard = group_index * group_size;
+        let mut missing_in_group = (first_shard
+            ..usize::min(first_shard + group_size, shards_count))
+            .filter(|idx| !received_shard_indices.contains(idx));
+
+        if let Some(missing_index) = missing_in_group.next() {
+            if missing_in_group.next().is_some() || !parity_shards.contains_key(&group_index) {
+                return false;
+            }
+
+            missing_shards.push((missing_index, group_index));
+        }
+    }
+
+    let Some(&(_, group_index)) = missing_shards.first() else {
+        return true;
+    };
+
+    let parity_header = &parity_shards[&group_index][SHARD_PREFIX_SIZE..];
+    let packet_length =
+        u32::from_be_bytes(parity_header[..PARITY_HEADER_SIZE].try_into().unwrap()) as usize;
+
+    if buffer.len() < packet_length {
+        buffer.resize(packet_length, 0);
+    }
+    *buffer_length = packet_length;
+
+    for (missing_index, group_index) in missing_shards {
+        let (start, length) = shard_data_range(missing_index, max_shard_data_size, packet_length);
+
+        let parity_payload = &parity_shards[&group_index][SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE..];
+        buffer[start..start + length].copy_from_slice(&parity_payload[..length]);
+
+        let first_shard = group_index * group_size;
+        for shard_index in first_shard..usize::min(first_shard + group_size, shards_count) {
+            if shard_index == missing_index {
+                continue;
+            }
+
+            let (other_start, other_length) =
+                shard_data_range(shard_index, max_shard_data_size, packet_length);
+            for offset in 0..usize::min(length, other_length) {
+                let data_byte = buffer[other_start + offset];
+                buffer[start + offset] ^= data_byte;
+            }
+        }
+
+        received_shard_indices.insert(missing_index);
+    }
+
+    true
+}
@@ -1,5 +1,8 @@
 mod backend;
+mod capture;
 mod control_socket;
+mod crypto;
+mod fec;
 mod stream_socket;
 
 use alvr_common::{anyhow::Result, info};
@@ -10,7 +13,10 @@ use std::{
     time::Duration,
 };
 
+pub use backend::emulator::{NetworkEmulator, VirtualLink};
+pub use capture::*;
 pub use control_socket::*;
+pub use crypto::{Identity, PublicKey, StreamKeys};
 pub use stream_socket::*;
 
 pub const LOCAL_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
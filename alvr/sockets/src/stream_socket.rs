@@ -16,11 +16,20 @@
 // Note: We can't clone the underlying socket for each StreamSender and the mutex around the socket
 // cannot be removed. This is because we need to make sure at least shards are written whole.
 
-use crate::backend::{tcp, udp, SocketReader, SocketWriter};
+use crate::{
+    backend::{
+        emulator::{EmulatedSocketWriter, VirtualLink},
+        encrypted::{self, EncryptedSocketReader, EncryptedSocketWriter},
+        quic, tcp, udp, SocketReader, SocketWriter,
+    },
+    capture::{CaptureChannel, PacketDirection, PacketRecorder},
+    crypto::StreamKeys,
+    fec,
+};
 use alvr_common::{
-    anyhow::Result, debug, parking_lot::Mutex, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+    anyhow::Result, debug, info, parking_lot::Mutex, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
 };
-use alvr_session::{DscpTos, SocketBufferSize, SocketProtocol};
+use alvr_session::{DscpTos, NetworkEmulatorConfig, SocketBufferSize, SocketProtocol};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     cmp::Ordering,
@@ -32,7 +41,7 @@ use std::{
     time::Duration,
 };
 
-const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
+pub(crate) const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
     + mem::size_of::<u16>() // stream ID
     + mem::size_of::<u32>() // packet index
     + mem::size_of::<u32>() // shards count
@@ -92,18 +101,58 @@ pub struct StreamSender<H> {
     // if the packet index overflows the worst that happens is a false positive packet loss
     next_packet_index: u32,
     used_buffers: Vec<Vec<u8>>,
+    fec_group_size: Option<usize>,
+    parity_buffer: Vec<u8>,
+    unreliable: bool,
+    recorder: Option<Arc<PacketRecorder>>,
     _phantom: PhantomData<H>,
 }
 
 impl<H> StreamSender<H> {
+    /// Allow the network to drop the shards of this stream. This has effect only for backends
+    /// that support both reliable and unreliable transmission (QUIC).
+    pub fn set_unreliable(&mut self, unreliable: bool) {
+        self.unreliable = unreliable;
+    }
+
+    fn send_shard(&self, shard: &[u8]) -> Result<()> {
+        if self.unreliable {
+            self.inner.lock().send_unreliable(shard)
+        } else {
+            self.inner.lock().send(shard)
+        }
+    }
+
     /// Shard and send a buffer with zero copies and zero allocations.
     /// The prefix of each shard is written over the previously sent shard to avoid reallocations.
     pub fn send(&mut self, mut buffer: Buffer<H>) -> Result<()> {
-        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE;
+        let max_shard_data_size =
+            fec::max_shard_data_size(self.max_packet_size, self.fec_group_size);
         let actual_buffer_size = buffer.hidden_offset + buffer.length;
         let data_size = actual_buffer_size - SHARD_PREFIX_SIZE;
         let shards_count = (data_size as f32 / max_shard_data_size as f32).ceil() as usize;
 
+        if let Some(recorder) = &self.recorder {
+            recorder.record(
+                PacketDirection::Sent,
+                CaptureChannel::Stream {
+                    stream_id: self.stream_id,
+                    packet_index: self.next_packet_index,
+                },
+                &buffer.inner[SHARD_PREFIX_SIZE..actual_buffer_size],
+            );
+        }
+
+        // Parity must be computed before sending, since shard prefixes overwrite the data
+        let parity_stride = self.fec_group_size.map(|group_size| {
+            fec::encode_parity_shards(
+                &buffer.inner[..actual_buffer_size],
+                max_shard_data_size,
+                group_size,
+                &mut self.parity_buffer,
+            )
+        });
+
         for idx in 0..shards_count {
             // this overlaps with the previous shard, this is intended behavior and allows to
             // reduce allocations
@@ -125,7 +174,32 @@ impl<H> StreamSender<H> {
             sub_buffer[10..14].copy_from_slice(&(shards_count as u32).to_be_bytes());
             sub_buffer[14..18].copy_from_slice(&(idx as u32).to_be_bytes());
 
-            self.inner.lock().send(&sub_buffer[..packet_length])?;
+            self.send_shard(&sub_buffer[..packet_length])?;
+        }
+
+        if let (Some(group_size), Some(stride)) = (self.fec_group_size, parity_stride) {
+            for group_idx in 0..fec::groups_count(shards_count, group_size) {
+                let sub_buffer = &mut self.parity_buffer[group_idx * stride..];
+
+                let packet_length = fec::parity_shard_size(
+                    group_idx,
+                    group_size,
+                    max_shard_data_size,
+                    actual_buffer_size,
+                );
+
+                // Parity shards are identified by an index past the data shards
+                sub_buffer[0..4].copy_from_slice(
+                    &((packet_length - mem::size_of::<u32>()) as u32).to_be_bytes(),
+                );
+                sub_buffer[4..6].copy_from_slice(&self.stream_id.to_be_bytes());
+                sub_buffer[6..10].copy_from_slice(&self.next_packet_index.to_be_bytes());
+                sub_buffer[10..14].copy_from_slice(&(shards_count as u32).to_be_bytes());
+                sub_buffer[14..18]
+                    .copy_from_slice(&((shards_count + group_idx) as u32).to_be_bytes());
+
+                self.send_shard(&self.parity_buffer[group_idx * stride..][..packet_length])?;
+            }
         }
 
         self.next_packet_index += 1;
@@ -205,9 +279,11 @@ struct ReconstructedPacket {
 }
 
 pub struct StreamReceiver<H> {
+    stream_id: u16,
     packet_receiver: mpsc::Receiver<ReconstructedPacket>,
     used_buffer_queue: mpsc::Sender<Vec<u8>>,
     last_packet_index: Option<u32>,
+    recorder: Option<Arc<PacketRecorder>>,
     _phantom: PhantomData<H>,
 }
 
@@ -251,6 +327,17 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
         }
         self.last_packet_index = Some(packet.index);
 
+        if let Some(recorder) = &self.recorder {
+            recorder.record(
+                PacketDirection::Received,
+                CaptureChannel::Stream {
+                    stream_id: self.stream_id,
+                    packet_index: packet.index,
+                },
+                &packet.buffer[SHARD_PREFIX_SIZE..packet.size],
+            );
+        }
+
         Ok(ReceiverData {
             buffer: Some(packet.buffer),
             size: packet.size,
@@ -261,9 +348,27 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
     }
 }
 
+// Each shard must fit in a single QUIC datagram, otherwise it is sent on the reliable stream. Both
+// peers get the same limit, so they agree on the shard size.
+// Note: a shard is 4 bytes bigger than max_packet_size, see StreamSocket::new_quic()
+fn clamp_quic_packet_size(max_packet_size: usize, socket: &quic::QuicSocketWriter) -> usize {
+    match socket.max_datagram_size() {
+        Some(datagram_size) if datagram_size - 4 < max_packet_size => {
+            info!(
+                "Limiting packet size to {} bytes for QUIC",
+                datagram_size - 4
+            );
+
+            datagram_size - 4
+        }
+        _ => max_packet_size,
+    }
+}
+
 pub enum StreamSocketBuilder {
     Tcp(TcpListener),
     Udp(UdpSocket),
+    Quic(quic::QuicListener),
 }
 
 impl StreamSocketBuilder {
@@ -289,6 +394,12 @@ impl StreamSocketBuilder {
                 send_buffer_bytes,
                 recv_buffer_bytes,
             )?),
+            SocketProtocol::Quic => StreamSocketBuilder::Quic(quic::bind(
+                port,
+                stream_tos_config,
+                send_buffer_bytes,
+                recv_buffer_bytes,
+            )?),
         })
     }
 
@@ -298,7 +409,11 @@ impl StreamSocketBuilder {
         port: u16,
         max_packet_size: usize,
         timeout: Duration,
+        stream_keys: &StreamKeys,
+        network_emulator: Option<&NetworkEmulatorConfig>,
     ) -> ConResult<StreamSocket> {
+        let is_datagram = matches!(self, StreamSocketBuilder::Udp(_));
+
         let (send_socket, receive_socket): (Box<dyn SocketWriter>, Box<dyn SocketReader>) =
             match self {
                 StreamSocketBuilder::Udp(socket) => {
@@ -313,17 +428,28 @@ impl StreamSocketBuilder {
 
                     (Box::new(send_socket), Box::new(receive_socket))
                 }
+                StreamSocketBuilder::Quic(listener) => {
+                    let (send_socket, receive_socket) =
+                        quic::accept_from_server(listener, server_ip, timeout, stream_keys)?;
+                    let max_packet_size = clamp_quic_packet_size(max_packet_size, &send_socket);
+
+                    return Ok(StreamSocket::new_quic(
+                        send_socket,
+                        receive_socket,
+                        max_packet_size,
+                        network_emulator,
+                    ));
+                }
             };
 
-        Ok(StreamSocket {
-            // +4 is a workaround to retain compatibilty with old protocol
-            // todo: remove +4
-            max_packet_size: max_packet_size + 4,
-            send_socket: Arc::new(Mutex::new(send_socket)),
+        Ok(StreamSocket::new_encrypted(
+            send_socket,
             receive_socket,
-            shard_recv_state: None,
-            stream_recv_components: HashMap::new(),
-        })
+            stream_keys,
+            is_datagram,
+            max_packet_size,
+            network_emulator,
+        ))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -336,7 +462,11 @@ impl StreamSocketBuilder {
         send_buffer_bytes: SocketBufferSize,
         recv_buffer_bytes: SocketBufferSize,
         max_packet_size: usize,
+        stream_keys: &StreamKeys,
+        network_emulator: Option<&NetworkEmulatorConfig>,
     ) -> ConResult<StreamSocket> {
+        let is_datagram = matches!(protocol, SocketProtocol::Udp);
+
         let (send_socket, receive_socket): (Box<dyn SocketWriter>, Box<dyn SocketReader>) =
             match protocol {
                 SocketProtocol::Udp => {
@@ -358,17 +488,35 @@ impl StreamSocketBuilder {
 
                     (Box::new(send_socket), Box::new(receive_socket))
                 }
+                SocketProtocol::Quic => {
+                    let (send_socket, receive_socket) = quic::connect_to_client(
+                        timeout,
+                        client_ip,
+                        port,
+                        dscp,
+                        send_buffer_bytes,
+                        recv_buffer_bytes,
+                        stream_keys,
+                    )?;
+                    let max_packet_size = clamp_quic_packet_size(max_packet_size, &send_socket);
+
+                    return Ok(StreamSocket::new_quic(
+                        send_socket,
+                        receive_socket,
+                        max_packet_size,
+                        network_emulator,
+                    ));
+                }
             };
 
-        Ok(StreamSocket {
-            // +4 is a workaround to retain compatibilty with old protocol
-            // todo: remove +4
-            max_packet_size: max_packet_size + 4,
-            send_socket: Arc::new(Mutex::new(send_socket)),
+        Ok(StreamSocket::new_encrypted(
+            send_socket,
             receive_socket,
-            shard_recv_state: None,
-            stream_recv_components: HashMap::new(),
-        })
+            stream_keys,
+            is_datagram,
+            max_packet_size,
+            network_emulator,
+        ))
     }
 }
 
@@ -387,6 +535,8 @@ struct InProgressPacket {
     buffer: Vec<u8>,
     buffer_length: usize,
     received_shard_indices: HashSet<usize>,
+    // Indexed by FEC group. Each parity shard contains also the prefix
+    parity_shards: HashMap<usize, Vec<u8>>,
 }
 
 struct StreamRecvComponents {
@@ -395,6 +545,8 @@ struct StreamRecvComponents {
     packet_queue: mpsc::Sender<ReconstructedPacket>,
     in_progress_packets: HashMap<u32, InProgressPacket>,
     discarded_shards_sink: InProgressPacket,
+    fec_group_size: Option<usize>,
+    last_completed_packet_index: Option<u32>,
 }
 
 // Note: used buffers don't *have* to be split by stream ID, but doing so improves memory usage
@@ -405,16 +557,125 @@ pub struct StreamSocket {
     receive_socket: Box<dyn SocketReader>,
     shard_recv_state: Option<RecvState>,
     stream_recv_components: HashMap<u16, StreamRecvComponents>,
+    recorder: Option<Arc<PacketRecorder>>,
 }
 
 impl StreamSocket {
+    // The emulator is placed right above the backend, so it acts on whole network packets
+    fn emulated(
+        send_socket: Box<dyn SocketWriter>,
+        is_datagram: bool,
+        network_emulator: Option<&NetworkEmulatorConfig>,
+    ) -> Box<dyn SocketWriter> {
+        if let Some(config) = network_emulator {
+            Box::new(EmulatedSocketWriter::new(
+                send_socket,
+                config.clone(),
+                rand::random(),
+                is_datagram,
+            ))
+        } else {
+            send_socket
+        }
+    }
+
+    fn new_encrypted(
+        send_socket: Box<dyn SocketWriter>,
+        receive_socket: Box<dyn SocketReader>,
+        stream_keys: &StreamKeys,
+        is_datagram: bool,
+        max_packet_size: usize,
+        network_emulator: Option<&NetworkEmulatorConfig>,
+    ) -> Self {
+        let send_socket = Self::emulated(send_socket, is_datagram, network_emulator);
+
+        Self {
+            // +4 is a workaround to retain compatibilty with old protocol
+            // todo: remove +4
+            // Each shard is wrapped in an encrypted record, which must fit in a packet too
+            max_packet_size: max_packet_size + 4 - encrypted::RECORD_OVERHEAD,
+            send_socket: Arc::new(Mutex::new(Box::new(EncryptedSocketWriter::new(
+                send_socket,
+                stream_keys,
+            )))),
+            receive_socket: Box::new(EncryptedSocketReader::new(
+                receive_socket,
+                stream_keys,
+                is_datagram,
+            )),
+            shard_recv_state: None,
+            stream_recv_components: HashMap::new(),
+            recorder: None,
+        }
+    }
+
+    // QUIC already encrypts the traffic with TLS 1.3 and the peer identity is pinned by its
+    // certificate verifier, so shards are not wrapped in encrypted records
+    fn new_quic(
+        send_socket: quic::QuicSocketWriter,
+        receive_socket: quic::QuicSocketReader,
+        max_packet_size: usize,
+        network_emulator: Option<&NetworkEmulatorConfig>,
+    ) -> Self {
+        Self {
+            // +4 is a workaround to retain compatibilty with old protocol
+            // todo: remove +4
+            max_packet_size: max_packet_size + 4,
+            send_socket: Arc::new(Mutex::new(Self::emulated(
+                Box::new(send_socket),
+                false,
+                network_emulator,
+            ))),
+            receive_socket: Box::new(receive_socket),
+            shard_recv_state: None,
+            stream_recv_components: HashMap::new(),
+            recorder: None,
+        }
+    }
+
+    // Unencrypted socket over in-process links, for deterministic tests. Use the same link for
+    // sending and receiving to get a loopback socket.
+    pub fn new_virtual(
+        send_link: VirtualLink,
+        receive_link: VirtualLink,
+        max_packet_size: usize,
+    ) -> Self {
+        Self {
+            max_packet_size,
+            send_socket: Arc::new(Mutex::new(Box::new(send_link))),
+            receive_socket: Box::new(receive_link),
+            shard_recv_state: None,
+            stream_recv_components: HashMap::new(),
+            recorder: None,
+        }
+    }
+
+    // Record the packets of the streams requested or subscribed from now on
+    pub fn set_recorder(&mut self, recorder: Arc<PacketRecorder>) {
+        self.recorder = Some(recorder);
+    }
+
     pub fn request_stream<T>(&self, stream_id: u16) -> StreamSender<T> {
+        self.request_stream_with_fec(stream_id, None)
+    }
+
+    // fec_group_size: if set, a parity shard is sent for each group of this many data shards. The
+    // receiver must subscribe with the same group size.
+    pub fn request_stream_with_fec<T>(
+        &self,
+        stream_id: u16,
+        fec_group_size: Option<usize>,
+    ) -> StreamSender<T> {
         StreamSender {
             inner: Arc::clone(&self.send_socket),
             stream_id,
             max_packet_size: self.max_packet_size,
             next_packet_index: 0,
             used_buffers: vec![],
+            fec_group_size,
+            parity_buffer: vec![],
+            unreliable: false,
+            recorder: self.recorder.clone(),
             _phantom: PhantomData,
         }
     }
@@ -426,6 +687,17 @@ impl StreamSocket {
         &mut self,
         stream_id: u16,
         max_concurrent_buffers: usize,
+    ) -> StreamReceiver<T> {
+        self.subscribe_to_stream_with_fec(stream_id, max_concurrent_buffers, None)
+    }
+
+    // fec_group_size: must match the group size used by the sender. Missing shards are rebuilt
+    // when possible, otherwise the packet is dropped as usual.
+    pub fn subscribe_to_stream_with_fec<T>(
+        &mut self,
+        stream_id: u16,
+        max_concurrent_buffers: usize,
+        fec_group_size: Option<usize>,
     ) -> StreamReceiver<T> {
         let (packet_sender, packet_receiver) = mpsc::channel();
         let (used_buffer_sender, used_buffer_receiver) = mpsc::channel();
@@ -445,15 +717,20 @@ impl StreamSocket {
                     buffer: vec![],
                     buffer_length: 0,
                     received_shard_indices: HashSet::new(),
+                    parity_shards: HashMap::new(),
                 },
+                fec_group_size,
+                last_completed_packet_index: None,
             },
         );
 
         StreamReceiver {
+            stream_id,
             packet_receiver,
             used_buffer_queue: used_buffer_sender,
             _phantom: PhantomData,
             last_packet_index: None,
+            recorder: self.recorder.clone(),
         }
     }
 
@@ -499,6 +776,18 @@ impl StreamSocket {
             return alvr_common::try_again();
         };
 
+        // Shards of already reconstructed packets are discarded. This happens when FEC rebuilt a
+        // packet before receiving all of its shards, or when receiving the parity shards of a
+        // packet that had no loss.
+        if let Some(last_idx) = components.last_completed_packet_index {
+            if !shard_recv_state_mut.should_discard
+                && wrapping_cmp(shard_recv_state_mut.packet_index, last_idx) != Ordering::Greater
+            {
+                shard_recv_state_mut.should_discard = true;
+                shard_recv_state_mut.shard_index = 0;
+            }
+        }
+
         let in_progress_packet = if shard_recv_state_mut.should_discard {
             &mut components.discarded_shards_sink
         } else if let Some(packet) = components
@@ -523,6 +812,7 @@ impl StreamSocket {
                     received_shard_indices: HashSet::with_capacity(
                         shard_recv_state_mut.shards_count,
                     ),
+                    parity_shards: HashMap::new(),
                 },
             );
             components
@@ -539,49 +829,73 @@ impl StreamSocket {
             &mut components.discarded_shards_sink
         };
 
-        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE;
-        // Note: there is no prefix offset, since we want to write the prefix too.
-        let packet_start_index = shard_recv_state_mut.shard_index * max_shard_data_size;
+        let max_shard_data_size =
+            fec::max_shard_data_size(self.max_packet_size, components.fec_group_size);
 
-        // Prepare buffer to accomodate receiving shard
+        if components.fec_group_size.is_some()
+            && !shard_recv_state_mut.should_discard
+            && shard_recv_state_mut.shard_index >= shard_recv_state_mut.shards_count
         {
-            // Note: this contains the prefix offset
-            in_progress_packet.buffer_length = usize::max(
-                in_progress_packet.buffer_length,
-                packet_start_index + shard_recv_state_mut.shard_length,
-            );
-
-            if in_progress_packet.buffer.len() < in_progress_packet.buffer_length {
-                in_progress_packet
-                    .buffer
-                    .resize(in_progress_packet.buffer_length, 0);
-            }
-        }
-
-        let sub_buffer = &mut in_progress_packet.buffer[packet_start_index..];
+            // Parity shards are kept aside, they are needed only if some data shard is lost
+            let group_index = shard_recv_state_mut.shard_index - shard_recv_state_mut.shards_count;
+            let parity_shard = in_progress_packet
+                .parity_shards
+                .entry(group_index)
+                .or_default();
+            parity_shard.resize(shard_recv_state_mut.shard_length, 0);
 
-        // Read shard into the single contiguous buffer
-        {
-            // Backup the small section of bytes that will be overwritten by reading from socket.
-            if shard_recv_state_mut.overwritten_data_backup.is_none() {
-                shard_recv_state_mut.overwritten_data_backup =
-                    Some(sub_buffer[..SHARD_PREFIX_SIZE].try_into().unwrap())
-            }
-
-            // This loop may bail out at any time if a timeout is reached. This is correctly handled by
-            // the previous code.
             while shard_recv_state_mut.packet_cursor < shard_recv_state_mut.shard_length {
                 let size = self.receive_socket.recv(
-                    &mut sub_buffer
+                    &mut parity_shard
                         [shard_recv_state_mut.packet_cursor..shard_recv_state_mut.shard_length],
                 )?;
                 shard_recv_state_mut.packet_cursor += size;
             }
+        } else {
+            // Note: there is no prefix offset, since we want to write the prefix too.
+            let packet_start_index = shard_recv_state_mut.shard_index * max_shard_data_size;
+
+            // Prepare buffer to accomodate receiving shard
+            {
+                // Note: this contains the prefix offset
+                in_progress_packet.buffer_length = usize::max(
+                    in_progress_packet.buffer_length,
+                    packet_start_index + shard_recv_state_mut.shard_length,
+                );
+
+                if in_progress_packet.buffer.len() < in_progress_packet.buffer_length {
+                    in_progress_packet
+                        .buffer
+                        .resize(in_progress_packet.buffer_length, 0);
+                }
+            }
+
+            let sub_buffer = &mut in_progress_packet.buffer[packet_start_index..];
+
+            // Read shard into the single contiguous buffer
+            {
+                // Backup the small section of bytes that will be overwritten by reading from
+                // socket.
+                if shard_recv_state_mut.overwritten_data_backup.is_none() {
+                    shard_recv_state_mut.overwritten_data_backup =
+                        Some(sub_buffer[..SHARD_PREFIX_SIZE].try_into().unwrap())
+                }
+
+                // This loop may bail out at any time if a timeout is reached. This is correctly
+                // handled by the previous code.
+                while shard_recv_state_mut.packet_cursor < shard_recv_state_mut.shard_length {
+                    let size = self.receive_socket.recv(
+                        &mut sub_buffer
+                            [shard_recv_state_mut.packet_cursor..shard_recv_state_mut.shard_length],
+                    )?;
+                    shard_recv_state_mut.packet_cursor += size;
+                }
 
-            // Restore backed up bytes
-            // Safety: overwritten_data_backup is always set just before receiving the packet
-            sub_buffer[..SHARD_PREFIX_SIZE]
-                .copy_from_slice(&shard_recv_state_mut.overwritten_data_backup.take().unwrap());
+                // Restore backed up bytes
+                // Safety: overwritten_data_backup is always set just before receiving the packet
+                sub_buffer[..SHARD_PREFIX_SIZE]
+                    .copy_from_slice(&shard_recv_state_mut.overwritten_data_backup.take().unwrap());
+            }
         }
 
         if !shard_recv_state_mut.should_discard {
@@ -590,8 +904,27 @@ impl StreamSocket {
                 .insert(shard_recv_state_mut.shard_index);
         }
 
+        let packet_complete = if let Some(group_size) = components.fec_group_size {
+            // Note: received_shard_indices contains also parity shards. Recovery can succeed only
+            // if the count of received shards is at least the count of data shards
+            !shard_recv_state_mut.should_discard
+                && in_progress_packet.received_shard_indices.len()
+                    >= shard_recv_state_mut.shards_count
+                && fec::recover_missing_shards(
+                    &mut in_progress_packet.buffer,
+                    &mut in_progress_packet.buffer_length,
+                    &mut in_progress_packet.received_shard_indices,
+                    &in_progress_packet.parity_shards,
+                    shard_recv_state_mut.shards_count,
+                    group_size,
+                    max_shard_data_size,
+                )
+        } else {
+            in_progress_packet.received_shard_indices.len() == shard_recv_state_mut.shards_count
+        };
+
         // Check if packet is complete and send
-        if in_progress_packet.received_shard_indices.len() == shard_recv_state_mut.shards_count {
+        if packet_complete {
             let size = in_progress_packet.buffer_length;
             components
                 .packet_queue
@@ -606,6 +939,8 @@ impl StreamSocket {
                 })
                 .ok();
 
+            components.last_completed_packet_index = Some(shard_recv_state_mut.packet_index);
+
             // Keep only shards with later packet index (using wrapping logic)
             while let Some((idx, _)) = components.in_progress_packets.iter().find(|(idx, _)| {
                 wrapping_cmp(**idx, shard_recv_state_mut.packet_index) == Ordering::Less
@@ -624,3 +959,206 @@ impl StreamSocket {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::ConnectionError;
+    use alvr_session::{settings_schema::Switch, GilbertElliottConfig, JitterDistribution};
+    use std::collections::VecDeque;
+
+    const MAX_PACKET_SIZE: usize = 100;
+    const STREAM_ID: u16 = 3;
+
+    type Datagrams = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+    // In-process datagram socket. The shards whose send order is contained in `dropped_shards` are
+    // lost
+    struct LossySocketWriter {
+        datagrams: Datagrams,
+        dropped_shards: HashSet<usize>,
+        sent_count: usize,
+    }
+
+    impl SocketWriter for LossySocketWriter {
+        fn send(&mut self, buffer: &[u8]) -> Result<()> {
+            if !self.dropped_shards.contains(&self.sent_count) {
+                self.datagrams.lock().push_back(buffer.to_vec());
+            }
+            self.sent_count += 1;
+
+            Ok(())
+        }
+    }
+
+    struct LoopbackSocketReader {
+        datagrams: Datagrams,
+    }
+
+    impl SocketReader for LoopbackSocketReader {
+        fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let Some(datagram) = self.datagrams.lock().pop_front() else {
+                return alvr_common::try_again();
+            };
+            buffer[..datagram.len()].copy_from_slice(&datagram);
+
+            Ok(datagram.len())
+        }
+
+        fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+            let datagrams = self.datagrams.lock();
+            let Some(datagram) = datagrams.front() else {
+                return alvr_common::try_again();
+            };
+            let size = usize::min(buffer.len(), datagram.len());
+            buffer[..size].copy_from_slice(&datagram[..size]);
+
+            Ok(size)
+        }
+    }
+
+    fn lossy_loopback_socket(dropped_shards: &[usize]) -> StreamSocket {
+        let datagrams = Datagrams::default();
+
+        StreamSocket {
+            max_packet_size: MAX_PACKET_SIZE,
+            send_socket: Arc::new(Mutex::new(Box::new(LossySocketWriter {
+                datagrams: Arc::clone(&datagrams),
+                dropped_shards: dropped_shards.iter().copied().collect(),
+                sent_count: 0,
+            }))),
+            receive_socket: Box::new(LoopbackSocketReader { datagrams }),
+            shard_recv_state: None,
+            stream_recv_components: HashMap::new(),
+            recorder: None,
+        }
+    }
+
+    // Sends a packet of 10 data shards, plus 3 parity shards if FEC is enabled with group size 4
+    fn send_and_receive(
+        dropped_shards: &[usize],
+        fec_group_size: Option<usize>,
+    ) -> Option<(u32, Vec<u8>)> {
+        let mut socket = lossy_loopback_socket(dropped_shards);
+        let mut sender = socket.request_stream_with_fec::<u32>(STREAM_ID, fec_group_size);
+        let mut receiver = socket.subscribe_to_stream_with_fec::<u32>(STREAM_ID, 2, fec_group_size);
+
+        let payload = expected_payload();
+        let mut buffer = sender.get_buffer(&42).unwrap();
+        buffer
+            .get_range_mut(0, payload.len())
+            .copy_from_slice(&payload);
+        sender.send(buffer).unwrap();
+
+        loop {
+            match socket.recv() {
+                Ok(()) => (),
+                Err(ConnectionError::TryAgain(_)) => break,
+                Err(ConnectionError::Other(e)) => panic!("{e}"),
+            }
+        }
+
+        let data = receiver.recv(Duration::from_millis(10)).ok()?;
+        let (header, payload) = data.get().unwrap();
+
+        Some((header, payload.to_vec()))
+    }
+
+    fn expected_payload() -> Vec<u8> {
+        (0..700).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_no_loss() {
+        assert_eq!(send_and_receive(&[], None), Some((42, expected_payload())));
+        assert_eq!(
+            send_and_receive(&[], Some(4)),
+            Some((42, expected_payload()))
+        );
+    }
+
+    #[test]
+    fn test_loss_without_fec() {
+        assert_eq!(send_and_receive(&[3], None), None);
+    }
+
+    #[test]
+    fn test_fec_recover_one_shard_per_group() {
+        // shard 9 is the last and shortest shard
+        for dropped_shards in [&[0][..], &[3], &[9], &[1, 6, 9]] {
+            assert_eq!(
+                send_and_receive(dropped_shards, Some(4)),
+                Some((42, expected_payload()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_fec_recover_with_parity_loss() {
+        // shard 12 is the parity of the last group
+        assert_eq!(
+            send_and_receive(&[2, 12], Some(4)),
+            Some((42, expected_payload()))
+        );
+    }
+
+    #[test]
+    fn test_fec_too_many_losses() {
+        assert_eq!(send_and_receive(&[0, 1], Some(4)), None);
+        assert_eq!(send_and_receive(&[9, 12], Some(4)), None);
+    }
+
+    // Returns the number of packets received out of 100, over a link with short loss bursts
+    fn count_received_with_burst_loss(fec_group_size: Option<usize>) -> usize {
+        let link = VirtualLink::new(
+            NetworkEmulatorConfig {
+                delay_ms: 5,
+                jitter: JitterDistribution::Uniform { max_jitter_ms: 2 },
+                bandwidth_limit: Switch::Disabled,
+                burst_loss: Switch::Enabled(GilbertElliottConfig {
+                    good_to_bad_probability: 0.01,
+                    bad_to_good_probability: 0.8,
+                    good_loss_probability: 0.0,
+                    bad_loss_probability: 1.0,
+                }),
+                reordering: Switch::Disabled,
+            },
+            42,
+        );
+        let mut socket = StreamSocket::new_virtual(link.clone(), link.clone(), MAX_PACKET_SIZE);
+        let mut sender = socket.request_stream_with_fec::<u32>(STREAM_ID, fec_group_size);
+        let mut receiver = socket.subscribe_to_stream_with_fec::<u32>(STREAM_ID, 2, fec_group_size);
+
+        let mut received_count = 0;
+        for index in 0..100 {
+            let payload = expected_payload();
+            let mut buffer = sender.get_buffer(&index).unwrap();
+            buffer
+                .get_range_mut(0, payload.len())
+                .copy_from_slice(&payload);
+            sender.send(buffer).unwrap();
+
+            link.advance(Duration::from_millis(10));
+            while socket.recv().is_ok() {}
+
+            if let Ok(data) = receiver.recv(Duration::ZERO) {
+                let (header, payload) = data.get().unwrap();
+                assert_eq!(header, index);
+                assert_eq!(payload, expected_payload());
+
+                received_count += 1;
+            }
+        }
+
+        received_count
+    }
+
+    #[test]
+    fn test_fec_with_emulated_burst_loss() {
+        let received_without_fec = count_received_with_burst_loss(None);
+        let received_with_fec = count_received_with_burst_loss(Some(4));
+
+        assert!(received_without_fec < 100);
+        assert!(received_with_fec > received_without_fec);
+    }
+}